@@ -0,0 +1,193 @@
+//! An answer cache that can be shared between SLG solvers. See
+//! [`AnswerCache`].
+
+use crate::Answer;
+use chalk_ir::interner::Interner;
+use chalk_ir::{AnswerSubst, Canonical, Constraints, Goal, InEnvironment, UCanonical};
+use chalk_solve::solve::persistent::{PersistedResult, PersistentCache};
+use rustc_hash::FxHashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use tracing::debug;
+
+/// Stores the answers of the tables that an SLG solver has completely
+/// solved, so that other solvers (possibly on other threads) can reuse them
+/// instead of solving the same goals again. A solver given a cache (see
+/// `SLGSolver::with_answer_cache`) adds its tables to the cache once they
+/// are complete, at the end of each query, and starts from the cached
+/// answers when it needs a table for a goal that is in the cache.
+///
+/// Cloning the cache gives another handle to the same answers. The answers
+/// depend on the program and on the `max_size` of the solver that found
+/// them, so a cache should only be shared between solvers with the same
+/// program and settings.
+pub struct AnswerCache<I: Interner> {
+    data: Arc<RwLock<CachedTables<I>>>,
+
+    /// Where the answers that can be rebuilt from their goal are also
+    /// stored, and looked up when they are not in `data`.
+    store: Option<(I, Arc<PersistentCache>)>,
+}
+
+type CachedTables<I> = FxHashMap<UCanonical<InEnvironment<Goal<I>>>, Arc<CachedAnswers<I>>>;
+
+/// The answers of a complete table.
+#[derive(Debug)]
+pub(crate) struct CachedAnswers<I: Interner> {
+    pub(crate) answers: Vec<Answer<I>>,
+    pub(crate) floundered: bool,
+}
+
+impl<I: Interner> AnswerCache<I> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a cache that also keeps the answers of tables in `store`,
+    /// if they can be rebuilt from the goal of the table (no answers, or a
+    /// single answer that does not constrain the variables of the goal),
+    /// and starts from the answers found there.
+    pub fn with_store(interner: I, store: Arc<PersistentCache>) -> Self {
+        Self {
+            data: Default::default(),
+            store: Some((interner, store)),
+        }
+    }
+
+    /// The number of goals in the cache.
+    pub fn len(&self) -> usize {
+        self.data.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a cache with the answers currently in this one, which does
+    /// not see the answers added to this cache from then on (and the other
+    /// way around). The snapshot does not use the store of this cache.
+    pub fn snapshot(&self) -> Self {
+        Self {
+            data: Arc::new(RwLock::new(self.data.read().unwrap().clone())),
+            store: None,
+        }
+    }
+
+    pub(crate) fn get(
+        &self,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+    ) -> Option<Arc<CachedAnswers<I>>> {
+        let mut result = self.data.read().unwrap().get(goal).cloned();
+        if result.is_none() {
+            result = self.load(goal).map(Arc::new);
+        }
+        debug!(?goal, hit = result.is_some(), "answer cache lookup");
+        result
+    }
+
+    fn load(&self, goal: &UCanonical<InEnvironment<Goal<I>>>) -> Option<CachedAnswers<I>> {
+        let (interner, store) = self.store.as_ref()?;
+        Some(CachedAnswers::rebuild(*interner, goal, store.get(goal)?))
+    }
+
+    pub(crate) fn insert(
+        &self,
+        goal: UCanonical<InEnvironment<Goal<I>>>,
+        answers: CachedAnswers<I>,
+    ) {
+        if let Some((interner, store)) = &self.store {
+            if let Some(persisted) = answers.persisted(*interner, &goal) {
+                store.insert(&goal, persisted);
+            }
+        }
+        self.data
+            .write()
+            .unwrap()
+            .entry(goal)
+            .or_insert_with(|| Arc::new(answers));
+    }
+}
+
+impl<I: Interner> CachedAnswers<I> {
+    /// The answers of a table for `goal` that `persisted` was stored for.
+    fn rebuild(
+        interner: I,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+        persisted: PersistedResult,
+    ) -> Self {
+        let trivial_answer = |ambiguous| Answer {
+            subst: Canonical {
+                binders: goal.canonical.binders.clone(),
+                value: AnswerSubst {
+                    subst: goal.trivial_substitution(interner),
+                    constraints: Constraints::empty(interner),
+                    delayed_subgoals: vec![],
+                },
+            },
+            ambiguous,
+        };
+        let answers = match persisted {
+            PersistedResult::NoSolution => vec![],
+            PersistedResult::Proven => vec![trivial_answer(false)],
+            PersistedResult::Ambiguous => vec![trivial_answer(true)],
+        };
+        CachedAnswers {
+            answers,
+            floundered: false,
+        }
+    }
+
+    /// How these answers, of a table for `goal`, can be stored, if they
+    /// can be rebuilt from the goal.
+    fn persisted(
+        &self,
+        interner: I,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+    ) -> Option<PersistedResult> {
+        let persisted = match (&self.answers[..], self.floundered) {
+            // Floundering discards the answers that the table had found,
+            // and which its users may have seen already.
+            (_, true) => return None,
+            ([], false) => PersistedResult::NoSolution,
+            ([answer], false) if answer.ambiguous => PersistedResult::Ambiguous,
+            ([_], false) => PersistedResult::Proven,
+            _ => return None,
+        };
+        let rebuilt = Self::rebuild(interner, goal, persisted);
+        let same = rebuilt.answers.len() == self.answers.len()
+            && rebuilt
+                .answers
+                .iter()
+                .zip(&self.answers)
+                .all(|(a, b)| a.subst == b.subst && a.ambiguous == b.ambiguous);
+        if same {
+            Some(persisted)
+        } else {
+            None
+        }
+    }
+}
+
+impl<I: Interner> Clone for AnswerCache<I> {
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+            store: self.store.clone(),
+        }
+    }
+}
+
+impl<I: Interner> Default for AnswerCache<I> {
+    fn default() -> Self {
+        Self {
+            data: Default::default(),
+            store: None,
+        }
+    }
+}
+
+impl<I: Interner> fmt::Debug for AnswerCache<I> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "AnswerCache({} goals)", self.len())
+    }
+}
@@ -0,0 +1,232 @@
+//! Reconstructs a proof tree from the `TableTrace`s recorded by a traced
+//! forest. Since tables are shared between all the strands that select the
+//! same subgoal, a table is only expanded the first time it is encountered;
+//! later occurrences are elided, and occurrences within the table's own
+//! derivation are reported as cycles.
+
+use crate::context::{AnswerResult, AnswerStream};
+use crate::forest::Forest;
+use crate::slg::aggregate::AggregateOps;
+use crate::slg::SlgContextOps;
+use crate::table::TracedSubgoal;
+use crate::{Answer, CompleteAnswer, TableIndex};
+
+use chalk_ir::interner::Interner;
+use chalk_ir::{Canonical, ConstrainedSubst, Goal, InEnvironment, Substitution, UCanonical};
+use chalk_solve::solve::proof_tree::{ClauseAttempt, ProofOutcome, ProofTree};
+use chalk_solve::Solution;
+use rustc_hash::FxHashSet;
+
+/// An answer stream over the answers a table has already found.
+struct RecordedAnswers<I: Interner> {
+    answers: Vec<CompleteAnswer<I>>,
+    next: usize,
+}
+
+impl<I: Interner> AnswerStream<I> for RecordedAnswers<I> {
+    fn peek_answer(&mut self, _should_continue: impl Fn() -> bool) -> AnswerResult<I> {
+        match self.answers.get(self.next) {
+            Some(answer) => AnswerResult::Answer(answer.clone()),
+            None => AnswerResult::NoMoreSolutions,
+        }
+    }
+
+    fn next_answer(&mut self, should_continue: impl Fn() -> bool) -> AnswerResult<I> {
+        let answer = self.peek_answer(should_continue);
+        self.next += 1;
+        answer
+    }
+
+    fn any_future_answer(&self, test: impl Fn(&Substitution<I>) -> bool) -> bool {
+        self.answers[self.next.min(self.answers.len())..]
+            .iter()
+            .any(|answer| test(&answer.subst.value.subst))
+    }
+}
+
+struct Explainer<'a, I: Interner> {
+    forest: &'a Forest<I>,
+    context: &'a SlgContextOps<'a, I>,
+    stack: Vec<TableIndex>,
+    visited: FxHashSet<TableIndex>,
+}
+
+impl<I: Interner> Forest<I> {
+    /// Builds the proof tree for `goal`, which must already have been
+    /// solved in this (traced) forest with the given `solution`.
+    pub(crate) fn proof_tree(
+        &self,
+        context: &SlgContextOps<'_, I>,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+        solution: Option<Solution<I>>,
+    ) -> ProofTree<I> {
+        let table = match self.tables.index_of(goal) {
+            Some(table) => table,
+            None => return ProofTree::leaf(goal.clone(), ProofOutcome::NoSolution),
+        };
+        let mut explainer = Explainer {
+            forest: self,
+            context,
+            stack: vec![],
+            visited: FxHashSet::default(),
+        };
+        let mut tree = explainer.explain_table(table);
+        // The root table may not have been run to completion; the solution
+        // that was actually computed for it is authoritative.
+        if !self.tables[table].is_floundered() {
+            tree.outcome = match solution {
+                Some(solution) => ProofOutcome::Proven(solution),
+                None => ProofOutcome::NoSolution,
+            };
+        }
+        tree
+    }
+}
+
+impl<I: Interner> Explainer<'_, I> {
+    fn explain_table(&mut self, table: TableIndex) -> ProofTree<I> {
+        let goal = self.forest.tables[table].table_goal.clone();
+        if self.stack.contains(&table) {
+            let provisional = self.solution(table, |_| true);
+            return ProofTree::leaf(goal, ProofOutcome::Cycle(provisional));
+        }
+        let outcome = self.table_outcome(table);
+        if !self.visited.insert(table) {
+            let mut tree = ProofTree::leaf(goal, outcome);
+            tree.elided = true;
+            return tree;
+        }
+        let trace = match &self.forest.tables[table].trace {
+            Some(trace) => trace,
+            None => return ProofTree::leaf(goal, outcome),
+        };
+
+        self.stack.push(table);
+        let mut tree = ProofTree::leaf(goal, outcome);
+        for (index, (clause, unified)) in trace.clauses.iter().enumerate() {
+            let outcome = if *unified {
+                self.clause_outcome(table, index)
+            } else {
+                ProofOutcome::DoesNotUnify
+            };
+            let subgoals = trace
+                .subgoals
+                .iter()
+                .filter(|(clause, _)| *clause == Some(index))
+                .map(|(_, subgoal)| self.explain_subgoal(subgoal))
+                .collect();
+            tree.clauses.push(ClauseAttempt {
+                clause: clause.clone(),
+                outcome,
+                subgoals,
+            });
+        }
+        tree.subgoals = trace
+            .subgoals
+            .iter()
+            .filter(|(clause, _)| clause.is_none())
+            .map(|(_, subgoal)| self.explain_subgoal(subgoal))
+            .collect();
+        self.stack.pop();
+        tree
+    }
+
+    fn explain_subgoal(&mut self, subgoal: &TracedSubgoal<I>) -> ProofTree<I> {
+        match subgoal {
+            TracedSubgoal::Table(table) => self.explain_table(*table),
+            TracedSubgoal::Unsolved(goal, outcome) => {
+                ProofTree::leaf(goal.clone(), outcome.clone())
+            }
+        }
+    }
+
+    /// Aggregates the (complete) answers of `table` that came from clauses
+    /// accepted by `filter`.
+    fn solution(
+        &self,
+        table: TableIndex,
+        filter: impl Fn(Option<usize>) -> bool,
+    ) -> Option<Solution<I>> {
+        let table = &self.forest.tables[table];
+        let clauses = table.trace.as_ref().map(|trace| &trace.answer_clauses);
+        let answers = table
+            .answers()
+            .enumerate()
+            .filter(|(index, _)| filter(clauses.and_then(|clauses| clauses[*index])))
+            .filter_map(|(_, answer)| complete_answer(answer))
+            .collect();
+        self.context.make_solution(
+            &table.table_goal,
+            RecordedAnswers { answers, next: 0 },
+            || true,
+        )
+    }
+
+    fn table_outcome(&self, table_index: TableIndex) -> ProofOutcome<I> {
+        let table = &self.forest.tables[table_index];
+        if table.is_floundered() {
+            return match &table.trace {
+                Some(trace) if trace.truncated => ProofOutcome::Truncated,
+                _ => ProofOutcome::Floundered,
+            };
+        }
+        match self.solution(table_index, |_| true) {
+            Some(solution) => ProofOutcome::Proven(solution),
+            None if table.strands().next().is_some() => ProofOutcome::Abandoned,
+            None => self.leak_outcome(table_index, |_| true),
+        }
+    }
+
+    fn clause_outcome(&self, table_index: TableIndex, clause: usize) -> ProofOutcome<I> {
+        let table = &self.forest.tables[table_index];
+        if table.is_floundered() {
+            return self.table_outcome(table_index);
+        }
+        if let Some(solution) = self.solution(table_index, |c| c == Some(clause)) {
+            return ProofOutcome::Proven(solution);
+        }
+        let cut = table.trace.as_ref().is_some_and(|trace| trace.cut);
+        if cut
+            || table
+                .strands()
+                .any(|strand| strand.value.clause == Some(clause))
+        {
+            ProofOutcome::Abandoned
+        } else {
+            self.leak_outcome(table_index, |c| c == Some(clause))
+        }
+    }
+
+    /// The outcome of a table (or of the clauses of it that `filter`
+    /// accepts) that has no answers: the first answer the leak check dropped,
+    /// if there was one.
+    fn leak_outcome(
+        &self,
+        table_index: TableIndex,
+        filter: impl Fn(Option<usize>) -> bool,
+    ) -> ProofOutcome<I> {
+        let trace = self.forest.tables[table_index].trace.as_ref();
+        match trace.and_then(|trace| trace.leaks.iter().find(|(clause, _)| filter(*clause))) {
+            Some((_, leak)) => ProofOutcome::PlaceholderLeak(leak.clone()),
+            None => ProofOutcome::NoSolution,
+        }
+    }
+}
+
+/// Answers with delayed subgoals are not yet known to hold, so they are not
+/// part of the solution (see `Forest::root_answer`).
+fn complete_answer<I: Interner>(answer: &Answer<I>) -> Option<CompleteAnswer<I>> {
+    if !answer.subst.value.delayed_subgoals.is_empty() {
+        return None;
+    }
+    Some(CompleteAnswer {
+        subst: Canonical {
+            binders: answer.subst.binders.clone(),
+            value: ConstrainedSubst {
+                subst: answer.subst.value.subst.clone(),
+                constraints: answer.subst.value.constraints.clone(),
+            },
+        },
+        ambiguous: answer.ambiguous,
+    })
+}
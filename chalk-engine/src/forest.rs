@@ -1,3 +1,4 @@
+use crate::cache::AnswerCache;
 use crate::context::{AnswerResult, AnswerStream};
 use crate::logic::RootSearchFail;
 use crate::slg::SlgContextOps;
@@ -7,6 +8,10 @@ use crate::{TableIndex, TimeStamp};
 
 use chalk_ir::interner::Interner;
 use chalk_ir::{Goal, InEnvironment, Substitution, UCanonical};
+use chalk_solve::solve::budget::{BudgetLimit, BudgetTracker};
+use chalk_solve::solve::stats::SolverStats;
+use rustc_hash::FxHashSet;
+use std::fmt;
 use tracing::debug;
 
 pub(crate) struct Forest<I: Interner> {
@@ -17,6 +22,71 @@ pub(crate) struct Forest<I: Interner> {
     /// This effectively gives us way to track what depth
     /// and loop a table or strand was last followed.
     pub(crate) clock: TimeStamp,
+
+    /// If true, each table records a `TableTrace` as it is solved.
+    pub(crate) traced: bool,
+
+    /// The number of tables to keep. When a query starts with more tables
+    /// than this, the least recently used ones are collected (see
+    /// `collect_tables`).
+    pub(crate) table_capacity: Option<usize>,
+
+    /// The number of tables collected so far.
+    collected_tables: usize,
+
+    /// The cache to take the answers of new tables from, and to add the
+    /// answers of complete tables to.
+    pub(crate) answer_cache: Option<AnswerCache<I>>,
+
+    /// The tables that are not in `answer_cache` yet.
+    pub(crate) uncached_tables: Vec<TableIndex>,
+
+    /// The number of tables taken from `answer_cache`.
+    pub(crate) cache_hits: usize,
+
+    /// Counts of the work done by the forest, for `Solver::stats`.
+    pub(crate) counters: SolverStats<I>,
+}
+
+/// Statistics about the memory used by the tables of an SLG solver.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ForestStats {
+    /// The number of tables in the forest.
+    pub tables: usize,
+
+    /// How many of `tables` are complete: they have found all of their
+    /// answers, and have no strands left.
+    pub complete_tables: usize,
+
+    /// The number of answers stored in the tables.
+    pub answers: usize,
+
+    /// The number of strands (pending attempts to find more answers) in
+    /// the tables.
+    pub strands: usize,
+
+    /// The number of tables collected so far.
+    pub collected_tables: usize,
+
+    /// The number of tables whose answers were taken from the answer
+    /// cache, rather than solved by this solver.
+    pub cache_hits: usize,
+}
+
+impl fmt::Display for ForestStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} tables ({} complete, {} from the answer cache), {} answers, {} strands, \
+             {} tables collected",
+            self.tables,
+            self.complete_tables,
+            self.cache_hits,
+            self.answers,
+            self.strands,
+            self.collected_tables
+        )
+    }
 }
 
 impl<I: Interner> Forest<I> {
@@ -24,6 +94,22 @@ impl<I: Interner> Forest<I> {
         Forest {
             tables: Tables::new(),
             clock: TimeStamp::default(),
+            traced: false,
+            table_capacity: None,
+            collected_tables: 0,
+            answer_cache: None,
+            uncached_tables: vec![],
+            cache_hits: 0,
+            counters: SolverStats::default(),
+        }
+    }
+
+    /// Creates a forest that records how each of its tables was solved, so
+    /// that a proof tree can be produced afterwards.
+    pub(crate) fn new_traced() -> Self {
+        Forest {
+            traced: true,
+            ..Forest::new()
         }
     }
 
@@ -33,6 +119,85 @@ impl<I: Interner> Forest<I> {
         self.clock
     }
 
+    /// Statistics about the tables of the forest.
+    pub(crate) fn stats(&self) -> ForestStats {
+        let mut stats = ForestStats {
+            collected_tables: self.collected_tables,
+            cache_hits: self.cache_hits,
+            ..ForestStats::default()
+        };
+        for (_, table) in self.tables.iter() {
+            stats.tables += 1;
+            stats.complete_tables += table.is_complete() as usize;
+            stats.answers += table.num_answers();
+            stats.strands += table.strands().count();
+        }
+        stats
+    }
+
+    /// Collects the complete tables that no strand is waiting on, least
+    /// recently used first, until at most `keep` tables are left (or there
+    /// are no such tables left). Returns the number of tables collected.
+    ///
+    /// Tables with strands are kept, and so is every table whose index is
+    /// held elsewhere in the forest: the tables that strands wait on, that
+    /// traces refer to, or that are not in the answer cache yet. So no
+    /// index in use ever refers to a collected table. The answers of the
+    /// collected tables are recomputed if their goals come up again, in new
+    /// tables.
+    pub(crate) fn collect_tables(&mut self, keep: usize) -> usize {
+        self.cache_complete_tables();
+        let live = self.tables.live_len();
+        if live <= keep {
+            return 0;
+        }
+        let pinned: FxHashSet<TableIndex> = self
+            .tables
+            .iter()
+            .flat_map(|(_, table)| table.referenced_tables())
+            .chain(self.uncached_tables.iter().copied())
+            .collect();
+        let mut collectable: Vec<(u64, TableIndex)> = self
+            .tables
+            .iter()
+            .filter(|(index, table)| table.is_complete() && !pinned.contains(index))
+            .map(|(index, table)| (table.last_used, index))
+            .collect();
+        collectable.sort_unstable();
+        let collected = collectable.len().min(live - keep);
+        for &(_, index) in &collectable[..collected] {
+            debug!(?index, "collecting table");
+            self.tables.remove(index);
+        }
+        self.collected_tables += collected;
+        collected
+    }
+
+    /// Adds the answers of the tables that were completed since the last
+    /// call to the answer cache.
+    fn cache_complete_tables(&mut self) {
+        let cache = match &self.answer_cache {
+            Some(cache) => cache,
+            None => return,
+        };
+        let tables = &self.tables;
+        self.uncached_tables.retain(|&index| {
+            let table = &tables[index];
+            if !table.is_complete() {
+                return true;
+            }
+            cache.insert(table.table_goal.clone(), table.cached_answers());
+            false
+        });
+    }
+
+    /// Collects tables if there are more than `table_capacity`.
+    fn enforce_table_capacity(&mut self) {
+        if let Some(capacity) = self.table_capacity {
+            self.collect_tables(capacity);
+        }
+    }
+
     /// Returns a "solver" for a given goal in the form of an
     /// iterator. Each time you invoke `next`, it will do the work to
     /// extract one more answer. These answers are cached in between
@@ -42,6 +207,28 @@ impl<I: Interner> Forest<I> {
         context: &'f SlgContextOps<'f, I>,
         goal: &UCanonical<InEnvironment<Goal<I>>>,
     ) -> impl AnswerStream<I> + 'f {
+        self.enforce_table_capacity();
+        let table = self.get_or_create_table_for_ucanonical_goal(context, goal.clone());
+        let answer = AnswerIndex::ZERO;
+        ForestSolver {
+            forest: self,
+            context,
+            table,
+            answer,
+            budget: None,
+        }
+    }
+
+    /// Like `iter_answers`, but stops (as if the quantum was exceeded)
+    /// once the work done exceeds the table or strand limits of `budget`.
+    pub(crate) fn iter_answers_within<'f>(
+        &'f mut self,
+        context: &'f SlgContextOps<'f, I>,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+        budget: &'f BudgetTracker,
+    ) -> impl AnswerStream<I> + 'f {
+        self.enforce_table_capacity();
+        let tables = self.tables.len();
         let table = self.get_or_create_table_for_ucanonical_goal(context, goal.clone());
         let answer = AnswerIndex::ZERO;
         ForestSolver {
@@ -49,6 +236,7 @@ impl<I: Interner> Forest<I> {
             context,
             table,
             answer,
+            budget: Some((budget, tables)),
         }
     }
 }
@@ -58,6 +246,28 @@ struct ForestSolver<'me, I: Interner> {
     context: &'me SlgContextOps<'me, I>,
     table: TableIndex,
     answer: AnswerIndex,
+
+    /// The budget to stay within, and the number of tables in the forest
+    /// before solving started.
+    budget: Option<(&'me BudgetTracker, usize)>,
+}
+
+impl<I: Interner> Drop for ForestSolver<'_, I> {
+    fn drop(&mut self) {
+        self.forest.cache_complete_tables();
+    }
+}
+
+impl<I: Interner> ForestSolver<'_, I> {
+    fn within_budget(&self) -> bool {
+        let (budget, initial_tables) = match self.budget {
+            Some(budget) => budget,
+            None => return true,
+        };
+        let tables = &self.forest.tables;
+        budget.within(BudgetLimit::Tables, tables.len() - initial_tables)
+            && budget.within(BudgetLimit::Strands, tables.pending_strands())
+    }
 }
 
 impl<'me, I: Interner> AnswerStream<I> for ForestSolver<'me, I> {
@@ -87,7 +297,7 @@ impl<'me, I: Interner> AnswerStream<I> for ForestSolver<'me, I> {
                 }
 
                 Err(RootSearchFail::QuantumExceeded) => {
-                    if !should_continue() {
+                    if !self.within_budget() || !should_continue() {
                         return AnswerResult::QuantumExceeded;
                     }
                 }
@@ -114,3 +324,43 @@ impl<'me, I: Interner> AnswerStream<I> for ForestSolver<'me, I> {
         self.forest.any_future_answer(self.table, self.answer, test)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::table::{Table, TableTrace, TracedSubgoal};
+    use chalk_integration::interner::ChalkIr;
+    use chalk_ir::{Canonical, CanonicalVarKinds, Environment, GoalData, Goals};
+
+    fn table(goal: GoalData<ChalkIr>) -> Table<ChalkIr> {
+        let goal = UCanonical {
+            canonical: Canonical {
+                value: InEnvironment::new(&Environment::new(ChalkIr), goal.intern(ChalkIr)),
+                binders: CanonicalVarKinds::empty(ChalkIr),
+            },
+            universes: 1,
+        };
+        Table::new(goal, false)
+    }
+
+    #[test]
+    fn traced_tables_are_pinned() {
+        let mut forest = Forest::new_traced();
+        let subgoal = forest.tables.insert(table(GoalData::CannotProve));
+        let mut root = table(GoalData::All(Goals::empty(ChalkIr)));
+        root.trace = Some(TableTrace {
+            subgoals: vec![(None, TracedSubgoal::Table(subgoal))],
+            ..TableTrace::default()
+        });
+        let root = forest.tables.insert(root);
+
+        // The trace of the root holds the index of the subgoal table, so
+        // only the root is collected at first.
+        assert_eq!(forest.collect_tables(0), 1);
+        assert!(forest.tables.get(root).is_none());
+        assert!(forest.tables.get(subgoal).is_some());
+
+        assert_eq!(forest.collect_tables(0), 1);
+        assert!(forest.tables.get(subgoal).is_none());
+    }
+}
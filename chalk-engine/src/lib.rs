@@ -64,8 +64,10 @@ use chalk_ir::{
 };
 use std::ops::ControlFlow;
 
+pub mod cache;
 pub mod context;
 mod derived;
+mod explain;
 pub mod forest;
 mod logic;
 mod normalize_deep;
@@ -77,9 +79,23 @@ mod strand;
 mod table;
 mod tables;
 
-index_struct! {
-    pub struct TableIndex { // FIXME: pub b/c TypeFoldable
-        value: usize,
+/// The index of a table in the forest. The slots of collected tables are
+/// given to later tables (see `Tables::remove`), so the index also records
+/// which generation of its slot it refers to: the index of a collected
+/// table never refers to the table that takes its place.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TableIndex {
+    // FIXME: pub b/c TypeFoldable
+    value: usize,
+    generation: u32,
+}
+
+impl std::fmt::Debug for TableIndex {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.generation {
+            0 => write!(fmt, "TableIndex({})", self.value),
+            generation => write!(fmt, "TableIndex({}/{})", self.value, generation),
+        }
     }
 }
 
@@ -3,7 +3,7 @@ use crate::normalize_deep::DeepNormalizer;
 use crate::slg::{ResolventOps, SlgContext, SlgContextOps};
 use crate::stack::{Stack, StackIndex};
 use crate::strand::{CanonicalStrand, SelectedSubgoal, Strand};
-use crate::table::{AnswerIndex, Table};
+use crate::table::{AnswerIndex, Table, TableTrace, TracedSubgoal};
 use crate::{
     Answer, AnswerMode, CompleteAnswer, ExClause, FlounderedSubgoal, Literal, Minimums, TableIndex,
     TimeStamp,
@@ -12,13 +12,16 @@ use crate::{
 use chalk_ir::could_match::CouldMatch;
 use chalk_ir::interner::Interner;
 use chalk_ir::{
-    AnswerSubst, Canonical, ConstrainedSubst, Constraints, FallibleOrFloundered, Floundered, Goal,
-    GoalData, InEnvironment, NoSolution, ProgramClause, Substitution, UCanonical, UniverseMap,
+    AnswerSubst, Canonical, ClausePriority, ConstrainedSubst, Constraints, FallibleOrFloundered,
+    Floundered, Goal, GoalData, InEnvironment, NoSolution, ProgramClause, Substitution, UCanonical,
+    UniverseIndex, UniverseMap,
 };
 use chalk_solve::clauses::program_clauses_that_could_match;
 use chalk_solve::coinductive_goal::IsCoinductive;
 use chalk_solve::infer::ucanonicalize::UCanonicalized;
 use chalk_solve::infer::InferenceTable;
+use chalk_solve::solve::leak_check::leak_check;
+use chalk_solve::solve::proof_tree::ProofOutcome;
 use chalk_solve::solve::truncate;
 use tracing::{debug, debug_span, info, instrument};
 
@@ -204,16 +207,44 @@ impl<I: Interner> Forest<I> {
     ) -> TableIndex {
         if let Some(table) = self.tables.index_of(&goal) {
             debug!(?table, "found existing table");
+            self.tables.touch(table);
             return table;
         }
 
+        if let Some(cached) = self
+            .answer_cache
+            .as_ref()
+            .and_then(|cache| cache.get(&goal))
+        {
+            info!(
+                table = ?self.tables.next_index(),
+                "creating new table from the answer cache with goal = {:#?}",
+                goal,
+            );
+            let coinductive = goal.is_coinductive(context.program());
+            self.cache_hits += 1;
+            return self
+                .tables
+                .insert(Table::from_cache(goal, coinductive, &cached));
+        }
+
         info!(
             table = ?self.tables.next_index(),
             "creating new table with goal = {:#?}",
             goal,
         );
-        let table = Self::build_table(context, self.tables.next_index(), goal);
-        self.tables.insert(table)
+        let interner = context.program().interner();
+        self.counters
+            .record_goal(interner, &goal.canonical.value.goal);
+        let table = Self::build_table(context, self.tables.next_index(), goal, self.traced);
+        self.counters.tables += 1;
+        self.counters.strands += table.strands().count();
+        self.counters.flounders += table.is_floundered() as usize;
+        let table = self.tables.insert(table);
+        if self.answer_cache.is_some() {
+            self.uncached_tables.push(table);
+        }
+        table
     }
 
     /// When a table is first created, this function is invoked to
@@ -231,9 +262,13 @@ impl<I: Interner> Forest<I> {
         context: &SlgContextOps<I>,
         table_idx: TableIndex,
         goal: UCanonical<InEnvironment<Goal<I>>>,
+        traced: bool,
     ) -> Table<I> {
         let coinductive = goal.is_coinductive(context.program());
         let mut table = Table::new(goal.clone(), coinductive);
+        if traced {
+            table.trace = Some(TableTrace::default());
+        }
 
         let goal_data = goal.canonical.value.goal.data(context.program().interner());
         match goal_data {
@@ -276,22 +311,29 @@ impl<I: Interner> Forest<I> {
 
                         let InEnvironment { environment, goal } = goal;
 
-                        for clause in clauses {
+                        for (clause_index, clause) in clauses.into_iter().enumerate() {
                             info!("program clause = {:#?}", clause);
+                            let priority = clause.data(db.interner()).0.skip_binders().priority;
                             let mut infer = infer.clone();
-                            if let Ok(resolvent) = infer.resolvent_clause(
+                            let resolvent = infer.resolvent_clause(
                                 context.unification_database(),
                                 context.program().interner(),
                                 &environment,
                                 &goal,
                                 &subst,
                                 &clause,
-                            ) {
+                            );
+                            if let Some(trace) = &mut table.trace {
+                                trace.clauses.push((clause, resolvent.is_ok()));
+                            }
+                            if let Ok(resolvent) = resolvent {
                                 info!("pushing initial strand with ex-clause: {:#?}", &resolvent,);
                                 let strand = Strand {
                                     ex_clause: resolvent,
                                     selected_subgoal: None,
                                     last_pursued_time: TimeStamp::default(),
+                                    clause: Some(clause_index),
+                                    priority,
                                 };
                                 let canonical_strand =
                                     Self::canonicalize_strand_from(context, &mut infer, &strand);
@@ -338,6 +380,8 @@ impl<I: Interner> Forest<I> {
                             ex_clause,
                             selected_subgoal: None,
                             last_pursued_time: TimeStamp::default(),
+                            clause: None,
+                            priority: ClausePriority::High,
                         };
                         let canonical_strand =
                             Self::canonicalize_strand_from(context, &mut infer, &strand);
@@ -512,6 +556,7 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
             // If we had an active strand, continue to pursue it
             let table = self.stack.top().table;
             let table_answer_mode = self.forest.tables[table].answer_mode;
+            let table_priority = self.forest.tables[table].priority;
 
             // We track when we last pursued each strand. If all the strands have been
             // pursued at this depth, then that means they all encountered a cycle.
@@ -527,7 +572,11 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
                         (AnswerMode::Complete, true) => false,
                         (AnswerMode::Ambiguous, _) => true,
                     };
-                    time_eligble && mode_eligble
+                    let priority_eligible = !matches!(
+                        (table_priority, strand.value.priority),
+                        (ClausePriority::High, ClausePriority::Low)
+                    );
+                    time_eligble && mode_eligble && priority_eligible
                 })
             });
             match next_strand {
@@ -631,11 +680,14 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
                     ex_clause: strand.ex_clause.clone(),
                     selected_subgoal: Some(next_subgoal),
                     last_pursued_time: strand.last_pursued_time,
+                    clause: strand.clause,
+                    priority: strand.priority,
                 };
                 let table = self.stack.top().table;
                 let canonical_next_strand =
                     Forest::canonicalize_strand_from(self.context, infer, &next_strand);
                 self.forest.tables[table].enqueue_strand(canonical_next_strand);
+                self.forest.counters.strands += 1;
             }
         }
 
@@ -817,6 +869,7 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
         // recursively requested an answer for itself. This
         // means that we have to delay this subgoal until we
         // reach a trivial self-cycle.
+        self.forest.counters.cycles += 1;
 
         // This subgoal selection for the strand is finished, so take it
         let selected_subgoal = canonical_strand.value.selected_subgoal.take().unwrap();
@@ -863,6 +916,8 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
         canonical_strand: CanonicalStrand<I>,
         minimums: Minimums,
     ) -> Result<(), RootSearchFail> {
+        self.forest.counters.cycles += 1;
+
         // We can't take this because we might need it later to clear the cycle
         let selected_subgoal = canonical_strand.value.selected_subgoal.as_ref().unwrap();
 
@@ -1048,9 +1103,9 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
                         // Delayed Trivial Self Cycle, Variant 3
                         // example).
 
-                        let answer = self.forest.answer(table, answer_index);
-                        if let Some(strand) = self.create_refinement_strand(table, answer) {
+                        if let Some(strand) = self.create_refinement_strand(table, answer_index) {
                             self.forest.tables[table].enqueue_strand(strand);
+                            self.forest.counters.strands += 1;
                         }
 
                         NoRemainingSubgoalsResult::RootAnswerAvailable
@@ -1087,8 +1142,9 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
     fn create_refinement_strand(
         &self,
         table: TableIndex,
-        answer: &Answer<I>,
+        answer_index: AnswerIndex,
     ) -> Option<CanonicalStrand<I>> {
+        let answer = self.forest.answer(table, answer_index);
         // If there are no delayed subgoals, then there is no need for
         // a refinement strand.
         if answer.subst.value.delayed_subgoals.is_empty() {
@@ -1129,6 +1185,8 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
             },
             selected_subgoal: None,
             last_pursued_time: TimeStamp::default(),
+            clause: None,
+            priority: self.forest.tables[table].answer_priority(answer_index),
         };
 
         Some(Forest::canonicalize_strand_from(
@@ -1203,6 +1261,20 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
             return Err(RootSearchFail::QuantumExceeded);
         }
 
+        // Likewise, the strands from low-priority clauses are only pursued
+        // once those from high-priority clauses ran out, so that the answers
+        // they override are known (see `Table::overrides`).
+        if let ClausePriority::High = self.forest.tables[table].priority {
+            if self.forest.tables[table]
+                .strands()
+                .any(|strand| strand.value.priority == ClausePriority::Low)
+            {
+                debug!("Allowing low-priority strands.");
+                self.forest.tables[table].priority = ClausePriority::Low;
+                return Err(RootSearchFail::QuantumExceeded);
+            }
+        }
+
         let clock = self.stack.top().clock;
         let cyclic_minimums = self.stack.top().cyclic_minimums;
         if cyclic_minimums.positive >= clock && cyclic_minimums.negative >= clock {
@@ -1359,6 +1431,9 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
                     &strand.ex_clause.subgoals[subgoal_index],
                 ) {
                     Some((subgoal_table, universe_map)) => {
+                        let table = self.stack.top().table;
+                        let subgoal = TracedSubgoal::Table(subgoal_table);
+                        self.trace_subgoal(table, canonical_strand.value.clause, subgoal);
                         canonical_strand.value.selected_subgoal = Some(SelectedSubgoal {
                             subgoal_index,
                             subgoal_table,
@@ -1370,7 +1445,19 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
                     None => {
                         // If we failed to create a table for the subgoal,
                         // that is because we have a floundered negative
-                        // literal.
+                        // literal, or a positive literal that is too large.
+                        let table = self.stack.top().table;
+                        if self.forest.tables[table].trace.is_some() {
+                            let interner = self.context.program().interner();
+                            let (goal, outcome) = match &strand.ex_clause.subgoals[subgoal_index] {
+                                Literal::Positive(goal) => (goal, ProofOutcome::Truncated),
+                                Literal::Negative(goal) => (goal, ProofOutcome::Floundered),
+                            };
+                            let goal = infer.canonicalize(interner, goal.clone()).quantified;
+                            let goal = InferenceTable::u_canonicalize(interner, &goal).quantified;
+                            let subgoal = TracedSubgoal::Unsolved(goal, outcome);
+                            self.trace_subgoal(table, canonical_strand.value.clause, subgoal);
+                        }
                         self.flounder_subgoal(&mut canonical_strand.value.ex_clause, subgoal_index);
                     }
                 }
@@ -1413,6 +1500,8 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
             binders,
             value: strand,
         } = canonical_strand;
+        let clause = strand.clause;
+        let priority = strand.priority;
         let ExClause {
             subst,
             constraints,
@@ -1458,7 +1547,11 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
             self.context.max_size(),
             &subst,
         ) {
+            if let Some(trace) = &mut self.forest.tables[table].trace {
+                trace.truncated = true;
+            }
             self.forest.tables[table].mark_floundered();
+            self.forest.counters.flounders += 1;
             return None;
         }
 
@@ -1491,6 +1584,35 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
 
         let answer = Answer { subst, ambiguous };
 
+        // The universes of the `forall`s in the table goal are closed here,
+        // so the answer must not leak their placeholders.
+        let table_goal = &self.forest.tables[table].table_goal;
+        if let Err(leak) = leak_check(
+            self.context.program().interner(),
+            &table_goal.canonical.value.environment,
+            UniverseIndex {
+                counter: table_goal.universes - 1,
+            },
+            &answer.subst.binders,
+            &answer.subst.value.constraints,
+        ) {
+            info!("answer: leaks a placeholder, returning None");
+            if let Some(trace) = &mut self.forest.tables[table].trace {
+                trace.leaks.push((clause, leak));
+            }
+            return None;
+        }
+
+        // An answer from a low-priority clause is dropped if the answers
+        // from the high-priority clauses are for the same inputs, as the
+        // recursive solver does.
+        if priority == ClausePriority::Low
+            && self.forest.tables[table].overrides(self.context.program().interner(), &answer)
+        {
+            info!("answer: overridden by higher-priority answers, returning None");
+            return None;
+        }
+
         // A "trivial" answer is one that is 'just true for all cases'
         // -- in other words, it gives no information back to the
         // caller. For example, `Vec<u32>: Sized` is "just true".
@@ -1560,11 +1682,20 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
                     .is_empty(self.context.program().interner())
         };
 
-        if let Some(answer_index) = self.forest.tables[table].push_answer(answer) {
+        if let Some(answer_index) = self.forest.tables[table].push_answer(answer, priority) {
+            self.forest.counters.answers += 1;
+            let table = &mut self.forest.tables[table];
+            if let Some(trace) = &mut table.trace {
+                trace.answer_clauses.push(clause);
+            }
+
             // See above, if we have a *complete* and trivial answer, we don't
             // want to follow any more strands
             if !ambiguous && is_trivial_answer {
-                self.forest.tables[table].take_strands();
+                if let Some(trace) = &mut table.trace {
+                    trace.cut = true;
+                }
+                table.take_strands();
             }
 
             Some(answer_index)
@@ -1574,6 +1705,22 @@ impl<'forest, I: Interner> SolveState<'forest, I> {
         }
     }
 
+    /// Records (once) that a strand of `table` that came from `clause`
+    /// selected `subgoal`.
+    fn trace_subgoal(
+        &mut self,
+        table: TableIndex,
+        clause: Option<usize>,
+        subgoal: TracedSubgoal<I>,
+    ) {
+        if let Some(trace) = &mut self.forest.tables[table].trace {
+            let edge = (clause, subgoal);
+            if !trace.subgoals.contains(&edge) {
+                trace.subgoals.push(edge);
+            }
+        }
+    }
+
     fn reconsider_floundered_subgoals(&mut self, ex_clause: &mut ExClause<I>) {
         info!("reconsider_floundered_subgoals(ex_clause={:#?})", ex_clause,);
         let ExClause {
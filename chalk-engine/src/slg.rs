@@ -291,8 +291,16 @@ impl<I: Interner> MayInvalidate<I> {
                 !c1.const_eq(new_ty, c2, interner)
             }
 
-            // Only variants left are placeholder = concrete, which always fails
-            (ConstValue::Placeholder(_), _) | (ConstValue::Concrete(_), _) => true,
+            (ConstValue::Expr(e1), ConstValue::Expr(e2)) => {
+                e1.op != e2.op
+                    || self.aggregate_consts(&e1.lhs, &e2.lhs)
+                    || self.aggregate_consts(&e1.rhs, &e2.rhs)
+            }
+
+            // Only variants left are mismatched kinds of values, which always fail
+            (ConstValue::Placeholder(_), _)
+            | (ConstValue::Concrete(_), _)
+            | (ConstValue::Expr(_), _) => true,
         }
     }
 
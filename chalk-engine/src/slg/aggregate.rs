@@ -548,9 +548,25 @@ impl<I: Interner> AntiUnifier<'_, I> {
                 }
             }
 
-            (ConstValue::Placeholder(_), _) | (_, ConstValue::Placeholder(_)) => {
-                self.new_const_variable(ty)
+            (ConstValue::Expr(e1), ConstValue::Expr(e2)) if e1.op == e2.op => {
+                let lhs = self.aggregate_consts(&e1.lhs, &e2.lhs);
+                let rhs = self.aggregate_consts(&e1.rhs, &e2.rhs);
+                ConstData {
+                    ty,
+                    value: ConstValue::Expr(ConstExpr {
+                        op: e1.op,
+                        lhs,
+                        rhs,
+                    }),
+                }
+                .intern(interner)
             }
+
+            (ConstValue::Placeholder(_), _)
+            | (_, ConstValue::Placeholder(_))
+            | (ConstValue::Concrete(_), _)
+            | (_, ConstValue::Concrete(_))
+            | (ConstValue::Expr(_), _) => self.new_const_variable(ty),
         }
     }
 
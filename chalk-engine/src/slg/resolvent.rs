@@ -8,6 +8,7 @@ use chalk_ir::interner::{HasInterner, Interner};
 use chalk_ir::zip::{Zip, Zipper};
 use chalk_ir::*;
 use chalk_solve::infer::InferenceTable;
+use chalk_solve::solve::regions::in_goal_environment;
 use tracing::{debug, instrument};
 
 ///////////////////////////////////////////////////////////////////////////
@@ -118,7 +119,12 @@ impl<I: Interner> ResolventOps<I> for InferenceTable<I> {
 
         ex_clause
             .constraints
-            .extend(constraints.as_slice(interner).to_owned());
+            .extend(constraints.iter(interner).map(|constraint| {
+                InEnvironment::new(
+                    &in_goal_environment(interner, environment, &constraint.environment),
+                    constraint.goal.clone(),
+                )
+            }));
 
         // Add the `conditions` from the program clause into the result too.
         ex_clause
@@ -686,6 +692,11 @@ impl<'i, I: Interner> Zipper<I> for AnswerSubstitutor<'i, I> {
                 Ok(())
             }
 
+            (ConstValue::Expr(e1), ConstValue::Expr(e2)) if e1.op == e2.op => {
+                self.zip_consts(variance, &e1.lhs, &e2.lhs)?;
+                self.zip_consts(variance, &e1.rhs, &e2.rhs)
+            }
+
             (ConstValue::InferenceVar(_), _) | (_, ConstValue::InferenceVar(_)) => panic!(
                 "unexpected inference var in answer `{:?}` or pending goal `{:?}`",
                 answer, pending,
@@ -693,7 +704,8 @@ impl<'i, I: Interner> Zipper<I> for AnswerSubstitutor<'i, I> {
 
             (ConstValue::BoundVar(_), _)
             | (ConstValue::Placeholder(_), _)
-            | (ConstValue::Concrete(_), _) => panic!(
+            | (ConstValue::Concrete(_), _)
+            | (ConstValue::Expr(_), _) => panic!(
                 "structural mismatch between answer `{:?}` and pending goal `{:?}`",
                 answer, pending,
             ),
@@ -1,9 +1,13 @@
+use crate::cache::AnswerCache;
 use crate::context::{AnswerResult, AnswerStream};
-use crate::forest::Forest;
+use crate::forest::{Forest, ForestStats};
 use crate::slg::aggregate::AggregateOps;
 use crate::slg::SlgContextOps;
 use chalk_ir::interner::Interner;
 use chalk_ir::{Canonical, ConstrainedSubst, Goal, InEnvironment, UCanonical};
+use chalk_solve::solve::budget::{Budget, BudgetExhausted, BudgetTracker};
+use chalk_solve::solve::proof_tree::ProofTree;
+use chalk_solve::solve::stats::SolverStats;
 use chalk_solve::{RustIrDatabase, Solution, Solver, SubstitutionResult};
 
 use std::fmt;
@@ -22,6 +26,37 @@ impl<I: Interner> SLGSolver<I> {
             expected_answers,
         }
     }
+
+    /// Keeps at most `capacity` tables between queries: when a query
+    /// starts with more, the least recently used tables that have found
+    /// all of their answers are collected, and their memory is reused by
+    /// later tables. Their goals are solved again if they come up in a
+    /// later query.
+    pub fn with_table_capacity(mut self, capacity: usize) -> Self {
+        self.forest.table_capacity = Some(capacity);
+        self
+    }
+
+    /// Shares the answers of the tables this solver completes with other
+    /// solvers using `cache`, and reuses the answers they found: see
+    /// `AnswerCache`.
+    pub fn with_answer_cache(mut self, cache: AnswerCache<I>) -> Self {
+        self.forest.answer_cache = Some(cache);
+        self
+    }
+
+    /// Collects every table that has found all of its answers, and returns
+    /// the number of tables collected. Tables that are still being solved
+    /// (after a query stopped early) are kept, along with the tables they
+    /// depend on.
+    pub fn collect_garbage(&mut self) -> usize {
+        self.forest.collect_tables(0)
+    }
+
+    /// Statistics about the memory used by the tables of the solver.
+    pub fn memory_stats(&self) -> ForestStats {
+        self.forest.stats()
+    }
 }
 
 impl<I: Interner> fmt::Debug for SLGSolver<I> {
@@ -50,6 +85,22 @@ impl<I: Interner> Solver<I> for SLGSolver<I> {
         ops.make_solution(goal, self.forest.iter_answers(&ops, goal), should_continue)
     }
 
+    fn solve_with_budget(
+        &mut self,
+        program: &dyn RustIrDatabase<I>,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+        budget: &Budget,
+    ) -> Result<Option<Solution<I>>, BudgetExhausted> {
+        let tracker = BudgetTracker::new(budget);
+        let ops = SlgContextOps::new(program, self.max_size, self.expected_answers);
+        let solution = ops.make_solution(
+            goal,
+            self.forest.iter_answers_within(&ops, goal, &tracker),
+            || tracker.should_continue(),
+        );
+        tracker.finish(solution)
+    }
+
     fn solve_multiple(
         &mut self,
         program: &dyn RustIrDatabase<I>,
@@ -86,4 +137,21 @@ impl<I: Interner> Solver<I> for SLGSolver<I> {
             }
         }
     }
+
+    fn explain(
+        &mut self,
+        program: &dyn RustIrDatabase<I>,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+    ) -> ProofTree<I> {
+        // Solve in a fresh forest, so that every table the goal depends on
+        // records how it was solved.
+        let ops = SlgContextOps::new(program, self.max_size, self.expected_answers);
+        let mut forest = Forest::new_traced();
+        let solution = ops.make_solution(goal, forest.iter_answers(&ops, goal), || true);
+        forest.proof_tree(&ops, goal, solution)
+    }
+
+    fn stats(&self) -> SolverStats<I> {
+        self.forest.counters.clone()
+    }
 }
@@ -5,7 +5,7 @@ use std::fmt::Debug;
 use chalk_derive::HasInterner;
 use chalk_ir::fold::{FallibleTypeFolder, TypeFoldable};
 use chalk_ir::interner::Interner;
-use chalk_ir::{Canonical, DebruijnIndex, UniverseMap};
+use chalk_ir::{Canonical, ClausePriority, DebruijnIndex, UniverseMap};
 
 #[derive(Clone, Debug, HasInterner)]
 pub(crate) struct Strand<I: Interner> {
@@ -15,6 +15,14 @@ pub(crate) struct Strand<I: Interner> {
     pub(crate) selected_subgoal: Option<SelectedSubgoal>,
 
     pub(crate) last_pursued_time: TimeStamp,
+
+    /// The index of the program clause (in the table's `TableTrace`) that
+    /// this strand was created from, if any.
+    pub(crate) clause: Option<usize>,
+
+    /// The priority of the program clause this strand was created from
+    /// (see `Table::priority`).
+    pub(crate) priority: ClausePriority,
 }
 
 pub(crate) type CanonicalStrand<I> = Canonical<Strand<I>>;
@@ -45,6 +53,8 @@ impl<I: Interner> TypeFoldable<I> for Strand<I> {
             ex_clause: self.ex_clause.try_fold_with(folder, outer_binder)?,
             last_pursued_time: self.last_pursued_time,
             selected_subgoal: self.selected_subgoal,
+            clause: self.clause,
+            priority: self.priority,
         })
     }
 }
@@ -1,13 +1,22 @@
+use crate::cache::CachedAnswers;
 use crate::index_struct;
 use crate::strand::CanonicalStrand;
-use crate::{Answer, AnswerMode};
+use crate::{Answer, AnswerMode, TableIndex};
 use rustc_hash::FxHashMap;
 use std::collections::hash_map::Entry;
 use std::collections::VecDeque;
 use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use chalk_ir::interner::Interner;
-use chalk_ir::{AnswerSubst, Canonical, Goal, InEnvironment, UCanonical};
+use chalk_ir::{
+    AnswerSubst, Canonical, ClausePriority, ConstrainedSubst, Goal, GoalData, InEnvironment,
+    ProgramClause, UCanonical,
+};
+use chalk_solve::solve::leak_check::PlaceholderLeak;
+use chalk_solve::solve::proof_tree::ProofOutcome;
+use chalk_solve::solve::{Guidance, Solution};
 use tracing::{debug, info, instrument};
 
 #[derive(Debug)]
@@ -44,7 +53,100 @@ pub(crate) struct Table<I: Interner> {
     /// answers.
     strands: VecDeque<CanonicalStrand<I>>,
 
+    /// The number of strands queued in all the tables of the forest, which
+    /// this table keeps up to date (see `Tables::pending_strands`).
+    pending_strands: Arc<AtomicUsize>,
+
     pub(crate) answer_mode: AnswerMode,
+
+    /// The strands from low-priority clauses are only pursued once this is
+    /// `Low`, after the strands from high-priority clauses ran out. Their
+    /// answers are dropped if the high-priority answers override them (see
+    /// `Table::overrides`), as the recursive solver does.
+    pub(crate) priority: ClausePriority,
+
+    /// The priority of the strand that found each answer, indexed like
+    /// `answers`.
+    answer_priorities: Vec<ClausePriority>,
+
+    /// What happened while solving this table; only recorded when the
+    /// forest is building a proof tree (see `Forest::new_traced`).
+    pub(crate) trace: Option<TableTrace<I>>,
+
+    /// When the table was last used (see `Tables::touch`), to decide which
+    /// tables to collect first.
+    pub(crate) last_used: u64,
+}
+
+/// A record of how a table was solved, from which a proof tree can be
+/// reconstructed once solving is done.
+#[derive(Debug)]
+pub(crate) struct TableTrace<I: Interner> {
+    /// The program clauses tried for the table goal, and whether each one
+    /// unified with it. Strands remember the index of the clause they came
+    /// from.
+    pub(crate) clauses: Vec<(ProgramClause<I>, bool)>,
+
+    /// The subgoals that strands of this table selected, along with the
+    /// clause each strand came from.
+    pub(crate) subgoals: Vec<(Option<usize>, TracedSubgoal<I>)>,
+
+    /// The clause that produced each answer, indexed like `answers`.
+    pub(crate) answer_clauses: Vec<Option<usize>>,
+
+    /// The answers that were dropped by the leak check, along with the
+    /// clause that produced each one.
+    pub(crate) leaks: Vec<(Option<usize>, PlaceholderLeak<I>)>,
+
+    /// True if an answer was too large, causing the table to flounder.
+    pub(crate) truncated: bool,
+
+    /// True if the remaining strands were dropped after a trivial answer
+    /// was found.
+    pub(crate) cut: bool,
+}
+
+/// A subgoal selected by a strand.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum TracedSubgoal<I: Interner> {
+    /// The subgoal was solved by the given table.
+    Table(TableIndex),
+
+    /// No table could be created for the subgoal, because it was too large
+    /// or because it was a negative goal with unresolved variables.
+    Unsolved(UCanonical<InEnvironment<Goal<I>>>, ProofOutcome<I>),
+}
+
+impl<I: Interner> Default for TableTrace<I> {
+    fn default() -> Self {
+        TableTrace {
+            clauses: vec![],
+            subgoals: vec![],
+            answer_clauses: vec![],
+            leaks: vec![],
+            truncated: false,
+            cut: false,
+        }
+    }
+}
+
+/// The solution that `answer` alone would give for the table goal.
+fn answer_solution<I: Interner>(answer: &Answer<I>) -> Solution<I> {
+    let Canonical { binders, value } = answer.subst.clone();
+    if answer.ambiguous {
+        Solution::Ambig(Guidance::Definite(Canonical {
+            binders,
+            value: value.subst,
+        }))
+    } else {
+        Solution::Unique(Canonical {
+            binders,
+            value: ConstrainedSubst {
+                subst: value.subst,
+                constraints: value.constraints,
+            },
+        })
+    }
 }
 
 index_struct! {
@@ -65,13 +167,55 @@ impl<I: Interner> Table<I> {
             floundered: false,
             answers_hash: FxHashMap::default(),
             strands: VecDeque::new(),
+            pending_strands: Arc::default(),
             answer_mode: AnswerMode::Complete,
+            priority: ClausePriority::High,
+            answer_priorities: Vec::new(),
+            trace: None,
+            last_used: 0,
+        }
+    }
+
+    /// Creates a complete table from the answers found by another forest
+    /// (see `AnswerCache`).
+    pub(crate) fn from_cache(
+        table_goal: UCanonical<InEnvironment<Goal<I>>>,
+        coinductive_goal: bool,
+        cached: &CachedAnswers<I>,
+    ) -> Table<I> {
+        let mut table = Table::new(table_goal, coinductive_goal);
+        table.floundered = cached.floundered;
+        table.answers = cached.answers.clone();
+        table.answer_priorities = vec![ClausePriority::High; table.answers.len()];
+        table
+    }
+
+    /// The answers of this table, for the answer cache. Only meaningful
+    /// once the table is complete.
+    pub(crate) fn cached_answers(&self) -> CachedAnswers<I> {
+        CachedAnswers {
+            answers: self.answers.clone(),
+            floundered: self.floundered,
         }
     }
 
+    /// Counts the strands of this table in `counter` rather than in the
+    /// counter it used so far.
+    pub(crate) fn set_strand_counter(&mut self, counter: Arc<AtomicUsize>) {
+        self.strands_removed(self.strands.len());
+        self.pending_strands = counter;
+        self.pending_strands
+            .fetch_add(self.strands.len(), Ordering::Relaxed);
+    }
+
+    fn strands_removed(&self, removed: usize) {
+        self.pending_strands.fetch_sub(removed, Ordering::Relaxed);
+    }
+
     /// Push a strand to the back of the queue of strands to be processed.
     pub(crate) fn enqueue_strand(&mut self, strand: CanonicalStrand<I>) {
         self.strands.push_back(strand);
+        self.pending_strands.fetch_add(1, Ordering::Relaxed);
     }
 
     pub(crate) fn strands_mut(&mut self) -> impl Iterator<Item = &mut CanonicalStrand<I>> {
@@ -83,6 +227,7 @@ impl<I: Interner> Table<I> {
     }
 
     pub(crate) fn take_strands(&mut self) -> VecDeque<CanonicalStrand<I>> {
+        self.strands_removed(self.strands.len());
         mem::take(&mut self.strands)
     }
 
@@ -93,6 +238,7 @@ impl<I: Interner> Table<I> {
     ) -> Option<CanonicalStrand<I>> {
         let first = self.strands.iter().position(test);
         if let Some(first) = first {
+            self.strands_removed(1);
             self.strands.rotate_left(first);
             self.strands.pop_front()
         } else {
@@ -100,11 +246,43 @@ impl<I: Interner> Table<I> {
         }
     }
 
+    /// A table is complete once it has no strands left: all of its answers
+    /// have been found.
+    pub(crate) fn is_complete(&self) -> bool {
+        self.strands.is_empty()
+    }
+
+    /// The tables whose indices this table holds: the tables its strands
+    /// are waiting on, and the tables its trace refers to.
+    pub(crate) fn referenced_tables(&self) -> impl Iterator<Item = TableIndex> + '_ {
+        let waited_on = self.strands.iter().filter_map(|strand| {
+            strand
+                .value
+                .selected_subgoal
+                .as_ref()
+                .map(|selected| selected.subgoal_table)
+        });
+        let traced = self
+            .trace
+            .iter()
+            .flat_map(|trace| trace.subgoals.iter())
+            .filter_map(|(_, subgoal)| match subgoal {
+                TracedSubgoal::Table(table) => Some(*table),
+                TracedSubgoal::Unsolved(..) => None,
+            });
+        waited_on.chain(traced)
+    }
+
+    /// The number of answers found so far.
+    pub(crate) fn num_answers(&self) -> usize {
+        self.answers.len()
+    }
+
     /// Mark the table as floundered -- this also discards all pre-existing answers,
     /// as they are no longer relevant.
     pub(crate) fn mark_floundered(&mut self) {
         self.floundered = true;
-        self.strands = Default::default();
+        self.take_strands();
         self.answers = Default::default();
     }
 
@@ -124,7 +302,11 @@ impl<I: Interner> Table<I> {
     /// `true` is returned here, that a *new* answer was added (instead of an)
     /// existing answer replaced.
     #[instrument(level = "debug", skip(self))]
-    pub(super) fn push_answer(&mut self, answer: Answer<I>) -> Option<AnswerIndex> {
+    pub(super) fn push_answer(
+        &mut self,
+        answer: Answer<I>,
+        priority: ClausePriority,
+    ) -> Option<AnswerIndex> {
         assert!(!self.floundered);
         debug!(
             "pre-existing entry: {:?}",
@@ -156,9 +338,40 @@ impl<I: Interner> Table<I> {
 
         let index = self.answers.len();
         self.answers.push(answer);
+        self.answer_priorities.push(priority);
         Some(AnswerIndex::from(index))
     }
 
+    /// The priority of the strand that found the answer at `index`.
+    pub(crate) fn answer_priority(&self, index: AnswerIndex) -> ClausePriority {
+        self.answer_priorities[index.value]
+    }
+
+    /// Whether the answers found so far by strands from high-priority
+    /// clauses override `answer`, found by a strand from a low-priority
+    /// clause: see `Solution::overrides`.
+    pub(crate) fn overrides(&self, interner: I, answer: &Answer<I>) -> bool {
+        let domain_goal = match self.table_goal.canonical.value.goal.data(interner) {
+            GoalData::DomainGoal(domain_goal) => domain_goal,
+            _ => return false,
+        };
+        let high = self
+            .answers
+            .iter()
+            .zip(&self.answer_priorities)
+            .filter(|(_, &priority)| priority == ClausePriority::High)
+            .map(|(answer, _)| answer_solution(answer))
+            .reduce(|high, solution| high.combine(solution, interner));
+        match high {
+            Some(high) => high.overrides(interner, domain_goal, &answer_solution(answer)),
+            None => false,
+        }
+    }
+
+    pub(crate) fn answers(&self) -> impl Iterator<Item = &Answer<I>> {
+        self.answers.iter()
+    }
+
     pub(super) fn answer(&self, index: AnswerIndex) -> Option<&Answer<I>> {
         self.answers.get(index.value)
     }
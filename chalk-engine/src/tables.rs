@@ -2,6 +2,8 @@ use crate::table::Table;
 use crate::TableIndex;
 use rustc_hash::FxHashMap;
 use std::ops::{Index, IndexMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use chalk_ir::interner::Interner;
 use chalk_ir::{Goal, InEnvironment, UCanonical};
@@ -13,30 +15,79 @@ pub(crate) struct Tables<I: Interner> {
     table_indices: FxHashMap<UCanonical<InEnvironment<Goal<I>>>, TableIndex>,
 
     /// Table: as described above, stores the key information for each
-    /// tree in the forest.
-    tables: Vec<Table<I>>,
+    /// tree in the forest. The slots of tables that were collected (see
+    /// `Forest::collect_tables`) are empty until a new table takes them.
+    slots: Vec<Slot<I>>,
+
+    /// The empty slots.
+    free_slots: Vec<usize>,
+
+    /// The number of tables ever inserted, including the collected ones.
+    inserted: usize,
+
+    /// Incremented each time a table is used; see `touch`.
+    uses: u64,
+
+    /// The number of strands queued in the tables, which the tables update
+    /// as they enqueue and drop strands.
+    pending_strands: Arc<AtomicUsize>,
 }
 
 impl<I: Interner> Tables<I> {
     pub(crate) fn new() -> Tables<I> {
         Tables {
             table_indices: FxHashMap::default(),
-            tables: Vec::default(),
+            slots: Vec::default(),
+            free_slots: Vec::default(),
+            inserted: 0,
+            uses: 0,
+            pending_strands: Arc::default(),
         }
     }
 
     /// The index that will be given to the next table to be inserted.
     pub(super) fn next_index(&self) -> TableIndex {
-        TableIndex {
-            value: self.tables.len(),
+        match self.free_slots.last() {
+            Some(&value) => TableIndex {
+                value,
+                generation: self.slots[value].generation,
+            },
+            None => TableIndex {
+                value: self.slots.len(),
+                generation: 0,
+            },
         }
     }
 
-    pub(super) fn insert(&mut self, table: Table<I>) -> TableIndex {
+    /// The number of tables ever inserted, including the collected ones.
+    pub(crate) fn len(&self) -> usize {
+        self.inserted
+    }
+
+    /// The number of tables that were not collected.
+    pub(crate) fn live_len(&self) -> usize {
+        self.table_indices.len()
+    }
+
+    /// The number of strands queued in all the tables.
+    pub(crate) fn pending_strands(&self) -> usize {
+        self.pending_strands.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn insert(&mut self, mut table: Table<I>) -> TableIndex {
+        table.set_strand_counter(self.pending_strands.clone());
         let goal = table.table_goal.clone();
         let index = self.next_index();
-        self.tables.push(table);
+        match self.free_slots.pop() {
+            Some(value) => self.slots[value].table = Some(table),
+            None => self.slots.push(Slot {
+                generation: 0,
+                table: Some(table),
+            }),
+        }
+        self.inserted += 1;
         self.table_indices.insert(goal, index);
+        self.touch(index);
         index
     }
 
@@ -46,27 +97,116 @@ impl<I: Interner> Tables<I> {
     ) -> Option<TableIndex> {
         self.table_indices.get(literal).cloned()
     }
+
+    /// Records that `index` was just used, for the least recently used
+    /// tables to be collected first.
+    pub(super) fn touch(&mut self, index: TableIndex) {
+        self.uses += 1;
+        self[index].last_used = self.uses;
+    }
+
+    /// Drops the table at `index`. Its slot is given to a later table, with
+    /// an index of the next generation; a later request for its goal
+    /// creates a new table, with a new index.
+    pub(super) fn remove(&mut self, index: TableIndex) -> Table<I> {
+        let slot = &mut self.slots[index.value];
+        assert_eq!(
+            slot.generation, index.generation,
+            "table was already collected"
+        );
+        let mut table = slot.table.take().expect("table was already collected");
+        slot.generation += 1;
+        self.free_slots.push(index.value);
+        self.table_indices.remove(&table.table_goal);
+        table.set_strand_counter(Arc::default());
+        table
+    }
+
+    /// The table at `index`, or `None` if it was collected.
+    pub(crate) fn get(&self, index: TableIndex) -> Option<&Table<I>> {
+        let slot = self.slots.get(index.value)?;
+        if slot.generation != index.generation {
+            return None;
+        }
+        slot.table.as_ref()
+    }
+
+    /// The tables that were not collected, with their indices.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (TableIndex, &Table<I>)> {
+        self.slots.iter().enumerate().filter_map(|(value, slot)| {
+            let index = TableIndex {
+                value,
+                generation: slot.generation,
+            };
+            Some((index, slot.table.as_ref()?))
+        })
+    }
 }
 
+/// A slot of `Tables`, which holds one table after the other.
+#[derive(Debug)]
+struct Slot<I: Interner> {
+    /// Incremented each time the table of the slot is collected.
+    generation: u32,
+    table: Option<Table<I>>,
+}
+
+/// Panics if the table at the index was collected. The forest never
+/// collects a table whose index it holds (see `Forest::collect_tables`);
+/// use `Tables::get` for an index that may be stale.
 impl<I: Interner> Index<TableIndex> for Tables<I> {
     type Output = Table<I>;
 
     fn index(&self, index: TableIndex) -> &Table<I> {
-        &self.tables[index.value]
+        self.get(index).expect("table was collected")
     }
 }
 
 impl<I: Interner> IndexMut<TableIndex> for Tables<I> {
     fn index_mut(&mut self, index: TableIndex) -> &mut Table<I> {
-        &mut self.tables[index.value]
+        match self.slots.get_mut(index.value) {
+            Some(slot) if slot.generation == index.generation => slot.table.as_mut(),
+            _ => None,
+        }
+        .expect("table was collected")
     }
 }
 
-impl<'a, I: Interner> IntoIterator for &'a mut Tables<I> {
-    type IntoIter = <&'a mut Vec<Table<I>> as IntoIterator>::IntoIter;
-    type Item = <&'a mut Vec<Table<I>> as IntoIterator>::Item;
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chalk_integration::interner::ChalkIr;
+    use chalk_ir::{Canonical, CanonicalVarKinds, Environment, GoalData, Goals};
+
+    fn table(goal: GoalData<ChalkIr>) -> Table<ChalkIr> {
+        let goal = UCanonical {
+            canonical: Canonical {
+                value: InEnvironment::new(&Environment::new(ChalkIr), goal.intern(ChalkIr)),
+                binders: CanonicalVarKinds::empty(ChalkIr),
+            },
+            universes: 1,
+        };
+        Table::new(goal, false)
+    }
+
+    #[test]
+    fn collected_index() {
+        let mut tables = Tables::new();
+        let first = tables.insert(table(GoalData::CannotProve));
+        tables.remove(first);
+        assert!(tables.get(first).is_none());
+        assert_eq!(
+            tables.index_of(&table(GoalData::CannotProve).table_goal),
+            None
+        );
 
-    fn into_iter(self) -> Self::IntoIter {
-        IntoIterator::into_iter(&mut self.tables)
+        // The next table takes the slot of the collected one, which the
+        // index of the collected table does not refer to.
+        let second = tables.insert(table(GoalData::All(Goals::empty(ChalkIr))));
+        assert_eq!(second.value, first.value);
+        assert_ne!(second, first);
+        assert!(tables.get(first).is_none());
+        assert!(tables.get(second).is_some());
+        assert_eq!((tables.len(), tables.live_len()), (2, 1));
     }
 }
@@ -0,0 +1,143 @@
+//! Solving the same goals with several solvers and reporting where their
+//! solutions diverge.
+
+use crate::{
+    db::ChalkDatabase, error::ChalkError, interner::ChalkIr, query::LoweringDatabase, tls,
+    SolverChoice,
+};
+use chalk_ir::{Constraints, Goal, InEnvironment, UCanonical};
+use chalk_solve::ext::GoalExt;
+use chalk_solve::{RustIrDatabase, Solution};
+use std::fmt;
+
+/// The solutions that each of a set of solvers found for a goal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SolverComparison {
+    pub goal: String,
+
+    /// The solution found by each solver, in the order the solvers were
+    /// given. Lifetime constraints are sorted, since the solvers may produce
+    /// them in different orders.
+    pub solutions: Vec<(SolverChoice, Option<Solution<ChalkIr>>)>,
+}
+
+impl SolverComparison {
+    /// Whether any two of the solvers found different solutions.
+    pub fn diverges(&self) -> bool {
+        self.solutions.windows(2).any(|pair| pair[0].1 != pair[1].1)
+    }
+}
+
+impl fmt::Display for SolverComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.goal)?;
+        for (solver_choice, solution) in &self.solutions {
+            match solution {
+                Some(solution) => {
+                    writeln!(f, "  {:?}: {}", solver_choice, solution.display(ChalkIr))?
+                }
+                None => writeln!(f, "  {:?}: No possible solution", solver_choice)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A goal of a corpus that the solvers disagree on, or that could not be
+/// solved at all.
+#[derive(Clone, Debug)]
+pub enum Divergence {
+    /// The solvers found different solutions for the goal.
+    Solutions(SolverComparison),
+
+    /// The goal could not be parsed or lowered.
+    Error { goal: String, error: ChalkError },
+}
+
+impl Divergence {
+    /// The text of the goal.
+    pub fn goal(&self) -> &str {
+        match self {
+            Divergence::Solutions(comparison) => &comparison.goal,
+            Divergence::Error { goal, .. } => goal,
+        }
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Solutions(comparison) => comparison.fmt(f),
+            Divergence::Error { goal, error } => {
+                writeln!(f, "{}", goal)?;
+                writeln!(f, "  error: {}", error)
+            }
+        }
+    }
+}
+
+impl ChalkDatabase {
+    /// Solves `goal` from scratch with each of `solver_choices`.
+    pub fn compare_solvers(
+        &self,
+        goal_text: &str,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+        solver_choices: &[SolverChoice],
+    ) -> SolverComparison {
+        let solutions = solver_choices
+            .iter()
+            .map(|&solver_choice| {
+                let solution = solver_choice.into_solver().solve(self, goal);
+                (solver_choice, solution.map(sort_constraints))
+            })
+            .collect();
+        SolverComparison {
+            goal: goal_text.to_string(),
+            solutions,
+        }
+    }
+}
+
+/// Solves each of `goals` in the program `program_text` with each of
+/// `solver_choices`, and returns the goals the solvers disagree on, in
+/// order. A goal that fails to parse or lower is reported as well, and the
+/// remaining goals are still compared; only an error in the program itself
+/// stops the comparison.
+pub fn divergent_goals<'a>(
+    program_text: &str,
+    goals: impl IntoIterator<Item = &'a str>,
+    solver_choices: &[SolverChoice],
+) -> Result<Vec<Divergence>, ChalkError> {
+    let db = ChalkDatabase::with(program_text, SolverChoice::default());
+    let program = db.checked_program()?;
+    tls::set_current_program(&program, || {
+        let mut divergent = vec![];
+        for goal_text in goals {
+            let goal = match db.parse_and_lower_goal(goal_text) {
+                Ok(goal) => goal,
+                Err(error) => {
+                    divergent.push(Divergence::Error {
+                        goal: goal_text.to_string(),
+                        error,
+                    });
+                    continue;
+                }
+            };
+            let peeled_goal = goal.into_peeled_goal(db.interner());
+            let comparison = db.compare_solvers(goal_text, &peeled_goal, solver_choices);
+            if comparison.diverges() {
+                divergent.push(Divergence::Solutions(comparison));
+            }
+        }
+        Ok(divergent)
+    })
+}
+
+fn sort_constraints(mut solution: Solution<ChalkIr>) -> Solution<ChalkIr> {
+    if let Solution::Unique(constrained) = &mut solution {
+        let mut sorted = constrained.value.constraints.as_slice(ChalkIr).to_vec();
+        sorted.sort_by_key(|c| format!("{:?}", c));
+        constrained.value.constraints = Constraints::from_iter(ChalkIr, sorted);
+    }
+    solution
+}
@@ -1,4 +1,5 @@
 use crate::{
+    edit::ItemEdit,
     error::ChalkError,
     interner::ChalkIr,
     lowering::lower_goal,
@@ -7,18 +8,22 @@ use crate::{
     tls, SolverChoice,
 };
 use chalk_ir::{
-    AdtId, AssocTypeId, Binders, Canonical, CanonicalVarKinds, ClosureId, ConstrainedSubst,
-    CoroutineId, Environment, FnDefId, GenericArg, Goal, ImplId, InEnvironment, OpaqueTyId,
-    ProgramClause, ProgramClauses, Substitution, TraitId, Ty, TyKind, UCanonical,
-    UnificationDatabase, Variances,
+    AdtId, AssocTypeId, Binders, Canonical, CanonicalVarKinds, ClosureId, ConcreteConst,
+    ConstBinOp, ConstrainedSubst, CoroutineId, Environment, FnDefId, GenericArg, Goal, ImplId,
+    InEnvironment, OpaqueTyId, ProgramClause, ProgramClauses, Substitution, TraitId, Ty, TyKind,
+    UCanonical, UnificationDatabase, Variances,
 };
 use chalk_solve::rust_ir::{
     AdtDatum, AdtRepr, AdtSizeAlign, AssociatedTyDatum, AssociatedTyValue, AssociatedTyValueId,
     ClosureKind, CoroutineDatum, CoroutineWitnessDatum, FnDefDatum, FnDefInputsAndOutputDatum,
     ImplDatum, OpaqueTyDatum, TraitDatum, WellKnownAssocType, WellKnownTrait,
 };
+use chalk_solve::solve::budget::{Budget, BudgetExhausted};
+use chalk_solve::solve::proof_tree::ProofTree;
+use chalk_solve::solve::stats::SolverStats;
 use chalk_solve::{RustIrDatabase, Solution, SubstitutionResult};
 use salsa::Database;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::sync::Arc;
 
@@ -34,10 +39,50 @@ impl ChalkDatabase {
     pub fn with(program_text: &str, solver_choice: SolverChoice) -> Self {
         let mut db = ChalkDatabase::default();
         db.set_program_text(Arc::new(program_text.to_string()));
+        db.set_program_dir(None);
+        db.set_included_files(Default::default());
         db.set_solver_choice(solver_choice);
+        db.set_persistent_cache_dir(None);
         db
     }
 
+    /// Reads the files that the program includes from disk, into the
+    /// `included_files` input. The program only sees the files as they were
+    /// when this was last called, so it must be called again after
+    /// `program_text` or `program_dir` is set, or an included file changes.
+    /// Errors reading the files are reported when the program is lowered.
+    pub fn load_included_files(&mut self) {
+        let mut files = BTreeMap::new();
+        if let Some(dir) = self.program_dir() {
+            let (mut program, _) = chalk_parse::parse_program_recovering(&self.program_text());
+            // Expanding the includes stops at the first error, just as it
+            // does when the program is lowered.
+            let _ = chalk_parse::expand_includes(&mut program, &dir, &mut |path| {
+                let file = chalk_parse::read_included_file(path);
+                files.insert(path.to_path_buf(), file.clone());
+                file
+            });
+        }
+        self.set_included_files(Arc::new(files));
+    }
+
+    /// Applies `edit` to the `program_text` input, and returns the previous
+    /// text, with which the edit can be undone. The whole program is lowered
+    /// and checked again when it is next needed, and the files it includes
+    /// are read again.
+    pub fn edit_program(&mut self, edit: ItemEdit<'_>) -> Result<Arc<String>, ChalkError> {
+        let text = self.program_text();
+        self.set_program_text(Arc::new(edit.apply(&text)?));
+        self.load_included_files();
+        Ok(text)
+    }
+
+    /// The program that the solver is given. Unlike the database, it can be
+    /// shared with the threads of a solver that solves goals in parallel.
+    fn solver_program(&self) -> Arc<Program> {
+        self.program_ir().unwrap()
+    }
+
     pub fn with_program<R>(&self, op: impl FnOnce(&Program) -> R) -> R {
         let program = &self.checked_program().unwrap();
         tls::set_current_program(program, || op(program))
@@ -52,8 +97,24 @@ impl ChalkDatabase {
         &self,
         goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
     ) -> Option<Solution<ChalkIr>> {
+        let program = self.solver_program();
+        let solver = self.solver();
+        let solution = solver.lock().unwrap().solve(&*program, goal);
+        solution
+    }
+
+    /// Like `solve`, but gives up once the solver has used up `budget`.
+    pub fn solve_with_budget(
+        &self,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+        budget: &Budget,
+    ) -> Result<Option<Solution<ChalkIr>>, BudgetExhausted> {
+        let program = self.solver_program();
         let solver = self.solver();
-        let solution = solver.lock().unwrap().solve(self, goal);
+        let solution = solver
+            .lock()
+            .unwrap()
+            .solve_with_budget(&*program, goal, budget);
         solution
     }
 
@@ -66,10 +127,28 @@ impl ChalkDatabase {
         goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
         f: &mut dyn FnMut(SubstitutionResult<Canonical<ConstrainedSubst<ChalkIr>>>, bool) -> bool,
     ) -> bool {
+        let program = self.solver_program();
         let solver = self.solver();
-        let solution = solver.lock().unwrap().solve_multiple(self, goal, f);
+        let solution = solver.lock().unwrap().solve_multiple(&*program, goal, f);
         solution
     }
+
+    /// Solves a given goal from scratch, recording how the solution was
+    /// reached. See [`chalk_solve::Solver::explain`].
+    pub fn explain(&self, goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>) -> ProofTree<ChalkIr> {
+        let program = self.solver_program();
+        let solver = self.solver();
+        let tree = solver.lock().unwrap().explain(&*program, goal);
+        tree
+    }
+
+    /// How much work the solver has done so far. See
+    /// [`chalk_solve::Solver::stats`].
+    pub fn solver_stats(&self) -> SolverStats<ChalkIr> {
+        let solver = self.solver();
+        let stats = solver.lock().unwrap().stats();
+        stats
+    }
 }
 
 impl UnificationDatabase<ChalkIr> for ChalkDatabase {
@@ -80,6 +159,18 @@ impl UnificationDatabase<ChalkIr> for ChalkDatabase {
     fn adt_variance(&self, adt_id: AdtId<ChalkIr>) -> Variances<ChalkIr> {
         self.program_ir().unwrap().adt_variance(adt_id)
     }
+
+    fn evaluate_const_expr(
+        &self,
+        ty: &Ty<ChalkIr>,
+        op: ConstBinOp,
+        lhs: &ConcreteConst<ChalkIr>,
+        rhs: &ConcreteConst<ChalkIr>,
+    ) -> Option<ConcreteConst<ChalkIr>> {
+        self.program_ir()
+            .unwrap()
+            .evaluate_const_expr(ty, op, lhs, rhs)
+    }
 }
 
 impl RustIrDatabase<ChalkIr> for ChalkDatabase {
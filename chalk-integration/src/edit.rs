@@ -0,0 +1,116 @@
+//! Editing the items of a program text, so that a program can be explored
+//! by adding, removing and replacing items one at a time.
+//!
+//! Items are named by the name of the definition, or, for impls, by their
+//! header (e.g. `impl<T> Clone for Vec<T>`), written as in the program up to
+//! whitespace. Modules are named by their name, and can only be edited as a
+//! whole. Clauses, `use` items and includes have no name, so they can only be
+//! added.
+//!
+//! An edit only changes the `program_text` input of the database. The
+//! checks of the program do not track which items they depend on, so the
+//! whole program is lowered and checked again after each edit.
+
+use crate::error::{ChalkError, ChalkErrorKind};
+use chalk_parse::ast::{ForeignDefn, Item, Span};
+
+/// An edit to the items of a program.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ItemEdit<'a> {
+    /// Adds the item(s) in the given text at the end of the program.
+    Add(&'a str),
+
+    /// Removes the item with the given name.
+    Remove(&'a str),
+
+    /// Replaces the item that has the same name as the item in the given
+    /// text with it.
+    Replace(&'a str),
+}
+
+impl ItemEdit<'_> {
+    /// Applies the edit to the program `text`, returning the new text. The
+    /// rest of the program is left untouched, comments included.
+    pub fn apply(&self, text: &str) -> Result<String, ChalkError> {
+        match *self {
+            ItemEdit::Add(item_text) => {
+                chalk_parse::parse_items(item_text)?;
+                let mut text = text.to_string();
+                if !text.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                text.push_str(item_text.trim());
+                text.push('\n');
+                Ok(text)
+            }
+            ItemEdit::Remove(name) => {
+                let span = find_item(text, name)?;
+                let mut hi = span.hi;
+                if text[hi..].starts_with('\n') {
+                    hi += 1;
+                }
+                Ok(format!("{}{}", &text[..span.lo], &text[hi..]))
+            }
+            ItemEdit::Replace(item_text) => {
+                let item_text = item_text.trim();
+                let name = match &chalk_parse::parse_items(item_text)?[..] {
+                    [(_, item)] => item_name(item_text, item).ok_or_else(|| {
+                        error("the item has no name, so it cannot be replaced".to_string())
+                    })?,
+                    _ => return Err(error("expected exactly one item".to_string())),
+                };
+                let span = find_item(text, &name)?;
+                Ok(format!(
+                    "{}{}{}",
+                    &text[..span.lo],
+                    item_text,
+                    &text[span.hi..]
+                ))
+            }
+        }
+    }
+}
+
+/// Finds the span of the item named `name` in the program `text`.
+fn find_item(text: &str, name: &str) -> Result<Span, ChalkError> {
+    let name = collapse_whitespace(name);
+    let mut spans = chalk_parse::parse_items(text)?
+        .into_iter()
+        .filter(|(_, item)| item_name(text, item).as_deref() == Some(&name))
+        .map(|(span, _)| span);
+    match (spans.next(), spans.next()) {
+        (Some(span), None) => Ok(span),
+        (None, _) => Err(error(format!("no item named `{}`", name))),
+        (Some(_), Some(_)) => Err(error(format!("more than one item is named `{}`", name))),
+    }
+}
+
+/// The name of `item`, which was parsed from `text`.
+fn item_name(text: &str, item: &Item) -> Option<String> {
+    let name = match item {
+        Item::AdtDefn(defn) => &defn.name,
+        Item::FnDefn(defn) => &defn.name,
+        Item::ClosureDefn(defn) => &defn.name,
+        Item::TraitDefn(defn) => &defn.name,
+        Item::OpaqueTyDefn(defn) => &defn.name,
+        Item::CoroutineDefn(defn) => &defn.name,
+        Item::Foreign(ForeignDefn(name)) => name,
+        Item::Module(defn) => &defn.name,
+        Item::Crate(defn) => &defn.name,
+        Item::TypeAlias(defn) => &defn.name,
+        Item::TraitAlias(defn) => &defn.name,
+        Item::Impl(impl_) => {
+            return Some(collapse_whitespace(&text[impl_.span.lo..impl_.span.hi]));
+        }
+        Item::Clause(_) | Item::Use(_) | Item::Include(_) => return None,
+    };
+    Some(name.str.to_string())
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn error(message: String) -> ChalkError {
+    ChalkError::new(ChalkErrorKind::Other, message, None)
+}
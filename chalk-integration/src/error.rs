@@ -1,62 +1,515 @@
 use crate::interner::ChalkIr;
+use crate::program::Program;
+use crate::RawId;
+use chalk_ir::{AdtId, ImplId, VariableKinds};
 use chalk_parse::ast::{Identifier, Kind};
+use chalk_parse::{IncludeError, SyntaxError};
+use chalk_solve::coherence::orphan::OrphanCheckViolationKind;
 use chalk_solve::coherence::CoherenceError;
 use chalk_solve::wf::WfError;
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use string_cache::DefaultAtom as Atom;
 
+pub use chalk_parse::ast::{FileId, Span};
+
+/// The text of a program and of the files it includes, which the spans of
+/// errors point into (see [`FileId`]).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceFiles {
+    program: String,
+    /// The name and text of each included file, that of `FileId(n)` being
+    /// the n-th.
+    included: Vec<(PathBuf, String)>,
+}
+
+impl SourceFiles {
+    /// The text of a program that includes no files.
+    pub fn new(program: &str) -> Self {
+        SourceFiles {
+            program: program.to_string(),
+            included: vec![],
+        }
+    }
+
+    /// Adds the next included file, named `name` in rendered errors.
+    pub fn include(&mut self, name: PathBuf, text: String) {
+        self.included.push((name, text));
+    }
+
+    /// The name and text of `file`, if known. The program has no name.
+    fn file(&self, file: FileId) -> Option<(Option<&Path>, &str)> {
+        match file.0.checked_sub(1) {
+            None => Some((None, &self.program)),
+            Some(index) => self
+                .included
+                .get(index)
+                .map(|(name, text)| (Some(name.as_path()), text.as_str())),
+        }
+    }
+
+    /// The text that `span` covers, if known.
+    pub fn text(&self, span: Span) -> Option<&str> {
+        self.file(span.file)?.1.get(span.lo..span.hi)
+    }
+}
+
 /// Wrapper type for the various errors that can occur during chalk
 /// processing.
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+///
+/// The error is rendered into a message eagerly (the underlying errors
+/// refer to ids that can only be printed while the program is in scope),
+/// but keeps the location in the program text that it refers to, so that
+/// it can be displayed as an annotated snippet with [`ChalkError::render`].
+/// The underlying error is kept too, see [`ChalkError::source_error`].
+#[derive(Clone, Debug)]
 pub struct ChalkError {
-    /// For now, we just convert the error into a string, which makes
-    /// it trivially hashable etc.
-    error_text: String,
+    kind: ChalkErrorKind,
+    message: String,
+    span: Option<Span>,
+    related: Vec<(Span, String)>,
+    source: Option<Arc<ChalkErrorSource>>,
+}
+
+/// The stage of processing a [`ChalkError`] came from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChalkErrorKind {
+    Parse,
+    Lowering,
+    Coherence,
+    WellFormedness,
+    Other,
+}
+
+/// The error a [`ChalkError`] was made from.
+#[derive(Debug)]
+pub enum ChalkErrorSource {
+    Syntax(Vec<SyntaxError>),
+    Include(IncludeError),
+    Lowering(RustIrError),
+    Coherence(CoherenceError<ChalkIr>),
+    WellFormedness(WfError<ChalkIr>),
+}
+
+impl ChalkErrorSource {
+    fn as_error(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ChalkErrorSource::Syntax(errors) => match errors.as_slice() {
+                [error] => Some(error),
+                _ => None,
+            },
+            ChalkErrorSource::Include(error) => Some(error),
+            ChalkErrorSource::Lowering(error) => Some(error),
+            ChalkErrorSource::Coherence(error) => Some(error),
+            ChalkErrorSource::WellFormedness(error) => Some(error),
+        }
+    }
+}
+
+impl ChalkError {
+    pub(crate) fn new(kind: ChalkErrorKind, message: String, span: Option<Span>) -> Self {
+        ChalkError {
+            kind,
+            message,
+            span,
+            related: vec![],
+            source: None,
+        }
+    }
+
+    /// Keeps `source` as the error this one was made from.
+    pub(crate) fn with_source(mut self, source: ChalkErrorSource) -> Self {
+        self.source = Some(Arc::new(source));
+        self
+    }
+
+    pub fn kind(&self) -> ChalkErrorKind {
+        self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The location in the program text the error is about, if known.
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    /// The error this one was made from, for errors of the parser, of
+    /// lowering and of the checks of the program.
+    pub fn source_error(&self) -> Option<&ChalkErrorSource> {
+        self.source.as_deref()
+    }
+
+    /// Other locations relevant to the error, with a label for each.
+    pub fn related(&self) -> &[(Span, String)] {
+        &self.related
+    }
+
+    /// Points the error at `span`, if it is known.
+    pub fn at(mut self, span: Option<Span>) -> Self {
+        if span.is_some() {
+            self.span = span;
+        }
+        self
+    }
+
+    /// Adds a labeled related location to the error, if it is known.
+    pub fn with_related(mut self, span: Option<Span>, label: impl Into<String>) -> Self {
+        if let Some(span) = span {
+            self.related.push((span, label.into()));
+        }
+        self
+    }
+
+    /// Converts a coherence error about `program`, which was lowered from
+    /// `sources`. Overlapping impls are named by their headers in `text`, and
+    /// the error points at both of them. Orphan check failures point at the
+    /// impl and at the `#[upstream]` and `#[fundamental]` types involved.
+    /// Errors about a specializing impl name both impls by their headers
+    /// too, and point at them.
+    pub(crate) fn from_coherence(
+        error: CoherenceError<ChalkIr>,
+        program: &Program,
+        sources: &SourceFiles,
+    ) -> Self {
+        let span = |id: ImplId<ChalkIr>| program.item_span(id.0);
+        let header = |id: ImplId<ChalkIr>| {
+            span(id)
+                .and_then(|span| sources.text(span))
+                .map(|header| header.split_whitespace().collect::<Vec<_>>().join(" "))
+                .unwrap_or_else(|| format!("{:?}", id))
+        };
+        let (trait_id, (a, b), witness) = match &error {
+            CoherenceError::OverlappingImpls {
+                trait_id,
+                impls,
+                witness,
+            } => (*trait_id, *impls, witness.clone()),
+            CoherenceError::FailedOrphanCheck {
+                trait_id,
+                impl_id,
+                violation,
+            } => {
+                let adt_span = |id: &AdtId<ChalkIr>| program.item_span(id.0);
+                let related: Vec<_> = violation
+                    .upstream_adts
+                    .iter()
+                    .map(|id| {
+                        // Types are also upstream if they are in another crate.
+                        let label = if program.adt_data[id].flags.upstream {
+                            "not local because of `#[upstream]`"
+                        } else {
+                            "not local to the crate of the impl"
+                        };
+                        (adt_span(id), label)
+                    })
+                    .chain(
+                        violation
+                            .fundamental_adts
+                            .iter()
+                            .map(|id| (adt_span(id), "`#[fundamental]` type")),
+                    )
+                    .collect();
+                let impl_span = span(*impl_id);
+                let binders = &program.impl_data[impl_id].binders.binders;
+                let message = program.with_parameter_names(impl_id.0, binders, |placeholders| {
+                    let mut violation = violation.clone();
+                    if let OrphanCheckViolationKind::UncoveredType { ty, .. } = &mut violation.kind
+                    {
+                        *ty = placeholders.apply(ty.clone(), ChalkIr);
+                    }
+                    CoherenceError::FailedOrphanCheck {
+                        trait_id: *trait_id,
+                        impl_id: *impl_id,
+                        violation,
+                    }
+                    .to_string()
+                });
+                return related.into_iter().fold(
+                    ChalkError::new(ChalkErrorKind::Coherence, message, impl_span)
+                        .with_source(ChalkErrorSource::Coherence(error)),
+                    |error, (span, label)| error.with_related(span, label),
+                );
+            }
+            CoherenceError::NonDefaultOverride {
+                trait_id,
+                impls: (less_special, more_special),
+                associated_ty_id,
+            } => {
+                let (less_special, more_special) = (*less_special, *more_special);
+                let message = format!(
+                    "impl `{}` of trait `{:?}` overrides associated type `{}`, \
+                     which the impl `{}` it specializes does not declare `default`",
+                    header(more_special),
+                    trait_id,
+                    program.associated_ty_data[associated_ty_id].name,
+                    header(less_special)
+                );
+                return ChalkError::new(ChalkErrorKind::Coherence, message, span(more_special))
+                    .with_related(span(less_special), "specialized impl here")
+                    .with_source(ChalkErrorSource::Coherence(error));
+            }
+            CoherenceError::NotAlwaysApplicable {
+                trait_id,
+                impls: (less_special, more_special),
+                violation,
+            } => {
+                let (less_special, more_special) = (*less_special, *more_special);
+                let message = format!(
+                    "impl `{}` of trait `{:?}` overrides items of the impl `{}` it \
+                     specializes, but is not always applicable: {}",
+                    header(more_special),
+                    trait_id,
+                    header(less_special),
+                    violation
+                );
+                return ChalkError::new(ChalkErrorKind::Coherence, message, span(more_special))
+                    .with_related(span(less_special), "specialized impl here")
+                    .with_source(ChalkErrorSource::Coherence(error));
+            }
+        };
+        let applies = match witness {
+            Some(witness) => format!("both apply to `{:?}`", witness.value.with_colon()),
+            None => "may both apply".to_string(),
+        };
+        let message = format!(
+            "overlapping impls of trait `{:?}`: `{}` and `{}` {}",
+            trait_id,
+            header(a),
+            header(b),
+            applies
+        );
+        ChalkError::new(ChalkErrorKind::Coherence, message, span(b))
+            .with_related(span(a), "first impl here")
+            .with_source(ChalkErrorSource::Coherence(error))
+    }
+
+    /// Like `ChalkError::from`, for a well-formedness error of the impl or
+    /// type declaration `id` of `program`, whose parameters are bound by
+    /// `binders`. The error points at the declaration, and the goals in its
+    /// message show the parameters by their names.
+    pub(crate) fn from_wf(
+        error: WfError<ChalkIr>,
+        program: &Program,
+        id: RawId,
+        binders: &VariableKinds<ChalkIr>,
+    ) -> Self {
+        let message = program.with_parameter_names(id, binders, |_| error.to_string());
+        ChalkError::new(
+            ChalkErrorKind::WellFormedness,
+            message,
+            program.item_span(id),
+        )
+        .with_source(ChalkErrorSource::WellFormedness(error))
+    }
+
+    /// Combines the syntax errors found in a program into one error, which
+    /// lists all of their messages and points at each of them.
+    pub(crate) fn from_syntax_errors(errors: Vec<SyntaxError>) -> Self {
+        let message = errors
+            .iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let spans: Vec<_> = errors.iter().map(|error| error.span).collect();
+        let mut spans = spans.into_iter();
+        let span = spans.next();
+        spans
+            .fold(
+                ChalkError::new(ChalkErrorKind::Parse, message, span),
+                |error, span| error.with_related(Some(span), "another syntax error"),
+            )
+            .with_source(ChalkErrorSource::Syntax(errors))
+    }
+
+    /// Adds the message and locations of `other`, an error found after this
+    /// one, to this error.
+    pub(crate) fn followed_by(mut self, other: ChalkError) -> Self {
+        self.message = format!("{}\n{}", self.message, other.message);
+        self = self.with_related(other.span, "error here");
+        self.related.extend(other.related);
+        self
+    }
+
+    /// Renders the error message followed by snippets of `sources` (the
+    /// program text it was reported for, and the files that it includes)
+    /// annotating its locations, e.g.:
+    ///
+    /// ```text
+    /// expected a trait, found `Foo`, which is not a trait
+    ///  --> 3:6
+    ///   |
+    /// 3 | impl Foo for Bar { }
+    ///   |      ^^^
+    /// ```
+    ///
+    /// Locations in an included file are prefixed with its name, as in
+    /// `--> lib.chalk:3:6`. Locations in files that are not in `sources`
+    /// are left out.
+    pub fn render(&self, sources: &SourceFiles) -> String {
+        let mut output = format!("{}\n", self.message);
+        let annotations: Vec<_> = self
+            .span
+            .map(|span| (span, '^', ""))
+            .into_iter()
+            .chain(
+                self.related
+                    .iter()
+                    .map(|(span, label)| (*span, '-', label.as_str())),
+            )
+            .filter_map(|(span, marker, label)| {
+                let (name, source) = sources.file(span.file)?;
+                let name = name.map(|name| format!("{}:", name.display()));
+                Some((Snippet::new(source, span), name, marker, label))
+            })
+            .collect();
+        let width = annotations
+            .iter()
+            .map(|(snippet, _, _, _)| (snippet.line + 1).to_string().len())
+            .max()
+            .unwrap_or(0);
+        for (index, (snippet, name, marker, label)) in annotations.iter().enumerate() {
+            let arrow = if *marker == '^' { "-->" } else { ":::" };
+            let gutter = " ".repeat(width);
+            if index > 0 {
+                writeln!(output, "{} |", gutter).unwrap();
+            }
+            writeln!(
+                output,
+                "{}{} {}{}:{}",
+                gutter,
+                arrow,
+                name.as_deref().unwrap_or(""),
+                snippet.line + 1,
+                snippet.column + 1
+            )
+            .unwrap();
+            writeln!(output, "{} |", gutter).unwrap();
+            writeln!(output, "{:>width$} | {}", snippet.line + 1, snippet.text).unwrap();
+            let underline = format!(
+                "{}{}",
+                " ".repeat(snippet.column),
+                marker.to_string().repeat(snippet.len)
+            );
+            let annotation = format!("{} {}", underline, label);
+            writeln!(output, "{} | {}", gutter, annotation.trim_end()).unwrap();
+        }
+        output
+    }
+}
+
+/// The line of source text containing the start of a span.
+struct Snippet<'s> {
+    /// Zero-based line number.
+    line: usize,
+    /// Zero-based column, in characters.
+    column: usize,
+    /// The number of characters of the span on this line (at least one).
+    len: usize,
+    text: &'s str,
+}
+
+impl<'s> Snippet<'s> {
+    fn new(source: &'s str, span: Span) -> Self {
+        let lo = floor_char_boundary(source, span.lo);
+        let hi = floor_char_boundary(source, span.hi.max(lo));
+        let line_start = source[..lo].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[lo..].find('\n').map_or(source.len(), |i| lo + i);
+        let text = source[line_start..line_end].trim_end_matches('\r');
+        Snippet {
+            line: source[..lo].matches('\n').count(),
+            column: source[line_start..lo].chars().count(),
+            len: source[lo..hi.min(line_end)].chars().count().max(1),
+            text,
+        }
+    }
+}
+
+fn floor_char_boundary(source: &str, mut index: usize) -> usize {
+    index = index.min(source.len());
+    while !source.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
 }
 
 impl From<Box<dyn std::error::Error>> for ChalkError {
     fn from(value: Box<dyn std::error::Error>) -> Self {
-        ChalkError {
-            error_text: value.to_string(),
+        let value = match value.downcast::<SyntaxError>() {
+            Ok(error) => return ChalkError::from_syntax_errors(vec![*error]),
+            Err(value) => value,
+        };
+        match value.downcast::<IncludeError>() {
+            Ok(error) => ChalkError::new(
+                ChalkErrorKind::Parse,
+                error.message.clone(),
+                Some(error.span),
+            )
+            .with_source(ChalkErrorSource::Include(*error)),
+            Err(value) => ChalkError::new(ChalkErrorKind::Other, value.to_string(), None),
         }
     }
 }
 
 impl From<WfError<ChalkIr>> for ChalkError {
     fn from(value: WfError<ChalkIr>) -> Self {
-        ChalkError {
-            error_text: value.to_string(),
-        }
+        ChalkError::new(ChalkErrorKind::WellFormedness, value.to_string(), None)
+            .with_source(ChalkErrorSource::WellFormedness(value))
     }
 }
 
 impl From<CoherenceError<ChalkIr>> for ChalkError {
     fn from(value: CoherenceError<ChalkIr>) -> Self {
-        ChalkError {
-            error_text: value.to_string(),
-        }
+        ChalkError::new(ChalkErrorKind::Coherence, value.to_string(), None)
+            .with_source(ChalkErrorSource::Coherence(value))
     }
 }
 
 impl From<RustIrError> for ChalkError {
     fn from(value: RustIrError) -> Self {
-        ChalkError {
-            error_text: value.to_string(),
-        }
+        ChalkError::new(ChalkErrorKind::Lowering, value.to_string(), value.span())
+            .with_source(ChalkErrorSource::Lowering(value))
     }
 }
 
 impl std::fmt::Display for ChalkError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.error_text)
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ChalkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_deref().and_then(ChalkErrorSource::as_error)
+    }
+}
+
+/// Errors are compared by what they report: the source error is rendered in
+/// the message and spans.
+impl PartialEq for ChalkError {
+    fn eq(&self, other: &Self) -> bool {
+        (self.kind, &self.message, self.span, &self.related)
+            == (other.kind, &other.message, other.span, &other.related)
     }
 }
 
-impl std::error::Error for ChalkError {}
+impl Eq for ChalkError {}
+
+impl Hash for ChalkError {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.kind, &self.message, self.span, &self.related).hash(state);
+    }
+}
 
 #[derive(Debug)]
 pub enum RustIrError {
     InvalidParameterName(Identifier),
     InvalidTraitName(Identifier),
+    UnresolvedPath(Identifier),
     NotTrait(Identifier),
     NotStruct(Identifier),
     DuplicateOrShadowedParameters,
@@ -98,6 +551,61 @@ pub enum RustIrError {
     },
     CannotApplyTypeParameter(Identifier),
     InvalidExternAbi(Atom),
+    InvalidCrateName(Identifier),
+    CrateDeclaredAfterDependent(Identifier),
+    NestedCrate(Identifier),
+    TraitAliasNotTrait(Identifier),
+    HigherRankedTraitAlias(Identifier),
+    ConstOutOfRange {
+        literal: String,
+        ty: &'static str,
+    },
+}
+
+impl RustIrError {
+    /// The location of the offending name in the program text, if any.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            RustIrError::InvalidParameterName(name)
+            | RustIrError::InvalidTraitName(name)
+            | RustIrError::UnresolvedPath(name)
+            | RustIrError::NotTrait(name)
+            | RustIrError::NotStruct(name)
+            | RustIrError::AutoTraitAssociatedTypes(name)
+            | RustIrError::AutoTraitParameters(name)
+            | RustIrError::AutoTraitWhereClauses(name)
+            | RustIrError::InvalidFundamentalTypesParameters(name)
+            | RustIrError::NegativeImplAssociatedValues(name)
+            | RustIrError::MissingAssociatedType(name)
+            | RustIrError::CannotApplyTypeParameter(name)
+            | RustIrError::InvalidCrateName(name)
+            | RustIrError::CrateDeclaredAfterDependent(name)
+            | RustIrError::NestedCrate(name)
+            | RustIrError::TraitAliasNotTrait(name)
+            | RustIrError::HigherRankedTraitAlias(name)
+            | RustIrError::IncorrectNumberOfVarianceParameters {
+                identifier: name, ..
+            }
+            | RustIrError::IncorrectNumberOfTypeParameters {
+                identifier: name, ..
+            }
+            | RustIrError::IncorrectNumberOfAssociatedTypeParameters {
+                identifier: name, ..
+            }
+            | RustIrError::IncorrectParameterKind {
+                identifier: name, ..
+            }
+            | RustIrError::IncorrectTraitParameterKind {
+                identifier: name, ..
+            }
+            | RustIrError::IncorrectAssociatedTypeParameterKind {
+                identifier: name, ..
+            } => Some(name.span),
+            RustIrError::DuplicateOrShadowedParameters
+            | RustIrError::InvalidExternAbi(_)
+            | RustIrError::ConstOutOfRange { .. } => None,
+        }
+    }
 }
 
 impl std::fmt::Display for RustIrError {
@@ -107,6 +615,7 @@ impl std::fmt::Display for RustIrError {
                 write!(f, "invalid parameter name `{}`", name)
             }
             RustIrError::InvalidTraitName(name) => write!(f, "invalid trait name `{}`", name),
+            RustIrError::UnresolvedPath(name) => write!(f, "unresolved path `{}`", name),
             RustIrError::NotTrait(name) => write!(
                 f,
                 "expected a trait, found `{}`, which is not a trait",
@@ -200,6 +709,28 @@ impl std::fmt::Display for RustIrError {
                 write!(f, "cannot apply type parameter `{}`", name)
             }
             RustIrError::InvalidExternAbi(abi) => write!(f, "invalid extern ABI `{}`", abi),
+            RustIrError::InvalidCrateName(name) => write!(f, "invalid crate name `{}`", name),
+            RustIrError::CrateDeclaredAfterDependent(name) => write!(
+                f,
+                "crate `{}` must be declared before the crates that depend on it",
+                name
+            ),
+            RustIrError::NestedCrate(name) => write!(
+                f,
+                "crate `{}` must be declared at the top level of the program",
+                name
+            ),
+            RustIrError::TraitAliasNotTrait(name) => {
+                write!(f, "expected a trait, found trait alias `{}`", name)
+            }
+            RustIrError::HigherRankedTraitAlias(name) => write!(
+                f,
+                "trait alias `{}` has higher-ranked bounds, which cannot be used here",
+                name
+            ),
+            RustIrError::ConstOutOfRange { literal, ty } => {
+                write!(f, "literal `{}` does not fit in `{}`", literal, ty)
+            }
         }
     }
 }
@@ -4,10 +4,11 @@ use chalk_ir::{
     TyKind,
 };
 use chalk_ir::{
-    AdtId, AliasTy, AssocTypeId, CanonicalVarKind, CanonicalVarKinds, ConstData, Constraint,
-    Constraints, FnDefId, Goals, InEnvironment, Lifetime, OpaqueTy, OpaqueTyId,
+    AdtId, AliasTy, AssocTypeId, CanonicalVarKind, CanonicalVarKinds, Const, ConstData, Constraint,
+    Constraints, FnDefId, Goals, InEnvironment, IntTy, Lifetime, OpaqueTy, OpaqueTyId,
     ProgramClauseImplication, ProgramClauses, ProjectionTy, QuantifiedWhereClauses,
-    SeparatorTraitRef, Substitution, TraitId, Ty, TyData, VariableKind, VariableKinds, Variances,
+    SeparatorTraitRef, Substitution, TraitId, Ty, TyData, UintTy, VariableKind, VariableKinds,
+    Variances,
 };
 use chalk_ir::{
     GenericArg, GenericArgData, Goal, GoalData, LifetimeData, ProgramClause, ProgramClauseData,
@@ -50,6 +51,55 @@ impl Debug for ChalkFnAbi {
     }
 }
 
+/// The value of a concrete const. Which variant is used is determined by
+/// the type of the const: signed integers use `Int`, unsigned ones `Uint`.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChalkConst {
+    Bool(bool),
+    Char(char),
+    Int(i128),
+    Uint(u128),
+    Str(DefaultAtom),
+}
+
+impl Debug for ChalkConst {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChalkConst::Bool(b) => write!(fmt, "{}", b),
+            ChalkConst::Char(c) => write!(fmt, "{:?}", c),
+            ChalkConst::Int(i) => write!(fmt, "{}", i),
+            ChalkConst::Uint(u) => write!(fmt, "{}", u),
+            ChalkConst::Str(s) => write!(fmt, "{:?}", &**s),
+        }
+    }
+}
+
+impl ChalkConst {
+    /// Creates a const of the unsigned integer type `ty`, if `value` fits.
+    pub fn uint(ty: UintTy, value: u128) -> Option<Self> {
+        let max = match ty {
+            UintTy::U8 => u8::MAX as u128,
+            UintTy::U16 => u16::MAX as u128,
+            UintTy::U32 => u32::MAX as u128,
+            UintTy::U64 | UintTy::Usize => u64::MAX as u128,
+            UintTy::U128 => u128::MAX,
+        };
+        (value <= max).then_some(ChalkConst::Uint(value))
+    }
+
+    /// Creates a const of the signed integer type `ty`, if `value` fits.
+    pub fn int(ty: IntTy, value: i128) -> Option<Self> {
+        let (min, max) = match ty {
+            IntTy::I8 => (i8::MIN as i128, i8::MAX as i128),
+            IntTy::I16 => (i16::MIN as i128, i16::MAX as i128),
+            IntTy::I32 => (i32::MIN as i128, i32::MAX as i128),
+            IntTy::I64 | IntTy::Isize => (i64::MIN as i128, i64::MAX as i128),
+            IntTy::I128 => (i128::MIN, i128::MAX),
+        };
+        (min <= value && value <= max).then_some(ChalkConst::Int(value))
+    }
+}
+
 /// The default "interner" and the only interner used by chalk
 /// itself. In this interner, no interning actually occurs.
 #[derive(Debug, Copy, Clone, Hash, PartialOrd, Ord, PartialEq, Eq)]
@@ -59,7 +109,7 @@ impl Interner for ChalkIr {
     type InternedType = Arc<TyData<ChalkIr>>;
     type InternedLifetime = LifetimeData<ChalkIr>;
     type InternedConst = Arc<ConstData<ChalkIr>>;
-    type InternedConcreteConst = u32;
+    type InternedConcreteConst = ChalkConst;
     type InternedGenericArg = GenericArgData<ChalkIr>;
     type InternedGoal = Arc<GoalData<ChalkIr>>;
     type InternedGoals = Vec<Goal<ChalkIr>>;
@@ -138,6 +188,10 @@ impl Interner for ChalkIr {
             .or_else(|| Some(write!(fmt, "{:?}", lifetime.interned())))
     }
 
+    fn debug_const(constant: &Const<ChalkIr>, fmt: &mut fmt::Formatter<'_>) -> Option<fmt::Result> {
+        tls::with_current_program(|prog| Some(prog?.debug_const(constant, fmt)))
+    }
+
     fn debug_generic_arg(
         generic_arg: &GenericArg<ChalkIr>,
         fmt: &mut fmt::Formatter<'_>,
@@ -261,7 +315,7 @@ impl Interner for ChalkIr {
         constant
     }
 
-    fn const_eq(self, _ty: &Arc<TyData<ChalkIr>>, c1: &u32, c2: &u32) -> bool {
+    fn const_eq(self, _ty: &Arc<TyData<ChalkIr>>, c1: &ChalkConst, c2: &ChalkConst) -> bool {
         c1 == c2
     }
 
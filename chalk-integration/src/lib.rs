@@ -1,7 +1,9 @@
 #![recursion_limit = "1024"]
 #![cfg_attr(feature = "bench", feature(test))]
 
+pub mod compare;
 pub mod db;
+pub mod edit;
 pub mod error;
 pub mod interner;
 pub mod lowering;
@@ -11,12 +13,15 @@ pub mod query;
 pub mod test_macros;
 pub mod tls;
 
+use chalk_engine::cache::AnswerCache;
 use chalk_engine::solve::SLGSolver;
 use chalk_ir::interner::HasInterner;
 use chalk_ir::Binders;
-use chalk_recursive::{Cache, RecursiveSolver};
+use chalk_recursive::{Cache, PersistentStore, RecursiveSolver};
+use chalk_solve::solve::persistent::PersistentCache;
 use chalk_solve::Solver;
 use interner::ChalkIr;
+use std::sync::Arc;
 
 pub use interner::{Identifier, RawId};
 
@@ -56,6 +61,10 @@ pub enum SolverChoice {
         overflow_depth: usize,
         caching_enabled: bool,
         max_size: usize,
+        /// Solve independent subgoals on several threads (see
+        /// `RecursiveSolver::with_parallel`). Only available if chalk is
+        /// built with the `parallel` feature.
+        parallel: bool,
     },
 }
 
@@ -79,6 +88,7 @@ impl SolverChoice {
             overflow_depth: 100,
             caching_enabled: true,
             max_size: 30,
+            parallel: false,
         }
     }
 
@@ -88,32 +98,109 @@ impl SolverChoice {
             overflow_depth,
             caching_enabled: true,
             max_size,
+            parallel: false,
         }
     }
 
+    /// Returns the default recursive solver setup, solving independent
+    /// subgoals on several threads.
+    pub fn recursive_parallel() -> Self {
+        SolverChoice::Recursive {
+            overflow_depth: 100,
+            caching_enabled: true,
+            max_size: 30,
+            parallel: true,
+        }
+    }
+
+    /// Returns the default setup of each solver, for comparing them.
+    pub fn all_defaults() -> Vec<Self> {
+        vec![
+            SolverChoice::slg_default(),
+            SolverChoice::recursive_default(),
+        ]
+    }
+
+    ///
+    /// # Panics
+    ///
+    /// If this is a recursive solver that solves in parallel, but chalk is
+    /// built without the `parallel` feature.
     pub fn into_solver(self) -> Box<dyn Solver<ChalkIr>> {
+        self.into_solver_with_persistent_cache(None)
+    }
+
+    /// Like `into_solver`, but the solver also keeps its results in
+    /// `persistent_cache`, if given, and reuses the results found there. The
+    /// recursive solver only does so if caching is enabled.
+    ///
+    /// # Panics
+    ///
+    /// Like `into_solver`.
+    pub fn into_solver_with_persistent_cache(
+        self,
+        persistent_cache: Option<Arc<PersistentCache>>,
+    ) -> Box<dyn Solver<ChalkIr>> {
         match self {
             SolverChoice::SLG {
                 max_size,
                 expected_answers,
-            } => Box::new(SLGSolver::new(max_size, expected_answers)),
+            } => {
+                let solver = SLGSolver::new(max_size, expected_answers);
+                Box::new(match persistent_cache {
+                    Some(store) => {
+                        solver.with_answer_cache(AnswerCache::with_store(ChalkIr, store))
+                    }
+                    None => solver,
+                })
+            }
             SolverChoice::Recursive {
                 overflow_depth,
                 caching_enabled,
                 max_size,
-            } => Box::new(RecursiveSolver::new(
-                overflow_depth,
-                max_size,
-                if caching_enabled {
-                    Some(Cache::default())
+                parallel,
+            } => {
+                let solver =
+                    recursive_solver(overflow_depth, caching_enabled, max_size, persistent_cache);
+                Box::new(if parallel {
+                    with_parallel(solver)
                 } else {
-                    None
-                },
-            )),
+                    solver
+                })
+            }
         }
     }
 }
 
+fn recursive_solver(
+    overflow_depth: usize,
+    caching_enabled: bool,
+    max_size: usize,
+    persistent_cache: Option<Arc<PersistentCache>>,
+) -> RecursiveSolver<ChalkIr> {
+    RecursiveSolver::new(
+        overflow_depth,
+        max_size,
+        match (caching_enabled, persistent_cache) {
+            (false, _) => None,
+            (true, None) => Some(Cache::default()),
+            (true, Some(store)) => Some(Cache::with_store(Arc::new(PersistentStore::new(
+                ChalkIr, store,
+            )))),
+        },
+    )
+}
+
+#[cfg(feature = "parallel")]
+fn with_parallel(solver: RecursiveSolver<ChalkIr>) -> RecursiveSolver<ChalkIr> {
+    solver.with_parallel()
+}
+
+#[cfg(not(feature = "parallel"))]
+fn with_parallel(_solver: RecursiveSolver<ChalkIr>) -> RecursiveSolver<ChalkIr> {
+    panic!("solving in parallel requires chalk to be built with the `parallel` feature")
+}
+
 impl Default for SolverChoice {
     fn default() -> Self {
         SolverChoice::slg(10, None)
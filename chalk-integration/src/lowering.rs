@@ -1,6 +1,8 @@
 mod env;
 mod program_lowerer;
 
+pub use env::{Modules, TraitAliases, TypeAliases};
+
 use chalk_ir::cast::{Cast, Caster};
 use chalk_ir::{
     self, BoundVar, ClausePriority, DebruijnIndex, ImplId, QuantifiedWhereClauses, Substitution,
@@ -10,11 +12,12 @@ use chalk_parse::ast::*;
 use chalk_solve::rust_ir::{self, IntoWhereClauses};
 use program_lowerer::ProgramLowerer;
 use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use string_cache::DefaultAtom as Atom;
 use tracing::debug;
 
 use crate::error::RustIrError;
-use crate::interner::{ChalkFnAbi, ChalkIr};
+use crate::interner::{ChalkConst, ChalkFnAbi, ChalkIr};
 use crate::program::Program as LoweredProgram;
 use crate::{Identifier as Ident, TypeSort};
 use env::*;
@@ -39,18 +42,19 @@ impl Lower for Program {
 
     fn lower(&self) -> Self::Lowered {
         let mut lowerer = ProgramLowerer::default();
+        let items = lowerer.flatten(self)?;
 
         // Make a vector mapping each thing in `items` to an id,
         // based just on its position:
-        let raw_ids = self
-            .items
+        let raw_ids = items
             .iter()
             .map(|_| lowerer.next_item_id())
             .collect::<Vec<_>>();
 
-        lowerer.extract_associated_types(self, &raw_ids)?;
-        lowerer.extract_ids(self, &raw_ids)?;
-        lowerer.lower(self, &raw_ids)
+        lowerer.extract_associated_types(&items, &raw_ids)?;
+        lowerer.extract_ids(&items, &raw_ids)?;
+        lowerer.lower_aliases(&items)?;
+        lowerer.lower(&items, &raw_ids)
     }
 }
 
@@ -115,6 +119,22 @@ fn get_type_of_usize() -> chalk_ir::Ty<ChalkIr> {
     chalk_ir::TyKind::Scalar(chalk_ir::Scalar::Uint(chalk_ir::UintTy::Usize)).intern(ChalkIr)
 }
 
+/// Lowers the type of a const parameter. The grammar only allows
+/// integers, `bool`, `char` and `&'static str` there.
+fn lower_const_param_ty(ty: &Ty) -> chalk_ir::Ty<ChalkIr> {
+    let interner = ChalkIr;
+    match ty {
+        Ty::Scalar { ty } => chalk_ir::TyKind::Scalar(ty.lower()).intern(interner),
+        Ty::Ref { .. } => chalk_ir::TyKind::Ref(
+            chalk_ir::Mutability::Not,
+            chalk_ir::LifetimeData::Static.intern(interner),
+            chalk_ir::TyKind::Str.intern(interner),
+        )
+        .intern(interner),
+        _ => panic!("unexpected const parameter type: {:?}", ty),
+    }
+}
+
 impl Lower for VariableKind {
     type Lowered = chalk_ir::WithKind<ChalkIr, Ident>;
     fn lower(&self) -> Self::Lowered {
@@ -132,7 +152,13 @@ impl Lower for VariableKind {
                 n,
             ),
             VariableKind::Lifetime(n) => (chalk_ir::VariableKind::Lifetime, n),
-            VariableKind::Const(ref n) => (chalk_ir::VariableKind::Const(get_type_of_usize()), n),
+            VariableKind::Const(ref n, ref ty) => (
+                chalk_ir::VariableKind::Const(
+                    ty.as_ref()
+                        .map_or_else(get_type_of_usize, lower_const_param_ty),
+                ),
+                n,
+            ),
         };
 
         chalk_ir::WithKind::new(kind, n.str.clone())
@@ -158,11 +184,23 @@ impl LowerWithEnv for WhereClause {
     /// Lower from an AST `where` clause to an internal IR.
     /// Some AST `where` clauses can lower to multiple ones, this is why we return a `Vec`.
     /// As for now, this is the only the case for `where T: Foo<Item = U>` which lowers to
-    /// `Implemented(T: Foo)` and `ProjectionEq(<T as Foo>::Item = U)`.
+    /// `Implemented(T: Foo)` and `ProjectionEq(<T as Foo>::Item = U)`, and for
+    /// `where T: Alias`, which lowers to the bounds of the trait alias.
     fn lower(&self, env: &Env) -> LowerResult<Self::Lowered> {
+        let interner = env.interner();
         Ok(match self {
             WhereClause::Implemented { trait_ref } => {
-                vec![chalk_ir::WhereClause::Implemented(trait_ref.lower(env)?)]
+                match expand_trait_alias(&trait_ref.trait_name, &trait_ref.args[1..], env)? {
+                    Some(bounds) => {
+                        let self_ty = trait_ref.args[0].lower(env)?;
+                        let self_ty = self_ty.assert_ty_ref(interner);
+                        unquantified_bounds(bounds, &trait_ref.trait_name)?
+                            .iter()
+                            .flat_map(|bound| bound.into_where_clauses(interner, self_ty.clone()))
+                            .collect()
+                    }
+                    None => vec![chalk_ir::WhereClause::Implemented(trait_ref.lower(env)?)],
+                }
             }
             WhereClause::ProjectionEq { projection, ty } => vec![
                 chalk_ir::WhereClause::AliasEq(chalk_ir::AliasEq {
@@ -462,7 +500,8 @@ impl LowerWithEnv for TraitBound {
         let parameters = self
             .args_no_self
             .iter()
-            .map(|a| a.lower(env))
+            .zip(lower_kinds_or_none(&k.binders.binders, interner))
+            .map(|(a, kind)| lower_generic_arg(a, kind, env))
             .collect::<LowerResult<Vec<_>>>()?;
 
         if parameters.len() != k.binders.len(interner) {
@@ -552,36 +591,109 @@ impl LowerWithEnv for QuantifiedInlineBound {
 impl LowerWithEnv for [QuantifiedInlineBound] {
     type Lowered = Vec<rust_ir::QuantifiedInlineBound<ChalkIr>>;
 
+    /// Lowers a list of bounds, expanding the trait aliases among them.
     fn lower(&self, env: &Env) -> LowerResult<Self::Lowered> {
-        fn trait_identifier(bound: &InlineBound) -> &Identifier {
-            match bound {
-                InlineBound::TraitBound(tb) => &tb.trait_name,
-                InlineBound::AliasEqBound(ab) => &ab.trait_bound.trait_name,
-            }
-        }
-
         let mut regular_traits = Vec::new();
         let mut auto_traits = Vec::new();
 
         for b in self {
-            let id = env.lookup_trait(trait_identifier(&b.bound))?;
-            if env.auto_trait(id) {
-                auto_traits.push((b, id))
-            } else {
-                regular_traits.push((b, id))
+            let bounds = match &b.bound {
+                InlineBound::TraitBound(tb) if env.lookup_trait_alias(&tb.trait_name).is_some() => {
+                    let variable_kinds = b.variable_kinds.iter().map(|k| k.lower());
+                    if variable_kinds.len() == 0 {
+                        expand_trait_alias(&tb.trait_name, &tb.args_no_self, env)?.unwrap()
+                    } else {
+                        env.in_binders(variable_kinds, |env| {
+                            let bounds = expand_trait_alias(&tb.trait_name, &tb.args_no_self, env)?;
+                            unquantified_bounds(bounds.unwrap(), &tb.trait_name)
+                        })?
+                        .into_iter()
+                        .collect()
+                    }
+                }
+                _ => vec![b.lower(env)?],
+            };
+            for bound in bounds {
+                let id = match bound.skip_binders() {
+                    rust_ir::InlineBound::TraitBound(tb) => tb.trait_id,
+                    rust_ir::InlineBound::AliasEqBound(ab) => ab.trait_bound.trait_id,
+                };
+                if env.auto_trait(id) {
+                    auto_traits.push((bound, id))
+                } else {
+                    regular_traits.push((bound, id))
+                }
             }
         }
 
         auto_traits.sort_by_key(|b| b.1);
 
-        regular_traits
-            .iter()
-            .chain(auto_traits.iter())
-            .map(|(b, _)| b.lower(env))
-            .collect()
+        Ok(regular_traits
+            .into_iter()
+            .chain(auto_traits)
+            .map(|(b, _)| b)
+            .collect())
     }
 }
 
+/// If `name` is a trait alias, the bounds it stands for with the arguments
+/// `args_no_self`.
+fn expand_trait_alias(
+    name: &Identifier,
+    args_no_self: &[GenericArg],
+    env: &Env,
+) -> LowerResult<Option<Vec<rust_ir::QuantifiedInlineBound<ChalkIr>>>> {
+    let interner = env.interner();
+    let alias = match env.lookup_trait_alias(name) {
+        Some(alias) => alias,
+        None => return Ok(None),
+    };
+
+    let parameters = args_no_self
+        .iter()
+        .map(|a| a.lower(env))
+        .collect::<LowerResult<Vec<_>>>()?;
+
+    if parameters.len() != alias.len(interner) {
+        return Err(RustIrError::IncorrectNumberOfTypeParameters {
+            identifier: name.clone(),
+            expected: alias.len(interner),
+            actual: parameters.len(),
+        });
+    }
+
+    for (binder, param) in alias.binders.iter(interner).zip(parameters.iter()) {
+        if binder.kind() != param.kind() {
+            return Err(RustIrError::IncorrectTraitParameterKind {
+                identifier: name.clone(),
+                expected: binder.kind(),
+                actual: param.kind(),
+            });
+        }
+    }
+
+    Ok(Some(alias.clone().substitute(interner, &parameters)))
+}
+
+/// The bounds of the trait alias `name`, where they cannot have binders of
+/// their own (e.g. in a `where` clause, which cannot introduce them).
+fn unquantified_bounds(
+    bounds: Vec<rust_ir::QuantifiedInlineBound<ChalkIr>>,
+    name: &Identifier,
+) -> LowerResult<Vec<rust_ir::InlineBound<ChalkIr>>> {
+    let interner = ChalkIr;
+    bounds
+        .into_iter()
+        .map(|bound| {
+            if bound.binders.is_empty(interner) {
+                Ok(bound.substitute(interner, &Substitution::empty(interner)))
+            } else {
+                Err(RustIrError::HigherRankedTraitAlias(name.clone()))
+            }
+        })
+        .collect()
+}
+
 impl Lower for Polarity {
     type Lowered = rust_ir::Polarity;
 
@@ -715,35 +827,36 @@ impl LowerWithEnv for Ty {
             .intern(interner),
 
             Ty::Apply { name, ref args } => {
-                macro_rules! tykind {
-                    ($k:expr, $tykind:ident, $id:expr) => {{
-                        if $k.binders.len(interner) != args.len() {
-                            return Err(RustIrError::IncorrectNumberOfTypeParameters {
+                let substitution = |kinds: &chalk_ir::VariableKinds<ChalkIr>| {
+                    if kinds.len(interner) != args.len() {
+                        return Err(RustIrError::IncorrectNumberOfTypeParameters {
+                            identifier: name.clone(),
+                            expected: kinds.len(interner),
+                            actual: args.len(),
+                        });
+                    }
+
+                    let substitution = chalk_ir::Substitution::from_fallible(
+                        interner,
+                        args.iter()
+                            .zip(kinds.iter(interner))
+                            .map(|(t, kind)| lower_generic_arg(t, Some(kind), env)),
+                    )?;
+
+                    for (param, arg) in kinds.iter(interner).zip(substitution.iter(interner)) {
+                        if param.kind() != arg.kind() {
+                            return Err(RustIrError::IncorrectParameterKind {
                                 identifier: name.clone(),
-                                expected: $k.binders.len(interner),
-                                actual: args.len(),
+                                expected: param.kind(),
+                                actual: arg.kind(),
                             });
                         }
-
-                        let substitution = chalk_ir::Substitution::from_fallible(
-                            interner,
-                            args.iter().map(|t| t.lower(env)),
-                        )?;
-
-                        for (param, arg) in $k
-                            .binders
-                            .binders
-                            .iter(interner)
-                            .zip(substitution.iter(interner))
-                        {
-                            if param.kind() != arg.kind() {
-                                return Err(RustIrError::IncorrectParameterKind {
-                                    identifier: name.clone(),
-                                    expected: param.kind(),
-                                    actual: arg.kind(),
-                                });
-                            }
-                        }
+                    }
+                    Ok(substitution)
+                };
+                macro_rules! tykind {
+                    ($k:expr, $tykind:ident, $id:expr) => {{
+                        let substitution = substitution(&$k.binders.binders)?;
                         chalk_ir::TyKind::$tykind($id, substitution).intern(interner)
                     }};
                 }
@@ -756,6 +869,10 @@ impl LowerWithEnv for Ty {
                     TypeLookup::Closure(id) => tykind!(env.closure_kind(id), Closure, id),
                     TypeLookup::Opaque(id) => tykind!(env.opaque_kind(id), OpaqueType, id),
                     TypeLookup::Coroutine(id) => tykind!(env.coroutine_kind(id), Coroutine, id),
+                    TypeLookup::TypeAlias(alias) => {
+                        let substitution = substitution(&alias.binders)?;
+                        alias.clone().substitute(interner, &substitution)
+                    }
                     TypeLookup::Foreign(_) | TypeLookup::Trait(_) => {
                         panic!("Unexpected apply type")
                     }
@@ -840,15 +957,157 @@ impl LowerWithEnv for Const {
                     })
                     .map(|c| c.clone())
             }
-            Const::Value(value) => Ok(chalk_ir::ConstData {
-                ty: get_type_of_usize(),
-                value: chalk_ir::ConstValue::Concrete(chalk_ir::ConcreteConst { interned: *value }),
+            Const::Value(_) | Const::Expr { .. } => lower_const(self, None, env),
+        }
+    }
+}
+
+/// Lowers `c`, giving unsuffixed integer literals the type `expected_ty` if
+/// it is an integer type (and `usize` otherwise).
+fn lower_const(
+    c: &Const,
+    expected_ty: Option<&chalk_ir::Ty<ChalkIr>>,
+    env: &Env,
+) -> LowerResult<chalk_ir::Const<ChalkIr>> {
+    let interner = env.interner();
+    match c {
+        Const::Id(_) => c.lower(env),
+        Const::Value(literal) => {
+            let (ty, interned) = lower_const_literal(literal, expected_ty)?;
+            Ok(chalk_ir::ConstData {
+                ty,
+                value: chalk_ir::ConstValue::Concrete(chalk_ir::ConcreteConst { interned }),
             }
-            .intern(interner)),
+            .intern(interner))
+        }
+        Const::Expr { op, lhs, rhs } => {
+            let lhs = lower_const(lhs, expected_ty, env)?;
+            let ty = lhs.data(interner).ty.clone();
+            let rhs = lower_const(rhs, Some(&ty), env)?;
+            Ok(chalk_ir::ConstData {
+                ty,
+                value: chalk_ir::ConstValue::Expr(chalk_ir::ConstExpr {
+                    op: op.lower(),
+                    lhs,
+                    rhs,
+                }),
+            }
+            .intern(interner))
+        }
+    }
+}
+
+fn lower_const_literal(
+    literal: &ConstLiteral,
+    expected_ty: Option<&chalk_ir::Ty<ChalkIr>>,
+) -> LowerResult<(chalk_ir::Ty<ChalkIr>, ChalkConst)> {
+    let interner = ChalkIr;
+    let scalar = |scalar| chalk_ir::TyKind::Scalar(scalar).intern(interner);
+    Ok(match literal {
+        ConstLiteral::Bool(b) => (scalar(chalk_ir::Scalar::Bool), ChalkConst::Bool(*b)),
+        ConstLiteral::Char(c) => (scalar(chalk_ir::Scalar::Char), ChalkConst::Char(*c)),
+        ConstLiteral::Str(s) => (
+            lower_const_param_ty(&Ty::Ref {
+                mutability: Mutability::Not,
+                lifetime: Lifetime::Static,
+                ty: Box::new(Ty::Str),
+            }),
+            ChalkConst::Str(s.clone()),
+        ),
+        ConstLiteral::Int {
+            value,
+            negative,
+            suffix,
+        } => {
+            let int_ty = match suffix {
+                Some(suffix) => suffix.lower(),
+                None => match expected_ty.map(|ty| ty.kind(interner)) {
+                    Some(chalk_ir::TyKind::Scalar(
+                        s @ (chalk_ir::Scalar::Int(_) | chalk_ir::Scalar::Uint(_)),
+                    )) => *s,
+                    _ => chalk_ir::Scalar::Uint(chalk_ir::UintTy::Usize),
+                },
+            };
+            let out_of_range = |ty| RustIrError::ConstOutOfRange {
+                literal: format!("{}{}", if *negative { "-" } else { "" }, value),
+                ty,
+            };
+            let interned = match int_ty {
+                chalk_ir::Scalar::Uint(uint_ty) => {
+                    let value = if *negative && *value != 0 {
+                        None
+                    } else {
+                        Some(*value)
+                    };
+                    value
+                        .and_then(|value| ChalkConst::uint(uint_ty, value))
+                        .ok_or_else(|| out_of_range(uint_ty_name(uint_ty)))?
+                }
+                chalk_ir::Scalar::Int(int_ty) => {
+                    let value = if *negative {
+                        0i128.checked_sub_unsigned(*value)
+                    } else {
+                        i128::try_from(*value).ok()
+                    };
+                    value
+                        .and_then(|value| ChalkConst::int(int_ty, value))
+                        .ok_or_else(|| out_of_range(int_ty_name(int_ty)))?
+                }
+                _ => unreachable!("integer literal with non-integer type {:?}", int_ty),
+            };
+            (scalar(int_ty), interned)
         }
+    })
+}
+
+fn uint_ty_name(ty: chalk_ir::UintTy) -> &'static str {
+    match ty {
+        chalk_ir::UintTy::U8 => "u8",
+        chalk_ir::UintTy::U16 => "u16",
+        chalk_ir::UintTy::U32 => "u32",
+        chalk_ir::UintTy::U64 => "u64",
+        chalk_ir::UintTy::U128 => "u128",
+        chalk_ir::UintTy::Usize => "usize",
+    }
+}
+
+fn int_ty_name(ty: chalk_ir::IntTy) -> &'static str {
+    match ty {
+        chalk_ir::IntTy::I8 => "i8",
+        chalk_ir::IntTy::I16 => "i16",
+        chalk_ir::IntTy::I32 => "i32",
+        chalk_ir::IntTy::I64 => "i64",
+        chalk_ir::IntTy::I128 => "i128",
+        chalk_ir::IntTy::Isize => "isize",
     }
 }
 
+/// Lowers `arg` as an argument for a parameter of kind `kind`, if known.
+fn lower_generic_arg(
+    arg: &GenericArg,
+    kind: Option<&chalk_ir::VariableKind<ChalkIr>>,
+    env: &Env,
+) -> LowerResult<chalk_ir::GenericArg<ChalkIr>> {
+    match (arg, kind) {
+        (GenericArg::Const(c), Some(chalk_ir::VariableKind::Const(ty))) => {
+            Ok(lower_const(c, Some(ty), env)?.cast(env.interner()))
+        }
+        _ => arg.lower(env),
+    }
+}
+
+/// The kinds in `kinds`, followed by an endless supply of `None`s, so that
+/// zipping with more arguments than there are parameters doesn't lose any.
+fn lower_kinds_or_none(
+    kinds: &chalk_ir::VariableKinds<ChalkIr>,
+    interner: ChalkIr,
+) -> impl Iterator<Item = Option<&chalk_ir::VariableKind<ChalkIr>>> {
+    kinds
+        .iter(interner)
+        .map(Some)
+        .chain(std::iter::repeat(None))
+}
+
 impl LowerWithEnv for GenericArg {
     type Lowered = chalk_ir::GenericArg<ChalkIr>;
 
@@ -1058,8 +1317,12 @@ pub fn lower_goal(goal: &Goal, program: &LoweredProgram) -> LowerResult<chalk_ir
         opaque_ty_kinds: &program.opaque_ty_kinds,
         associated_ty_lookups: &associated_ty_lookups,
         foreign_ty_ids: &program.foreign_ty_ids,
+        type_aliases: &program.type_aliases,
+        trait_aliases: &program.trait_aliases,
         parameter_map: BTreeMap::new(),
         auto_traits: &auto_traits,
+        modules: &program.modules,
+        module: Modules::ROOT,
     };
 
     goal.lower(&env)
@@ -1123,6 +1386,20 @@ impl LowerWithEnv for (&Goal, chalk_ir::QuantifierKind, &Vec<VariableKind>) {
     }
 }
 
+impl Lower for ConstOp {
+    type Lowered = chalk_ir::ConstBinOp;
+
+    fn lower(&self) -> Self::Lowered {
+        match self {
+            ConstOp::Add => chalk_ir::ConstBinOp::Add,
+            ConstOp::Sub => chalk_ir::ConstBinOp::Sub,
+            ConstOp::Mul => chalk_ir::ConstBinOp::Mul,
+            ConstOp::Div => chalk_ir::ConstBinOp::Div,
+            ConstOp::Rem => chalk_ir::ConstBinOp::Rem,
+        }
+    }
+}
+
 impl Lower for WellKnownTrait {
     type Lowered = rust_ir::WellKnownTrait;
 
@@ -5,7 +5,7 @@ use chalk_ir::{
 };
 use chalk_ir::{cast::Cast, ForeignDefId, WithKind};
 use chalk_parse::ast::*;
-use chalk_solve::rust_ir::AssociatedTyValueId;
+use chalk_solve::rust_ir::{self, AssociatedTyValueId};
 use std::collections::BTreeMap;
 
 use crate::error::RustIrError;
@@ -29,6 +29,9 @@ pub type AssociatedTyLookups = BTreeMap<(chalk_ir::TraitId<ChalkIr>, Ident), Ass
 pub type AssociatedTyValueIds =
     BTreeMap<(chalk_ir::ImplId<ChalkIr>, Ident), AssociatedTyValueId<ChalkIr>>;
 pub type ForeignIds = BTreeMap<Ident, chalk_ir::ForeignDefId<ChalkIr>>;
+pub type TypeAliases = BTreeMap<Ident, chalk_ir::Binders<chalk_ir::Ty<ChalkIr>>>;
+pub type TraitAliases =
+    BTreeMap<Ident, chalk_ir::Binders<Vec<rust_ir::QuantifiedInlineBound<ChalkIr>>>>;
 
 pub type ParameterMap = BTreeMap<Ident, chalk_ir::WithKind<ChalkIr, BoundVar>>;
 
@@ -51,9 +54,197 @@ pub struct Env<'k> {
     pub foreign_ty_ids: &'k ForeignIds,
     pub coroutine_ids: &'k CoroutineIds,
     pub coroutine_kinds: &'k CoroutineKinds,
+    pub type_aliases: &'k TypeAliases,
+    pub trait_aliases: &'k TraitAliases,
     /// GenericArg identifiers are used as keys, therefore
     /// all identifiers in an environment must be unique (no shadowing).
     pub parameter_map: ParameterMap,
+    pub modules: &'k Modules,
+    /// The module that names are resolved in.
+    pub module: ModuleIndex,
+}
+
+pub type ModuleIndex = usize;
+
+/// The modules of a program, and the names in scope in each of them.
+///
+/// Items are keyed in the id maps (e.g. `AdtIds`) by their qualified name,
+/// such as `a::b::Foo` for `struct Foo` in `mod b` in `mod a`, so resolving
+/// a name as written in a module amounts to finding the qualified name it
+/// refers to. A name is looked up in the module it is used in, then in the
+/// enclosing modules in turn (so, unlike in Rust, the items of outer modules
+/// need not be imported). The first segment of a qualified name may also be
+/// `crate`, `self` or `super`.
+///
+/// The top-level modules declared with `crate name: dependencies { .. }` are
+/// crates: from inside a crate, `crate` refers to it, and the other crates
+/// can only be named if they are among its dependencies.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Modules {
+    modules: Vec<ModuleScope>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct ModuleScope {
+    parent: Option<ModuleIndex>,
+    /// The prefix of the qualified names of the items in the module, e.g.
+    /// `a::b::` (empty for the root module).
+    prefix: String,
+    /// The items and modules declared in the module.
+    members: BTreeMap<Ident, Binding>,
+    /// The names introduced by `use a::B;` (or `use a::B as C;`), and the
+    /// paths they stand for.
+    imports: BTreeMap<Ident, Ident>,
+    /// The paths of the modules imported with `use a::*;`.
+    globs: Vec<Ident>,
+    /// The crate that the module is in, if any.
+    krate: Option<ModuleIndex>,
+    /// For a crate, the names of the crates it depends on.
+    dependencies: Vec<Ident>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Binding {
+    Item(Ident),
+    Module(ModuleIndex),
+}
+
+/// Imports may refer to each other; this bounds the number of imports
+/// followed to resolve a name, so that cyclic imports fail to resolve.
+const MAX_IMPORT_DEPTH: usize = 32;
+
+impl Modules {
+    pub const ROOT: ModuleIndex = 0;
+
+    pub fn new() -> Self {
+        Modules {
+            modules: vec![ModuleScope::default()],
+        }
+    }
+
+    /// Declares the module `name` in `parent`.
+    pub fn add_module(&mut self, parent: ModuleIndex, name: &Identifier) -> ModuleIndex {
+        let index = self.modules.len();
+        let prefix = format!("{}{}::", self.modules[parent].prefix, name.str);
+        self.modules.push(ModuleScope {
+            parent: Some(parent),
+            prefix,
+            krate: self.modules[parent].krate,
+            ..ModuleScope::default()
+        });
+        self.modules[parent]
+            .members
+            .insert(name.str.clone(), Binding::Module(index));
+        index
+    }
+
+    /// Declares the crate `defn` at the top level of the program.
+    pub fn add_crate(&mut self, defn: &CrateDefn) -> ModuleIndex {
+        let index = self.add_module(Self::ROOT, &defn.name);
+        let scope = &mut self.modules[index];
+        scope.krate = Some(index);
+        scope.dependencies = defn.dependencies.iter().map(|d| d.str.clone()).collect();
+        index
+    }
+
+    /// Declares the item `name` in `module`, and returns its qualified name.
+    pub fn add_item(&mut self, module: ModuleIndex, name: &Identifier) -> Ident {
+        let scope = &mut self.modules[module];
+        let qualified = Ident::from(format!("{}{}", scope.prefix, name.str));
+        scope
+            .members
+            .insert(name.str.clone(), Binding::Item(qualified.clone()));
+        qualified
+    }
+
+    pub fn add_use(&mut self, module: ModuleIndex, use_: &UseDefn) {
+        let scope = &mut self.modules[module];
+        match use_ {
+            UseDefn::Name { path, alias } => {
+                let name = alias.as_ref().map_or_else(
+                    || path.str.rsplit("::").next().unwrap().into(),
+                    |alias| alias.str.clone(),
+                );
+                scope.imports.insert(name, path.str.clone());
+            }
+            UseDefn::Glob { path } => scope.globs.push(path.str.clone()),
+        }
+    }
+
+    /// Finds the qualified name of the item that `name` refers to in
+    /// `module`, if any.
+    pub fn resolve(&self, module: ModuleIndex, name: &str) -> Option<Ident> {
+        match self.resolve_path(module, name, 0)? {
+            Binding::Item(qualified) => Some(qualified),
+            Binding::Module(_) => None,
+        }
+    }
+
+    fn resolve_path(&self, module: ModuleIndex, path: &str, depth: usize) -> Option<Binding> {
+        if depth > MAX_IMPORT_DEPTH {
+            return None;
+        }
+        let mut segments = path.split("::");
+        let mut binding = match segments.next()? {
+            "crate" => Binding::Module(self.modules[module].krate.unwrap_or(Self::ROOT)),
+            "self" => Binding::Module(module),
+            "super" => Binding::Module(self.modules[module].parent?),
+            first => std::iter::successors(Some(module), |&m| self.modules[m].parent)
+                .find_map(|m| self.lookup_member(m, first, depth))
+                .filter(|binding| self.is_visible(module, binding))?,
+        };
+        for segment in segments {
+            let module = match binding {
+                Binding::Module(module) => module,
+                Binding::Item(_) => return None,
+            };
+            binding = match segment {
+                "super" => Binding::Module(self.modules[module].parent?),
+                _ => self.lookup_member(module, segment, depth)?,
+            };
+        }
+        Some(binding)
+    }
+
+    /// Whether `binding` may be named from `module`: crates are only visible
+    /// from themselves and the crates that depend on them.
+    fn is_visible(&self, module: ModuleIndex, binding: &Binding) -> bool {
+        let target = match binding {
+            Binding::Module(target) if self.modules[*target].krate == Some(*target) => *target,
+            _ => return true,
+        };
+        match self.modules[module].krate {
+            Some(krate) => {
+                let name = Ident::from(self.modules[target].prefix.trim_end_matches("::"));
+                krate == target || self.modules[krate].dependencies.contains(&name)
+            }
+            None => false,
+        }
+    }
+
+    /// Looks up `name` among the members and imports of `module`.
+    fn lookup_member(&self, module: ModuleIndex, name: &str, depth: usize) -> Option<Binding> {
+        let scope = &self.modules[module];
+        if let Some(binding) = scope.members.get(&Ident::from(name)) {
+            return Some(binding.clone());
+        }
+        if let Some(path) = scope.imports.get(&Ident::from(name)) {
+            return self.resolve_path(module, path, depth + 1);
+        }
+        scope
+            .globs
+            .iter()
+            .find_map(|glob| match self.resolve_path(module, glob, depth + 1)? {
+                Binding::Module(imported) => self.lookup_member(imported, name, depth + 1),
+                Binding::Item(_) => None,
+            })
+    }
+}
+
+impl Default for Modules {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// Information about an associated type **declaration** (i.e., an
@@ -84,6 +275,7 @@ pub enum TypeLookup<'k> {
     Foreign(ForeignDefId<ChalkIr>),
     Trait(TraitId<ChalkIr>),
     Coroutine(CoroutineId<ChalkIr>),
+    TypeAlias(&'k chalk_ir::Binders<chalk_ir::Ty<ChalkIr>>),
 }
 
 impl Env<'_> {
@@ -145,28 +337,59 @@ impl Env<'_> {
             Ok(TypeLookup::Foreign(id)) => Ok(chalk_ir::TyKind::Foreign(id)
                 .intern(interner)
                 .cast(interner)),
+            Ok(TypeLookup::TypeAlias(alias)) => {
+                if alias.len(interner) > 0 {
+                    Err(RustIrError::IncorrectNumberOfTypeParameters {
+                        identifier: name.clone(),
+                        expected: alias.len(interner),
+                        actual: 0,
+                    })
+                } else {
+                    Ok(alias
+                        .clone()
+                        .substitute(interner, &chalk_ir::Substitution::empty(interner))
+                        .cast(interner))
+                }
+            }
             Ok(TypeLookup::Trait(_)) => Err(RustIrError::NotStruct(name.clone())),
+            Err(e @ RustIrError::UnresolvedPath(_)) => Err(e),
             Err(_) => Err(RustIrError::InvalidParameterName(name.clone())),
         }
     }
 
+    /// The key under which the item `name` refers to is found in the id
+    /// maps: its qualified name, if `name` resolves in the current module.
+    /// Paths that do not resolve (e.g. because they name an item of a crate
+    /// that is not visible) are an error; other names are looked up as is.
+    fn resolve(&self, name: &Identifier) -> LowerResult<Ident> {
+        match self.modules.resolve(self.module, &name.str) {
+            Some(qualified) => Ok(qualified),
+            None if name.str.contains("::") => Err(RustIrError::UnresolvedPath(name.clone())),
+            None => Ok(name.str.clone()),
+        }
+    }
+
     pub fn lookup_type(&self, name: &Identifier) -> LowerResult<TypeLookup> {
         if let Some(id) = self.parameter_map.get(&name.str) {
-            Ok(TypeLookup::Parameter(id))
-        } else if let Some(id) = self.adt_ids.get(&name.str) {
+            return Ok(TypeLookup::Parameter(id));
+        }
+        let key = &self.resolve(name)?;
+        if let Some(id) = self.adt_ids.get(key) {
             Ok(TypeLookup::Adt(*id))
-        } else if let Some(id) = self.fn_def_ids.get(&name.str) {
+        } else if let Some(id) = self.fn_def_ids.get(key) {
             Ok(TypeLookup::FnDef(*id))
-        } else if let Some(id) = self.closure_ids.get(&name.str) {
+        } else if let Some(id) = self.closure_ids.get(key) {
             Ok(TypeLookup::Closure(*id))
-        } else if let Some(id) = self.opaque_ty_ids.get(&name.str) {
+        } else if let Some(id) = self.opaque_ty_ids.get(key) {
             Ok(TypeLookup::Opaque(*id))
-        } else if let Some(id) = self.foreign_ty_ids.get(&name.str) {
+        } else if let Some(id) = self.foreign_ty_ids.get(key) {
             Ok(TypeLookup::Foreign(*id))
-        } else if let Some(id) = self.trait_ids.get(&name.str) {
+        } else if let Some(id) = self.trait_ids.get(key) {
             Ok(TypeLookup::Trait(*id))
-        } else if let Some(id) = self.coroutine_ids.get(&name.str) {
+        } else if let Some(id) = self.coroutine_ids.get(key) {
             Ok(TypeLookup::Coroutine(*id))
+        } else if let Some(alias) = self.type_aliases.get(key) {
+            Ok(TypeLookup::TypeAlias(alias))
         } else {
             Err(RustIrError::NotStruct(name.clone()))
         }
@@ -177,17 +400,26 @@ impl Env<'_> {
     }
 
     pub fn lookup_trait(&self, name: &Identifier) -> LowerResult<TraitId<ChalkIr>> {
-        if let Some(&id) = self.trait_ids.get(&name.str) {
+        let key = &self.resolve(name)?;
+        if let Some(&id) = self.trait_ids.get(key) {
             Ok(id)
-        } else if self.parameter_map.get(&name.str).is_some()
-            || self.adt_ids.get(&name.str).is_some()
-        {
+        } else if self.trait_aliases.contains_key(key) {
+            Err(RustIrError::TraitAliasNotTrait(name.clone()))
+        } else if self.parameter_map.get(&name.str).is_some() || self.adt_ids.get(key).is_some() {
             Err(RustIrError::NotTrait(name.clone()))
         } else {
             Err(RustIrError::InvalidTraitName(name.clone()))
         }
     }
 
+    /// The bounds that the trait alias `name` stands for, if it is one.
+    pub fn lookup_trait_alias(
+        &self,
+        name: &Identifier,
+    ) -> Option<&chalk_ir::Binders<Vec<rust_ir::QuantifiedInlineBound<ChalkIr>>>> {
+        self.trait_aliases.get(&self.resolve(name).ok()?)
+    }
+
     pub fn trait_kind(&self, id: chalk_ir::TraitId<ChalkIr>) -> &TypeKind {
         &self.trait_kinds[&id]
     }
@@ -9,13 +9,13 @@ use chalk_solve::rust_ir::{
     CoroutineWitnessDatum, CoroutineWitnessExistential, OpaqueTyDatum, OpaqueTyDatumBound,
 };
 use rust_ir::IntoWhereClauses;
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::sync::Arc;
 use string_cache::DefaultAtom as Atom;
 
 use super::{env::*, lower_adt_size_align, Lower, LowerParameterMap, LowerWithEnv, FIXME_SELF};
 use crate::error::RustIrError;
-use crate::program::Program as LoweredProgram;
+use crate::program::{CrateDatum, CrateIndex, Program as LoweredProgram};
 use crate::RawId;
 use crate::{interner::ChalkIr, TypeKind, TypeSort};
 
@@ -40,6 +40,21 @@ pub(super) struct ProgramLowerer {
     opaque_ty_kinds: OpaqueTyVariableKinds,
     object_safe_traits: HashSet<TraitId<ChalkIr>>,
     foreign_ty_ids: ForeignIds,
+    type_aliases: TypeAliases,
+    trait_aliases: TraitAliases,
+    modules: Modules,
+    /// The crates of the program, with the names of their dependencies.
+    crates: Vec<(Identifier, Vec<Identifier>)>,
+}
+
+/// An item of the program, once modules and includes have been flattened
+/// away. The names of definitions are qualified with the modules they are in.
+pub(super) struct ScopedItem {
+    pub item: Item,
+    /// The module the item is in, in which the names it uses are resolved.
+    pub module: ModuleIndex,
+    /// The crate the item is in, if any.
+    pub krate: Option<CrateIndex>,
 }
 
 impl ProgramLowerer {
@@ -49,14 +64,96 @@ impl ProgramLowerer {
         RawId { index }
     }
 
+    /// Flattens the modules and includes of the program into a list of
+    /// items, recording the names each module declares and imports.
+    pub fn flatten(&mut self, program: &Program) -> LowerResult<Vec<ScopedItem>> {
+        let mut flattened = vec![];
+        self.flatten_items(&program.items, Modules::ROOT, None, &mut flattened)?;
+        Ok(flattened)
+    }
+
+    fn flatten_items(
+        &mut self,
+        items: &[Item],
+        module: ModuleIndex,
+        krate: Option<CrateIndex>,
+        flattened: &mut Vec<ScopedItem>,
+    ) -> LowerResult<()> {
+        for item in items {
+            match item {
+                Item::Module(defn) => {
+                    let inner = self.modules.add_module(module, &defn.name);
+                    self.flatten_items(&defn.items, inner, krate, flattened)?;
+                }
+                Item::Crate(defn) => {
+                    if module != Modules::ROOT {
+                        return Err(RustIrError::NestedCrate(defn.name.clone()));
+                    }
+                    let inner = self.modules.add_crate(defn);
+                    let index = self.crates.len();
+                    self.crates
+                        .push((defn.name.clone(), defn.dependencies.clone()));
+                    self.flatten_items(&defn.items, inner, Some(index), flattened)?;
+                }
+                Item::Use(use_) => self.modules.add_use(module, use_),
+                Item::Include(include) => {
+                    self.flatten_items(&include.items, module, krate, flattened)?
+                }
+                _ => {
+                    let mut item = item.clone();
+                    if let Some(name) = defined_name(&mut item) {
+                        name.str = self.modules.add_item(module, name);
+                    }
+                    flattened.push(ScopedItem {
+                        item,
+                        module,
+                        krate,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves the dependencies of each crate, which must be declared
+    /// before it (so that crates cannot depend on each other cyclically).
+    fn lower_crates(&self) -> LowerResult<Vec<CrateDatum>> {
+        self.crates
+            .iter()
+            .enumerate()
+            .map(|(index, (name, dependencies))| {
+                let dependencies = dependencies
+                    .iter()
+                    .map(|dependency| {
+                        match self
+                            .crates
+                            .iter()
+                            .position(|(n, _)| n.str == dependency.str)
+                        {
+                            Some(d) if d < index => Ok(d),
+                            Some(_) => {
+                                Err(RustIrError::CrateDeclaredAfterDependent(dependency.clone()))
+                            }
+                            None => Err(RustIrError::InvalidCrateName(dependency.clone())),
+                        }
+                    })
+                    .collect::<LowerResult<_>>()?;
+                Ok(CrateDatum {
+                    name: name.str.clone(),
+                    dependencies,
+                })
+            })
+            .collect()
+    }
+
     /// Create ids for associated type declarations and values
     pub fn extract_associated_types(
         &mut self,
-        program: &Program,
+        items: &[ScopedItem],
         raw_ids: &[RawId],
     ) -> LowerResult<()> {
-        for (item, &raw_id) in program.items.iter().zip(raw_ids) {
-            match item {
+        for (item, &raw_id) in items.iter().zip(raw_ids) {
+            match &item.item {
                 Item::TraitDefn(d) => {
                     if d.flags.auto && !d.assoc_ty_defns.is_empty() {
                         return Err(RustIrError::AutoTraitAssociatedTypes(d.name.clone()));
@@ -86,9 +183,9 @@ impl ProgramLowerer {
         Ok(())
     }
 
-    pub fn extract_ids(&mut self, program: &Program, raw_ids: &[RawId]) -> LowerResult<()> {
-        for (item, &raw_id) in program.items.iter().zip(raw_ids) {
-            match item {
+    pub fn extract_ids(&mut self, items: &[ScopedItem], raw_ids: &[RawId]) -> LowerResult<()> {
+        for (item, &raw_id) in items.iter().zip(raw_ids) {
+            match &item.item {
                 Item::AdtDefn(defn) => {
                     let type_kind = defn.lower_type_kind()?;
                     let id = AdtId(raw_id);
@@ -135,12 +232,81 @@ impl ProgramLowerer {
                 }
                 Item::Impl(_) => continue,
                 Item::Clause(_) => continue,
+                Item::TypeAlias(_) | Item::TraitAlias(_) => continue,
+                Item::Module(_) | Item::Use(_) | Item::Include(_) | Item::Crate(_) => {
+                    unreachable!("flattened by `flatten`")
+                }
             };
         }
         Ok(())
     }
 
-    pub fn lower(self, program: &Program, raw_ids: &[RawId]) -> LowerResult<LoweredProgram> {
+    /// The environment that the items of `module` are lowered in.
+    fn env(&self, module: ModuleIndex) -> Env<'_> {
+        Env {
+            adt_ids: &self.adt_ids,
+            adt_kinds: &self.adt_kinds,
+            fn_def_ids: &self.fn_def_ids,
+            fn_def_kinds: &self.fn_def_kinds,
+            closure_ids: &self.closure_ids,
+            closure_kinds: &self.closure_kinds,
+            trait_ids: &self.trait_ids,
+            trait_kinds: &self.trait_kinds,
+            opaque_ty_ids: &self.opaque_ty_ids,
+            opaque_ty_kinds: &self.opaque_ty_kinds,
+            coroutine_ids: &self.coroutine_ids,
+            coroutine_kinds: &self.coroutine_kinds,
+            associated_ty_lookups: &self.associated_ty_lookups,
+            parameter_map: BTreeMap::new(),
+            auto_traits: &self.auto_traits,
+            foreign_ty_ids: &self.foreign_ty_ids,
+            type_aliases: &self.type_aliases,
+            trait_aliases: &self.trait_aliases,
+            modules: &self.modules,
+            module,
+        }
+    }
+
+    /// Lowers the type and trait aliases, which are expanded wherever they
+    /// are used. An alias may use aliases declared after it, so the aliases
+    /// are lowered in rounds, each lowering those whose aliases are known;
+    /// if a round makes no progress, the first error is reported.
+    pub fn lower_aliases(&mut self, items: &[ScopedItem]) -> LowerResult<()> {
+        let mut pending: Vec<_> = items
+            .iter()
+            .filter(|item| matches!(item.item, Item::TypeAlias(_) | Item::TraitAlias(_)))
+            .collect();
+        while !pending.is_empty() {
+            let mut type_aliases = vec![];
+            let mut trait_aliases = vec![];
+            let mut errors = vec![];
+            pending.retain(|item| {
+                let env = self.env(item.module);
+                let result = match &item.item {
+                    Item::TypeAlias(defn) => {
+                        let variable_kinds = defn.variable_kinds.iter().map(|k| k.lower());
+                        env.in_binders(variable_kinds, |env| defn.ty.lower(env))
+                            .map(|ty| type_aliases.push((defn.name.str.clone(), ty)))
+                    }
+                    Item::TraitAlias(defn) => {
+                        let variable_kinds = defn.variable_kinds.iter().map(|k| k.lower());
+                        env.in_binders(variable_kinds, |env| defn.bounds.lower(env))
+                            .map(|bounds| trait_aliases.push((defn.name.str.clone(), bounds)))
+                    }
+                    _ => unreachable!(),
+                };
+                result.map_err(|e| errors.push(e)).is_err()
+            });
+            if type_aliases.is_empty() && trait_aliases.is_empty() {
+                return Err(errors.remove(0));
+            }
+            self.type_aliases.extend(type_aliases);
+            self.trait_aliases.extend(trait_aliases);
+        }
+        Ok(())
+    }
+
+    pub fn lower(self, items: &[ScopedItem], raw_ids: &[RawId]) -> LowerResult<LoweredProgram> {
         let mut adt_data = BTreeMap::new();
         let mut adt_reprs = BTreeMap::new();
         let mut adt_size_aligns = BTreeMap::new();
@@ -161,32 +327,29 @@ impl ProgramLowerer {
         let mut coroutine_witness_data = BTreeMap::new();
         let mut hidden_opaque_types = BTreeMap::new();
         let mut custom_clauses = Vec::new();
+        let mut item_spans = BTreeMap::new();
+        let mut parameter_names = BTreeMap::new();
+        let mut item_crates = BTreeMap::new();
+        let crates = self.lower_crates()?;
+
+        for (scoped_item, &raw_id) in items.iter().zip(raw_ids) {
+            let item = &scoped_item.item;
+            if let Some(span) = item.span() {
+                item_spans.insert(raw_id, span);
+            }
+            if let Some(krate) = scoped_item.krate {
+                item_crates.insert(raw_id, krate);
+            }
 
-        for (item, &raw_id) in program.items.iter().zip(raw_ids) {
-            let empty_env = Env {
-                adt_ids: &self.adt_ids,
-                adt_kinds: &self.adt_kinds,
-                fn_def_ids: &self.fn_def_ids,
-                fn_def_kinds: &self.fn_def_kinds,
-                closure_ids: &self.closure_ids,
-                closure_kinds: &self.closure_kinds,
-                trait_ids: &self.trait_ids,
-                trait_kinds: &self.trait_kinds,
-                opaque_ty_ids: &self.opaque_ty_ids,
-                opaque_ty_kinds: &self.opaque_ty_kinds,
-                coroutine_ids: &self.coroutine_ids,
-                coroutine_kinds: &self.coroutine_kinds,
-                associated_ty_lookups: &self.associated_ty_lookups,
-                parameter_map: BTreeMap::new(),
-                auto_traits: &self.auto_traits,
-                foreign_ty_ids: &self.foreign_ty_ids,
-            };
+            let empty_env = self.env(scoped_item.module);
 
             match *item {
                 Item::AdtDefn(ref d) => {
                     let identifier = d.name.clone();
                     let adt_id = AdtId(raw_id);
                     adt_data.insert(adt_id, Arc::new((d, adt_id).lower(&empty_env)?));
+                    let names = d.all_parameters().into_iter();
+                    parameter_names.insert(raw_id, names.map(|p| p.skip_kind().clone()).collect());
                     adt_reprs.insert(adt_id, Arc::new(d.repr.lower(&empty_env)?));
                     adt_size_aligns.insert(adt_id, Arc::new(lower_adt_size_align(&d.flags)));
                     let n_params = d.all_parameters().len();
@@ -329,6 +492,8 @@ impl ProgramLowerer {
                         (impl_defn, impl_id, &self.associated_ty_value_ids).lower(&empty_env)?,
                     );
                     impl_data.insert(impl_id, impl_datum.clone());
+                    let names = impl_defn.all_parameters().into_iter();
+                    parameter_names.insert(raw_id, names.map(|p| p.skip_kind().clone()).collect());
                     let trait_id = impl_datum.trait_id();
 
                     for atv in &impl_defn.assoc_ty_values {
@@ -355,6 +520,11 @@ impl ProgramLowerer {
                                 impl_id,
                                 associated_ty_id: lookup.id,
                                 value,
+                                defaultness: if atv.default {
+                                    rust_ir::Defaultness::Default
+                                } else {
+                                    rust_ir::Defaultness::Final
+                                },
                             }),
                         );
                     }
@@ -476,7 +646,10 @@ impl ProgramLowerer {
                     coroutine_data.insert(id, Arc::new(coroutine_datum));
                     coroutine_witness_data.insert(id, Arc::new(coroutine_witness));
                 }
-                Item::Foreign(_) => {}
+                Item::Foreign(_) | Item::TypeAlias(_) | Item::TraitAlias(_) => {}
+                Item::Module(_) | Item::Use(_) | Item::Include(_) | Item::Crate(_) => {
+                    unreachable!("flattened by `flatten`")
+                }
             }
         }
 
@@ -515,10 +688,39 @@ impl ProgramLowerer {
             custom_clauses,
             object_safe_traits: self.object_safe_traits,
             foreign_ty_ids: self.foreign_ty_ids,
+            type_aliases: self.type_aliases,
+            trait_aliases: self.trait_aliases,
+            item_spans,
+            parameter_names,
+            modules: self.modules,
+            crates,
+            item_crates,
+            dependency_impls: BTreeSet::new(),
         })
     }
 }
 
+/// The name of the item that `item` defines, if any.
+fn defined_name(item: &mut Item) -> Option<&mut Identifier> {
+    match item {
+        Item::AdtDefn(defn) => Some(&mut defn.name),
+        Item::FnDefn(defn) => Some(&mut defn.name),
+        Item::ClosureDefn(defn) => Some(&mut defn.name),
+        Item::TraitDefn(defn) => Some(&mut defn.name),
+        Item::OpaqueTyDefn(defn) => Some(&mut defn.name),
+        Item::CoroutineDefn(defn) => Some(&mut defn.name),
+        Item::Foreign(ForeignDefn(name)) => Some(name),
+        Item::TypeAlias(defn) => Some(&mut defn.name),
+        Item::TraitAlias(defn) => Some(&mut defn.name),
+        Item::Impl(_)
+        | Item::Clause(_)
+        | Item::Module(_)
+        | Item::Use(_)
+        | Item::Include(_)
+        | Item::Crate(_) => None,
+    }
+}
+
 trait LowerTypeKind {
     fn lower_type_kind(&self) -> LowerResult<TypeKind>;
 }
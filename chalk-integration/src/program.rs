@@ -1,13 +1,17 @@
-use crate::interner::ChalkIr;
-use crate::{tls, Identifier, TypeKind};
+use crate::interner::{ChalkConst, ChalkIr};
+use crate::lowering::{Modules, TraitAliases, TypeAliases};
+use crate::{tls, Identifier, RawId, TypeKind};
+use chalk_ir::cast::Cast;
 use chalk_ir::{could_match::CouldMatch, UnificationDatabase};
 use chalk_ir::{debug::Angle, Variance};
 use chalk_ir::{
     debug::SeparatorTraitRef, AdtId, AliasTy, AssocTypeId, Binders, CanonicalVarKinds, ClosureId,
-    CoroutineId, FnDefId, ForeignDefId, GenericArg, Goal, Goals, ImplId, IntTy, Lifetime, OpaqueTy,
-    OpaqueTyId, ProgramClause, ProgramClauseImplication, ProgramClauses, ProjectionTy, Scalar,
-    Substitution, TraitId, Ty, TyKind, UintTy, Variances,
+    ConcreteConst, Const, ConstBinOp, ConstValue, CoroutineId, FnDefId, ForeignDefId, GenericArg,
+    Goal, Goals, ImplId, IntTy, Lifetime, LifetimeData, OpaqueTy, OpaqueTyId, PlaceholderIndex,
+    ProgramClause, ProgramClauseImplication, ProgramClauses, ProjectionTy, Scalar, Substitution,
+    TraitId, Ty, TyKind, UintTy, UniverseIndex, VariableKind, VariableKinds, Variances,
 };
+use chalk_parse::ast::Span;
 use chalk_solve::rust_ir::{
     AdtDatum, AdtRepr, AdtSizeAlign, AssociatedTyDatum, AssociatedTyValue, AssociatedTyValueId,
     ClosureKind, CoroutineDatum, CoroutineWitnessDatum, FnDefDatum, FnDefInputsAndOutputDatum,
@@ -15,9 +19,10 @@ use chalk_solve::rust_ir::{
 };
 use chalk_solve::split::Split;
 use chalk_solve::RustIrDatabase;
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt;
 use std::sync::Arc;
+use string_cache::DefaultAtom as Atom;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Program {
@@ -110,6 +115,44 @@ pub struct Program {
 
     /// For each foreign type `extern { type A; }`
     pub foreign_ty_ids: BTreeMap<Identifier, ForeignDefId<ChalkIr>>,
+
+    /// For each type alias `type Foo<T> = Ty;`, the type it stands for.
+    pub type_aliases: TypeAliases,
+
+    /// For each trait alias `trait Foo<T> = A + B;`, the bounds it stands for.
+    pub trait_aliases: TraitAliases,
+
+    /// For each item, where it is in the program text (see `Item::span`).
+    pub item_spans: BTreeMap<RawId, Span>,
+
+    /// For each impl and type declaration, the names of the parameters it
+    /// binds, in order.
+    pub parameter_names: BTreeMap<RawId, Vec<Atom>>,
+
+    /// The modules of the program, in which the names used in goals are
+    /// resolved.
+    pub modules: Modules,
+
+    /// The crates declared with `crate name: dependencies { .. }`. The items
+    /// outside of them make up the root crate, which every crate depends on.
+    pub crates: Vec<CrateDatum>,
+
+    /// For each item declared in one of `crates`, the crate it is in.
+    pub item_crates: BTreeMap<RawId, CrateIndex>,
+
+    /// In the view of a crate (see `crate_view`), the impls of the crates it
+    /// depends on, which its own impls must not overlap with.
+    pub dependency_impls: BTreeSet<ImplId<ChalkIr>>,
+}
+
+/// The index of a crate in `Program::crates`.
+pub type CrateIndex = usize;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CrateDatum {
+    pub name: Identifier,
+    /// The crates this one depends on directly, which are declared before it.
+    pub dependencies: Vec<CrateIndex>,
 }
 
 impl Program {
@@ -121,6 +164,103 @@ impl Program {
             .map(|(&impl_id, _)| impl_id)
             .collect()
     }
+
+    /// The program as seen by `krate` (or by the root crate, for `None`),
+    /// which coherence is checked from: only the impls, traits and types of
+    /// the crate and of its (transitive) dependencies are visible, and only
+    /// the items of the crate itself are local. The items of other crates
+    /// cannot be named from the crate, so they are left out.
+    pub fn crate_view(&self, krate: Option<CrateIndex>) -> Program {
+        let mut visible = BTreeSet::from([None]);
+        let mut stack: Vec<_> = krate.into_iter().collect();
+        while let Some(k) = stack.pop() {
+            if visible.insert(Some(k)) {
+                stack.extend(&self.crates[k].dependencies);
+            }
+        }
+        let crate_of = |id: RawId| self.item_crates.get(&id).copied();
+
+        let mut view = self.clone();
+        view.impl_data
+            .retain(|id, _| visible.contains(&crate_of(id.0)));
+        for (&id, datum) in &mut view.impl_data {
+            if crate_of(id.0) != krate {
+                Arc::make_mut(datum).impl_type = ImplType::External;
+                view.dependency_impls.insert(id);
+            }
+        }
+        view.adt_data
+            .retain(|id, _| visible.contains(&crate_of(id.0)));
+        for (id, datum) in &mut view.adt_data {
+            if crate_of(id.0) != krate {
+                Arc::make_mut(datum).flags.upstream = true;
+            }
+        }
+        view.trait_data
+            .retain(|id, _| visible.contains(&crate_of(id.0)));
+        for (id, datum) in &mut view.trait_data {
+            if crate_of(id.0) != krate {
+                Arc::make_mut(datum).flags.upstream = true;
+            }
+        }
+        view
+    }
+
+    /// The programs that coherence is checked on: the program itself, or
+    /// if it declares crates, its view from the root crate and each crate.
+    pub fn crate_views(self: &Arc<Self>) -> Vec<Arc<Program>> {
+        if self.crates.is_empty() {
+            return vec![self.clone()];
+        }
+        std::iter::once(None)
+            .chain((0..self.crates.len()).map(Some))
+            .map(|krate| Arc::new(self.crate_view(krate)))
+            .collect()
+    }
+
+    /// Formats something under the binders `binders` of the impl or type
+    /// declaration `id` with `format`, which is given the substitution to
+    /// apply to it. This instantiates the binders with placeholders (as the
+    /// solver does when proving goals about the declaration), which are
+    /// shown with the names of the declaration's parameters.
+    pub(crate) fn with_parameter_names(
+        &self,
+        id: RawId,
+        binders: &VariableKinds<ChalkIr>,
+        format: impl FnOnce(&Substitution<ChalkIr>) -> String,
+    ) -> String {
+        let ui = UniverseIndex::ROOT.next();
+        let placeholders = Substitution::from_iter(
+            ChalkIr,
+            binders
+                .iter(ChalkIr)
+                .enumerate()
+                .map(|(idx, kind)| -> GenericArg<ChalkIr> {
+                    let placeholder = PlaceholderIndex { ui, idx };
+                    match kind {
+                        VariableKind::Lifetime => placeholder.to_lifetime(ChalkIr).cast(ChalkIr),
+                        VariableKind::Ty(_) => placeholder.to_ty(ChalkIr).cast(ChalkIr),
+                        VariableKind::Const(ty) => {
+                            placeholder.to_const(ChalkIr, ty.clone()).cast(ChalkIr)
+                        }
+                    }
+                }),
+        );
+        let names = self
+            .parameter_names
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .map(|(idx, name)| (PlaceholderIndex { ui, idx }, name.clone()))
+            .collect();
+        tls::with_placeholder_names(names, || format(&placeholders))
+    }
+
+    /// Returns the span of the item with the given id, for diagnostics.
+    pub(crate) fn item_span(&self, id: RawId) -> Option<Span> {
+        self.item_spans.get(&id).copied()
+    }
 }
 
 impl tls::DebugContext for Program {
@@ -232,6 +372,11 @@ impl tls::DebugContext for Program {
 
     fn debug_ty(&self, ty: &Ty<ChalkIr>, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         let interner = self.interner();
+        if let TyKind::Placeholder(placeholder) = ty.kind(interner) {
+            if let Some(name) = tls::placeholder_name(*placeholder) {
+                return write!(fmt, "{}", name);
+            }
+        }
         write!(fmt, "{:?}", ty.kind(interner).debug(interner))
     }
 
@@ -241,9 +386,28 @@ impl tls::DebugContext for Program {
         fmt: &mut fmt::Formatter<'_>,
     ) -> Result<(), fmt::Error> {
         let interner = self.interner();
+        if let LifetimeData::Placeholder(placeholder) = lifetime.data(interner) {
+            if let Some(name) = tls::placeholder_name(*placeholder) {
+                return write!(fmt, "{}", name);
+            }
+        }
         write!(fmt, "{:?}", lifetime.data(interner))
     }
 
+    fn debug_const(
+        &self,
+        constant: &Const<ChalkIr>,
+        fmt: &mut fmt::Formatter<'_>,
+    ) -> Result<(), fmt::Error> {
+        let interner = self.interner();
+        if let ConstValue::Placeholder(placeholder) = constant.data(interner).value {
+            if let Some(name) = tls::placeholder_name(placeholder) {
+                return write!(fmt, "{}", name);
+            }
+        }
+        write!(fmt, "{:?}", constant.data(interner))
+    }
+
     fn debug_generic_arg(
         &self,
         generic_arg: &GenericArg<ChalkIr>,
@@ -382,6 +546,40 @@ impl UnificationDatabase<ChalkIr> for Program {
     fn adt_variance(&self, adt_id: AdtId<ChalkIr>) -> Variances<ChalkIr> {
         Variances::from_iter(self.interner(), self.adt_variances[&adt_id].iter().copied())
     }
+
+    fn evaluate_const_expr(
+        &self,
+        ty: &Ty<ChalkIr>,
+        op: ConstBinOp,
+        lhs: &ConcreteConst<ChalkIr>,
+        rhs: &ConcreteConst<ChalkIr>,
+    ) -> Option<ConcreteConst<ChalkIr>> {
+        // Only integers support arithmetic; the result must fit in `ty`.
+        let interned = match (ty.kind(self.interner()), &lhs.interned, &rhs.interned) {
+            (TyKind::Scalar(Scalar::Uint(uint_ty)), ChalkConst::Uint(l), ChalkConst::Uint(r)) => {
+                let value = match op {
+                    ConstBinOp::Add => l.checked_add(*r),
+                    ConstBinOp::Sub => l.checked_sub(*r),
+                    ConstBinOp::Mul => l.checked_mul(*r),
+                    ConstBinOp::Div => l.checked_div(*r),
+                    ConstBinOp::Rem => l.checked_rem(*r),
+                }?;
+                ChalkConst::uint(*uint_ty, value)?
+            }
+            (TyKind::Scalar(Scalar::Int(int_ty)), ChalkConst::Int(l), ChalkConst::Int(r)) => {
+                let value = match op {
+                    ConstBinOp::Add => l.checked_add(*r),
+                    ConstBinOp::Sub => l.checked_sub(*r),
+                    ConstBinOp::Mul => l.checked_mul(*r),
+                    ConstBinOp::Div => l.checked_div(*r),
+                    ConstBinOp::Rem => l.checked_rem(*r),
+                }?;
+                ChalkConst::int(*int_ty, value)?
+            }
+            _ => return None,
+        };
+        Some(ConcreteConst { interned })
+    }
 }
 
 impl RustIrDatabase<ChalkIr> for Program {
@@ -482,8 +680,10 @@ impl RustIrDatabase<ChalkIr> for Program {
     fn local_impls_to_coherence_check(&self, trait_id: TraitId<ChalkIr>) -> Vec<ImplId<ChalkIr>> {
         self.impl_data
             .iter()
-            .filter(|(_, impl_datum)| {
-                impl_datum.trait_id() == trait_id && impl_datum.impl_type == ImplType::Local
+            .filter(|(impl_id, impl_datum)| {
+                impl_datum.trait_id() == trait_id
+                    && (impl_datum.impl_type == ImplType::Local
+                        || self.dependency_impls.contains(impl_id))
             })
             .map(|(&impl_id, _)| impl_id)
             .collect()
@@ -630,4 +830,8 @@ impl RustIrDatabase<ChalkIr> for Program {
             _ => TyKind::Scalar(Scalar::Uint(UintTy::U8)).intern(interner),
         }
     }
+
+    fn as_sync(&self) -> Option<&(dyn RustIrDatabase<ChalkIr> + Sync)> {
+        Some(self)
+    }
 }
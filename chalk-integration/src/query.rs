@@ -1,7 +1,7 @@
 // https://crates.io/crates/salsa
 // hello world https://github.com/salsa-rs/salsa/blob/master/examples/hello_world/main.rs
 
-use crate::error::ChalkError;
+use crate::error::{ChalkError, SourceFiles};
 use crate::interner::ChalkIr;
 use crate::lowering::Lower;
 use crate::program::Program;
@@ -9,18 +9,22 @@ use crate::program_environment::ProgramEnvironment;
 use crate::tls;
 use crate::SolverChoice;
 use chalk_ir::TraitId;
+use chalk_parse::{ast, IncludedFile, SyntaxError};
 use chalk_solve::clauses::builder::ClauseBuilder;
 use chalk_solve::clauses::program_clauses::ToProgramClauses;
 use chalk_solve::coherence::orphan;
 use chalk_solve::coherence::{CoherenceSolver, SpecializationPriorities};
+use chalk_solve::solve::persistent::{stable_hash, PersistentCache};
 use chalk_solve::wf;
 use chalk_solve::RustIrDatabase;
 use chalk_solve::Solver;
 use salsa::Database;
 use std::clone::Clone;
 use std::cmp::{Eq, PartialEq};
+use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
 use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::Mutex;
 
@@ -31,11 +35,43 @@ pub trait LoweringDatabase:
     #[salsa::input]
     fn program_text(&self) -> Arc<String>;
 
+    /// The directory that the paths of `include` directives in the
+    /// program text are relative to, if the program has one.
+    #[salsa::input]
+    fn program_dir(&self) -> Option<Arc<PathBuf>>;
+
+    /// The files that the program includes, by their path as found from
+    /// `program_dir`. See [`ChalkDatabase::load_included_files`].
+    ///
+    /// [`ChalkDatabase::load_included_files`]: crate::db::ChalkDatabase::load_included_files
+    #[salsa::input]
+    fn included_files(&self) -> Arc<BTreeMap<PathBuf, IncludedFile>>;
+
     #[salsa::input]
     fn solver_choice(&self) -> SolverChoice;
 
+    /// The directory to keep the results of the solver in between runs, if
+    /// any, in a file for each program and solver choice. See
+    /// [`PersistentCache`].
+    #[salsa::input]
+    fn persistent_cache_dir(&self) -> Option<Arc<PathBuf>>;
+
+    /// The text of the program and of the files it includes, which the
+    /// spans of errors point into.
+    fn source_files(&self) -> Arc<SourceFiles>;
+
+    /// The lowered IR, or the syntax errors of the program if it has any.
     fn program_ir(&self) -> Result<Arc<Program>, ChalkError>;
 
+    /// The lowered IR of the items that could be parsed, even if the
+    /// program has syntax errors. The checks run on it, so that their
+    /// errors are reported along with the syntax errors.
+    fn lowered_program(&self) -> Result<Arc<Program>, ChalkError>;
+
+    /// A hash of the program, including the files it includes, which
+    /// changes whenever the program does.
+    fn program_fingerprint(&self) -> Result<u64, ChalkError>;
+
     /// Performs coherence check and computes which impls specialize
     /// one another (the "specialization priorities").
     fn coherence(
@@ -122,73 +158,176 @@ impl<T> Clone for ArcEq<T> {
     }
 }
 
-fn program_ir(db: &dyn LoweringDatabase) -> Result<Arc<Program>, ChalkError> {
+/// Parses the program text, and the files it includes. The program is
+/// returned even if it has syntax errors, with the items that could be
+/// parsed, along with the errors and the paths of the included files (see
+/// `chalk_parse::expand_includes`).
+fn parse_program(
+    db: &dyn LoweringDatabase,
+) -> Result<(ast::Program, Vec<SyntaxError>, Vec<PathBuf>), ChalkError> {
     let text = db.program_text();
-    Ok(Arc::new(chalk_parse::parse_program(&text)?.lower()?))
+    let (mut program, errors) = chalk_parse::parse_program_recovering(&text);
+    let dir = db.program_dir();
+    let files = db.included_files();
+    let missing = match dir {
+        Some(_) => "the file has not been loaded",
+        None => "the program has no directory to include files from",
+    };
+    let dir = dir.as_deref().map_or(Path::new(""), |dir| dir.as_path());
+    let paths = chalk_parse::expand_includes(&mut program, dir, &mut |path| {
+        files
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| Err(missing.to_string()))
+    })?;
+    Ok((program, errors, paths))
+}
+
+fn source_files(db: &dyn LoweringDatabase) -> Arc<SourceFiles> {
+    let mut sources = SourceFiles::new(&db.program_text());
+    let paths = parse_program(db).map_or(vec![], |(_, _, paths)| paths);
+    let files = db.included_files();
+    let dir = db.program_dir();
+    for path in paths {
+        // Files are named relative to the directory of the program.
+        let name = dir
+            .as_deref()
+            .and_then(|dir| path.strip_prefix(dir).ok())
+            .unwrap_or(&path)
+            .to_path_buf();
+        let text = match files.get(&path) {
+            Some(Ok((_, text))) => text.clone(),
+            _ => String::new(),
+        };
+        sources.include(name, text);
+    }
+    Arc::new(sources)
+}
+
+fn program_ir(db: &dyn LoweringDatabase) -> Result<Arc<Program>, ChalkError> {
+    let (_, syntax_errors, _) = parse_program(db)?;
+    if !syntax_errors.is_empty() {
+        return Err(ChalkError::from_syntax_errors(syntax_errors));
+    }
+    db.lowered_program()
+}
+
+fn lowered_program(db: &dyn LoweringDatabase) -> Result<Arc<Program>, ChalkError> {
+    let (program, _, _) = parse_program(db)?;
+    Ok(Arc::new(program.lower()?))
+}
+
+fn program_fingerprint(db: &dyn LoweringDatabase) -> Result<u64, ChalkError> {
+    // Spans are not hashed, so changes to the whitespace or comments of
+    // the program keep the fingerprint.
+    let (program, syntax_errors, _) = parse_program(db)?;
+    if !syntax_errors.is_empty() {
+        return Err(ChalkError::from_syntax_errors(syntax_errors));
+    }
+    Ok(stable_hash(&program))
 }
 
 fn orphan_check(db: &dyn LoweringDatabase) -> Result<(), ChalkError> {
-    let program = db.program_ir()?;
+    let program = db.lowered_program()?;
+    let sources = db.source_files();
+    let solver_choice = db.solver_choice();
 
-    tls::set_current_program(&program, || -> Result<(), ChalkError> {
-        let local_impls = program.local_impl_ids();
-        for impl_id in local_impls {
-            let mut solver = db.solver_choice().into_solver();
-            orphan::perform_orphan_check::<ChalkIr>(db.upcast(), &mut *solver, impl_id)?;
-        }
-        Ok(())
-    })
+    for view in program.crate_views() {
+        tls::set_current_program(&view, || -> Result<(), ChalkError> {
+            for impl_id in view.local_impl_ids() {
+                let mut solver = solver_choice.into_solver();
+                orphan::perform_orphan_check::<ChalkIr>(&*view, &mut *solver, impl_id)
+                    .map_err(|e| ChalkError::from_coherence(e, &program, &sources))?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(())
 }
 
 fn coherence(
     db: &dyn LoweringDatabase,
 ) -> Result<BTreeMap<TraitId<ChalkIr>, Arc<SpecializationPriorities<ChalkIr>>>, ChalkError> {
-    let program = db.program_ir()?;
+    let program = db.lowered_program()?;
+    let sources = db.source_files();
     let solver_choice = db.solver_choice();
-    let priorities_map = tls::set_current_program(&program, || -> Result<_, ChalkError> {
-        let solver_builder = || solver_choice.into_solver();
-        let priorities_map: Result<BTreeMap<_, _>, ChalkError> = program
-            .trait_data
-            .keys()
-            .map(|&trait_id| {
+
+    // Each crate is checked from its own point of view; the priorities of
+    // the impls of a trait are merged from all of the crates that see them.
+    let mut priorities_map: BTreeMap<_, Arc<SpecializationPriorities<ChalkIr>>> = BTreeMap::new();
+    for view in program.crate_views() {
+        tls::set_current_program(&view, || -> Result<(), ChalkError> {
+            let solver_builder = || solver_choice.into_solver();
+            for &trait_id in view.trait_data.keys() {
                 let solver: CoherenceSolver<ChalkIr> =
-                    CoherenceSolver::new(db.upcast(), &solver_builder, trait_id);
-                let priorities = solver.specialization_priorities()?;
-                Ok((trait_id, priorities))
-            })
-            .collect();
-        let priorities_map = priorities_map?;
-        Ok(priorities_map)
-    });
+                    CoherenceSolver::new(&*view, &solver_builder, trait_id);
+                let priorities = solver
+                    .specialization_priorities()
+                    .map_err(|e| ChalkError::from_coherence(e, &program, &sources))?;
+                match priorities_map.entry(trait_id) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(priorities);
+                    }
+                    Entry::Occupied(mut entry) => {
+                        Arc::make_mut(entry.get_mut()).merge(&priorities);
+                    }
+                }
+            }
+            Ok(())
+        })?;
+    }
     let () = db.orphan_check()?;
-    priorities_map
+    Ok(priorities_map)
 }
 
 fn checked_program(db: &dyn LoweringDatabase) -> Result<Arc<Program>, ChalkError> {
-    let program = db.program_ir()?;
+    // The items that could be parsed are still lowered and checked, so that
+    // their errors are reported along with the syntax errors.
+    let checked = db.lowered_program().and_then(|program| {
+        db.coherence()?;
+        check_well_formed(&program, db.solver_choice())?;
+        Ok(program)
+    });
 
-    db.coherence()?;
+    let (_, syntax_errors, _) = parse_program(db)?;
+    if syntax_errors.is_empty() {
+        return checked;
+    }
+    let error = ChalkError::from_syntax_errors(syntax_errors);
+    Err(match checked {
+        Ok(_) => error,
+        Err(other) => error.followed_by(other),
+    })
+}
 
-    let solver_choice = db.solver_choice();
-    let () = tls::set_current_program(&program, || -> Result<(), ChalkError> {
+/// Checks that the types and impls of `program` are well-formed.
+fn check_well_formed(
+    program: &Arc<Program>,
+    solver_choice: SolverChoice,
+) -> Result<(), ChalkError> {
+    tls::set_current_program(program, || -> Result<(), ChalkError> {
         let solver_builder = || solver_choice.into_solver();
-        let solver: wf::WfSolver<ChalkIr> = wf::WfSolver::new(db.upcast(), &solver_builder);
-        for &id in program.adt_data.keys() {
-            solver.verify_adt_decl(id)?;
+        let solver: wf::WfSolver<ChalkIr> = wf::WfSolver::new(&**program, &solver_builder);
+        for (&id, adt_datum) in &program.adt_data {
+            solver
+                .verify_adt_decl(id)
+                .map_err(|e| ChalkError::from_wf(e, program, id.0, &adt_datum.binders.binders))?;
         }
 
         for &opaque_ty_id in program.opaque_ty_data.keys() {
-            solver.verify_opaque_ty_decl(opaque_ty_id)?;
+            solver
+                .verify_opaque_ty_decl(opaque_ty_id)
+                .map_err(|e| ChalkError::from(e).at(program.item_span(opaque_ty_id.0)))?;
         }
 
-        for &impl_id in program.impl_data.keys() {
-            solver.verify_trait_impl(impl_id)?;
+        for (&impl_id, impl_datum) in &program.impl_data {
+            solver.verify_trait_impl(impl_id).map_err(|e| {
+                ChalkError::from_wf(e, program, impl_id.0, &impl_datum.binders.binders)
+            })?;
         }
 
         Ok(())
-    })?;
-
-    Ok(program)
+    })
 }
 
 fn environment(db: &dyn LoweringDatabase) -> Result<Arc<ProgramEnvironment>, ChalkError> {
@@ -253,5 +392,14 @@ fn environment(db: &dyn LoweringDatabase) -> Result<Arc<ProgramEnvironment>, Cha
 fn solver(db: &dyn LoweringDatabase) -> ArcEq<Mutex<Box<dyn Solver<ChalkIr>>>> {
     db.salsa_runtime().report_untracked_read();
     let choice = db.solver_choice();
-    ArcEq::new(Mutex::new(choice.into_solver()))
+    let persistent_cache = db.persistent_cache_dir().and_then(|dir| {
+        // The solver choice holds all of the settings of the solver, which
+        // change its results: the overflow depth, the maximum size, etc.
+        let fingerprint = stable_hash(&(db.program_fingerprint().ok()?, choice));
+        let path = dir.join(format!("{:016x}.chalk-cache", fingerprint));
+        Some(Arc::new(PersistentCache::open(path, fingerprint)))
+    });
+    ArcEq::new(Mutex::new(
+        choice.into_solver_with_persistent_cache(persistent_cache),
+    ))
 }
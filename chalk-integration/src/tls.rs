@@ -1,16 +1,20 @@
 use crate::interner::ChalkIr;
 use chalk_ir::{
-    debug::SeparatorTraitRef, AdtId, AliasTy, AssocTypeId, CanonicalVarKinds, Constraints, FnDefId,
-    GenericArg, Goal, Goals, Lifetime, OpaqueTy, OpaqueTyId, ProgramClause,
-    ProgramClauseImplication, ProgramClauses, ProjectionTy, QuantifiedWhereClauses, Substitution,
-    TraitId, Ty, VariableKinds, Variances,
+    debug::SeparatorTraitRef, AdtId, AliasTy, AssocTypeId, CanonicalVarKinds, Const, Constraints,
+    FnDefId, GenericArg, Goal, Goals, Lifetime, OpaqueTy, OpaqueTyId, PlaceholderIndex,
+    ProgramClause, ProgramClauseImplication, ProgramClauses, ProjectionTy, QuantifiedWhereClauses,
+    Substitution, TraitId, Ty, VariableKinds, Variances,
 };
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::sync::Arc;
+use string_cache::DefaultAtom as Atom;
 
 thread_local! {
-    static PROGRAM: RefCell<Option<Arc<dyn DebugContext>>> = RefCell::new(None)
+    static PROGRAM: RefCell<Option<Arc<dyn DebugContext>>> = RefCell::new(None);
+    static PLACEHOLDER_NAMES: RefCell<BTreeMap<PlaceholderIndex, Atom>> =
+        const { RefCell::new(BTreeMap::new()) };
 }
 
 pub trait DebugContext {
@@ -70,6 +74,12 @@ pub trait DebugContext {
         fmt: &mut fmt::Formatter<'_>,
     ) -> Result<(), fmt::Error>;
 
+    fn debug_const(
+        &self,
+        constant: &Const<ChalkIr>,
+        fmt: &mut fmt::Formatter<'_>,
+    ) -> Result<(), fmt::Error>;
+
     fn debug_generic_arg(
         &self,
         generic_arg: &GenericArg<ChalkIr>,
@@ -174,3 +184,21 @@ where
         r
     })
 }
+
+/// Runs `op` with the placeholders in `names` shown by their names (rather
+/// than as e.g. `!1_0`) in the debug output of the current program.
+pub fn with_placeholder_names<R>(
+    names: BTreeMap<PlaceholderIndex, Atom>,
+    op: impl FnOnce() -> R,
+) -> R {
+    let previous = PLACEHOLDER_NAMES.with(|names_cell| names_cell.replace(names));
+    let r = op();
+    PLACEHOLDER_NAMES.with(|names_cell| *names_cell.borrow_mut() = previous);
+    r
+}
+
+/// The name to show `placeholder` with, if any (see
+/// [`with_placeholder_names`]).
+pub fn placeholder_name(placeholder: PlaceholderIndex) -> Option<Atom> {
+    PLACEHOLDER_NAMES.with(|names_cell| names_cell.borrow().get(&placeholder).cloned())
+}
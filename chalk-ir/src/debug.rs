@@ -87,6 +87,25 @@ impl<I: Interner> Debug for ConcreteConst<I> {
     }
 }
 
+impl<I: Interner> Debug for ConstExpr<I> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(fmt, "({:?} {} {:?})", self.lhs, self.op, self.rhs)
+    }
+}
+
+impl Display for ConstBinOp {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), Error> {
+        let op = match self {
+            ConstBinOp::Add => "+",
+            ConstBinOp::Sub => "-",
+            ConstBinOp::Mul => "*",
+            ConstBinOp::Div => "/",
+            ConstBinOp::Rem => "%",
+        };
+        write!(fmt, "{}", op)
+    }
+}
+
 impl<I: Interner> Debug for GenericArg<I> {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), Error> {
         I::debug_generic_arg(self, fmt).unwrap_or_else(|| write!(fmt, "{:?}", self.interned))
@@ -368,6 +387,7 @@ impl<I: Interner> Debug for ConstData<I> {
             ConstValue::InferenceVar(var) => write!(fmt, "{:?}", var),
             ConstValue::Placeholder(index) => write!(fmt, "{:?}", index),
             ConstValue::Concrete(evaluated) => write!(fmt, "{:?}", evaluated),
+            ConstValue::Expr(expr) => write!(fmt, "{:?}", expr),
         }
     }
 }
@@ -874,6 +874,15 @@ where
                 }),
             }
             .intern(folder.interner())),
+            ConstValue::Expr(expr) => Ok(ConstData {
+                ty: fold_ty()?,
+                value: ConstValue::Expr(ConstExpr {
+                    op: expr.op,
+                    lhs: expr.lhs.clone().try_fold_with(folder, outer_binder)?,
+                    rhs: expr.rhs.clone().try_fold_with(folder, outer_binder)?,
+                }),
+            }
+            .intern(folder.interner())),
         }
     }
 }
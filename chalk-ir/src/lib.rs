@@ -708,19 +708,7 @@ impl<I: Interner> TyKind<I> {
                 lifetime.compute_flags(interner) | ty.data(interner).flags
             }
             TyKind::Array(ty, const_ty) => {
-                let flags = ty.data(interner).flags;
-                let const_data = const_ty.data(interner);
-                flags
-                    | const_data.ty.data(interner).flags
-                    | match const_data.value {
-                        ConstValue::BoundVar(_) | ConstValue::Concrete(_) => TypeFlags::empty(),
-                        ConstValue::InferenceVar(_) => {
-                            TypeFlags::HAS_CT_INFER | TypeFlags::STILL_FURTHER_SPECIALIZABLE
-                        }
-                        ConstValue::Placeholder(_) => {
-                            TypeFlags::HAS_CT_PLACEHOLDER | TypeFlags::STILL_FURTHER_SPECIALIZABLE
-                        }
-                    }
+                ty.data(interner).flags | const_ty.compute_flags(interner)
             }
             TyKind::Placeholder(_) => TypeFlags::HAS_TY_PLACEHOLDER,
             TyKind::Dyn(dyn_ty) => {
@@ -1182,6 +1170,26 @@ impl<I: Interner> Const<I> {
             ConstValue::InferenceVar(_) => false,
             ConstValue::Placeholder(_) => false,
             ConstValue::Concrete(_) => false,
+            ConstValue::Expr(_) => false,
+        }
+    }
+
+    /// Compute type flags for Const<I>
+    fn compute_flags(&self, interner: I) -> TypeFlags {
+        let data = self.data(interner);
+        let flags = data.ty.data(interner).flags;
+        match &data.value {
+            ConstValue::BoundVar(_) => flags,
+            ConstValue::InferenceVar(_) => {
+                flags | TypeFlags::HAS_CT_INFER | TypeFlags::STILL_FURTHER_SPECIALIZABLE
+            }
+            ConstValue::Placeholder(_) => {
+                flags | TypeFlags::HAS_CT_PLACEHOLDER | TypeFlags::STILL_FURTHER_SPECIALIZABLE
+            }
+            ConstValue::Concrete(_) => flags,
+            ConstValue::Expr(expr) => {
+                flags | expr.lhs.compute_flags(interner) | expr.rhs.compute_flags(interner)
+            }
         }
     }
 }
@@ -1206,9 +1214,16 @@ pub enum ConstValue<I: Interner> {
     Placeholder(PlaceholderIndex),
     /// Concrete constant value.
     Concrete(ConcreteConst<I>),
+    /// Constant expression that has not been evaluated yet, e.g. `N + 1`.
+    Expr(ConstExpr<I>),
 }
 
-impl<I: Interner> Copy for ConstValue<I> where I::InternedConcreteConst: Copy {}
+impl<I: Interner> Copy for ConstValue<I>
+where
+    I::InternedConcreteConst: Copy,
+    I::InternedConst: Copy,
+{
+}
 
 impl<I: Interner> ConstData<I> {
     /// Wraps the constant data in a `Const`.
@@ -1232,6 +1247,36 @@ impl<I: Interner> ConcreteConst<I> {
     }
 }
 
+/// An operation on constants, applied by
+/// [`UnificationDatabase::evaluate_const_expr`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum ConstBinOp {
+    /// `a + b`
+    Add,
+    /// `a - b`
+    Sub,
+    /// `a * b`
+    Mul,
+    /// `a / b`
+    Div,
+    /// `a % b`
+    Rem,
+}
+
+/// A constant expression such as `N + 1`, whose operands have the same
+/// type as the expression. It is evaluated once its operands are concrete.
+#[derive(Clone, PartialEq, Eq, Hash, HasInterner)]
+pub struct ConstExpr<I: Interner> {
+    /// The operation.
+    pub op: ConstBinOp,
+    /// The left operand.
+    pub lhs: Const<I>,
+    /// The right operand.
+    pub rhs: Const<I>,
+}
+
+impl<I: Interner> Copy for ConstExpr<I> where I::InternedConst: Copy {}
+
 /// A Rust lifetime.
 #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, HasInterner)]
 pub struct Lifetime<I: Interner> {
@@ -1500,22 +1545,7 @@ impl<I: Interner> GenericArg<I> {
         match self.data(interner) {
             GenericArgData::Ty(ty) => ty.data(interner).flags,
             GenericArgData::Lifetime(lifetime) => lifetime.compute_flags(interner),
-            GenericArgData::Const(constant) => {
-                let data = constant.data(interner);
-                let flags = data.ty.data(interner).flags;
-                match data.value {
-                    ConstValue::BoundVar(_) => flags,
-                    ConstValue::InferenceVar(_) => {
-                        flags | TypeFlags::HAS_CT_INFER | TypeFlags::STILL_FURTHER_SPECIALIZABLE
-                    }
-                    ConstValue::Placeholder(_) => {
-                        flags
-                            | TypeFlags::HAS_CT_PLACEHOLDER
-                            | TypeFlags::STILL_FURTHER_SPECIALIZABLE
-                    }
-                    ConstValue::Concrete(_) => flags,
-                }
-            }
+            GenericArgData::Const(constant) => constant.compute_flags(interner),
         }
     }
 }
@@ -3061,4 +3091,14 @@ where
 
     /// Gets the variances for the substitution of a adt
     fn adt_variance(&self, adt_id: AdtId<I>) -> Variances<I>;
+
+    /// Evaluates `lhs op rhs`, for constants of type `ty`. Returns `None`
+    /// if the operation fails (e.g. on overflow, or division by zero).
+    fn evaluate_const_expr(
+        &self,
+        ty: &Ty<I>,
+        op: ConstBinOp,
+        lhs: &ConcreteConst<I>,
+        rhs: &ConcreteConst<I>,
+    ) -> Option<ConcreteConst<I>>;
 }
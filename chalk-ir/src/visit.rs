@@ -368,6 +368,10 @@ impl<I: Interner> TypeSuperVisitable<I> for Const<I> {
                 visitor.visit_free_placeholder(*universe, outer_binder)
             }
             ConstValue::Concrete(_) => ControlFlow::Continue(()),
+            ConstValue::Expr(expr) => {
+                try_break!(expr.lhs.visit_with(visitor, outer_binder));
+                expr.rhs.visit_with(visitor, outer_binder)
+            }
         }
     }
 }
@@ -1,24 +1,49 @@
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use string_cache::DefaultAtom as Atom;
 
+/// Identifies the text a span is in: that of the program itself, or of one
+/// of the files it includes, numbered from 1 in the order that
+/// [`expand_includes`](crate::expand_includes) reads them.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct FileId(pub usize);
+
+impl FileId {
+    /// The program itself.
+    pub const PROGRAM: FileId = FileId(0);
+}
+
+/// Spans are not hashed, so that the hash of a program (see
+/// `program_fingerprint` in `chalk-integration`) does not change when only
+/// its layout does.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct Span {
     pub lo: usize,
     pub hi: usize,
+    pub file: FileId,
 }
 
 impl Span {
+    /// A span of the text of the program itself.
     pub fn new(lo: usize, hi: usize) -> Self {
-        Span { lo, hi }
+        Span::in_file(FileId::PROGRAM, lo, hi)
+    }
+
+    pub fn in_file(file: FileId, lo: usize, hi: usize) -> Self {
+        Span { lo, hi, file }
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+impl Hash for Span {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Program {
     pub items: Vec<Item>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Item {
     AdtDefn(AdtDefn),
     FnDefn(FnDefn),
@@ -29,12 +54,89 @@ pub enum Item {
     Impl(Impl),
     Clause(Clause),
     Foreign(ForeignDefn),
+    Module(ModuleDefn),
+    Use(UseDefn),
+    Include(Include),
+    Crate(CrateDefn),
+    TypeAlias(TypeAliasDefn),
+    TraitAlias(TraitAliasDefn),
+}
+
+impl Item {
+    /// The span that diagnostics about this item point at: the name of a
+    /// definition, or the header of an impl.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Item::AdtDefn(defn) => Some(defn.name.span),
+            Item::FnDefn(defn) => Some(defn.name.span),
+            Item::ClosureDefn(defn) => Some(defn.name.span),
+            Item::TraitDefn(defn) => Some(defn.name.span),
+            Item::OpaqueTyDefn(defn) => Some(defn.name.span),
+            Item::CoroutineDefn(defn) => Some(defn.name.span),
+            Item::Impl(impl_) => Some(impl_.span),
+            Item::Clause(_) => None,
+            Item::Foreign(ForeignDefn(name)) => Some(name.span),
+            Item::Module(defn) => Some(defn.name.span),
+            Item::Use(use_) => Some(use_.path().span),
+            Item::Include(include) => Some(include.span),
+            Item::Crate(defn) => Some(defn.name.span),
+            Item::TypeAlias(defn) => Some(defn.name.span),
+            Item::TraitAlias(defn) => Some(defn.name.span),
+        }
+    }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+/// `mod name { items }`. The items are named `name::Item` outside of the
+/// module.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ModuleDefn {
+    pub name: Identifier,
+    pub items: Vec<Item>,
+}
+
+/// `crate name: dependencies { items }`, which may only appear at the top
+/// level of a program. The items are named `name::Item`, and are only
+/// visible from the crate itself and the crates that depend on it.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct CrateDefn {
+    pub name: Identifier,
+    pub dependencies: Vec<Identifier>,
+    pub items: Vec<Item>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum UseDefn {
+    /// `use a::B;`, or `use a::B as C;`
+    Name {
+        path: Identifier,
+        alias: Option<Identifier>,
+    },
+    /// `use a::*;`
+    Glob { path: Identifier },
+}
+
+impl UseDefn {
+    pub fn path(&self) -> &Identifier {
+        match self {
+            UseDefn::Name { path, .. } | UseDefn::Glob { path } => path,
+        }
+    }
+}
+
+/// `include "path";`, which stands for the items of the file at `path`
+/// (relative to the including file). The parser leaves `items` empty; they
+/// are filled in by [`expand_includes`](crate::expand_includes).
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Include {
+    pub path: String,
+    pub span: Span,
+    pub items: Vec<Item>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct ForeignDefn(pub Identifier);
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct AdtDefn {
     pub name: Identifier,
     pub variable_kinds: Vec<VariableKind>,
@@ -45,18 +147,18 @@ pub struct AdtDefn {
     pub variances: Option<Vec<Variance>>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Variant {
     pub name: Identifier,
     pub fields: Vec<Field>,
 }
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum Movability {
     Static,
     Movable,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct CoroutineDefn {
     pub name: Identifier,
     pub movability: Movability,
@@ -69,7 +171,7 @@ pub struct CoroutineDefn {
     pub witness_lifetimes: Vec<Identifier>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct AdtFlags {
     pub upstream: bool,
     pub fundamental: bool,
@@ -78,35 +180,35 @@ pub struct AdtFlags {
     pub kind: AdtKind,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum AdtKind {
     Struct,
     Enum,
     Union,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum AdtReprAttr {
     C,
     Packed,
     Int(Ty),
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct AdtRepr {
     pub c: bool,
     pub packed: bool,
     pub int: Option<Ty>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct FnSig {
     pub abi: FnAbi,
     pub safety: Safety,
     pub variadic: bool,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct FnDefn {
     pub name: Identifier,
     pub variable_kinds: Vec<VariableKind>,
@@ -117,7 +219,7 @@ pub struct FnDefn {
     pub variances: Option<Vec<Variance>>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct ClosureDefn {
     pub name: Identifier,
     pub kind: ClosureKind,
@@ -127,7 +229,7 @@ pub struct ClosureDefn {
     pub upvars: Vec<Ty>,
 }
 
-#[derive(Clone, Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub struct FnAbi(pub Atom);
 
 impl Default for FnAbi {
@@ -136,7 +238,7 @@ impl Default for FnAbi {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct TraitDefn {
     pub name: Identifier,
     pub variable_kinds: Vec<VariableKind>,
@@ -146,7 +248,7 @@ pub struct TraitDefn {
     pub well_known: Option<WellKnownTrait>,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum WellKnownTrait {
     Sized,
     Copy,
@@ -170,7 +272,7 @@ pub enum WellKnownTrait {
     Future,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct TraitFlags {
     pub auto: bool,
     pub marker: bool,
@@ -181,7 +283,7 @@ pub struct TraitFlags {
     pub object_safe: bool,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct AssocTyDefn {
     pub name: Identifier,
     pub variable_kinds: Vec<VariableKind>,
@@ -195,7 +297,24 @@ pub enum WellKnownAssocType {
     AsyncFnOnceOutput,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+/// `type Name<T> = Ty;`, which stands for `Ty` wherever `Name<T>` is used.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TypeAliasDefn {
+    pub name: Identifier,
+    pub variable_kinds: Vec<VariableKind>,
+    pub ty: Ty,
+}
+
+/// `trait Name<T> = A<T> + B;`, which stands for its bounds wherever
+/// `Name<T>` is used as a bound.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TraitAliasDefn {
+    pub name: Identifier,
+    pub variable_kinds: Vec<VariableKind>,
+    pub bounds: Vec<QuantifiedInlineBound>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct OpaqueTyDefn {
     pub ty: Ty,
     pub variable_kinds: Vec<VariableKind>,
@@ -204,16 +323,17 @@ pub struct OpaqueTyDefn {
     pub where_clauses: Vec<QuantifiedWhereClause>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum VariableKind {
     Ty(Identifier),
     IntegerTy(Identifier),
     FloatTy(Identifier),
     Lifetime(Identifier),
-    Const(Identifier),
+    /// A const parameter, with its type if one was given (`usize` otherwise).
+    Const(Identifier, Option<Ty>),
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum GenericArg {
     Ty(Ty),
     Lifetime(Lifetime),
@@ -221,26 +341,55 @@ pub enum GenericArg {
     Const(Const),
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Const {
     Id(Identifier),
-    Value(u32),
+    Value(ConstLiteral),
+    /// A binary const expression, e.g. `N + 1`.
+    Expr {
+        op: ConstOp,
+        lhs: Box<Const>,
+        rhs: Box<Const>,
+    },
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ConstLiteral {
+    Bool(bool),
+    Char(char),
+    Str(Atom),
+    /// An integer literal, e.g. `3`, `-1` or `7u8`. Without a suffix, the
+    /// type is taken from the parameter the literal is an argument for.
+    Int {
+        value: u128,
+        negative: bool,
+        suffix: Option<ScalarType>,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ConstOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 /// An inline bound, e.g. `: Foo<K>` in `impl<K, T: Foo<K>> SomeType<T>`.
 pub enum InlineBound {
     TraitBound(TraitBound),
     AliasEqBound(AliasEqBound),
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct QuantifiedInlineBound {
     pub variable_kinds: Vec<VariableKind>,
     pub bound: InlineBound,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 /// Represents a trait bound on e.g. a type or type parameter.
 /// Does not know anything about what it's binding.
 pub struct TraitBound {
@@ -248,7 +397,7 @@ pub struct TraitBound {
     pub args_no_self: Vec<GenericArg>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 /// Represents an alias equality bound on e.g. a type or type parameter.
 /// Does not know anything about what it's binding.
 pub struct AliasEqBound {
@@ -258,7 +407,7 @@ pub struct AliasEqBound {
     pub value: Ty,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Kind {
     Ty,
     Lifetime,
@@ -275,7 +424,7 @@ impl fmt::Display for Kind {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Impl {
     pub variable_kinds: Vec<VariableKind>,
     pub trait_ref: TraitRef,
@@ -283,15 +432,17 @@ pub struct Impl {
     pub where_clauses: Vec<QuantifiedWhereClause>,
     pub assoc_ty_values: Vec<AssocTyValue>,
     pub impl_type: ImplType,
+    /// The span of the impl header, up to and including the self type.
+    pub span: Span,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum ImplType {
     Local,
     External,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct AssocTyValue {
     pub name: Identifier,
     pub variable_kinds: Vec<VariableKind>,
@@ -299,7 +450,7 @@ pub struct AssocTyValue {
     pub default: bool,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Ty {
     Id {
         name: Identifier,
@@ -346,7 +497,7 @@ pub enum Ty {
     Never,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum IntTy {
     Isize,
     I8,
@@ -356,7 +507,7 @@ pub enum IntTy {
     I128,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum UintTy {
     Usize,
     U8,
@@ -366,7 +517,7 @@ pub enum UintTy {
     U128,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum FloatTy {
     F16,
     F32,
@@ -374,7 +525,7 @@ pub enum FloatTy {
     F128,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum ScalarType {
     Bool,
     Char,
@@ -383,13 +534,13 @@ pub enum ScalarType {
     Float(FloatTy),
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Mutability {
     Mut,
     Not,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Safety {
     Safe,
     Unsafe,
@@ -401,27 +552,27 @@ impl Default for Safety {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Lifetime {
     Id { name: Identifier },
     Static,
     Erased,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct ProjectionTy {
     pub trait_ref: TraitRef,
     pub name: Identifier,
     pub args: Vec<GenericArg>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct TraitRef {
     pub trait_name: Identifier,
     pub args: Vec<GenericArg>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Polarity {
     /// `impl Foo for Bar`
     Positive,
@@ -440,7 +591,7 @@ impl Polarity {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Identifier {
     pub str: Atom,
     pub span: Span,
@@ -452,7 +603,7 @@ impl fmt::Display for Identifier {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum WhereClause {
     Implemented { trait_ref: TraitRef },
     ProjectionEq { projection: ProjectionTy, ty: Ty },
@@ -460,7 +611,7 @@ pub enum WhereClause {
     TypeOutlives { ty: Ty, lifetime: Lifetime },
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum DomainGoal {
     Holds { where_clause: WhereClause },
     Normalize { projection: ProjectionTy, ty: Ty },
@@ -478,26 +629,26 @@ pub enum DomainGoal {
     ObjectSafe { id: Identifier },
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum LeafGoal {
     DomainGoal { goal: DomainGoal },
     UnifyGenericArgs { a: GenericArg, b: GenericArg },
     SubtypeGenericArgs { a: Ty, b: Ty },
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct QuantifiedWhereClause {
     pub variable_kinds: Vec<VariableKind>,
     pub where_clause: WhereClause,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Field {
     pub name: Identifier,
     pub ty: Ty,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 /// This allows users to add arbitrary `A :- B` clauses into the
 /// logic; it has no equivalent in Rust, but it's useful for testing.
 pub struct Clause {
@@ -506,7 +657,7 @@ pub struct Clause {
     pub conditions: Vec<Box<Goal>>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Goal {
     ForAll(Vec<VariableKind>, Box<Goal>),
     Exists(Vec<VariableKind>, Box<Goal>),
@@ -518,22 +669,22 @@ pub enum Goal {
     Compatible(Box<Goal>),
 
     // Additional kinds of goals:
-    Leaf(LeafGoal),
+    Leaf(Box<LeafGoal>),
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum ClosureKind {
     Fn,
     FnMut,
     FnOnce,
 }
 
-#[derive(Clone, Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub enum FnArg {
     NonVariadic(Ty),
     Variadic,
 }
-#[derive(Clone, Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub enum FnArgs {
     NonVariadic(Vec<Ty>),
     Variadic(Vec<Ty>),
@@ -573,7 +724,7 @@ impl FnArgs {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Variance {
     Invariant,
     Covariant,
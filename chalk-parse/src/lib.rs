@@ -6,46 +6,239 @@ extern crate lalrpop_util;
 
 pub mod ast;
 #[rustfmt::skip]
-lalrpop_mod!(pub parser);
+lalrpop_mod!(
+    // The parsers take the list of recovered errors as `&mut Vec`.
+    #[allow(clippy::ptr_arg)]
+    pub parser
+);
 
 use lalrpop_util::ParseError;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// A syntax error in a program, along with where it was found.
+#[derive(Clone, Debug)]
+pub struct SyntaxError {
+    pub message: String,
+    pub span: ast::Span,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "parse error: {}", self.message)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// An error reported by an action of the grammar, rather than by the parser
+/// itself, e.g. for an unknown `#[repr]` flag.
+#[derive(Clone, Debug)]
+pub struct UserError {
+    pub message: &'static str,
+    /// The span of the text the error is about.
+    pub span: ast::Span,
+}
+
+impl fmt::Display for UserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Parses a program. On failure, the error is the first [`SyntaxError`];
+/// use [`parse_program_recovering`] to find all of them.
 pub fn parse_program(text: &str) -> Result<ast::Program> {
-    parser::ProgramParser::new()
-        .parse(text)
-        .map_err(|e| format!("parse error: {}", e).into())
+    let (program, errors) = parse_program_recovering(text);
+    match errors.into_iter().next() {
+        Some(error) => Err(error.into()),
+        None => Ok(program),
+    }
 }
 
-pub fn parse_ty(text: &str) -> Result<ast::Ty> {
-    parser::TyParser::new()
-        .parse(text)
-        .map_err(|e| format!("error parsing `{}`: {}", text, e).into())
+/// Parses a program, recovering from syntax errors: an item containing an
+/// error is skipped, and parsing resumes at the next item. Returns the items
+/// that could be parsed, along with an error for each item that could not.
+pub fn parse_program_recovering(text: &str) -> (ast::Program, Vec<SyntaxError>) {
+    parse_file_recovering(text, ast::FileId::PROGRAM)
 }
 
-pub fn parse_goal(text: &str) -> Result<Box<ast::Goal>> {
-    parser::GoalParser::new().parse(text).map_err(|e| {
-        let mut output = format!("parse error: {}", &e);
-        if let Some(s) = match e {
-            ParseError::InvalidToken { location } => {
-                Some(position_string(text, location, location + 1))
+/// Like [`parse_program_recovering`], for the text of `file`.
+fn parse_file_recovering(text: &str, file: ast::FileId) -> (ast::Program, Vec<SyntaxError>) {
+    let mut errors = vec![];
+    let result = parser::ProgramParser::new().parse(&mut errors, file, text);
+    let mut errors: Vec<_> = errors
+        .into_iter()
+        .map(|recovery| SyntaxError::from(recovery.error))
+        .collect();
+    let program = result.unwrap_or_else(|e| {
+        errors.push(e.into());
+        ast::Program { items: vec![] }
+    });
+    (program, errors)
+}
+
+/// An `include` directive that could not be expanded.
+#[derive(Clone, Debug)]
+pub struct IncludeError {
+    pub message: String,
+    /// The span of the `include` directive, in the including file.
+    pub span: ast::Span,
+}
+
+impl fmt::Display for IncludeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for IncludeError {}
+
+/// An included file, as read by [`read_included_file`]: its canonical path,
+/// which tells whether a file includes itself, and its text. On failure, the
+/// error message.
+pub type IncludedFile = std::result::Result<(PathBuf, String), String>;
+
+/// Reads the file at `path` from disk.
+pub fn read_included_file(path: &Path) -> IncludedFile {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let path = fs::canonicalize(path).map_err(|e| e.to_string())?;
+    Ok((path, text))
+}
+
+/// Parses the files that the `include` directives of `program` refer to,
+/// filling in their items. `read` gives the file at a path, e.g.
+/// [`read_included_file`]. Paths are relative to `dir` (the directory of the
+/// program) or, in included files, to the directory of the including file.
+/// On failure, the error is an [`IncludeError`] for the outermost `include`
+/// involved.
+///
+/// The spans of the items of an included file are in that file: returns the
+/// paths that were given to `read` for the included files, the path of the
+/// file with `FileId(n)` being the n-th.
+pub fn expand_includes(
+    program: &mut ast::Program,
+    dir: &Path,
+    read: &mut dyn FnMut(&Path) -> IncludedFile,
+) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    expand_includes_in(&mut program.items, dir, read, &mut vec![], &mut files)?;
+    Ok(files)
+}
+
+fn expand_includes_in(
+    items: &mut [ast::Item],
+    dir: &Path,
+    read: &mut dyn FnMut(&Path) -> IncludedFile,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> std::result::Result<(), IncludeError> {
+    for item in items {
+        let include = match item {
+            ast::Item::Module(module) => {
+                expand_includes_in(&mut module.items, dir, read, stack, files)?;
+                continue;
             }
+            ast::Item::Crate(krate) => {
+                expand_includes_in(&mut krate.items, dir, read, stack, files)?;
+                continue;
+            }
+            ast::Item::Include(include) => include,
+            _ => continue,
+        };
+        let (include_path, span) = (include.path.clone(), include.span);
+        let error = |message: String| IncludeError {
+            message: format!("cannot include `{}`: {}", include_path, message),
+            span,
+        };
+        let read_path = dir.join(&include.path);
+        let (path, text) = read(&read_path).map_err(error)?;
+        if stack.contains(&path) {
+            return Err(error("the file includes itself".to_string()));
+        }
+        files.push(read_path);
+        let (program, errors) = parse_file_recovering(&text, ast::FileId(files.len()));
+        if let Some(e) = errors.into_iter().next() {
+            return Err(error(e.to_string()));
+        }
+        include.items = program.items;
+        stack.push(path);
+        let dir = stack.last().unwrap().parent().unwrap_or(dir).to_path_buf();
+        let result = expand_includes_in(&mut include.items, &dir, read, stack, files);
+        stack.pop();
+        result.map_err(|e| error(e.message))?;
+    }
+    Ok(())
+}
+
+/// Parses the items of a program, along with the span of the text that
+/// each of them covers (including attributes). On failure, the error is the
+/// first [`SyntaxError`].
+pub fn parse_items(text: &str) -> Result<Vec<(ast::Span, ast::Item)>> {
+    let mut errors = vec![];
+    let items = parser::SpannedItemsParser::new()
+        .parse(&mut errors, ast::FileId::PROGRAM, text)
+        .map_err(SyntaxError::from)?;
+    match errors.into_iter().next() {
+        Some(recovery) => Err(SyntaxError::from(recovery.error).into()),
+        None => Ok(items),
+    }
+}
+
+impl<T: fmt::Display> From<ParseError<usize, T, UserError>> for SyntaxError {
+    fn from(e: ParseError<usize, T, UserError>) -> Self {
+        let span = match e {
+            ParseError::InvalidToken { location } => ast::Span::new(location, location + 1),
+            ParseError::UnrecognizedEof { location, .. } => ast::Span::new(location, location),
             ParseError::UnrecognizedToken {
                 token: (start, _, end),
                 ..
-            } => Some(position_string(text, start, end)),
-            ParseError::ExtraToken {
+            }
+            | ParseError::ExtraToken {
                 token: (start, _, end),
-                ..
-            } => Some(position_string(text, start, end)),
-            _ => None,
-        } {
-            output.push('\n');
-            output += &s;
+            } => ast::Span::new(start, end),
+            ParseError::User { ref error } => error.span,
+        };
+        SyntaxError {
+            message: e.to_string(),
+            span,
         }
-        output.into()
-    })
+    }
+}
+
+pub fn parse_ty(text: &str) -> Result<ast::Ty> {
+    parser::TyParser::new()
+        .parse(&mut vec![], ast::FileId::PROGRAM, text)
+        .map_err(|e| format!("error parsing `{}`: {}", text, e).into())
+}
+
+pub fn parse_goal(text: &str) -> Result<Box<ast::Goal>> {
+    parser::GoalParser::new()
+        .parse(&mut vec![], ast::FileId::PROGRAM, text)
+        .map_err(|e| {
+            let mut output = format!("parse error: {}", &e);
+            if let Some(s) = match e {
+                ParseError::InvalidToken { location } => {
+                    Some(position_string(text, location, location + 1))
+                }
+                ParseError::UnrecognizedToken {
+                    token: (start, _, end),
+                    ..
+                } => Some(position_string(text, start, end)),
+                ParseError::ExtraToken {
+                    token: (start, _, end),
+                    ..
+                } => Some(position_string(text, start, end)),
+                _ => None,
+            } {
+                output.push('\n');
+                output += &s;
+            }
+            output.into()
+        })
 }
 
 fn position_string(text: &str, start: usize, end: usize) -> String {
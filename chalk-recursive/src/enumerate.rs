@@ -0,0 +1,213 @@
+//! Enumerating the individual answers to a goal, for `solve_multiple`.
+//!
+//! The recursive solver normally combines the answers from all matching
+//! clauses into a single (possibly ambiguous) solution. To enumerate
+//! answers instead, we split an ambiguous goal on its alternatives: each
+//! clause that could prove a domain goal is pursued separately, and if a
+//! clause is still ambiguous because one of its conditions has several
+//! answers, we recursively enumerate the answers to that condition and
+//! pursue each of them in turn. Every answer produced is the result of an
+//! ordinary `solve` of the refined goal, so it is exactly as sound as the
+//! aggregated solution; splitting only ever adds precision.
+
+use crate::fixed_point::Minimums;
+use crate::fulfill::{Fulfill, FulfillState, SolveOrSplit};
+use crate::solve::{clauses_for_goal, SolveDatabase};
+use crate::UCanonicalGoal;
+use chalk_ir::interner::Interner;
+use chalk_ir::{
+    Binders, Canonical, ClausePriority, DomainGoal, Fallible, GoalData, InEnvironment,
+    ProgramClauseData, ProgramClauseImplication, Substitution, UCanonical,
+};
+use chalk_solve::infer::InferenceTable;
+use chalk_solve::Solution;
+
+/// Invoked with each answer; returns false to stop the enumeration.
+pub(super) type AnswerCallback<'a, S, I> = dyn FnMut(&mut S, Solution<I>) -> bool + 'a;
+
+pub(super) trait EnumerateAnswers<I: Interner>: SolveDatabase<I> {
+    /// Invokes `f` with the answers to `goal`, splitting ambiguous results
+    /// on their alternatives at most `max_depth` times along any path.
+    /// Answers are not deduplicated. Returns false if `f` stopped the
+    /// enumeration.
+    fn enumerate_answers(
+        &mut self,
+        goal: &UCanonicalGoal<I>,
+        max_depth: usize,
+        f: &mut AnswerCallback<'_, Self, I>,
+    ) -> bool {
+        let solution = match self.solve_goal(goal.clone(), &mut Minimums::new(), || true) {
+            Ok(solution) => solution,
+            Err(_) => return true,
+        };
+        if !solution.is_ambig() || max_depth == 0 {
+            return f(self, solution);
+        }
+
+        let interner = self.interner();
+        let UCanonical {
+            universes,
+            canonical:
+                Canonical {
+                    binders,
+                    value: InEnvironment { environment, goal },
+                },
+        } = goal.clone();
+
+        match goal.data(interner) {
+            GoalData::DomainGoal(domain_goal) => {
+                let canonical_goal = UCanonical {
+                    universes,
+                    canonical: Canonical {
+                        binders,
+                        value: InEnvironment::new(&environment, domain_goal.clone()),
+                    },
+                };
+                let (infer, subst, goal) = InferenceTable::from_canonical(
+                    interner,
+                    universes,
+                    canonical_goal.canonical.clone(),
+                );
+                let clauses = match clauses_for_goal(self.db(), &canonical_goal, &goal.environment)
+                {
+                    Ok(clauses) => clauses,
+                    Err(_) => return f(self, solution),
+                };
+
+                // As in `solve_from_clauses`, a low-priority clause has no
+                // answers of its own when the high-priority clauses have a
+                // solution for the same inputs.
+                let mut high_solution = None;
+                for clause in &clauses {
+                    let ProgramClauseData(implication) = clause.data(interner);
+                    if implication.skip_binders().priority == ClausePriority::High {
+                        if let Ok(solution) = self.solve_clause(&infer, &subst, &goal, implication)
+                        {
+                            high_solution = Some(match high_solution {
+                                Some(high) => solution.combine(high, interner),
+                                None => solution,
+                            });
+                        }
+                    }
+                }
+
+                for clause in clauses {
+                    let ProgramClauseData(implication) = clause.data(interner);
+                    if implication.skip_binders().priority == ClausePriority::Low {
+                        if let Some(high) = &high_solution {
+                            let domain_goal = &canonical_goal.canonical.value.goal;
+                            let overridden = self
+                                .solve_clause(&infer, &subst, &goal, implication)
+                                .map_or(true, |low| high.overrides(interner, domain_goal, &low));
+                            if overridden {
+                                continue;
+                            }
+                        }
+                    }
+                    let state = match Fulfill::new_with_clause(
+                        self,
+                        infer.clone(),
+                        subst.clone(),
+                        goal.clone(),
+                        implication,
+                    ) {
+                        Ok(fulfill) => fulfill.into_state(),
+                        Err(_) => continue,
+                    };
+                    if !self.enumerate_state(state, max_depth - 1, f) {
+                        return false;
+                    }
+                }
+                true
+            }
+            _ => {
+                let (infer, subst, goal) = InferenceTable::from_canonical(
+                    interner,
+                    universes,
+                    Canonical {
+                        binders,
+                        value: InEnvironment { environment, goal },
+                    },
+                );
+                match Fulfill::new_with_simplification(self, infer, subst, goal) {
+                    Ok(fulfill) => {
+                        let state = fulfill.into_state();
+                        self.enumerate_state(state, max_depth - 1, f)
+                    }
+                    Err(_) => true,
+                }
+            }
+        }
+    }
+
+    /// Solves `goal` with the single clause `implication`, the way
+    /// `solve_from_clauses` does for each clause.
+    fn solve_clause(
+        &mut self,
+        infer: &InferenceTable<I>,
+        subst: &Substitution<I>,
+        goal: &InEnvironment<DomainGoal<I>>,
+        implication: &Binders<ProgramClauseImplication<I>>,
+    ) -> Fallible<Solution<I>> {
+        Fulfill::new_with_clause(
+            self,
+            infer.clone(),
+            subst.clone(),
+            goal.clone(),
+            implication,
+        )?
+        .solve(&mut Minimums::new(), || true)
+    }
+
+    /// Invokes `f` with the answers reachable from the given `Fulfill`
+    /// state (see `enumerate_answers`).
+    fn enumerate_state(
+        &mut self,
+        state: FulfillState<I>,
+        max_depth: usize,
+        f: &mut AnswerCallback<'_, Self, I>,
+    ) -> bool {
+        let minimums = &mut Minimums::new();
+        let split = Fulfill::from_state(self, state).solve_or_split(minimums, || true);
+        let (subgoal, state) = match split {
+            Err(_) => return true,
+            Ok(SolveOrSplit::Solved(solution)) => return f(self, solution),
+            Ok(SolveOrSplit::Split(subgoal, state)) => (subgoal, *state),
+        };
+        if max_depth == 0 {
+            let solution = Fulfill::from_state(self, state).solve(minimums, || true);
+            return match solution {
+                Ok(solution) => f(self, solution),
+                Err(_) => true,
+            };
+        }
+
+        let interner = self.interner();
+        let goal = subgoal.goal.clone();
+        self.enumerate_answers(&goal, max_depth - 1, &mut |solver, answer| {
+            let mut fulfill = Fulfill::from_state(solver, state.clone());
+            let progress = match answer.definite_subst(interner) {
+                Some(answer) => fulfill.apply_answer(&subgoal, answer),
+                None => false,
+            };
+            if progress {
+                let state = fulfill.into_state();
+                solver.enumerate_state(state, max_depth - 1, f)
+            } else {
+                // This answer doesn't help, so all we know is the ambiguous
+                // solution we started from.
+                match fulfill.solve(&mut Minimums::new(), || true) {
+                    Ok(solution) => f(solver, solution),
+                    Err(_) => true,
+                }
+            }
+        })
+    }
+}
+
+impl<S, I> EnumerateAnswers<I> for S
+where
+    S: SolveDatabase<I>,
+    I: Interner,
+{
+}
@@ -1,5 +1,8 @@
+use rustc_hash::FxHashSet;
+use std::cell::Cell;
 use std::fmt::Debug;
 use std::hash::Hash;
+use std::sync::Arc;
 use tracing::debug;
 use tracing::{info, instrument};
 
@@ -7,7 +10,7 @@ mod cache;
 mod search_graph;
 mod stack;
 
-pub use cache::Cache;
+pub use cache::{Cache, CacheStore};
 use search_graph::{DepthFirstNumber, SearchGraph};
 use stack::{Stack, StackDepth};
 
@@ -29,6 +32,17 @@ where
 
     /// The maximum size for goals.
     max_size: usize,
+
+    /// For a context solving a goal on behalf of another context (on
+    /// another thread, see `parallel`), the goals in the search graph of
+    /// that context. Results that depend on them cannot be computed
+    /// without it, so the search is abandoned if one of them comes up.
+    outer_goals: Option<Arc<FxHashSet<K>>>,
+
+    /// Set once a search on behalf of another context is abandoned because
+    /// of an outer goal. Nothing is cached from then on, as the results may
+    /// be wrong.
+    abandoned: bool,
 }
 
 pub(super) trait SolverStuff<K, V>: Copy
@@ -47,24 +61,67 @@ where
     ) -> V;
     fn reached_fixed_point(self, old_value: &V, new_value: &V) -> bool;
     fn error_value(self) -> V;
+
+    /// The value for a goal that could not be solved because the stack
+    /// reached the overflow depth.
+    fn overflow_value(self) -> V;
+
+    /// Invoked as the search progresses; used to record proof trees.
+    fn on_event(self, _event: SearchEvent<'_, K, V>) {}
+}
+
+/// The steps of a search that a [`SolverStuff`] can observe.
+pub(super) enum SearchEvent<'a, K, V> {
+    /// A previously cached result was returned for the goal.
+    CacheHit(&'a K, &'a V),
+
+    /// The goal is already being solved further up the stack, so its
+    /// current (provisional) result was returned.
+    Cycle(&'a K, &'a V),
+
+    /// The goal could not be solved without exceeding the overflow depth.
+    Overflow(&'a K),
+
+    /// The solver started solving the goal.
+    Enter(&'a K),
+
+    /// The goal from the innermost `Enter` is being solved again, as part
+    /// of iterating to a fixed point.
+    Restart,
+
+    /// The goal from the innermost `Enter` was solved with the given result.
+    Exit(&'a V),
 }
 
 /// The `minimums` struct is used while solving to track whether we encountered
-/// any cycles in the process.
+/// any cycles in the process, and whether any goal overflowed.
 #[derive(Copy, Clone, Debug)]
 pub(super) struct Minimums {
     positive: DepthFirstNumber,
+
+    /// Whether the result depends on a goal that reached the overflow
+    /// depth. Such a goal might not overflow when reached at a smaller
+    /// depth, so the result is not cached.
+    overflowed: bool,
 }
 
 impl Minimums {
     pub fn new() -> Self {
         Minimums {
             positive: DepthFirstNumber::MAX,
+            overflowed: false,
         }
     }
 
     pub fn update_from(&mut self, minimums: Minimums) {
         self.positive = ::std::cmp::min(self.positive, minimums.positive);
+        self.overflowed |= minimums.overflowed;
+    }
+
+    /// Records that the result depends on a goal that overflowed, if
+    /// `minimums` does, without taking its cycles into account.
+    pub fn update_overflow_from(&mut self, minimums: Minimums) {
+        self.overflowed |= minimums.overflowed;
     }
 }
 
@@ -79,13 +136,57 @@ where
             search_graph: SearchGraph::new(),
             cache,
             max_size,
+            outer_goals: None,
+            abandoned: false,
         }
     }
 
+    /// Creates a context to solve goals on behalf of `outer`, as if they
+    /// were subgoals of the goal on top of its stack. The results that can
+    /// be computed without `outer` are added to the shared cache; the
+    /// others are abandoned.
+    #[cfg(feature = "parallel")]
+    pub(super) fn detached_from(outer: &Self, outer_goals: Arc<FxHashSet<K>>) -> Self {
+        RecursiveContext {
+            outer_goals: Some(outer_goals),
+            ..RecursiveContext::new(
+                outer.overflow_depth() - outer.stack.len(),
+                outer.max_size,
+                outer.cache.clone(),
+            )
+        }
+    }
+
+    /// The goals that this context has a result for, in its cache or in
+    /// its search graph.
+    #[cfg(feature = "parallel")]
+    pub(super) fn knows(&self, goal: &K) -> bool {
+        self.search_graph.lookup(goal).is_some()
+            || self
+                .cache
+                .as_ref()
+                .is_some_and(|cache| cache.contains(goal))
+    }
+
+    /// The goals in the search graph: those being solved, and those whose
+    /// results depend on them.
+    #[cfg(feature = "parallel")]
+    pub(super) fn search_graph_goals(&self) -> impl Iterator<Item = &K> {
+        self.search_graph.goals()
+    }
+
     pub fn max_size(&self) -> usize {
         self.max_size
     }
 
+    pub fn overflow_depth(&self) -> usize {
+        self.stack.overflow_depth()
+    }
+
+    pub fn cache(&self) -> Option<&Cache<K, V>> {
+        self.cache.as_ref()
+    }
+
     /// Solves a canonical goal. The substitution returned in the
     /// solution will be for the fully decomposed goal. For example, given the
     /// program
@@ -128,10 +229,25 @@ where
         if let Some(cache) = &self.cache {
             if let Some(value) = cache.get(goal) {
                 debug!("solve_reduced_goal: cache hit, value={:?}", value);
+                solver_stuff.on_event(SearchEvent::CacheHit(goal, &value));
                 return value;
             }
         }
 
+        // A context solving goals on behalf of another one cannot solve the
+        // goals in the search graph of the other context.
+        if self
+            .outer_goals
+            .as_ref()
+            .is_some_and(|goals| goals.contains(goal))
+        {
+            debug!("solve_goal: goal of the outer context, abandoning search");
+            self.abandoned = true;
+        }
+        if self.abandoned {
+            return solver_stuff.error_value();
+        }
+
         // Next, check if the goal is in the search tree already.
         if let Some(dfn) = self.search_graph.lookup(goal) {
             // Check if this table is still on the stack.
@@ -141,7 +257,9 @@ where
                 // see the corresponding section in the coinduction chapter:
                 // https://rust-lang.github.io/chalk/book/recursive/coinduction.html#mixed-co-inductive-and-inductive-cycles
                 if self.stack.mixed_inductive_coinductive_cycle_from(depth) {
-                    return solver_stuff.error_value();
+                    let error = solver_stuff.error_value();
+                    solver_stuff.on_event(SearchEvent::Cycle(goal, &error));
+                    return error;
                 }
             }
 
@@ -153,15 +271,39 @@ where
                 "solve_goal: cycle detected, previous solution {:?}",
                 previous_solution,
             );
+            if self.search_graph[dfn].stack_depth.is_some() {
+                solver_stuff.on_event(SearchEvent::Cycle(goal, &previous_solution));
+            } else {
+                solver_stuff.on_event(SearchEvent::CacheHit(goal, &previous_solution));
+            }
             previous_solution
         } else {
             // Otherwise, push the goal onto the stack and create a table.
             // The initial result for this table depends on whether the goal is coinductive.
             let coinductive_goal = solver_stuff.is_coinductive_goal(goal);
             let initial_solution = solver_stuff.initial_value(goal, coinductive_goal);
-            let depth = self.stack.push(coinductive_goal);
+            let depth = match self.stack.push(coinductive_goal) {
+                Some(depth) => depth,
+                None => {
+                    info!("solve_goal: overflow depth reached");
+                    minimums.overflowed = true;
+                    solver_stuff.on_event(SearchEvent::Overflow(goal));
+                    return solver_stuff.overflow_value();
+                }
+            };
             let dfn = self.search_graph.insert(goal, depth, initial_solution);
 
+            // Whether `should_continue` returned false while solving the goal.
+            let stopped = Cell::new(false);
+            let should_continue: &dyn Fn() -> bool = &|| {
+                let continues = should_continue();
+                if !continues {
+                    stopped.set(true);
+                }
+                continues
+            };
+
+            solver_stuff.on_event(SearchEvent::Enter(goal));
             let subgoal_minimums =
                 self.solve_new_subgoal(goal, depth, dfn, solver_stuff, should_continue);
 
@@ -172,20 +314,39 @@ where
 
             // Read final result from table.
             let result = self.search_graph[dfn].solution.clone();
+            solver_stuff.on_event(SearchEvent::Exit(&result));
 
             // If processing this subgoal did not involve anything
             // outside of its subtree, then we can promote it to the
             // cache now. This is a sort of hack to alleviate the
             // worst of the repeated work that we do during tabling.
+            //
+            // Results computed after `should_continue` returned false may
+            // be incomplete, so they are not cached. Neither are results
+            // that depend on a goal that overflowed, as they depend on the
+            // depth they were computed at; these are kept in the search
+            // graph until the root goal is solved, so that the other goals
+            // it needs can still reuse them.
             if subgoal_minimums.positive >= dfn {
-                if let Some(cache) = &mut self.cache {
-                    self.search_graph.move_to_cache(dfn, cache);
-                    debug!("solve_reduced_goal: SCC head encountered, moving to cache");
-                } else {
-                    debug!(
-                        "solve_reduced_goal: SCC head encountered, rolling back as caching disabled"
-                    );
-                    self.search_graph.rollback_to(dfn);
+                let cacheable = !stopped.get() && !self.abandoned;
+                match &mut self.cache {
+                    Some(cache) if cacheable && !subgoal_minimums.overflowed => {
+                        self.search_graph.move_to_cache(dfn, cache);
+                        debug!("solve_reduced_goal: SCC head encountered, moving to cache");
+                    }
+                    Some(_) if cacheable && !self.stack.is_empty() => {
+                        debug!(
+                            "solve_reduced_goal: SCC head encountered, keeping it in the \
+                             search graph as it overflowed"
+                        );
+                    }
+                    _ => {
+                        debug!(
+                            "solve_reduced_goal: SCC head encountered, rolling back as caching \
+                             disabled or solving was stopped, overflowed or abandoned"
+                        );
+                        self.search_graph.rollback_to(dfn);
+                    }
                 }
             }
 
@@ -242,6 +403,7 @@ where
 
             // Otherwise: rollback the search tree and try again.
             self.search_graph.rollback_to(dfn + 1);
+            solver_stuff.on_event(SearchEvent::Restart);
         }
     }
 }
@@ -13,6 +13,19 @@ where
     V: Debug + Clone,
 {
     data: Arc<Mutex<CacheData<K, V>>>,
+
+    /// Where results are also stored, and looked up when they are not in
+    /// `data`.
+    store: Option<Arc<dyn CacheStore<K, V>>>,
+}
+
+/// A store that a [`Cache`] keeps its results in, in addition to memory, so
+/// that they outlive it: like a `chalk_solve::solve::persistent::PersistentCache`,
+/// which keeps them on disk. The store may keep only some of the results.
+pub trait CacheStore<K, V>: Send + Sync {
+    fn load(&self, goal: &K) -> Option<V>;
+
+    fn store(&self, goal: &K, result: &V);
 }
 struct CacheData<K, V>
 where
@@ -31,23 +44,55 @@ where
         Self::default()
     }
 
+    /// Creates a cache that also stores its results in `store`, and starts
+    /// from the results found there.
+    pub fn with_store(store: Arc<dyn CacheStore<K, V>>) -> Self {
+        Self {
+            data: Default::default(),
+            store: Some(store),
+        }
+    }
+
     /// Record a cache result.
     #[instrument(skip(self))]
     pub fn insert(&self, goal: K, result: V) {
+        if let Some(store) = &self.store {
+            store.store(&goal, &result);
+        }
         let mut data = self.data.lock().unwrap();
         data.cache.insert(goal, result);
     }
 
+    /// The number of results in the cache.
+    pub fn len(&self) -> usize {
+        self.data.lock().unwrap().cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether there is a result for `goal` in the cache.
+    pub fn contains(&self, goal: &K) -> bool {
+        self.data.lock().unwrap().cache.contains_key(goal) || self.load(goal).is_some()
+    }
+
     /// Record a cache result.
     pub fn get(&self, goal: &K) -> Option<V> {
-        let data = self.data.lock().unwrap();
-        if let Some(result) = data.cache.get(goal) {
+        if let Some(result) = self.data.lock().unwrap().cache.get(goal) {
             debug!(?goal, ?result, "Cache hit");
-            Some(result.clone())
-        } else {
-            debug!(?goal, "Cache miss");
-            None
+            return Some(result.clone());
+        }
+        if let Some(result) = self.load(goal) {
+            debug!(?goal, ?result, "Cache hit in store");
+            return Some(result);
         }
+        debug!(?goal, "Cache miss");
+        None
+    }
+
+    fn load(&self, goal: &K) -> Option<V> {
+        self.store.as_ref()?.load(goal)
     }
 }
 
@@ -59,6 +104,7 @@ where
     fn clone(&self) -> Self {
         Self {
             data: self.data.clone(),
+            store: self.store.clone(),
         }
     }
 }
@@ -71,6 +117,7 @@ where
     fn default() -> Self {
         Self {
             data: Default::default(),
+            store: None,
         }
     }
 }
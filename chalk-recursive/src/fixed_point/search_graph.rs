@@ -57,6 +57,11 @@ where
         self.indices.get(goal).cloned()
     }
 
+    #[cfg(feature = "parallel")]
+    pub(crate) fn goals(&self) -> impl Iterator<Item = &K> {
+        self.indices.keys()
+    }
+
     /// Insert a new search node in the tree. The node will be in the initial
     /// state for a search node:
     ///
@@ -77,7 +82,10 @@ where
             goal: goal.clone(),
             solution,
             stack_depth: Some(stack_depth),
-            links: Minimums { positive: dfn },
+            links: Minimums {
+                positive: dfn,
+                overflowed: false,
+            },
         };
         self.nodes.push(node);
         let previous_index = self.indices.insert(goal.clone(), dfn);
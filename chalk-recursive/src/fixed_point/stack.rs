@@ -39,23 +39,34 @@ impl Stack {
         self.entries.is_empty()
     }
 
-    pub(super) fn push(&mut self, coinductive_goal: bool) -> StackDepth {
+    /// Pushes a new entry onto the stack, returning its depth, or `None` if
+    /// the stack has already reached the overflow depth.
+    pub(super) fn push(&mut self, coinductive_goal: bool) -> Option<StackDepth> {
         let depth = StackDepth {
             depth: self.entries.len(),
         };
 
         if depth.depth >= self.overflow_depth {
-            // This should perhaps be a result or something, though
-            // really I'd prefer to move to subgoal abstraction for
+            // Really I'd prefer to move to subgoal abstraction for
             // guaranteeing termination. -nmatsakis
-            panic!("overflow depth reached")
+            return None;
         }
 
         self.entries.push(StackEntry {
             coinductive_goal,
             cycle: false,
         });
-        depth
+        Some(depth)
+    }
+
+    /// The number of goals on the stack.
+    #[cfg(feature = "parallel")]
+    pub(super) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(super) fn overflow_depth(&self) -> usize {
+        self.overflow_depth
     }
 
     pub(super) fn pop(&mut self, depth: StackDepth) {
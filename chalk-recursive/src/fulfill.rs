@@ -9,10 +9,13 @@ use chalk_ir::{
     Binders, BoundVar, Canonical, ConstrainedSubst, Constraint, Constraints, DomainGoal,
     Environment, EqGoal, Fallible, GenericArg, GenericArgData, Goal, GoalData, InEnvironment,
     NoSolution, ProgramClauseImplication, QuantifierKind, Substitution, SubtypeGoal, TyKind,
-    TyVariableKind, UCanonical, UnificationDatabase, UniverseMap, Variance,
+    TyVariableKind, UCanonical, UnificationDatabase, UniverseIndex, UniverseMap, Variance,
 };
 use chalk_solve::debug_span;
 use chalk_solve::infer::{InferenceTable, ParameterEnaVariableExt};
+use chalk_solve::solve::leak_check::leak_check;
+use chalk_solve::solve::proof_tree::ProofOutcome;
+use chalk_solve::solve::regions::in_goal_environment;
 use chalk_solve::solve::truncate;
 use chalk_solve::{Guidance, Solution};
 use rustc_hash::FxHashSet;
@@ -124,6 +127,10 @@ pub(super) struct Fulfill<'s, I: Interner, Solver: SolveDatabase<I>> {
     /// The remaining goals to prove or refute
     obligations: Vec<Obligation<I>>,
 
+    /// Whether obligations were pushed since the goals of the obligations
+    /// were last solved in parallel (see `solve_obligations_in_parallel`).
+    new_obligations: bool,
+
     /// Lifetime constraints that must be fulfilled for a solution to be fully
     /// validated.
     constraints: FxHashSet<InEnvironment<Constraint<I>>>,
@@ -132,6 +139,43 @@ pub(super) struct Fulfill<'s, I: Interner, Solver: SolveDatabase<I>> {
     /// refuted. In such a case the solution will be either `CannotProve`, or `Err`
     /// in the case where some other goal leads to an error.
     cannot_prove: bool,
+
+    /// The environment of the goal being fulfilled, whose outlives facts
+    /// the leak check takes into account.
+    environment: Environment<I>,
+
+    /// The maximum universe of the goal being fulfilled; the leak check
+    /// only looks at placeholders of the universes created after it.
+    goal_universe: UniverseIndex,
+}
+
+/// The inference state of a `Fulfill`, detached from its solver so that it
+/// can be cloned and pursued along several alternatives (see
+/// `enumerate.rs`).
+#[derive(Clone)]
+pub(super) struct FulfillState<I: Interner> {
+    subst: Substitution<I>,
+    infer: InferenceTable<I>,
+    obligations: Vec<Obligation<I>>,
+    new_obligations: bool,
+    constraints: FxHashSet<InEnvironment<Constraint<I>>>,
+    cannot_prove: bool,
+    environment: Environment<I>,
+    goal_universe: UniverseIndex,
+}
+
+/// A goal that is left ambiguous after fulfilling, but whose answers (if
+/// enumerated) could make progress on the remaining obligations.
+pub(super) struct AmbiguousSubgoal<I: Interner> {
+    pub(super) goal: UCanonical<InEnvironment<Goal<I>>>,
+    free_vars: Vec<GenericArg<I>>,
+    universes: UniverseMap,
+}
+
+/// The result of `Fulfill::solve_or_split`.
+pub(super) enum SolveOrSplit<I: Interner> {
+    Solved(Solution<I>),
+    Split(AmbiguousSubgoal<I>, Box<FulfillState<I>>),
 }
 
 impl<'s, I: Interner, Solver: SolveDatabase<I>> Fulfill<'s, I, Solver> {
@@ -143,13 +187,17 @@ impl<'s, I: Interner, Solver: SolveDatabase<I>> Fulfill<'s, I, Solver> {
         canonical_goal: InEnvironment<DomainGoal<I>>,
         clause: &Binders<ProgramClauseImplication<I>>,
     ) -> Fallible<Self> {
+        let goal_universe = infer.max_universe();
         let mut fulfill = Fulfill {
             solver,
             infer,
             subst,
             obligations: vec![],
+            new_obligations: false,
             constraints: FxHashSet::default(),
             cannot_prove: false,
+            environment: canonical_goal.environment.clone(),
+            goal_universe,
         };
 
         let ProgramClauseImplication {
@@ -162,9 +210,19 @@ impl<'s, I: Interner, Solver: SolveDatabase<I>> Fulfill<'s, I, Solver> {
             .instantiate_binders_existentially(fulfill.solver.interner(), clause.clone());
 
         debug!(?consequence, ?conditions, ?constraints);
+        let interner = fulfill.interner();
         fulfill
             .constraints
-            .extend(constraints.as_slice(fulfill.interner()).to_owned());
+            .extend(constraints.iter(interner).map(|constraint| {
+                InEnvironment::new(
+                    &in_goal_environment(
+                        interner,
+                        &canonical_goal.environment,
+                        &constraint.environment,
+                    ),
+                    constraint.goal.clone(),
+                )
+            }));
 
         debug!("the subst is {:?}", fulfill.subst);
 
@@ -193,13 +251,17 @@ impl<'s, I: Interner, Solver: SolveDatabase<I>> Fulfill<'s, I, Solver> {
         subst: Substitution<I>,
         canonical_goal: InEnvironment<Goal<I>>,
     ) -> Fallible<Self> {
+        let goal_universe = infer.max_universe();
         let mut fulfill = Fulfill {
             solver,
             infer,
             subst,
             obligations: vec![],
+            new_obligations: false,
             constraints: FxHashSet::default(),
             cannot_prove: false,
+            environment: canonical_goal.environment.clone(),
+            goal_universe,
         };
 
         if let Err(e) = fulfill.push_goal(&canonical_goal.environment, canonical_goal.goal.clone())
@@ -210,6 +272,43 @@ impl<'s, I: Interner, Solver: SolveDatabase<I>> Fulfill<'s, I, Solver> {
         Ok(fulfill)
     }
 
+    pub(super) fn from_state(solver: &'s mut Solver, state: FulfillState<I>) -> Self {
+        let FulfillState {
+            subst,
+            infer,
+            obligations,
+            new_obligations,
+            constraints,
+            cannot_prove,
+            environment,
+            goal_universe,
+        } = state;
+        Fulfill {
+            solver,
+            subst,
+            infer,
+            obligations,
+            new_obligations,
+            constraints,
+            cannot_prove,
+            environment,
+            goal_universe,
+        }
+    }
+
+    pub(super) fn into_state(self) -> FulfillState<I> {
+        FulfillState {
+            subst: self.subst,
+            infer: self.infer,
+            obligations: self.obligations,
+            new_obligations: self.new_obligations,
+            constraints: self.constraints,
+            cannot_prove: self.cannot_prove,
+            environment: self.environment,
+            goal_universe: self.goal_universe,
+        }
+    }
+
     fn push_obligation(&mut self, obligation: Obligation<I>) {
         // truncate to avoid overflows
         match &obligation {
@@ -221,6 +320,7 @@ impl<'s, I: Interner, Solver: SolveDatabase<I>> Fulfill<'s, I, Solver> {
                     goal,
                 ) {
                     // the goal is too big. Record that we should return Ambiguous
+                    self.record_truncated(goal.clone());
                     self.cannot_prove = true;
                     return;
                 }
@@ -233,12 +333,30 @@ impl<'s, I: Interner, Solver: SolveDatabase<I>> Fulfill<'s, I, Solver> {
                     goal,
                 ) {
                     // the goal is too big. Record that we should return Ambiguous
+                    self.record_truncated(goal.clone());
                     self.cannot_prove = true;
                     return;
                 }
             }
         };
         self.obligations.push(obligation);
+        self.new_obligations = true;
+    }
+
+    /// Notes in the proof tree (if one is being recorded) that `goal` was
+    /// too large to be solved.
+    fn record_truncated(&mut self, goal: InEnvironment<Goal<I>>) {
+        if self.solver.proof_tree().is_none() {
+            return;
+        }
+        let interner = self.solver.interner();
+        let (quantified, _) = canonicalize(&mut self.infer, interner, goal);
+        let (quantified, _) = u_canonicalize(&mut self.infer, interner, &quantified);
+        if let Some(proof_tree) = self.solver.proof_tree() {
+            proof_tree
+                .borrow_mut()
+                .leaf(quantified, ProofOutcome::Truncated, false);
+        }
     }
 
     /// Unifies `a` and `b` in the given environment.
@@ -365,6 +483,7 @@ impl<'s, I: Interner, Solver: SolveDatabase<I>> Fulfill<'s, I, Solver> {
     fn refute(
         &mut self,
         goal: InEnvironment<Goal<I>>,
+        minimums: &mut Minimums,
         should_continue: impl std::ops::Fn() -> bool + Clone,
     ) -> Fallible<NegativeSolution> {
         let canonicalized = match self
@@ -382,11 +501,12 @@ impl<'s, I: Interner, Solver: SolveDatabase<I>> Fulfill<'s, I, Solver> {
         // Negate the result
         let (quantified, _) =
             u_canonicalize(&mut self.infer, self.solver.interner(), &canonicalized);
-        let mut minimums = Minimums::new(); // FIXME -- minimums here seems wrong
-        if let Ok(solution) = self
+        let mut negative_minimums = Minimums::new(); // FIXME -- minimums here seems wrong
+        let result = self
             .solver
-            .solve_goal(quantified, &mut minimums, should_continue)
-        {
+            .solve_goal(quantified, &mut negative_minimums, should_continue);
+        minimums.update_overflow_from(negative_minimums);
+        if let Ok(solution) = result {
             if solution.is_unique() {
                 Err(NoSolution)
             } else {
@@ -397,6 +517,38 @@ impl<'s, I: Interner, Solver: SolveDatabase<I>> Fulfill<'s, I, Solver> {
         }
     }
 
+    /// Solves the goals of the positive obligations on other threads, if
+    /// the solver supports it, so that their results are in the cache when
+    /// `prove` needs them. This is only done once for the obligations
+    /// pushed since the last time: obligations that are left ambiguous are
+    /// retried by `fulfill` with what was learned from the others, but
+    /// their goals are solved as needed then.
+    fn solve_obligations_in_parallel(&mut self) {
+        if !std::mem::take(&mut self.new_obligations)
+            || self.obligations.len() < 2
+            || !self.solver.solves_in_parallel()
+        {
+            return;
+        }
+        let interner = self.interner();
+        let goals: Vec<_> = self
+            .obligations
+            .iter()
+            .filter_map(|obligation| match obligation {
+                Obligation::Prove(wc) => Some(wc.clone()),
+                Obligation::Refute(_) => None,
+            })
+            .collect();
+        let goals = goals
+            .into_iter()
+            .map(|wc| {
+                let (quantified, _) = canonicalize(&mut self.infer, interner, wc);
+                u_canonicalize(&mut self.infer, interner, &quantified).0
+            })
+            .collect();
+        self.solver.solve_in_parallel(goals);
+    }
+
     /// Trying to prove some goal led to a the substitution `subst`; we
     /// wish to apply that substitution to our own inference variables
     /// (and incorporate any region constraints). This substitution
@@ -467,6 +619,7 @@ impl<'s, I: Interner, Solver: SolveDatabase<I>> Fulfill<'s, I, Solver> {
             // `solve_one` may also push onto the `self.to_prove` list
             // directly.
             assert!(obligations.is_empty());
+            self.solve_obligations_in_parallel();
             while let Some(obligation) = self.obligations.pop() {
                 let ambiguous = match &obligation {
                     Obligation::Prove(wc) => {
@@ -498,7 +651,8 @@ impl<'s, I: Interner, Solver: SolveDatabase<I>> Fulfill<'s, I, Solver> {
                         solution.is_ambig()
                     }
                     Obligation::Refute(goal) => {
-                        let answer = self.refute(goal.clone(), should_continue.clone())?;
+                        let answer =
+                            self.refute(goal.clone(), minimums, should_continue.clone())?;
                         answer == NegativeSolution::Ambiguous
                     }
                 };
@@ -537,7 +691,66 @@ impl<'s, I: Interner, Solver: SolveDatabase<I>> Fulfill<'s, I, Solver> {
             Ok(o) => o,
             Err(e) => return Err(e),
         };
+        self.solution(outcome, minimums, should_continue)
+    }
+
+    /// Like `solve`, but if the result would be ambiguous because of a
+    /// positive obligation that still has unresolved inference variables,
+    /// returns that obligation instead, along with the state needed to
+    /// resume with `apply_answer` once answers to it are known.
+    pub(super) fn solve_or_split(
+        mut self,
+        minimums: &mut Minimums,
+        should_continue: impl std::ops::Fn() -> bool + Clone,
+    ) -> Fallible<SolveOrSplit<I>> {
+        let outcome = self.fulfill(minimums, should_continue.clone())?;
+        if !self.cannot_prove && !outcome.is_complete() {
+            let interner = self.interner();
+            for obligation in &self.obligations {
+                if let Obligation::Prove(goal) = obligation {
+                    let (quantified, free_vars) =
+                        canonicalize(&mut self.infer, interner, goal.clone());
+                    if free_vars.is_empty() {
+                        continue;
+                    }
+                    let (goal, universes) = u_canonicalize(&mut self.infer, interner, &quantified);
+                    let subgoal = AmbiguousSubgoal {
+                        goal,
+                        free_vars,
+                        universes,
+                    };
+                    return Ok(SolveOrSplit::Split(subgoal, Box::new(self.into_state())));
+                }
+            }
+        }
+        self.solution(outcome, minimums, should_continue)
+            .map(SolveOrSplit::Solved)
+    }
 
+    /// Applies an answer to the subgoal returned by `solve_or_split`.
+    /// Returns false if the answer does not tell us anything new.
+    pub(super) fn apply_answer(
+        &mut self,
+        subgoal: &AmbiguousSubgoal<I>,
+        answer: Canonical<ConstrainedSubst<I>>,
+    ) -> bool {
+        let interner = self.interner();
+        if is_trivial_canonical_subst(interner, &answer.value.subst)
+            && answer.value.constraints.is_empty(interner)
+        {
+            return false;
+        }
+        self.apply_solution(subgoal.free_vars.clone(), subgoal.universes.clone(), answer);
+        true
+    }
+
+    /// Builds the solution once `fulfill` has returned `outcome`.
+    fn solution(
+        mut self,
+        outcome: Outcome,
+        minimums: &mut Minimums,
+        should_continue: impl std::ops::Fn() -> bool + Clone,
+    ) -> Fallible<Solution<I>> {
         if self.cannot_prove {
             debug!("Goal cannot be proven (cannot_prove = true), returning ambiguous");
             return Ok(Solution::Ambig(Guidance::Unknown));
@@ -556,7 +769,26 @@ impl<'s, I: Interner, Solver: SolveDatabase<I>> Fulfill<'s, I, Solver> {
                     constraints,
                 },
             );
-            return Ok(Solution::Unique(constrained.0));
+
+            // The universes of the `forall`s in the goal are closed here, so
+            // the solution must not leak their placeholders.
+            let interner = self.solver.interner();
+            let answer = constrained.0;
+            if let Err(leak) = leak_check(
+                interner,
+                &self.environment,
+                self.goal_universe,
+                &answer.binders,
+                &answer.value.constraints,
+            ) {
+                if let Some(proof_tree) = self.solver.proof_tree() {
+                    proof_tree
+                        .borrow_mut()
+                        .override_outcome(ProofOutcome::PlaceholderLeak(leak));
+                }
+                return Err(NoSolution);
+            }
+            return Ok(Solution::Unique(answer));
         }
 
         // Otherwise, we have (positive or negative) obligations remaining, but
@@ -3,10 +3,13 @@ use chalk_ir::{Goal, InEnvironment, UCanonical};
 pub type UCanonicalGoal<I> = UCanonical<InEnvironment<Goal<I>>>;
 
 mod combine;
+mod enumerate;
 mod fixed_point;
 mod fulfill;
+#[cfg(feature = "parallel")]
+mod parallel;
 mod recursive;
 pub mod solve;
 
-pub use fixed_point::Cache;
-pub use recursive::RecursiveSolver;
+pub use fixed_point::{Cache, CacheStore};
+pub use recursive::{PersistentStore, RecursiveSolver};
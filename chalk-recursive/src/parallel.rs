@@ -0,0 +1,79 @@
+//! Solving independent subgoals on several threads. See
+//! [`RecursiveSolver::with_parallel`](crate::RecursiveSolver::with_parallel).
+//!
+//! When a `Fulfill` has several obligations to prove, their goals are
+//! first solved on rayon's thread pool, each in a fresh context that shares
+//! the cache of the solver. The `Fulfill` then proves its obligations one
+//! after the other as usual, finding their results in the cache.
+//!
+//! A goal solved on another thread cannot see the stack and search graph
+//! of the solver, so it cannot take part in their cycles: if it runs into
+//! one of the goals in the search graph, its search is abandoned, and the
+//! goal is left for the solver to solve itself. As in the solver itself,
+//! results that depend on a goal that overflowed are not cached. Only the
+//! results of completed searches are cached, so they are the same as the
+//! results the solver would have computed on its own.
+
+use crate::fixed_point::RecursiveContext;
+use crate::recursive::ProgramContext;
+use crate::UCanonicalGoal;
+use chalk_ir::interner::Interner;
+use chalk_ir::Fallible;
+use chalk_solve::{RustIrDatabase, Solution};
+use rayon::prelude::*;
+use rustc_hash::FxHashSet;
+use std::sync::Arc;
+
+type Context<I> = RecursiveContext<UCanonicalGoal<I>, Fallible<Solution<I>>>;
+
+/// Solves goals on other threads on behalf of a context; see
+/// `SolveDatabase::solve_in_parallel`. This is a function pointer, so that
+/// the rest of the solver does not need the `Send` and `Sync` bounds of
+/// [`solve_in_parallel`], which are checked where it is instantiated.
+pub(crate) type SolveInParallel<I> = fn(
+    &Context<I>,
+    &(dyn RustIrDatabase<I> + Sync),
+    &(dyn Fn() -> bool + Sync),
+    Vec<UCanonicalGoal<I>>,
+);
+
+/// A program that can be shared between threads, with the `should_continue`
+/// of the caller, which the searches on other threads check as well.
+#[derive(Copy, Clone)]
+pub(crate) struct ParallelProgram<'me, I: Interner> {
+    pub(crate) db: &'me (dyn RustIrDatabase<I> + Sync),
+    pub(crate) should_continue: &'me (dyn Fn() -> bool + Sync),
+    pub(crate) solve: SolveInParallel<I>,
+}
+
+impl<I: Interner> ParallelProgram<'_, I> {
+    pub(crate) fn solve_in_parallel(&self, context: &Context<I>, goals: Vec<UCanonicalGoal<I>>) {
+        (self.solve)(context, self.db, self.should_continue, goals)
+    }
+}
+
+pub(crate) fn solve_in_parallel<I>(
+    context: &Context<I>,
+    db: &(dyn RustIrDatabase<I> + Sync),
+    should_continue: &(dyn Fn() -> bool + Sync),
+    goals: Vec<UCanonicalGoal<I>>,
+) where
+    I: Interner + Send + Sync,
+    UCanonicalGoal<I>: Send + Sync,
+    Fallible<Solution<I>>: Send + Sync,
+{
+    let mut seen = FxHashSet::default();
+    let goals: Vec<_> = goals
+        .into_iter()
+        .filter(|goal| !context.knows(goal) && seen.insert(goal.clone()))
+        .collect();
+    if goals.len() < 2 {
+        return;
+    }
+    let outer_goals = Arc::new(context.search_graph_goals().cloned().collect());
+    goals.into_par_iter().for_each(|goal| {
+        let mut detached = RecursiveContext::detached_from(context, Arc::clone(&outer_goals));
+        // Only the results it caches are of use.
+        let _ = detached.solve_root_goal(&goal, ProgramContext::new(db), should_continue);
+    });
+}
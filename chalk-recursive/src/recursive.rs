@@ -1,11 +1,24 @@
-use crate::fixed_point::{Cache, Minimums, RecursiveContext, SolverStuff};
+use crate::enumerate::EnumerateAnswers;
+use crate::fixed_point::{Cache, CacheStore, Minimums, RecursiveContext, SearchEvent, SolverStuff};
+#[cfg(feature = "parallel")]
+use crate::parallel::{ParallelProgram, SolveInParallel};
 use crate::solve::{SolveDatabase, SolveIteration};
 use crate::UCanonicalGoal;
 use chalk_ir::{interner::Interner, NoSolution};
 use chalk_ir::{Canonical, ConstrainedSubst, Goal, InEnvironment, UCanonical};
 use chalk_ir::{Constraints, Fallible};
-use chalk_solve::{coinductive_goal::IsCoinductive, RustIrDatabase, Solution};
+use chalk_solve::ext::CanonicalExt;
+use chalk_solve::solve::budget::{Budget, BudgetExhausted, BudgetLimit, BudgetTracker};
+use chalk_solve::solve::persistent::{PersistedResult, PersistentCache};
+use chalk_solve::solve::proof_tree::{ProofOutcome, ProofTree, ProofTreeBuilder};
+use chalk_solve::solve::stats::SolverStats;
+use chalk_solve::{
+    coinductive_goal::IsCoinductive, Guidance, RustIrDatabase, Solution, SubstitutionResult,
+};
+use rustc_hash::FxHashSet;
+use std::cell::RefCell;
 use std::fmt;
+use std::sync::Arc;
 
 /// A Solver is the basic context in which you can propose goals for a given
 /// program. **All questions posed to the solver are in canonical, closed form,
@@ -13,12 +26,77 @@ use std::fmt;
 /// allows for better caching, and simplifies management of the inference
 /// context.
 struct Solver<'me, I: Interner> {
-    program: &'me dyn RustIrDatabase<I>,
+    program: ProgramContext<'me, I>,
     context: &'me mut RecursiveContext<UCanonicalGoal<I>, Fallible<Solution<I>>>,
 }
 
+/// The program we are solving goals against, along with the proof tree
+/// being recorded (if any). This is what the fixed-point machinery is
+/// parameterized over.
+#[derive(Copy, Clone)]
+pub(crate) struct ProgramContext<'me, I: Interner> {
+    db: &'me dyn RustIrDatabase<I>,
+    proof_tree: Option<&'me RefCell<ProofTreeBuilder<I>>>,
+
+    /// The statistics of the solver, if the search should be counted in
+    /// them.
+    stats: Option<&'me RefCell<SolverStats<I>>>,
+
+    /// Set to solve independent subgoals on several threads.
+    #[cfg(feature = "parallel")]
+    parallel: Option<ParallelProgram<'me, I>>,
+}
+
+impl<'me, I: Interner> ProgramContext<'me, I> {
+    pub(crate) fn new(db: &'me dyn RustIrDatabase<I>) -> Self {
+        ProgramContext {
+            db,
+            proof_tree: None,
+            stats: None,
+            #[cfg(feature = "parallel")]
+            parallel: None,
+        }
+    }
+
+    /// A context whose search is counted in `stats`.
+    fn counted(db: &'me dyn RustIrDatabase<I>, stats: &'me RefCell<SolverStats<I>>) -> Self {
+        ProgramContext {
+            stats: Some(stats),
+            ..ProgramContext::new(db)
+        }
+    }
+
+    /// Lets the search solve independent subgoals on other threads with
+    /// `solve`, if it is given and the program can be shared between them.
+    #[cfg(feature = "parallel")]
+    fn in_parallel(
+        self,
+        solve: Option<SolveInParallel<I>>,
+        should_continue: &'me (dyn Fn() -> bool + Sync),
+    ) -> Self {
+        let parallel = match (solve, self.db.as_sync()) {
+            (Some(solve), Some(db)) => Some(ParallelProgram {
+                db,
+                should_continue,
+                solve,
+            }),
+            _ => None,
+        };
+        ProgramContext { parallel, ..self }
+    }
+}
+
 pub struct RecursiveSolver<I: Interner> {
     ctx: Box<RecursiveContext<UCanonicalGoal<I>, Fallible<Solution<I>>>>,
+
+    /// Counts of the goals solved by `ctx`. Goals solved on other threads
+    /// (see `with_parallel`) are not counted, but finding their results in
+    /// the cache is.
+    stats: RefCell<SolverStats<I>>,
+
+    /// Set by `with_parallel`.
+    #[cfg(feature = "parallel")]
+    parallel: Option<SolveInParallel<I>>,
 }
 
 impl<I: Interner> RecursiveSolver<I> {
@@ -29,6 +107,73 @@ impl<I: Interner> RecursiveSolver<I> {
     ) -> Self {
         Self {
             ctx: Box::new(RecursiveContext::new(overflow_depth, max_size, cache)),
+            stats: RefCell::new(SolverStats::default()),
+            #[cfg(feature = "parallel")]
+            parallel: None,
+        }
+    }
+
+    /// Solves `goal` from the root, with the program that the solver is
+    /// given.
+    fn solve_root(
+        &mut self,
+        program: &dyn RustIrDatabase<I>,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+        should_continue: &(dyn Fn() -> bool + Sync),
+    ) -> Fallible<Solution<I>> {
+        let program_context = ProgramContext::counted(program, &self.stats);
+        #[cfg(feature = "parallel")]
+        let program_context = program_context.in_parallel(self.parallel, should_continue);
+        self.ctx
+            .solve_root_goal(goal, program_context, should_continue)
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl<I: Interner> RecursiveSolver<I>
+where
+    I: Send + Sync,
+    UCanonicalGoal<I>: Send + Sync,
+    Fallible<Solution<I>>: Send + Sync,
+{
+    /// Makes the solver solve the independent subgoals of each goal on
+    /// rayon's thread pool, when the program it is given can be shared
+    /// between threads (see `RustIrDatabase::as_sync`). Subgoals are only
+    /// solved on other threads if the solver has a cache, which is where
+    /// their results are stored; the solutions are the same as the ones the
+    /// solver would give without this.
+    pub fn with_parallel(self) -> Self {
+        RecursiveSolver {
+            parallel: Some(crate::parallel::solve_in_parallel::<I>),
+            ..self
+        }
+    }
+}
+
+/// A [`CacheStore`] that keeps the results of the recursive solver in a
+/// [`PersistentCache`], for those that can be rebuilt from their goal (see
+/// [`PersistedResult::from_solution`]).
+pub struct PersistentStore<I: Interner> {
+    interner: I,
+    cache: Arc<PersistentCache>,
+}
+
+impl<I: Interner> PersistentStore<I> {
+    pub fn new(interner: I, cache: Arc<PersistentCache>) -> Self {
+        PersistentStore { interner, cache }
+    }
+}
+
+impl<I: Interner + Send + Sync> CacheStore<UCanonicalGoal<I>, Fallible<Solution<I>>>
+    for PersistentStore<I>
+{
+    fn load(&self, goal: &UCanonicalGoal<I>) -> Option<Fallible<Solution<I>>> {
+        Some(self.cache.get(goal)?.to_solution(self.interner, goal))
+    }
+
+    fn store(&self, goal: &UCanonicalGoal<I>, result: &Fallible<Solution<I>>) {
+        if let Some(result) = PersistedResult::from_solution(self.interner, goal, result) {
+            self.cache.insert(goal, result);
         }
     }
 }
@@ -42,15 +187,22 @@ impl<I: Interner> fmt::Debug for RecursiveSolver<I> {
 impl<'me, I: Interner> Solver<'me, I> {
     pub(crate) fn new(
         context: &'me mut RecursiveContext<UCanonicalGoal<I>, Fallible<Solution<I>>>,
-        program: &'me dyn RustIrDatabase<I>,
+        program: ProgramContext<'me, I>,
     ) -> Self {
         Self { program, context }
     }
 }
 
-impl<I: Interner> SolverStuff<UCanonicalGoal<I>, Fallible<Solution<I>>> for &dyn RustIrDatabase<I> {
+fn proof_outcome<I: Interner>(result: &Fallible<Solution<I>>) -> ProofOutcome<I> {
+    match result {
+        Ok(solution) => ProofOutcome::Proven(solution.clone()),
+        Err(NoSolution) => ProofOutcome::NoSolution,
+    }
+}
+
+impl<I: Interner> SolverStuff<UCanonicalGoal<I>, Fallible<Solution<I>>> for ProgramContext<'_, I> {
     fn is_coinductive_goal(self, goal: &UCanonicalGoal<I>) -> bool {
-        goal.is_coinductive(self)
+        goal.is_coinductive(self.db)
     }
 
     fn initial_value(
@@ -61,8 +213,8 @@ impl<I: Interner> SolverStuff<UCanonicalGoal<I>, Fallible<Solution<I>>> for &dyn
         if coinductive_goal {
             Ok(Solution::Unique(Canonical {
                 value: ConstrainedSubst {
-                    subst: goal.trivial_substitution(self.interner()),
-                    constraints: Constraints::empty(self.interner()),
+                    subst: goal.trivial_substitution(self.db.interner()),
+                    constraints: Constraints::empty(self.db.interner()),
                 },
                 binders: goal.canonical.binders.clone(),
             }))
@@ -102,6 +254,47 @@ impl<I: Interner> SolverStuff<UCanonicalGoal<I>, Fallible<Solution<I>>> for &dyn
     fn error_value(self) -> Fallible<Solution<I>> {
         Err(NoSolution)
     }
+
+    fn overflow_value(self) -> Fallible<Solution<I>> {
+        Ok(Solution::Ambig(Guidance::Unknown))
+    }
+
+    fn on_event(self, event: SearchEvent<'_, UCanonicalGoal<I>, Fallible<Solution<I>>>) {
+        if let Some(stats) = self.stats {
+            let mut stats = stats.borrow_mut();
+            match &event {
+                SearchEvent::CacheHit(..) => stats.cache_hits += 1,
+                SearchEvent::Overflow(_) => stats.overflows += 1,
+                SearchEvent::Enter(goal) => {
+                    stats.cache_misses += 1;
+                    stats.fixed_point_iterations += 1;
+                    stats.record_goal(self.db.interner(), &goal.canonical.value.goal);
+                }
+                SearchEvent::Restart => stats.fixed_point_iterations += 1,
+                SearchEvent::Cycle(..) | SearchEvent::Exit(_) => {}
+            }
+        }
+        let mut builder = match self.proof_tree {
+            Some(builder) => builder.borrow_mut(),
+            None => return,
+        };
+        match event {
+            SearchEvent::CacheHit(goal, result) => {
+                builder.leaf(goal.clone(), proof_outcome(result), true)
+            }
+            SearchEvent::Cycle(goal, result) => builder.leaf(
+                goal.clone(),
+                ProofOutcome::Cycle(result.as_ref().ok().cloned()),
+                false,
+            ),
+            SearchEvent::Overflow(goal) => {
+                builder.leaf(goal.clone(), ProofOutcome::Overflow, false)
+            }
+            SearchEvent::Enter(goal) => builder.enter_goal(goal.clone()),
+            SearchEvent::Restart => builder.restart_goal(),
+            SearchEvent::Exit(result) => builder.exit_goal(proof_outcome(result)),
+        }
+    }
 }
 
 impl<'me, I: Interner> SolveDatabase<I> for Solver<'me, I> {
@@ -116,16 +309,32 @@ impl<'me, I: Interner> SolveDatabase<I> for Solver<'me, I> {
     }
 
     fn interner(&self) -> I {
-        self.program.interner()
+        self.program.db.interner()
     }
 
     fn db(&self) -> &dyn RustIrDatabase<I> {
-        self.program
+        self.program.db
+    }
+
+    fn proof_tree(&self) -> Option<&RefCell<ProofTreeBuilder<I>>> {
+        self.program.proof_tree
     }
 
     fn max_size(&self) -> usize {
         self.context.max_size()
     }
+
+    #[cfg(feature = "parallel")]
+    fn solves_in_parallel(&self) -> bool {
+        self.program.parallel.is_some() && self.context.cache().is_some()
+    }
+
+    #[cfg(feature = "parallel")]
+    fn solve_in_parallel(&mut self, goals: Vec<UCanonicalGoal<I>>) {
+        if let Some(parallel) = self.program.parallel {
+            parallel.solve_in_parallel(self.context, goals);
+        }
+    }
 }
 
 impl<I: Interner> chalk_solve::Solver<I> for RecursiveSolver<I> {
@@ -134,7 +343,7 @@ impl<I: Interner> chalk_solve::Solver<I> for RecursiveSolver<I> {
         program: &dyn RustIrDatabase<I>,
         goal: &UCanonical<InEnvironment<Goal<I>>>,
     ) -> Option<chalk_solve::Solution<I>> {
-        self.ctx.solve_root_goal(goal, program, || true).ok()
+        self.solve_root(program, goal, &|| true).ok()
     }
 
     fn solve_limited(
@@ -143,20 +352,123 @@ impl<I: Interner> chalk_solve::Solver<I> for RecursiveSolver<I> {
         goal: &UCanonical<InEnvironment<Goal<I>>>,
         should_continue: &dyn std::ops::Fn() -> bool,
     ) -> Option<chalk_solve::Solution<I>> {
+        let program_context = ProgramContext::counted(program, &self.stats);
         self.ctx
-            .solve_root_goal(goal, program, should_continue)
+            .solve_root_goal(goal, program_context, should_continue)
             .ok()
     }
 
+    fn solve_limited_parallel(
+        &mut self,
+        program: &dyn RustIrDatabase<I>,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+        should_continue: &(dyn std::ops::Fn() -> bool + Sync),
+    ) -> Option<chalk_solve::Solution<I>> {
+        self.solve_root(program, goal, should_continue).ok()
+    }
+
+    fn solve_with_budget(
+        &mut self,
+        program: &dyn RustIrDatabase<I>,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+        budget: &Budget,
+    ) -> Result<Option<chalk_solve::Solution<I>>, BudgetExhausted> {
+        let tracker = BudgetTracker::new(budget);
+        let cache = self.ctx.cache().cloned();
+        let should_continue = || {
+            tracker.should_continue()
+                && cache
+                    .as_ref()
+                    .is_none_or(|cache| tracker.within(BudgetLimit::CacheEntries, cache.len()))
+        };
+        let program_context = ProgramContext::counted(program, &self.stats);
+        // The searches on other threads cannot check the cache, but once its
+        // limit is reached here, the tracker stops them as well.
+        #[cfg(feature = "parallel")]
+        let shared_should_continue = || tracker.should_continue();
+        #[cfg(feature = "parallel")]
+        let program_context = program_context.in_parallel(self.parallel, &shared_should_continue);
+        let solution = self
+            .ctx
+            .solve_root_goal(goal, program_context, should_continue)
+            .ok();
+        tracker.finish(solution)
+    }
+
     fn solve_multiple(
         &mut self,
-        _program: &dyn RustIrDatabase<I>,
-        _goal: &UCanonical<InEnvironment<Goal<I>>>,
-        _f: &mut dyn FnMut(
-            chalk_solve::SubstitutionResult<Canonical<ConstrainedSubst<I>>>,
-            bool,
-        ) -> bool,
+        program: &dyn RustIrDatabase<I>,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+        f: &mut dyn FnMut(SubstitutionResult<Canonical<ConstrainedSubst<I>>>, bool) -> bool,
     ) -> bool {
-        unimplemented!("Recursive solver doesn't support multiple answers")
+        let interner = program.interner();
+        let max_depth = self.ctx.overflow_depth();
+        let program_context = ProgramContext::counted(program, &self.stats);
+        #[cfg(feature = "parallel")]
+        let program_context = program_context.in_parallel(self.parallel, &|| true);
+        let mut solver = Solver::new(&mut self.ctx, program_context);
+
+        // Different alternatives can lead to the same answer, so we remember
+        // the answers we have seen. We also hold each answer back until we
+        // know whether there is another one after it.
+        let mut seen = FxHashSet::default();
+        let mut pending = None;
+        let finished = solver.enumerate_answers(goal, max_depth, &mut |_, solution| {
+            let answer = match solution {
+                Solution::Unique(subst) => SubstitutionResult::Definite(subst),
+                Solution::Ambig(Guidance::Definite(subst) | Guidance::Suggested(subst))
+                    if !subst.value.is_identity_subst(interner) =>
+                {
+                    SubstitutionResult::Ambiguous(subst.map(interner, |subst| ConstrainedSubst {
+                        subst,
+                        constraints: Constraints::empty(interner),
+                    }))
+                }
+                Solution::Ambig(_) => SubstitutionResult::Floundered,
+            };
+            if !seen.insert(answer.clone()) {
+                return true;
+            }
+            match pending.replace(answer) {
+                Some(previous) => f(previous, true),
+                None => true,
+            }
+        });
+        if !finished {
+            return false;
+        }
+        match pending {
+            Some(last) => f(last, false),
+            None => true,
+        }
+    }
+
+    fn explain(
+        &mut self,
+        program: &dyn RustIrDatabase<I>,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+    ) -> ProofTree<I> {
+        // Use a fresh context and cache, so that the derivation of each
+        // subgoal is recorded the first time it is solved, rather than being
+        // elided because an earlier query already cached it.
+        let mut ctx = RecursiveContext::new(
+            self.ctx.overflow_depth(),
+            self.ctx.max_size(),
+            Some(Cache::new()),
+        );
+        let builder = RefCell::new(ProofTreeBuilder::new());
+        let program = ProgramContext {
+            proof_tree: Some(&builder),
+            ..ProgramContext::new(program)
+        };
+        let result = ctx.solve_root_goal(goal, program, || true);
+        builder
+            .into_inner()
+            .finish()
+            .unwrap_or_else(|| ProofTree::leaf(goal.clone(), proof_outcome(&result)))
+    }
+
+    fn stats(&self) -> SolverStats<I> {
+        self.stats.borrow().clone()
     }
 }
@@ -6,13 +6,15 @@ use chalk_ir::could_match::CouldMatch;
 use chalk_ir::fold::TypeFoldable;
 use chalk_ir::interner::{HasInterner, Interner};
 use chalk_ir::{
-    Canonical, ClausePriority, DomainGoal, Fallible, Floundered, Goal, GoalData, InEnvironment,
-    NoSolution, ProgramClause, ProgramClauseData, Substitution, UCanonical,
+    Canonical, ClausePriority, DomainGoal, Environment, Fallible, Floundered, Goal, GoalData,
+    InEnvironment, NoSolution, ProgramClause, ProgramClauseData, Substitution, UCanonical,
 };
 use chalk_solve::clauses::program_clauses_that_could_match;
 use chalk_solve::debug_span;
 use chalk_solve::infer::InferenceTable;
+use chalk_solve::solve::proof_tree::{ProofOutcome, ProofTreeBuilder};
 use chalk_solve::{Guidance, RustIrDatabase, Solution};
+use std::cell::RefCell;
 use tracing::{debug, instrument};
 
 pub(super) trait SolveDatabase<I: Interner>: Sized {
@@ -28,6 +30,20 @@ pub(super) trait SolveDatabase<I: Interner>: Sized {
     fn interner(&self) -> I;
 
     fn db(&self) -> &dyn RustIrDatabase<I>;
+
+    /// The proof tree being recorded, if this solve is being explained.
+    fn proof_tree(&self) -> Option<&RefCell<ProofTreeBuilder<I>>>;
+
+    /// Whether `solve_in_parallel` does anything.
+    fn solves_in_parallel(&self) -> bool {
+        false
+    }
+
+    /// Solves `goals`, which are about to be solved with `solve_goal`, on
+    /// other threads, so that `solve_goal` finds their results in the cache.
+    /// Goals whose results depend on the goals being solved by this
+    /// database are left to `solve_goal`.
+    fn solve_in_parallel(&mut self, _goals: Vec<UCanonicalGoal<I>>) {}
 }
 
 /// The `solve_iteration` method -- implemented for any type that implements
@@ -132,46 +148,50 @@ trait SolveIterationHelpers<I: Interner>: SolveDatabase<I> {
         minimums: &mut Minimums,
         should_continue: impl std::ops::Fn() -> bool + Clone,
     ) -> Fallible<Solution<I>> {
-        let mut clauses = vec![];
-
-        let db = self.db();
-        let could_match = |c: &ProgramClause<I>| {
-            c.could_match(
-                db.interner(),
-                db.unification_database(),
-                &canonical_goal.canonical.value.goal,
-            )
-        };
-        clauses.extend(db.custom_clauses().into_iter().filter(could_match));
-        match program_clauses_that_could_match(db, canonical_goal) {
-            Ok(goal_clauses) => clauses.extend(goal_clauses.into_iter().filter(could_match)),
+        let (infer, subst, goal) = self.new_inference_table(canonical_goal);
+        let clauses = match clauses_for_goal(self.db(), canonical_goal, &goal.environment) {
+            Ok(clauses) => clauses,
             Err(Floundered) => {
+                if let Some(proof_tree) = self.proof_tree() {
+                    proof_tree
+                        .borrow_mut()
+                        .override_outcome(ProofOutcome::Floundered);
+                }
                 return Ok(Solution::Ambig(Guidance::Unknown));
             }
-        }
-
-        let (infer, subst, goal) = self.new_inference_table(canonical_goal);
-        clauses.extend(
-            db.program_clauses_for_env(&goal.environment)
-                .iter(db.interner())
-                .cloned()
-                .filter(could_match),
-        );
+        };
 
         let mut cur_solution = None;
         for program_clause in clauses {
             debug_span!("solve_from_clauses", clause = ?program_clause);
 
+            if let Some(proof_tree) = self.proof_tree() {
+                proof_tree.borrow_mut().enter_clause(program_clause.clone());
+            }
+
             let ProgramClauseData(implication) = program_clause.data(self.interner());
             let infer = infer.clone();
             let subst = subst.clone();
             let goal = goal.clone();
             let res = match Fulfill::new_with_clause(self, infer, subst, goal, implication) {
-                Ok(fulfill) => (
-                    fulfill.solve(minimums, should_continue.clone()),
-                    implication.skip_binders().priority,
-                ),
-                Err(e) => (Err(e), ClausePriority::High),
+                Ok(fulfill) => {
+                    let result = fulfill.solve(minimums, should_continue.clone());
+                    if let Some(proof_tree) = self.proof_tree() {
+                        proof_tree.borrow_mut().exit_clause(match &result {
+                            Ok(solution) => ProofOutcome::Proven(solution.clone()),
+                            Err(NoSolution) => ProofOutcome::NoSolution,
+                        });
+                    }
+                    (result, implication.skip_binders().priority)
+                }
+                Err(e) => {
+                    if let Some(proof_tree) = self.proof_tree() {
+                        proof_tree
+                            .borrow_mut()
+                            .exit_clause(ProofOutcome::DoesNotUnify);
+                    }
+                    (Err(e), ClausePriority::High)
+                }
             };
 
             if let (Ok(solution), priority) = res {
@@ -220,6 +240,40 @@ trait SolveIterationHelpers<I: Interner>: SolveDatabase<I> {
     }
 }
 
+/// Collects the clauses that could be used to prove `canonical_goal`:
+/// custom clauses, program clauses, and the clauses in `environment` (the
+/// goal's environment, instantiated).
+pub(super) fn clauses_for_goal<I: Interner>(
+    db: &dyn RustIrDatabase<I>,
+    canonical_goal: &UCanonical<InEnvironment<DomainGoal<I>>>,
+    environment: &Environment<I>,
+) -> Result<Vec<ProgramClause<I>>, Floundered> {
+    let could_match = |c: &ProgramClause<I>| {
+        c.could_match(
+            db.interner(),
+            db.unification_database(),
+            &canonical_goal.canonical.value.goal,
+        )
+    };
+    let mut clauses: Vec<_> = db
+        .custom_clauses()
+        .into_iter()
+        .filter(could_match)
+        .collect();
+    clauses.extend(
+        program_clauses_that_could_match(db, canonical_goal)?
+            .into_iter()
+            .filter(could_match),
+    );
+    clauses.extend(
+        db.program_clauses_for_env(environment)
+            .iter(db.interner())
+            .cloned()
+            .filter(could_match),
+    );
+    Ok(clauses)
+}
+
 impl<S, I> SolveIterationHelpers<I> for S
 where
     S: SolveDatabase<I>,
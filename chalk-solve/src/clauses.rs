@@ -1094,7 +1094,6 @@ fn match_ty<I: Interner>(
         TyKind::Dyn(dyn_ty) => {
             // FIXME(#203)
             // - Object safety? (not needed with RFC 2027)
-            // - Implied bounds
             // - Bounds on the associated types
             // - Checking that all associated types are specified, including
             //   those on supertraits.
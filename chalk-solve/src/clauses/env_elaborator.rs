@@ -7,9 +7,13 @@ use crate::ProgramClause;
 use crate::RustIrDatabase;
 use crate::Ty;
 use crate::{debug_span, TyKind};
+use chalk_ir::cast::Cast;
 use chalk_ir::interner::Interner;
 use chalk_ir::visit::{TypeVisitable, TypeVisitor};
-use chalk_ir::{DebruijnIndex, Environment};
+use chalk_ir::{
+    DebruijnIndex, Environment, ProgramClauseData, ProgramClauseImplication, TypeOutlives,
+    WhereClause,
+};
 use rustc_hash::FxHashSet;
 use std::ops::ControlFlow;
 use tracing::instrument;
@@ -20,6 +24,11 @@ use tracing::instrument;
 /// For example, when `T: Clone` is in the environment, we can prove
 /// `T: Copy` by adding the clauses from `trait Clone`, which includes
 /// the rule `FromEnv(T: Copy) :- FromEnv(T: Clone)`
+///
+/// Similarly, a well-formed type in the environment implies that the types
+/// it is made of (the fields of an ADT, the signature of a function, the
+/// referent of a reference...) are well-formed, and thus their own implied
+/// bounds: `FromEnv(&'a Vec<T>)` gives `FromEnv(Vec<T>)` and `T: 'a`.
 pub(super) fn elaborate_env_clauses<I: Interner>(
     db: &dyn RustIrDatabase<I>,
     in_clauses: &[ProgramClause<I>],
@@ -35,6 +44,62 @@ pub(super) fn elaborate_env_clauses<I: Interner>(
     };
     in_clauses.visit_with(&mut elaborater, DebruijnIndex::INNERMOST);
     out.extend(this_round);
+
+    for clause in in_clauses {
+        out.extend(elaborate_structural_from_env(db, clause));
+    }
+}
+
+/// `FromEnv(&'a T)` implies `FromEnv(T)` and `T: 'a`, and likewise the
+/// other structural types imply that the types they are made of are
+/// well-formed. Generic rules like `forall<'a, T> { FromEnv(T) :- FromEnv(&'a T) }`
+/// would apply to any `FromEnv` goal, and send the solver looking for ever
+/// larger types, so instead, for a clause `FromEnv(&'a T) :- Conditions`, we
+/// derive `FromEnv(T) :- Conditions` and `T: 'a :- Conditions` directly.
+fn elaborate_structural_from_env<I: Interner>(
+    db: &dyn RustIrDatabase<I>,
+    clause: &ProgramClause<I>,
+) -> Vec<ProgramClause<I>> {
+    let interner = db.interner();
+    let implication = &clause.data(interner).0;
+    let ty = match &implication.skip_binders().consequence {
+        DomainGoal::FromEnv(FromEnv::Ty(ty)) => ty,
+        _ => return vec![],
+    };
+
+    let component = |ty: &Ty<I>| FromEnv::Ty(ty.clone()).cast(interner);
+    let consequences: Vec<DomainGoal<I>> = match ty.kind(interner) {
+        TyKind::Ref(_, lifetime, ty) => vec![
+            component(ty),
+            WhereClause::TypeOutlives(TypeOutlives {
+                ty: ty.clone(),
+                lifetime: lifetime.clone(),
+            })
+            .cast(interner),
+        ],
+        TyKind::Raw(_, ty) | TyKind::Slice(ty) | TyKind::Array(ty, _) => vec![component(ty)],
+        TyKind::Tuple(_, substitution) => substitution
+            .iter(interner)
+            .filter_map(|arg| arg.ty(interner))
+            .map(component)
+            .collect(),
+        _ => return vec![],
+    };
+
+    consequences
+        .into_iter()
+        .filter(|consequence| {
+            // Type parameters imply nothing by themselves.
+            !matches!(consequence, DomainGoal::FromEnv(FromEnv::Ty(ty)) if ty.bound_var(interner).is_some())
+        })
+        .map(|consequence| {
+            ProgramClauseData(implication.map_ref(|implication| ProgramClauseImplication {
+                consequence,
+                ..implication.clone()
+            }))
+            .intern(interner)
+        })
+        .collect()
 }
 
 struct EnvElaborator<'me, 'builder, I: Interner> {
@@ -59,11 +124,13 @@ impl<'me, 'builder, I: Interner> TypeVisitor<I> for EnvElaborator<'me, 'builder,
             TyKind::Alias(alias_ty) => match_alias_ty(self.builder, self.environment, alias_ty),
             TyKind::Placeholder(_) => {}
 
-            // FIXME(#203) -- We haven't fully figured out the implied
-            // bounds story around `dyn Trait` types.
-            TyKind::Dyn(_) => (),
+            // The bounds of `dyn Trait` hold for it by the object clauses,
+            // whatever the environment, so it implies nothing more. Neither
+            // does a function pointer type, which is well-formed even if
+            // its signature is not.
+            TyKind::Dyn(_) | TyKind::Function(_) => (),
 
-            TyKind::Function(_) | TyKind::BoundVar(_) | TyKind::InferenceVar(_, _) => (),
+            TyKind::BoundVar(_) | TyKind::InferenceVar(_, _) => (),
 
             _ => {
                 // This shouldn't fail because of the above clauses
@@ -1,6 +1,9 @@
 use crate::clauses::builder::ClauseBuilder;
+use crate::coherence::specializes_goal;
+use crate::goal_builder::GoalBuilder;
 use crate::rust_ir::*;
 use crate::split::Split;
+use crate::RustIrDatabase;
 use chalk_ir::cast::{Cast, Caster};
 use chalk_ir::interner::Interner;
 use chalk_ir::*;
@@ -75,6 +78,21 @@ impl<I: Interner> ToProgramClauses<I> for AssociatedTyValue<I> {
     ///         Implemented(Iter<'a, T>: 'a).   // (2)
     /// }
     /// ```
+    ///
+    /// If the value is declared `default type`, impls specializing this one
+    /// may override it, so the projection only normalizes once the impl is
+    /// known to be final: when revealing (as for opaque types), and no impl
+    /// that specializes this one and overrides the value applies:
+    ///
+    /// ```notrust
+    /// forall<'a, T> {
+    ///     Normalize(<Vec<T> as Iterable>::IntoIter<'a> -> Iter<'a, T>>) :-
+    ///         Implemented(T: Clone),
+    ///         Implemented(Iter<'a, T>: 'a),
+    ///         Reveal,
+    ///         not { exists<..> { Vec<T> = ..., WC_other, Specializes(Other, Self) } }, // (3)
+    /// }
+    /// ```
     fn to_program_clauses(
         &self,
         builder: &mut ClauseBuilder<'_, I>,
@@ -113,6 +131,18 @@ impl<I: Interner> ToProgramClauses<I> for AssociatedTyValue<I> {
                 .into_iter()
                 .map(|wc| wc.cloned().substitute(interner, &projection.substitution));
 
+            // 3. for a `default type`, that the impl is final for these types
+            let finality_goals = if self.is_default() {
+                let trait_ref = impl_datum
+                    .binders
+                    .map_ref(|b| &b.trait_ref)
+                    .cloned()
+                    .substitute(interner, impl_params);
+                not_overridden_goals(builder.db, self, trait_ref)
+            } else {
+                vec![]
+            };
+
             // Create the final program clause:
             //
             // ```notrust
@@ -128,12 +158,70 @@ impl<I: Interner> ToProgramClauses<I> for AssociatedTyValue<I> {
                     alias: AliasTy::Projection(projection.clone()),
                     ty: assoc_ty_value.ty,
                 },
-                impl_where_clauses.chain(assoc_ty_where_clauses),
+                impl_where_clauses
+                    .casted(interner)
+                    .chain(assoc_ty_where_clauses.casted(interner))
+                    .chain(finality_goals),
             );
         });
     }
 }
 
+/// The goals under which the `default` value `value` is final for
+/// `trait_ref`: we are revealing, and for each other impl that also gives a
+/// value for the associated type, that impl does not both apply and
+/// specialize the impl of `value`.
+fn not_overridden_goals<I: Interner>(
+    db: &dyn RustIrDatabase<I>,
+    value: &AssociatedTyValue<I>,
+    trait_ref: TraitRef<I>,
+) -> Vec<Goal<I>> {
+    let interner = db.interner();
+    let overriding_impls = db
+        .impls_for_trait(
+            trait_ref.trait_id,
+            trait_ref.substitution.as_slice(interner),
+            &CanonicalVarKinds::empty(interner),
+        )
+        .into_iter()
+        .filter(|&impl_id| impl_id != value.impl_id)
+        .filter(|&impl_id| {
+            let impl_datum = db.impl_datum(impl_id);
+            impl_datum.is_positive()
+                && impl_datum.associated_ty_value_ids.iter().any(|&id| {
+                    db.associated_ty_value(id).associated_ty_id == value.associated_ty_id
+                })
+        });
+
+    iter::once(DomainGoal::Reveal.cast(interner))
+        .chain(overriding_impls.map(|impl_id| {
+            let specializes = specializes_goal(db, value.impl_id, impl_id);
+            let gb = &mut GoalBuilder::new(db);
+            let applies = gb.exists(
+                &db.impl_datum(impl_id).binders,
+                (trait_ref.clone(), specializes),
+                |gb, _, impl_bound, (trait_ref, specializes)| {
+                    let interner = gb.interner();
+                    let params_goals = trait_ref
+                        .substitution
+                        .iter(interner)
+                        .cloned()
+                        .zip(impl_bound.trait_ref.substitution.iter(interner).cloned())
+                        .map(|(a, b)| GoalData::EqGoal(EqGoal { a, b }).intern(interner));
+                    let where_clause_goals =
+                        impl_bound.where_clauses.iter().cloned().casted(interner);
+                    gb.all(
+                        params_goals
+                            .chain(where_clause_goals)
+                            .chain(iter::once(specializes)),
+                    )
+                },
+            );
+            GoalData::Not(applies).intern(interner)
+        }))
+        .collect()
+}
+
 impl<I: Interner> ToProgramClauses<I> for OpaqueTyDatum<I> {
     /// Given `opaque type T<U>: A + B = HiddenTy where U: C;`, we generate:
     ///
@@ -322,8 +410,38 @@ fn implied_bounds_program_clauses<'a, I, Wc>(
     }
 }
 
+/// Generates the rules stating that the types a type is made of are
+/// well-formed whenever the type is, so that their own implied bounds hold.
+/// Type parameters imply nothing by themselves, and are skipped.
+///
+/// ```ignore
+/// struct S<T> { field: Vec<T> }
+/// ```
+///
+/// gives
+///
+/// ```notrust
+/// FromEnv(Vec<T>) :- FromEnv(S<T>)
+/// ```
+fn implied_component_program_clauses<'a, I, Tys>(
+    builder: &mut ClauseBuilder<'_, I>,
+    ty: &Ty<I>,
+    component_tys: Tys,
+) where
+    I: Interner + 'a,
+    Tys: IntoIterator<Item = &'a Ty<I>>,
+{
+    let interner = builder.interner();
+    for component_ty in component_tys {
+        if component_ty.bound_var(interner).is_some() {
+            continue;
+        }
+        builder.push_clause(component_ty.clone().from_env(), Some(ty.clone().from_env()));
+    }
+}
+
 impl<I: Interner> ToProgramClauses<I> for AdtDatum<I> {
-    /// Given the following type definition: `struct Foo<T: Eq> { }`, generate:
+    /// Given the following type definition: `struct Foo<T: Eq> { f: Vec<T> }`, generate:
     ///
     /// ```notrust
     /// -- Rule WellFormed-Type
@@ -336,6 +454,11 @@ impl<I: Interner> ToProgramClauses<I> for AdtDatum<I> {
     ///     FromEnv(T: Eq) :- FromEnv(Foo<T>).
     /// }
     ///
+    /// -- Rule Implied-Bound-From-Field
+    /// forall<T> {
+    ///     FromEnv(Vec<T>) :- FromEnv(Foo<T>).
+    /// }
+    ///
     /// forall<T> {
     ///     IsFullyVisible(Foo<T>) :- IsFullyVisible(T).
     /// }
@@ -380,14 +503,19 @@ impl<I: Interner> ToProgramClauses<I> for AdtDatum<I> {
         _environment: &Environment<I>,
     ) {
         let interner = builder.interner();
-        let binders = self.binders.map_ref(|b| &b.where_clauses).cloned();
 
-        builder.push_binders(binders, |builder, where_clauses| {
+        builder.push_binders(self.binders.clone(), |builder, bound| {
             let self_ty = TyKind::Adt(self.id, builder.substitution_in_scope()).intern(interner);
 
-            well_formed_program_clauses(builder, self_ty.clone(), where_clauses.iter());
+            well_formed_program_clauses(builder, self_ty.clone(), bound.where_clauses.iter());
+
+            implied_bounds_program_clauses(builder, &self_ty, bound.where_clauses.iter());
 
-            implied_bounds_program_clauses(builder, &self_ty, where_clauses.iter());
+            implied_component_program_clauses(
+                builder,
+                &self_ty,
+                bound.variants.iter().flat_map(|variant| &variant.fields),
+            );
 
             fully_visible_program_clauses(
                 builder,
@@ -460,6 +588,18 @@ impl<I: Interner> ToProgramClauses<I> for FnDefDatum<I> {
     ///     IsFullyVisible(bar<T>) :- IsFullyVisible(T).
     /// }
     /// ```
+    ///
+    /// The types in the signature of the function are implied by it too,
+    /// for any choice of its late-bound lifetimes: given
+    /// `fn baz<T>(x: &'a Vec<T>) -> Option<T>`, we also generate:
+    ///
+    /// ```notrust
+    /// -- Rule Implied-Bound-From-Signature
+    /// forall<T> {
+    ///     forall<'a> { FromEnv(&'a Vec<T>) :- FromEnv(baz<T>). }
+    ///     forall<'a> { FromEnv(Option<T>) :- FromEnv(baz<T>). }
+    /// }
+    /// ```
     #[instrument(level = "debug", skip(builder))]
     fn to_program_clauses(
         &self,
@@ -467,14 +607,24 @@ impl<I: Interner> ToProgramClauses<I> for FnDefDatum<I> {
         _environment: &Environment<I>,
     ) {
         let interner = builder.interner();
-        let binders = self.binders.map_ref(|b| &b.where_clauses).cloned();
 
-        builder.push_binders(binders, |builder, where_clauses| {
+        builder.push_binders(self.binders.clone(), |builder, bound| {
             let ty = TyKind::FnDef(self.id, builder.substitution_in_scope()).intern(interner);
 
-            well_formed_program_clauses(builder, ty.clone(), where_clauses.iter());
+            well_formed_program_clauses(builder, ty.clone(), bound.where_clauses.iter());
 
-            implied_bounds_program_clauses(builder, &ty, where_clauses.iter());
+            implied_bounds_program_clauses(builder, &ty, bound.where_clauses.iter());
+
+            builder.push_binders(bound.inputs_and_output, |builder, inputs_and_output| {
+                implied_component_program_clauses(
+                    builder,
+                    &ty,
+                    inputs_and_output
+                        .argument_types
+                        .iter()
+                        .chain(Some(&inputs_and_output.return_type)),
+                );
+            });
 
             fully_visible_program_clauses(builder, ty, &builder.substitution_in_scope());
         });
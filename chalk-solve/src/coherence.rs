@@ -5,13 +5,18 @@ use rustc_hash::FxHashMap;
 use crate::solve::Solver;
 use crate::RustIrDatabase;
 use chalk_ir::interner::Interner;
-use chalk_ir::{self, ImplId, TraitId};
+use chalk_ir::{
+    self, AssocTypeId, Canonical, GenericArg, ImplId, Lifetime, TraitId, TraitRef, WhereClause,
+};
 use std::fmt;
 use std::sync::Arc;
 
 pub mod orphan;
 mod solve;
 
+use orphan::OrphanCheckViolation;
+pub(crate) use solve::specializes_goal;
+
 pub struct CoherenceSolver<'a, I: Interner> {
     db: &'a dyn RustIrDatabase<I>,
     solver_builder: &'a dyn Fn() -> Box<dyn Solver<I>>,
@@ -20,18 +25,131 @@ pub struct CoherenceSolver<'a, I: Interner> {
 
 #[derive(Debug)]
 pub enum CoherenceError<I: Interner> {
-    OverlappingImpls(TraitId<I>),
-    FailedOrphanCheck(TraitId<I>),
+    /// Two impls of the trait overlap, and neither specializes the other.
+    OverlappingImpls {
+        trait_id: TraitId<I>,
+        impls: (ImplId<I>, ImplId<I>),
+        /// A trait reference that both impls apply to, if one could be
+        /// found. It may still contain (canonical) variables, in which case
+        /// the impls overlap for every choice of them.
+        witness: Option<Canonical<TraitRef<I>>>,
+    },
+    /// An impl of the trait violates the orphan rules.
+    FailedOrphanCheck {
+        trait_id: TraitId<I>,
+        impl_id: ImplId<I>,
+        violation: OrphanCheckViolation<I>,
+    },
+    /// An impl gives a value for an associated type that the impl it
+    /// specializes does not declare `default`.
+    NonDefaultOverride {
+        trait_id: TraitId<I>,
+        /// The specialized and the specializing impl.
+        impls: (ImplId<I>, ImplId<I>),
+        associated_ty_id: AssocTypeId<I>,
+    },
+    /// An impl overrides a `default` associated type of the impl it
+    /// specializes, but is not always applicable.
+    NotAlwaysApplicable {
+        trait_id: TraitId<I>,
+        /// The specialized and the specializing impl.
+        impls: (ImplId<I>, ImplId<I>),
+        violation: AlwaysApplicableViolation<I>,
+    },
+}
+
+/// Why a specializing impl is not always applicable.
+///
+/// Impls are selected after lifetimes have been erased, so whether a
+/// specializing impl applies (and its values override those of the impl it
+/// specializes) may not depend on lifetimes: it must apply to *every* choice
+/// of lifetimes the specialized impl applies to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AlwaysApplicableViolation<I: Interner> {
+    /// A parameter of the specializing impl (under its binders) is used for
+    /// more than one parameter of the specialized impl, or more than once for
+    /// the same one.
+    RepeatedParameter(GenericArg<I>),
+
+    /// The specializing impl uses a concrete lifetime where the specialized
+    /// impl has a parameter.
+    ConcreteLifetime(Lifetime<I>),
+
+    /// An outlives requirement of the specializing impl (under its binders)
+    /// is not also a requirement of the specialized impl.
+    UnimpliedWhereClause(WhereClause<I>),
+}
+
+impl<I: Interner> fmt::Display for AlwaysApplicableViolation<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlwaysApplicableViolation::RepeatedParameter(param) => {
+                write!(f, "it repeats parameter `{:?}`", param)
+            }
+            AlwaysApplicableViolation::ConcreteLifetime(lifetime) => {
+                write!(f, "it specializes on lifetime `{:?}`", lifetime)
+            }
+            AlwaysApplicableViolation::UnimpliedWhereClause(where_clause) => write!(
+                f,
+                "its where clause `{:?}` is not implied by the specialized impl",
+                where_clause
+            ),
+        }
+    }
 }
 
 impl<I: Interner> fmt::Display for CoherenceError<I> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            CoherenceError::OverlappingImpls(id) => {
-                write!(f, "overlapping impls of trait `{:?}`", id)
+            CoherenceError::OverlappingImpls {
+                trait_id,
+                impls: (a, b),
+                witness,
+            } => {
+                write!(
+                    f,
+                    "overlapping impls of trait `{:?}`: impls `{:?}` and `{:?}` ",
+                    trait_id, a, b
+                )?;
+                match witness {
+                    Some(witness) => write!(f, "both apply to `{:?}`", witness.value.with_colon()),
+                    None => write!(f, "may both apply"),
+                }
             }
-            CoherenceError::FailedOrphanCheck(id) => {
-                write!(f, "impl for trait `{:?}` violates the orphan rules", id)
+            CoherenceError::FailedOrphanCheck {
+                trait_id,
+                violation,
+                ..
+            } => {
+                write!(
+                    f,
+                    "impl for trait `{:?}` violates the orphan rules: {}",
+                    trait_id, violation
+                )
+            }
+            CoherenceError::NonDefaultOverride {
+                trait_id,
+                impls: (less_special, more_special),
+                associated_ty_id,
+            } => {
+                write!(
+                    f,
+                    "impl `{:?}` of trait `{:?}` overrides associated type `{:?}`, \
+                     which the impl `{:?}` it specializes does not declare `default`",
+                    more_special, trait_id, associated_ty_id, less_special
+                )
+            }
+            CoherenceError::NotAlwaysApplicable {
+                trait_id,
+                impls: (less_special, more_special),
+                violation,
+            } => {
+                write!(
+                    f,
+                    "impl `{:?}` of trait `{:?}` overrides items of the impl `{:?}` it \
+                     specializes, but is not always applicable: {}",
+                    more_special, trait_id, less_special, violation
+                )
             }
         }
     }
@@ -58,6 +176,16 @@ impl<I: Interner> SpecializationPriorities<I> {
         self.map[&impl_id]
     }
 
+    /// Adds the priorities of `other`, found for another set of impls of the
+    /// same trait. Impls that are in both keep the higher of their
+    /// priorities.
+    pub fn merge(&mut self, other: &SpecializationPriorities<I>) {
+        for (&impl_id, &p) in &other.map {
+            let priority = self.map.entry(impl_id).or_insert(p);
+            *priority = (*priority).max(p);
+        }
+    }
+
     /// Store the priority of an impl (used during construction).
     /// Panics if we have already stored the priority for this impl.
     fn insert(&mut self, impl_id: ImplId<I>, p: SpecializationPriority) {
@@ -5,8 +5,72 @@ use crate::RustIrDatabase;
 use chalk_ir::cast::*;
 use chalk_ir::interner::Interner;
 use chalk_ir::*;
+use std::fmt;
 use tracing::{debug, instrument};
 
+/// Why an impl violates the orphan rules.
+///
+/// The rules (RFC 2451) allow an impl of an upstream trait
+/// `impl<P..> Trait<T1..Tn> for T0` only if some `Ti` is local, and no type
+/// before the first such `Ti` contains (is not "fully visible" because of)
+/// one of the impl's type parameters `P`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrphanCheckViolation<I: Interner> {
+    pub kind: OrphanCheckViolationKind<I>,
+
+    /// The `#[upstream]` types that made the types checked (up to the
+    /// uncovered type, if any) non-local.
+    pub upstream_adts: Vec<AdtId<I>>,
+
+    /// The `#[upstream]` `#[fundamental]` types among the types checked,
+    /// whose locality was decided by their parameters instead.
+    pub fundamental_adts: Vec<AdtId<I>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OrphanCheckViolationKind<I: Interner> {
+    /// None of the types in the trait reference is local.
+    NoLocalType,
+
+    /// The type at `index` in the trait reference (`0` being the self type)
+    /// is neither local nor fully visible, and no local type comes before
+    /// it. The type is under the binders of the impl.
+    UncoveredType { index: usize, ty: Ty<I> },
+}
+
+impl<I: Interner> fmt::Display for OrphanCheckViolation<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            OrphanCheckViolationKind::NoLocalType => {
+                write!(f, "none of the types in the trait reference is local")?
+            }
+            OrphanCheckViolationKind::UncoveredType { index, ty } => {
+                write!(f, "`{:?}` ", ty)?;
+                match index {
+                    0 => write!(f, "(the self type)")?,
+                    _ => write!(f, "(type parameter {} of the trait)", index)?,
+                }
+                write!(
+                    f,
+                    " is not local and contains an uncovered type parameter, \
+                     which may not appear before the first local type"
+                )?
+            }
+        }
+        for id in &self.upstream_adts {
+            write!(f, "; `{:?}` is not local because it is `#[upstream]`", id)?;
+        }
+        for id in &self.fundamental_adts {
+            write!(
+                f,
+                "; `{:?}` is `#[fundamental]`, so only its parameters were considered",
+                id
+            )?;
+        }
+        Ok(())
+    }
+}
+
 // Test if a local impl violates the orphan rules.
 //
 // For `impl<T> Trait for MyType<T>` we generate:
@@ -37,8 +101,95 @@ pub fn perform_orphan_check<I: Interner>(
 
     if !is_allowed {
         let trait_id = impl_datum.trait_id();
-        return Err(CoherenceError::FailedOrphanCheck(trait_id));
+        return Err(CoherenceError::FailedOrphanCheck {
+            trait_id,
+            impl_id,
+            violation: explain_orphan_check_failure(db, solver, impl_id),
+        });
     }
 
     Ok(())
 }
+
+// Finds which of the rules encoded by the `LocalImplAllowed` clauses (see
+// `TraitDatum::to_program_clauses`) an impl that failed the orphan check
+// violates, by checking the types of its trait reference in order:
+//
+//     forall<T> { IsLocal(Ti) }
+//     forall<T> { IsFullyVisible(Ti) }
+fn explain_orphan_check_failure<I: Interner>(
+    db: &dyn RustIrDatabase<I>,
+    solver: &mut dyn Solver<I>,
+    impl_id: ImplId<I>,
+) -> OrphanCheckViolation<I> {
+    let interner = db.interner();
+    let impl_datum = db.impl_datum(impl_id);
+    let types: Vec<Ty<I>> = impl_datum
+        .binders
+        .skip_binders()
+        .trait_ref
+        .type_parameters(interner)
+        .collect();
+
+    let mut holds = |goal: fn(Ty<I>) -> DomainGoal<I>, ty: &Ty<I>| {
+        let goal: Goal<I> = impl_datum
+            .binders
+            .map_ref(|_| goal(ty.clone()))
+            .cast(interner);
+        solver
+            .solve(db, &goal.into_closed_goal(interner))
+            .is_some_and(|solution| solution.is_unique())
+    };
+
+    // As the impl failed the check, every type before the first local one
+    // is fully visible, so the first type that is neither is the culprit.
+    let mut kind = OrphanCheckViolationKind::NoLocalType;
+    let mut checked = types.len();
+    for (index, ty) in types.iter().enumerate() {
+        if !holds(DomainGoal::IsLocal, ty) && !holds(DomainGoal::IsFullyVisible, ty) {
+            kind = OrphanCheckViolationKind::UncoveredType {
+                index,
+                ty: ty.clone(),
+            };
+            checked = index + 1;
+            break;
+        }
+    }
+
+    let mut violation = OrphanCheckViolation {
+        kind,
+        upstream_adts: vec![],
+        fundamental_adts: vec![],
+    };
+    for ty in &types[..checked] {
+        collect_non_local_adts(db, ty, &mut violation);
+    }
+    debug!(?violation);
+    violation
+}
+
+// Records the `#[upstream]` ADTs that `IsLocal(ty)` depends on: the head of
+// `ty`, and if it is `#[fundamental]`, those of its type parameters.
+fn collect_non_local_adts<I: Interner>(
+    db: &dyn RustIrDatabase<I>,
+    ty: &Ty<I>,
+    violation: &mut OrphanCheckViolation<I>,
+) {
+    let interner = db.interner();
+    if let TyKind::Adt(id, substitution) = ty.kind(interner) {
+        let flags = &db.adt_datum(*id).flags;
+        if !flags.upstream {
+            return;
+        }
+        if flags.fundamental {
+            if !violation.fundamental_adts.contains(id) {
+                violation.fundamental_adts.push(*id);
+            }
+            for ty in substitution.type_parameters(interner) {
+                collect_non_local_adts(db, &ty, violation);
+            }
+        } else if !violation.upstream_adts.contains(id) {
+            violation.upstream_adts.push(*id);
+        }
+    }
+}
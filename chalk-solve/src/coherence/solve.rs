@@ -1,13 +1,17 @@
-use crate::coherence::{CoherenceError, CoherenceSolver};
+use crate::coherence::{AlwaysApplicableViolation, CoherenceError, CoherenceSolver};
 use crate::debug_span;
 use crate::ext::*;
+use crate::infer::InferenceTable;
 use crate::rust_ir::*;
-use crate::{goal_builder::GoalBuilder, Solution};
+use crate::{goal_builder::GoalBuilder, Guidance, RustIrDatabase, Solution};
 use chalk_ir::cast::*;
 use chalk_ir::fold::shift::Shift;
 use chalk_ir::interner::Interner;
+use chalk_ir::visit::{TypeSuperVisitable, TypeVisitable, TypeVisitor};
 use chalk_ir::*;
 use itertools::Itertools;
+use rustc_hash::FxHashSet;
+use std::ops::ControlFlow;
 use tracing::{debug, instrument};
 
 impl<I: Interner> CoherenceSolver<'_, I> {
@@ -32,15 +36,30 @@ impl<I: Interner> CoherenceSolver<'_, I> {
                 continue;
             }
 
+            // The overlap of two upstream impls is checked by their own crates.
+            if lhs.impl_type == ImplType::External && rhs.impl_type == ImplType::External {
+                continue;
+            }
+
             // Check if the impls overlap, then if they do, check if one specializes
             // the other. Note that specialization can only run one way - if both
             // specialization checks return *either* true or false, that's an error.
             if !self.disjoint(lhs, rhs) {
                 match (self.specializes(l_id, r_id), self.specializes(r_id, l_id)) {
-                    (true, false) => record_specialization(l_id, r_id),
-                    (false, true) => record_specialization(r_id, l_id),
+                    (true, false) => {
+                        self.check_specialization(l_id, r_id)?;
+                        record_specialization(l_id, r_id)
+                    }
+                    (false, true) => {
+                        self.check_specialization(r_id, l_id)?;
+                        record_specialization(r_id, l_id)
+                    }
                     (_, _) => {
-                        return Err(CoherenceError::OverlappingImpls(self.trait_id));
+                        return Err(CoherenceError::OverlappingImpls {
+                            trait_id: self.trait_id,
+                            impls: (l_id, r_id),
+                            witness: self.overlap_witness(lhs, rhs),
+                        });
                     }
                 }
             }
@@ -86,8 +105,37 @@ impl<I: Interner> CoherenceSolver<'_, I> {
     fn disjoint(&self, lhs: &ImplDatum<I>, rhs: &ImplDatum<I>) -> bool {
         let interner = self.db.interner();
 
+        let (rhs_binders, _) = rhs.binders.as_ref().into();
+        let goal = Box::new(self.intersection_goal(lhs, rhs))
+            .quantify(interner, QuantifierKind::Exists, rhs_binders)
+            .compatible(interner)
+            .negate(interner);
+
+        let canonical_goal = &goal.into_closed_goal(interner);
+        let mut fresh_solver = (self.solver_builder)();
+        let solution = fresh_solver.solve(self.db, canonical_goal);
+        let result = match solution {
+            // Goal was proven with a unique solution, so no impl was found that causes these two
+            // to overlap
+            Some(Solution::Unique(_)) => true,
+            // Goal was ambiguous, so there *may* be overlap
+            Some(Solution::Ambig(_)) |
+            // Goal cannot be proven, so there is some impl that causes overlap
+            None => false,
+        };
+        debug!("overlaps: result = {:?}", result);
+        result
+    }
+
+    // Builds the goal that holds if both impls apply to the same trait reference: the inputs
+    // to the trait of both impls are equal, and the where clauses of both impls hold. The
+    // variables of the lhs impl are existentially quantified, while those of the rhs impl are
+    // left free (with the binders of the rhs impl in scope).
+    fn intersection_goal(&self, lhs: &ImplDatum<I>, rhs: &ImplDatum<I>) -> Goal<I> {
+        let interner = self.db.interner();
+
         let (lhs_binders, lhs_bound) = lhs.binders.as_ref().into();
-        let (rhs_binders, rhs_bound) = rhs.binders.as_ref().into();
+        let (_, rhs_bound) = rhs.binders.as_ref().into();
 
         // Upshift the rhs variables in params to account for the joined binders
         let lhs_params = lhs_bound
@@ -122,27 +170,59 @@ impl<I: Interner> CoherenceSolver<'_, I> {
             .map(|wc| wc.cast(interner));
 
         // Join all the goals we've created together with And, then quantify them
-        // over the joined binders. This is our query.
-        let goal = Box::new(Goal::all(interner, params_goals.chain(wc_goals)))
-            .quantify(interner, QuantifierKind::Exists, lhs_binders)
-            .quantify(interner, QuantifierKind::Exists, rhs_binders)
-            .compatible(interner)
-            .negate(interner);
+        // over the binders of the lhs impl.
+        Box::new(Goal::all(interner, params_goals.chain(wc_goals))).quantify(
+            interner,
+            QuantifierKind::Exists,
+            lhs_binders,
+        )
+    }
+
+    // Finds a trait reference that two overlapping impls both apply to, by solving their
+    // intersection goal (in the compatible modality, like `disjoint`) for the variables of the
+    // rhs impl. Gives up if the solver cannot say what those variables must be.
+    #[instrument(level = "debug", skip(self))]
+    fn overlap_witness(
+        &self,
+        lhs: &ImplDatum<I>,
+        rhs: &ImplDatum<I>,
+    ) -> Option<Canonical<TraitRef<I>>> {
+        let interner = self.db.interner();
+
+        let goal = self.intersection_goal(lhs, rhs).compatible(interner);
+        let binders = CanonicalVarKinds::from_iter(
+            interner,
+            rhs.binders
+                .binders
+                .iter(interner)
+                .map(|kind| WithKind::new(kind.clone(), UniverseIndex::root())),
+        );
+        let canonical_goal = UCanonical {
+            canonical: Canonical {
+                binders,
+                value: InEnvironment::new(&Environment::new(interner), goal),
+            },
+            universes: 1,
+        };
 
-        let canonical_goal = &goal.into_closed_goal(interner);
         let mut fresh_solver = (self.solver_builder)();
-        let solution = fresh_solver.solve(self.db, canonical_goal);
-        let result = match solution {
-            // Goal was proven with a unique solution, so no impl was found that causes these two
-            // to overlap
-            Some(Solution::Unique(_)) => true,
-            // Goal was ambiguous, so there *may* be overlap
-            Some(Solution::Ambig(_)) |
-            // Goal cannot be proven, so there is some impl that causes overlap
-            None => false,
+        let subst = match fresh_solver.solve(self.db, &canonical_goal)? {
+            Solution::Unique(constrained) => Canonical {
+                binders: constrained.binders,
+                value: constrained.value.subst,
+            },
+            Solution::Ambig(Guidance::Definite(subst)) => subst,
+            Solution::Ambig(_) => return None,
         };
-        debug!("overlaps: result = {:?}", result);
-        result
+        let witness = Canonical {
+            binders: subst.binders,
+            value: rhs
+                .binders
+                .map_ref(|bound| bound.trait_ref.clone())
+                .substitute(interner, &subst.value),
+        };
+        debug!("overlap_witness: witness = {:?}", witness);
+        Some(witness)
     }
 
     // Creates a goal which, if provable, means "more special" impl specializes the "less special" one.
@@ -200,54 +280,7 @@ impl<I: Interner> CoherenceSolver<'_, I> {
 
         let interner = self.db.interner();
 
-        let gb = &mut GoalBuilder::new(self.db);
-
-        // forall<P0..Pn> { ... }
-        let goal = gb.forall(
-            &more_special.binders,
-            less_special_id,
-            |gb, _, more_special_impl, less_special_id| {
-                // if (WC_more) { ... }
-                gb.implies(more_special_impl.where_clauses.iter().cloned(), |gb| {
-                    let less_special = &gb.db().impl_datum(less_special_id);
-
-                    // exists<Q0..Qn> { ... }
-                    gb.exists(
-                        &less_special.binders,
-                        more_special_impl.trait_ref.clone(),
-                        |gb, _, less_special_impl, more_special_trait_ref| {
-                            let interner = gb.interner();
-
-                            // T0 = U0, ..., Tm = Um
-                            let params_goals = more_special_trait_ref
-                                .substitution
-                                .as_slice(interner)
-                                .iter()
-                                .cloned()
-                                .zip(
-                                    less_special_impl
-                                        .trait_ref
-                                        .substitution
-                                        .as_slice(interner)
-                                        .iter()
-                                        .cloned(),
-                                )
-                                .map(|(a, b)| GoalData::EqGoal(EqGoal { a, b }).intern(interner));
-
-                            // <less_special_wc_goals> = where clauses from the less special impl
-                            let less_special_wc_goals = less_special_impl
-                                .where_clauses
-                                .iter()
-                                .cloned()
-                                .casted(interner);
-
-                            // <equality_goals> && WC_less
-                            gb.all(params_goals.chain(less_special_wc_goals))
-                        },
-                    )
-                })
-            },
-        );
+        let goal = specializes_goal(self.db, less_special_id, more_special_id);
 
         let canonical_goal = &goal.into_closed_goal(interner);
         let mut fresh_solver = (self.solver_builder)();
@@ -257,4 +290,241 @@ impl<I: Interner> CoherenceSolver<'_, I> {
 
         result
     }
+
+    // Checks the associated type values that the "more special" impl gives for associated
+    // types the "less special" impl also gives values for: those must be declared `default` in
+    // the less special impl, and if there are any, the more special impl must be always
+    // applicable (see `AlwaysApplicableViolation`).
+    fn check_specialization(
+        &self,
+        less_special_id: ImplId<I>,
+        more_special_id: ImplId<I>,
+    ) -> Result<(), CoherenceError<I>> {
+        let less_special = &self.db.impl_datum(less_special_id);
+        let more_special = &self.db.impl_datum(more_special_id);
+
+        let mut overrides = false;
+        for &value_id in &more_special.associated_ty_value_ids {
+            let associated_ty_id = self.db.associated_ty_value(value_id).associated_ty_id;
+            let overridden = less_special
+                .associated_ty_value_ids
+                .iter()
+                .map(|&id| self.db.associated_ty_value(id))
+                .find(|value| value.associated_ty_id == associated_ty_id);
+            match overridden {
+                Some(value) if !value.is_default() => {
+                    return Err(CoherenceError::NonDefaultOverride {
+                        trait_id: self.trait_id,
+                        impls: (less_special_id, more_special_id),
+                        associated_ty_id,
+                    });
+                }
+                Some(_) => overrides = true,
+                None => {}
+            }
+        }
+
+        if overrides {
+            if let Some(violation) = self.always_applicable_violation(less_special, more_special) {
+                return Err(CoherenceError::NotAlwaysApplicable {
+                    trait_id: self.trait_id,
+                    impls: (less_special_id, more_special_id),
+                    violation,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Checks that the "more special" impl applies for every choice of lifetimes that the "less
+    // special" impl applies for.
+    //
+    // We instantiate the more special impl with placeholders and find the parameters of the less
+    // special impl in terms of them. Those may not repeat a placeholder, nor contain a concrete
+    // lifetime, and the outlives requirements of the more special impl must also be
+    // requirements of the less special one.
+    //
+    // Examples (specializing `impl<A, B> Foo for (A, B)`):
+    //
+    //      impl<T> Foo for (T, T)                  // repeats `T`
+    //      impl<T> Foo for (&'static T, u32)       // specializes on `'static`
+    //      impl<'a, T> Foo for (&'a T, T) where T: 'a  // `T: 'a` is not implied
+    //      impl<T> Foo for (T, u32) where T: Clone // ok
+    #[instrument(level = "debug", skip(self))]
+    fn always_applicable_violation(
+        &self,
+        less_special: &ImplDatum<I>,
+        more_special: &ImplDatum<I>,
+    ) -> Option<AlwaysApplicableViolation<I>> {
+        let interner = self.db.interner();
+        let mut table = InferenceTable::new();
+
+        let more_bound =
+            table.instantiate_binders_universally(interner, more_special.binders.clone());
+        let identity = less_special.binders.identity_substitution(interner);
+        let (less_params, less_bound) = table.instantiate_binders_existentially(
+            interner,
+            less_special
+                .binders
+                .map_ref(|bound| (identity, bound.clone())),
+        );
+        table
+            .relate(
+                interner,
+                self.db.unification_database(),
+                &Environment::new(interner),
+                Variance::Invariant,
+                &more_bound.trait_ref,
+                &less_bound.trait_ref,
+            )
+            .ok()?;
+
+        let less_params = table.canonicalize(interner, less_params).quantified.value;
+        let less_where_clauses: Vec<_> = less_bound
+            .where_clauses
+            .into_iter()
+            .map(|wc| table.canonicalize(interner, wc).quantified.value)
+            .collect();
+        debug!(?less_params, ?less_where_clauses);
+
+        let mut collector = ParameterCollector {
+            interner,
+            placeholders: vec![],
+            concrete_lifetimes: vec![],
+        };
+        let _ = less_params.visit_with(&mut collector, DebruijnIndex::INNERMOST);
+
+        if let Some(lifetime) = collector.concrete_lifetimes.into_iter().next() {
+            return Some(AlwaysApplicableViolation::ConcreteLifetime(lifetime));
+        }
+
+        let mut seen = FxHashSet::default();
+        if let Some(placeholder) = collector
+            .placeholders
+            .into_iter()
+            .find(|&p| !seen.insert(p))
+        {
+            let param = more_special
+                .binders
+                .identity_substitution(interner)
+                .at(interner, placeholder.idx)
+                .clone();
+            return Some(AlwaysApplicableViolation::RepeatedParameter(param));
+        }
+
+        more_bound
+            .where_clauses
+            .iter()
+            .zip(&more_special.binders.skip_binders().where_clauses)
+            .filter(|(wc, _)| {
+                matches!(
+                    wc.skip_binders(),
+                    WhereClause::LifetimeOutlives(_) | WhereClause::TypeOutlives(_)
+                ) && !less_where_clauses.contains(wc)
+            })
+            .find_map(|(_, wc)| wc.skip_binders().clone().shifted_out(interner).ok())
+            .map(AlwaysApplicableViolation::UnimpliedWhereClause)
+    }
+}
+
+/// Collects the placeholders and concrete lifetimes in a value, in order.
+struct ParameterCollector<I: Interner> {
+    interner: I,
+    placeholders: Vec<PlaceholderIndex>,
+    concrete_lifetimes: Vec<Lifetime<I>>,
+}
+
+impl<I: Interner> TypeVisitor<I> for ParameterCollector<I> {
+    type BreakTy = ();
+
+    fn as_dyn(&mut self) -> &mut dyn TypeVisitor<I, BreakTy = Self::BreakTy> {
+        self
+    }
+
+    fn visit_lifetime(
+        &mut self,
+        lifetime: &Lifetime<I>,
+        outer_binder: DebruijnIndex,
+    ) -> ControlFlow<()> {
+        match lifetime.data(self.interner) {
+            LifetimeData::BoundVar(_)
+            | LifetimeData::InferenceVar(_)
+            | LifetimeData::Placeholder(_) => lifetime.super_visit_with(self, outer_binder),
+            _ => {
+                self.concrete_lifetimes.push(lifetime.clone());
+                ControlFlow::Continue(())
+            }
+        }
+    }
+
+    fn visit_free_placeholder(
+        &mut self,
+        placeholder: PlaceholderIndex,
+        _outer_binder: DebruijnIndex,
+    ) -> ControlFlow<()> {
+        self.placeholders.push(placeholder);
+        ControlFlow::Continue(())
+    }
+
+    fn interner(&self) -> I {
+        self.interner
+    }
+}
+
+/// Creates the (closed) goal that holds if the impl `more_special_id`
+/// specializes the impl `less_special_id`.
+pub(crate) fn specializes_goal<I: Interner>(
+    db: &dyn RustIrDatabase<I>,
+    less_special_id: ImplId<I>,
+    more_special_id: ImplId<I>,
+) -> Goal<I> {
+    let more_special = &db.impl_datum(more_special_id);
+    let gb = &mut GoalBuilder::new(db);
+
+    // forall<P0..Pn> { ... }
+    gb.forall(
+        &more_special.binders,
+        less_special_id,
+        |gb, _, more_special_impl, less_special_id| {
+            // if (WC_more) { ... }
+            gb.implies(more_special_impl.where_clauses.iter().cloned(), |gb| {
+                let less_special = &gb.db().impl_datum(less_special_id);
+
+                // exists<Q0..Qn> { ... }
+                gb.exists(
+                    &less_special.binders,
+                    more_special_impl.trait_ref.clone(),
+                    |gb, _, less_special_impl, more_special_trait_ref| {
+                        let interner = gb.interner();
+
+                        // T0 = U0, ..., Tm = Um
+                        let params_goals = more_special_trait_ref
+                            .substitution
+                            .as_slice(interner)
+                            .iter()
+                            .cloned()
+                            .zip(
+                                less_special_impl
+                                    .trait_ref
+                                    .substitution
+                                    .as_slice(interner)
+                                    .iter()
+                                    .cloned(),
+                            )
+                            .map(|(a, b)| GoalData::EqGoal(EqGoal { a, b }).intern(interner));
+
+                        // <less_special_wc_goals> = where clauses from the less special impl
+                        let less_special_wc_goals = less_special_impl
+                            .where_clauses
+                            .iter()
+                            .cloned()
+                            .casted(interner);
+
+                        // <equality_goals> && WC_less
+                        gb.all(params_goals.chain(less_special_wc_goals))
+                    },
+                )
+            })
+        },
+    )
 }
@@ -449,7 +449,11 @@ impl<I: Interner> RenderAsRust<I> for AssociatedTyValue<I> {
             .db()
             .split_associated_ty_value_parameters(&display_params, self);
 
-        write!(f, "{}type {}", s.indent(), assoc_ty_data.id.display(s))?;
+        write!(f, "{}", s.indent())?;
+        if self.is_default() {
+            write!(f, "default ")?;
+        }
+        write!(f, "type {}", assoc_ty_data.id.display(s))?;
         write_joined_non_empty_list!(f, "<{}>", assoc_ty_value_display, ", ")?;
         write!(f, " = {};", value.ty.display(s))?;
         Ok(())
@@ -34,6 +34,18 @@ impl<I: Interner, DB: RustIrDatabase<I>> UnificationDatabase<I> for StubWrapper<
     fn adt_variance(&self, adt_id: chalk_ir::AdtId<I>) -> Variances<I> {
         self.db.unification_database().adt_variance(adt_id)
     }
+
+    fn evaluate_const_expr(
+        &self,
+        ty: &chalk_ir::Ty<I>,
+        op: chalk_ir::ConstBinOp,
+        lhs: &chalk_ir::ConcreteConst<I>,
+        rhs: &chalk_ir::ConcreteConst<I>,
+    ) -> Option<chalk_ir::ConcreteConst<I>> {
+        self.db
+            .unification_database()
+            .evaluate_const_expr(ty, op, lhs, rhs)
+    }
 }
 
 impl<I: Interner, DB: RustIrDatabase<I>> RustIrDatabase<I> for StubWrapper<'_, DB> {
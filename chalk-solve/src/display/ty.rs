@@ -264,6 +264,13 @@ impl<I: Interner> RenderAsRust<I> for ConstValue<I> {
             ConstValue::InferenceVar(_) => write!(f, "_"),
             ConstValue::Placeholder(_) => write!(f, "<const placeholder>"),
             ConstValue::Concrete(value) => write!(f, "{:?}", value.interned),
+            ConstValue::Expr(expr) => write!(
+                f,
+                "{{ {} {} {} }}",
+                expr.lhs.display(s),
+                expr.op,
+                expr.rhs.display(s)
+            ),
         }
     }
 }
@@ -17,6 +17,16 @@ impl UnificationDatabase<ChalkIr> for TestDatabase {
     fn adt_variance(&self, _adt_id: AdtId<ChalkIr>) -> Variances<ChalkIr> {
         Variances::from_iter(ChalkIr, [Variance::Invariant; 20].iter().copied())
     }
+
+    fn evaluate_const_expr(
+        &self,
+        _ty: &Ty<ChalkIr>,
+        _op: ConstBinOp,
+        _lhs: &ConcreteConst<ChalkIr>,
+        _rhs: &ConcreteConst<ChalkIr>,
+    ) -> Option<ConcreteConst<ChalkIr>> {
+        None
+    }
 }
 
 #[test]
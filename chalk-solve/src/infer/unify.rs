@@ -1050,6 +1050,13 @@ impl<'t, I: Interner> Unifier<'t, I> {
         let a = n_a.as_ref().unwrap_or(a);
         let b = n_b.as_ref().unwrap_or(b);
 
+        // Evaluate any const expressions whose operands are known, so
+        // that e.g. `2 + 1` relates to `3`.
+        let e_a = self.evaluate_const(a)?;
+        let e_b = self.evaluate_const(b)?;
+        let a = &e_a;
+        let b = &e_b;
+
         debug_span!("relate_const_const", ?variance, ?a, ?b);
 
         let ConstData {
@@ -1105,6 +1112,39 @@ impl<'t, I: Interner> Unifier<'t, I> {
             (&ConstValue::Concrete(_), &ConstValue::Placeholder(_))
             | (&ConstValue::Placeholder(_), &ConstValue::Concrete(_)) => Err(NoSolution),
 
+            // Unifying an inference variable with an unevaluated expression:
+            // bind the variable to the expression itself.
+            (&ConstValue::InferenceVar(var), &ConstValue::Expr(_)) => {
+                debug!(?var, ty=?b, "unify_var_ty");
+                self.unify_var_const(var, b)
+            }
+
+            (&ConstValue::Expr(_), &ConstValue::InferenceVar(var)) => {
+                debug!(?var, ty=?a, "unify_var_ty");
+                self.unify_var_const(var, a)
+            }
+
+            // Unevaluated expressions are related structurally.
+            (ConstValue::Expr(e1), ConstValue::Expr(e2)) if e1.op == e2.op => {
+                self.relate_const_const(variance, &e1.lhs, &e2.lhs)?;
+                self.relate_const_const(variance, &e1.rhs, &e2.rhs)
+            }
+
+            // An expression that still mentions unresolved inference
+            // variables may become equal to the other side once those are
+            // known, so we can only report ambiguity.
+            (ConstValue::Expr(_), _) | (_, ConstValue::Expr(_)) => {
+                if self.const_has_inference_vars(a) || self.const_has_inference_vars(b) {
+                    self.goals.push(InEnvironment::new(
+                        self.environment,
+                        GoalData::CannotProve.intern(interner),
+                    ));
+                    Ok(())
+                } else {
+                    Err(NoSolution)
+                }
+            }
+
             (ConstValue::BoundVar(_), _) | (_, ConstValue::BoundVar(_)) => panic!(
                 "unification encountered bound variable: a={:?} b={:?}",
                 a, b
@@ -1112,6 +1152,54 @@ impl<'t, I: Interner> Unifier<'t, I> {
         }
     }
 
+    /// Evaluates the const expressions in `c` whose operands are all
+    /// concrete, resolving inference variables along the way. Fails if the
+    /// database reports that an expression cannot be evaluated (e.g. on
+    /// overflow or division by zero).
+    fn evaluate_const(&mut self, c: &Const<I>) -> Fallible<Const<I>> {
+        let interner = self.interner;
+        let c = self
+            .table
+            .normalize_const_shallow(interner, c)
+            .unwrap_or_else(|| c.clone());
+        let data = c.data(interner);
+        let expr = match &data.value {
+            ConstValue::Expr(expr) => expr,
+            _ => return Ok(c),
+        };
+        let lhs = self.evaluate_const(&expr.lhs)?;
+        let rhs = self.evaluate_const(&expr.rhs)?;
+        let value = match (&lhs.data(interner).value, &rhs.data(interner).value) {
+            (ConstValue::Concrete(l), ConstValue::Concrete(r)) => ConstValue::Concrete(
+                self.db
+                    .evaluate_const_expr(&data.ty, expr.op, l, r)
+                    .ok_or(NoSolution)?,
+            ),
+            _ => ConstValue::Expr(ConstExpr {
+                op: expr.op,
+                lhs,
+                rhs,
+            }),
+        };
+        Ok(ConstData {
+            ty: data.ty.clone(),
+            value,
+        }
+        .intern(interner))
+    }
+
+    /// Whether `c` (after [`Self::evaluate_const`]) still refers to an
+    /// unresolved inference variable.
+    fn const_has_inference_vars(&self, c: &Const<I>) -> bool {
+        match &c.data(self.interner).value {
+            ConstValue::InferenceVar(_) => true,
+            ConstValue::Expr(expr) => {
+                self.const_has_inference_vars(&expr.lhs) || self.const_has_inference_vars(&expr.rhs)
+            }
+            _ => false,
+        }
+    }
+
     #[instrument(level = "debug", skip(self))]
     fn unify_var_const(&mut self, var: InferenceVar, c: &Const<I>) -> Fallible<()> {
         let interner = self.interner;
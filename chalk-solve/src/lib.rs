@@ -116,6 +116,9 @@ pub trait RustIrDatabase<I: Interner>: Debug {
     /// - It can exclude impls not defined in the current crate.
     /// - It can exclude "built-in" impls, like those for closures; only the
     ///   impls actually written by users need to be checked.
+    ///
+    /// It may also include impls of upstream crates, which the local impls
+    /// are then checked against; pairs of non-local impls are not checked.
     fn local_impls_to_coherence_check(&self, trait_id: TraitId<I>) -> Vec<ImplId<I>>;
 
     /// Returns true if there is an explicit impl of the auto trait
@@ -207,6 +210,12 @@ pub trait RustIrDatabase<I: Interner>: Debug {
 
     // Retrieves the discriminant type for a type (mirror of rustc `Ty::discriminant_ty`)
     fn discriminant_type(&self, ty: Ty<I>) -> Ty<I>;
+
+    /// This database, if it can be shared between threads. Solvers that
+    /// solve goals on several threads only do so when it can.
+    fn as_sync(&self) -> Option<&(dyn RustIrDatabase<I> + Sync)> {
+        None
+    }
 }
 
 pub use clauses::program_clauses_for_env;
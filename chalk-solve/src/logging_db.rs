@@ -107,6 +107,18 @@ where
     fn adt_variance(&self, adt_id: chalk_ir::AdtId<I>) -> Variances<I> {
         self.ws.db().unification_database().adt_variance(adt_id)
     }
+    fn evaluate_const_expr(
+        &self,
+        ty: &chalk_ir::Ty<I>,
+        op: chalk_ir::ConstBinOp,
+        lhs: &chalk_ir::ConcreteConst<I>,
+        rhs: &chalk_ir::ConcreteConst<I>,
+    ) -> Option<chalk_ir::ConcreteConst<I>> {
+        self.ws
+            .db()
+            .unification_database()
+            .evaluate_const_expr(ty, op, lhs, rhs)
+    }
 }
 
 impl<I, DB, P> RustIrDatabase<I> for LoggingRustIrDatabase<I, DB, P>
@@ -398,6 +410,18 @@ where
     fn adt_variance(&self, adt_id: chalk_ir::AdtId<I>) -> Variances<I> {
         self.db.borrow().unification_database().adt_variance(adt_id)
     }
+    fn evaluate_const_expr(
+        &self,
+        ty: &chalk_ir::Ty<I>,
+        op: chalk_ir::ConstBinOp,
+        lhs: &chalk_ir::ConcreteConst<I>,
+        rhs: &chalk_ir::ConcreteConst<I>,
+    ) -> Option<chalk_ir::ConcreteConst<I>> {
+        self.db
+            .borrow()
+            .unification_database()
+            .evaluate_const_expr(ty, op, lhs, rhs)
+    }
 }
 
 impl<I, W, DB, P> RustIrDatabase<I> for WriteOnDropRustIrDatabase<I, W, DB, P>
@@ -634,8 +634,35 @@ pub struct AssociatedTyValue<I: Interner> {
     /// }
     /// ```
     pub value: Binders<AssociatedTyValueBound<I>>,
+
+    /// Whether impls specializing this one may override the value.
+    ///
+    /// ```ignore
+    /// impl<T> Iterator for Vec<T> {
+    ///     default type Item = T; // <-- `Defaultness::Default`
+    /// }
+    /// ```
+    pub defaultness: Defaultness,
+}
+
+impl<I: Interner> AssociatedTyValue<I> {
+    pub fn is_default(&self) -> bool {
+        self.defaultness == Defaultness::Default
+    }
+}
+
+/// Whether an item in an impl can be overridden by a specializing impl.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Defaultness {
+    /// The item was declared `default`, so it can be overridden.
+    Default,
+    /// The item is final.
+    Final,
 }
 
+chalk_ir::copy_fold!(Defaultness);
+chalk_ir::const_visit!(Defaultness);
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, TypeFoldable, TypeVisitable, HasInterner)]
 pub struct AssociatedTyValueBound<I: Interner> {
     /// Type that we normalize to. The X in `type Foo<'a> = X`.
@@ -1,3 +1,6 @@
+use self::budget::{Budget, BudgetExhausted, BudgetTracker};
+use self::proof_tree::{ProofOutcome, ProofTree};
+use self::stats::SolverStats;
 use crate::RustIrDatabase;
 use chalk_derive::HasInterner;
 use chalk_ir::interner::Interner;
@@ -5,6 +8,12 @@ use chalk_ir::*;
 use std::fmt;
 use tracing::debug;
 
+pub mod budget;
+pub mod leak_check;
+pub mod persistent;
+pub mod proof_tree;
+pub mod regions;
+pub mod stats;
 pub mod truncate;
 
 /// A (possible) solution for a proposed goal.
@@ -111,6 +120,27 @@ impl<I: Interner> Solution<I> {
         }
     }
 
+    /// Whether this solution, found with high-priority clauses, overrides
+    /// `lower`, found with low-priority clauses for the same `goal`. It does
+    /// *if* both are for the same inputs -- we don't want a more specific
+    /// high-priority solution overriding a general low-priority one.
+    /// Currently inputs only matter for projections; in a goal like
+    /// `AliasEq(<?0 as Trait>::Type = ?1)`, ?0 is the input.
+    pub fn overrides(&self, interner: I, goal: &DomainGoal<I>, lower: &Solution<I>) -> bool {
+        self.inputs(interner, goal) == lower.inputs(interner, goal)
+    }
+
+    /// The inputs of `goal` (see `DomainGoal::inputs`), with this solution
+    /// applied.
+    fn inputs(&self, interner: I, goal: &DomainGoal<I>) -> Vec<GenericArg<I>> {
+        if let Some(subst) = self.constrained_subst(interner) {
+            let subst_goal = subst.value.subst.apply(goal.clone(), interner);
+            subst_goal.inputs(interner)
+        } else {
+            goal.inputs(interner)
+        }
+    }
+
     /// View this solution purely in terms of type inference guidance
     pub fn into_guidance(self) -> Guidance<I> {
         match self {
@@ -160,6 +190,35 @@ impl<I: Interner> Solution<I> {
         }
     }
 
+    /// Checks the region constraints of a unique solution with
+    /// [`regions::solve_region_constraints`], keeping only those that the
+    /// caller still has to prove. Ambiguous solutions carry no constraints
+    /// and are returned unchanged. `environment` is the environment of the
+    /// goal that was solved, whose outlives facts hold for every constraint.
+    pub fn solve_regions(
+        self,
+        interner: I,
+        environment: &Environment<I>,
+    ) -> Result<Solution<I>, regions::UnsatisfiableConstraint<I>> {
+        match self {
+            Solution::Unique(Canonical { value, binders }) => {
+                let constraints = regions::solve_region_constraints(
+                    interner,
+                    environment,
+                    value.constraints.as_slice(interner),
+                )?;
+                Ok(Solution::Unique(Canonical {
+                    value: ConstrainedSubst {
+                        subst: value.subst,
+                        constraints: Constraints::from_iter(interner, constraints),
+                    },
+                    binders,
+                }))
+            }
+            Solution::Ambig(guidance) => Ok(Solution::Ambig(guidance)),
+        }
+    }
+
     pub fn is_unique(&self) -> bool {
         matches!(*self, Solution::Unique(..))
     }
@@ -211,7 +270,7 @@ impl<'a, I: Interner> fmt::Display for SolutionDisplay<'a, I> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum SubstitutionResult<S> {
     Definite(S),
     Ambiguous(S),
@@ -306,6 +365,47 @@ where
         should_continue: &dyn std::ops::Fn() -> bool,
     ) -> Option<Solution<I>>;
 
+    /// Like `solve_limited`, but with a `should_continue` that can be shared
+    /// between threads. Solvers that solve subgoals on several threads only
+    /// do so here (and in `solve` and `solve_with_budget`), calling
+    /// `should_continue` from each of them.
+    ///
+    /// By default, this solves with `solve_limited`.
+    fn solve_limited_parallel(
+        &mut self,
+        program: &dyn RustIrDatabase<I>,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+        should_continue: &(dyn std::ops::Fn() -> bool + Sync),
+    ) -> Option<Solution<I>> {
+        self.solve_limited(program, goal, should_continue)
+    }
+
+    /// Attempts to solve the given goal, which must be in canonical form,
+    /// within the limits of `budget`.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(None)` if the goal cannot be proven.
+    /// - `Ok(Some(solution))` if we succeeded in finding *some* answers,
+    ///   although `solution` may reflect ambiguity and unknowns.
+    /// - `Err(exhausted)` if the budget ran out before the goal was solved.
+    ///   Work done so far may still be cached for future attempts, but
+    ///   results cut short by the budget are not.
+    ///
+    /// By default, this solves with `solve_limited_parallel`, counting each call to
+    /// `should_continue` as a step; only the timeout and the step limit
+    /// apply then.
+    fn solve_with_budget(
+        &mut self,
+        program: &dyn RustIrDatabase<I>,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+        budget: &Budget,
+    ) -> Result<Option<Solution<I>>, BudgetExhausted> {
+        let tracker = BudgetTracker::new(budget);
+        let solution = self.solve_limited_parallel(program, goal, &|| tracker.should_continue());
+        tracker.finish(solution)
+    }
+
     /// Attempts to solve the given goal, which must be in canonical
     /// form. Provides multiple solutions to function `f`.  This will do
     /// only as much work towards `goal` as it has to (and that work
@@ -335,6 +435,36 @@ where
         f: &mut dyn FnMut(SubstitutionResult<Canonical<ConstrainedSubst<I>>>, bool) -> bool,
     ) -> bool;
 
+    /// Solves the given goal like [`Solver::solve`], but also records how
+    /// the solution was reached: which program clauses were tried for each
+    /// subgoal and why each of them succeeded, failed, floundered,
+    /// overflowed or was truncated. The solution itself is the outcome of
+    /// the root of the returned tree.
+    ///
+    /// Explanations are computed from scratch, without consulting (or
+    /// populating) the solver's cache, so that every step of the derivation
+    /// is recorded. This makes them considerably more expensive than `solve`.
+    ///
+    /// Solvers that do not record their derivations return a single leaf
+    /// with the solution of `solve`.
+    fn explain(
+        &mut self,
+        program: &dyn RustIrDatabase<I>,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+    ) -> ProofTree<I> {
+        let outcome = match self.solve(program, goal) {
+            Some(solution) => ProofOutcome::Proven(solution),
+            None => ProofOutcome::NoSolution,
+        };
+        ProofTree::leaf(goal.clone(), outcome)
+    }
+
+    /// How much work the solver has done so far. Solvers that keep no
+    /// statistics return all zeros.
+    fn stats(&self) -> SolverStats<I> {
+        SolverStats::default()
+    }
+
     /// A convenience method for when one doesn't need the actual solution,
     /// only whether or not one exists.
     fn has_unique_solution(
@@ -0,0 +1,176 @@
+//! Limits on how much work a solver may do for one goal. See
+//! [`Solver::solve_with_budget`](super::Solver::solve_with_budget).
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// The resources a solver may use to solve a goal. Each limit is optional;
+/// the default budget is unlimited.
+///
+/// Not every limit applies to every solver: tables and strands are only
+/// created by the SLG solver, and only the recursive solver has a cache of
+/// completed goals.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Budget {
+    /// The wall-clock time the solve may take.
+    pub timeout: Option<Duration>,
+
+    /// The number of steps the solver may take. What a step is depends on
+    /// the solver: an attempt to solve a subgoal for the recursive solver,
+    /// and a quantum of work on the root table for the SLG solver.
+    pub max_steps: Option<usize>,
+
+    /// The number of tables the SLG solver may create.
+    pub max_tables: Option<usize>,
+
+    /// The number of strands the SLG solver may have pending at once.
+    pub max_strands: Option<usize>,
+
+    /// The number of goals the recursive solver may have in its cache.
+    pub max_cache_entries: Option<usize>,
+}
+
+impl Budget {
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Budget {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    pub fn with_max_steps(self, max_steps: usize) -> Self {
+        Budget {
+            max_steps: Some(max_steps),
+            ..self
+        }
+    }
+
+    pub fn with_max_tables(self, max_tables: usize) -> Self {
+        Budget {
+            max_tables: Some(max_tables),
+            ..self
+        }
+    }
+
+    pub fn with_max_strands(self, max_strands: usize) -> Self {
+        Budget {
+            max_strands: Some(max_strands),
+            ..self
+        }
+    }
+
+    pub fn with_max_cache_entries(self, max_cache_entries: usize) -> Self {
+        Budget {
+            max_cache_entries: Some(max_cache_entries),
+            ..self
+        }
+    }
+}
+
+/// One of the limits of a [`Budget`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BudgetLimit {
+    Timeout,
+    Steps,
+    Tables,
+    Strands,
+    CacheEntries,
+}
+
+/// The outcome of a solve that ran out of budget before it found a
+/// solution.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BudgetExhausted {
+    /// The limit that was reached.
+    pub limit: BudgetLimit,
+
+    /// The number of steps taken before the solve was stopped.
+    pub steps: usize,
+}
+
+impl fmt::Display for BudgetExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limit = match self.limit {
+            BudgetLimit::Timeout => "timed out",
+            BudgetLimit::Steps => "step limit reached",
+            BudgetLimit::Tables => "table limit reached",
+            BudgetLimit::Strands => "strand limit reached",
+            BudgetLimit::CacheEntries => "cache entry limit reached",
+        };
+        write!(f, "budget exhausted: {} after {} steps", limit, self.steps)
+    }
+}
+
+impl std::error::Error for BudgetExhausted {}
+
+/// Keeps track of the resources used by a solve. Solvers check the budget
+/// from their `should_continue` callbacks; once a limit has been reached,
+/// the tracker keeps reporting it, so that the solve unwinds. The tracker
+/// can be shared between threads, whose steps count against the same
+/// budget.
+#[derive(Debug)]
+pub struct BudgetTracker {
+    budget: Budget,
+    start: Instant,
+    steps: AtomicUsize,
+    exhausted: OnceLock<BudgetLimit>,
+}
+
+impl BudgetTracker {
+    pub fn new(budget: &Budget) -> Self {
+        BudgetTracker {
+            budget: *budget,
+            start: Instant::now(),
+            steps: AtomicUsize::new(0),
+            exhausted: OnceLock::new(),
+        }
+    }
+
+    /// Takes a step, and returns whether the solve may continue.
+    pub fn should_continue(&self) -> bool {
+        if self.exhausted.get().is_some() {
+            return false;
+        }
+        let steps = self.steps.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.budget.max_steps.is_some_and(|max| steps > max) {
+            let _ = self.exhausted.set(BudgetLimit::Steps);
+        } else if self
+            .budget
+            .timeout
+            .is_some_and(|timeout| self.start.elapsed() > timeout)
+        {
+            let _ = self.exhausted.set(BudgetLimit::Timeout);
+        }
+        self.exhausted.get().is_none()
+    }
+
+    /// Checks a resource whose use is measured by the solver (tables,
+    /// strands or cache entries), returning whether the solve may continue.
+    pub fn within(&self, limit: BudgetLimit, used: usize) -> bool {
+        let max = match limit {
+            BudgetLimit::Tables => self.budget.max_tables,
+            BudgetLimit::Strands => self.budget.max_strands,
+            BudgetLimit::CacheEntries => self.budget.max_cache_entries,
+            BudgetLimit::Timeout | BudgetLimit::Steps => None,
+        };
+        if max.is_some_and(|max| used > max) {
+            // Only the first limit to be reached is reported.
+            let _ = self.exhausted.set(limit);
+        }
+        self.exhausted.get().is_none()
+    }
+
+    /// Returns `result`, the outcome of the solve, unless the budget was
+    /// exhausted along the way (in which case the result is incomplete).
+    pub fn finish<T>(&self, result: T) -> Result<T, BudgetExhausted> {
+        match self.exhausted.get() {
+            None => Ok(result),
+            Some(&limit) => Err(BudgetExhausted {
+                limit,
+                steps: self.steps.load(Ordering::Relaxed),
+            }),
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! The placeholder leak check. See [`leak_check`].
+//!
+//! Solving `forall<'a> { G }` replaces `'a` with a placeholder in a fresh
+//! universe, which names no lifetime in particular. Region constraints
+//! mentioning the placeholder are returned like any other, but one that
+//! makes the placeholder outlive another placeholder, or a region variable
+//! of an outer universe (one that cannot name the placeholder), can only
+//! hold if *every* lifetime outlives that region; one that makes another
+//! placeholder outlive it, only if that placeholder outlives every lifetime.
+//! Like rustc, the leak check does not accept such solutions: the goal
+//! `for<'a> fn(&'a u8): Trait` is not proven by `impl<'b> Trait for
+//! fn(&'b u8)`, since `'b` would have to be equal to every `'a`.
+//!
+//! Constraints such as `'!1_0: 'static` or `!1_0: 'a`, on the other hand,
+//! are left to the region solver (see [`Solution::solve_regions`]).
+//!
+//! The solvers run the leak check on the answer of each goal they solve,
+//! as the universes of its `forall`s are closed: the recursive solver when
+//! fulfilling the goal, and the SLG solver when a strand yields an answer.
+//! An answer that fails it is dropped, as if the goal had no solution, and
+//! the leak is recorded in the proof tree (see `ProofOutcome::PlaceholderLeak`).
+//!
+//! [`Solution::solve_regions`]: super::Solution::solve_regions
+
+use super::regions::{in_goal_environment, OutlivesFacts};
+use chalk_ir::interner::Interner;
+use chalk_ir::*;
+use std::fmt;
+use tracing::debug;
+
+/// A region constraint of an answer that relates a placeholder to another
+/// placeholder, or requires it to outlive a region from an outer universe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlaceholderLeak<I: Interner> {
+    /// The constraint, as found in the answer.
+    pub constraint: InEnvironment<Constraint<I>>,
+
+    /// The placeholder lifetime that leaks.
+    pub placeholder: Lifetime<I>,
+
+    /// The universe of the placeholder.
+    pub placeholder_universe: UniverseIndex,
+
+    /// The region the placeholder is related to.
+    pub region: Lifetime<I>,
+
+    /// The universe of `region`.
+    pub region_universe: UniverseIndex,
+
+    /// Whether the placeholder would have to outlive `region`, rather than
+    /// be outlived by it.
+    pub outlives_region: bool,
+}
+
+impl<I: Interner> fmt::Display for PlaceholderLeak<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "placeholder `{:?}` from universe {:?} would have to {} `{:?}` \
+             from universe {:?}, in region constraint `{:?}`",
+            self.placeholder,
+            self.placeholder_universe,
+            if self.outlives_region {
+                "outlive"
+            } else {
+                "be outlived by"
+            },
+            self.region,
+            self.region_universe,
+            self.constraint.goal
+        )
+    }
+}
+
+impl<I: Interner> std::error::Error for PlaceholderLeak<I> {}
+
+/// Checks that none of the region `constraints` of an answer relates a
+/// placeholder to another placeholder, or makes it outlive a region
+/// variable from an outer universe. `binders` are the canonical variables
+/// of the answer. Constraints that follow from the outlives facts of their
+/// environment, or of `environment` (that of the goal the answer was found
+/// for), are not considered.
+///
+/// Only placeholders of universes created while solving the goal are
+/// checked: those up to `goal_universe`, the maximum universe of the goal,
+/// come from `forall`s around it and are checked where those are closed.
+/// The placeholders of the outermost `forall`s of a goal peeled for the
+/// solvers (see `GoalExt::into_peeled_goal`) stand for the parameters of an
+/// item, like the free regions of a function body in rustc, and are never
+/// checked.
+pub fn leak_check<I: Interner>(
+    interner: I,
+    environment: &Environment<I>,
+    goal_universe: UniverseIndex,
+    binders: &CanonicalVarKinds<I>,
+    constraints: &Constraints<I>,
+) -> Result<(), PlaceholderLeak<I>> {
+    let new_placeholder = |lifetime: &Lifetime<I>| match lifetime.data(interner) {
+        LifetimeData::Placeholder(p) if p.ui > goal_universe => Some(p.ui),
+        _ => None,
+    };
+    for constraint in constraints.iter(interner) {
+        let environment = in_goal_environment(interner, environment, &constraint.environment);
+        let facts = OutlivesFacts::new(interner, &environment);
+        for simplified in facts.simplify(&constraint.goal) {
+            let (a, b) = match simplified.unwrap_or_else(|c| c) {
+                Constraint::LifetimeOutlives(a, b) => (a, b),
+                Constraint::TypeOutlives(..) => continue,
+            };
+            if a == b {
+                continue;
+            }
+            let leak = if let Some(placeholder_universe) = new_placeholder(&a) {
+                let region_universe = match b.data(interner) {
+                    // Another placeholder names some other lifetime.
+                    LifetimeData::Placeholder(p) => p.ui,
+                    // A region variable that can name the placeholder may be it.
+                    LifetimeData::BoundVar(bound_var) => match bound_var.index_if_innermost() {
+                        Some(index) => {
+                            let ui = *binders.at(interner, index).skip_kind();
+                            if ui.can_see(placeholder_universe) {
+                                continue;
+                            }
+                            ui
+                        }
+                        None => continue,
+                    },
+                    _ => continue,
+                };
+                PlaceholderLeak {
+                    constraint: constraint.clone(),
+                    placeholder: a,
+                    placeholder_universe,
+                    region: b,
+                    region_universe,
+                    outlives_region: true,
+                }
+            } else if let Some(placeholder_universe) = new_placeholder(&b) {
+                // Any region may outlive the placeholder (`'static` does),
+                // but another placeholder is not known to.
+                let region_universe = match a.data(interner) {
+                    LifetimeData::Placeholder(p) => p.ui,
+                    _ => continue,
+                };
+                PlaceholderLeak {
+                    constraint: constraint.clone(),
+                    placeholder: b,
+                    placeholder_universe,
+                    region: a,
+                    region_universe,
+                    outlives_region: false,
+                }
+            } else {
+                continue;
+            };
+            debug!("leak check failed: {}", leak);
+            return Err(leak);
+        }
+    }
+    Ok(())
+}
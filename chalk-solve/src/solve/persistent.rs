@@ -0,0 +1,303 @@
+//! A cache of solver results that is kept on disk between runs. See
+//! [`PersistentCache`].
+//!
+//! Only results that can be rebuilt from their goal alone are kept (see
+//! [`PersistedResult`]): no solution, a solution that holds without
+//! constraining the variables of the goal, or no guidance at all. Keeping
+//! the others would mean writing out their substitution and region
+//! constraints, and so types, lifetimes and environments, which an
+//! interner gives no way to do. Those results are recomputed by each run.
+
+use crate::solve::{Guidance, Solution};
+use chalk_ir::interner::Interner;
+use chalk_ir::*;
+use rustc_hash::{FxHashMap, FxHasher};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+/// The first line of a cache file, followed by the fingerprint. Changing
+/// the format (or the hashes) changes this, so old files are not used. The
+/// version of chalk is part of it, as the `Hash` impls of its types, and so
+/// the keys of goals, may change from one version to the next.
+const HEADER: &str = concat!("chalk-cache v2 ", env!("CARGO_PKG_VERSION"));
+
+/// Hashes `value` the same way in every run: unlike the standard library's
+/// default hasher, `FxHasher` is not randomly seeded.
+///
+/// This hashes the contents of `value` only as far as its `Hash` impl does.
+/// Values of an interner whose interned data hash by address (rather than
+/// by content, like `Arc`s do) hash differently in each run, and so must
+/// not be given to `stable_hash` or kept in a [`PersistentCache`].
+pub fn stable_hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = FxHasher::default();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The results of goals that are persisted: those that can be rebuilt from
+/// the goal alone. The results of other goals (those with a substitution,
+/// region constraints or guidance) are not persisted, and are recomputed by
+/// each run (see the module documentation).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PersistedResult {
+    /// The goal cannot be proven.
+    NoSolution,
+
+    /// The goal holds, without constraining its variables and without
+    /// region constraints.
+    Proven,
+
+    /// The goal may hold, but nothing is known about its variables.
+    Ambiguous,
+}
+
+impl PersistedResult {
+    fn name(self) -> &'static str {
+        match self {
+            PersistedResult::NoSolution => "no-solution",
+            PersistedResult::Proven => "proven",
+            PersistedResult::Ambiguous => "ambiguous",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [
+            PersistedResult::NoSolution,
+            PersistedResult::Proven,
+            PersistedResult::Ambiguous,
+        ]
+        .iter()
+        .copied()
+        .find(|result| result.name() == name)
+    }
+
+    /// The persisted form of `result`, the result of the recursive solver
+    /// for `goal`, if it has one.
+    pub fn from_solution<I: Interner>(
+        interner: I,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+        result: &Fallible<Solution<I>>,
+    ) -> Option<Self> {
+        let persisted = match result {
+            Err(NoSolution) => PersistedResult::NoSolution,
+            Ok(Solution::Unique(_)) => PersistedResult::Proven,
+            Ok(Solution::Ambig(Guidance::Unknown)) => PersistedResult::Ambiguous,
+            Ok(Solution::Ambig(_)) => return None,
+        };
+        // Only keep the results that are rebuilt as they are.
+        if persisted.to_solution(interner, goal) == *result {
+            Some(persisted)
+        } else {
+            None
+        }
+    }
+
+    /// The result of the recursive solver for `goal` that this was
+    /// persisted from.
+    pub fn to_solution<I: Interner>(
+        self,
+        interner: I,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+    ) -> Fallible<Solution<I>> {
+        match self {
+            PersistedResult::NoSolution => Err(NoSolution),
+            PersistedResult::Proven => Ok(Solution::Unique(Canonical {
+                binders: goal.canonical.binders.clone(),
+                value: ConstrainedSubst {
+                    subst: goal.trivial_substitution(interner),
+                    constraints: Constraints::empty(interner),
+                },
+            })),
+            PersistedResult::Ambiguous => Ok(Solution::Ambig(Guidance::Unknown)),
+        }
+    }
+}
+
+/// Solver results kept in a file, so that later runs over the same program
+/// can reuse them. Results are keyed by a stable hash of their goal (see
+/// [`stable_hash`], whose requirements on the interner apply), and the file
+/// records a fingerprint of the program and solver settings they were
+/// computed with: a file with another fingerprint is ignored, and
+/// overwritten when the cache is saved. The fingerprint must cover every
+/// setting that affects the results, such as the overflow depth and maximum
+/// size of the solver.
+///
+/// The solvers consult the cache through their in-memory caches (see
+/// `chalk_recursive::Cache::with_store` and
+/// `chalk_engine::cache::AnswerCache::with_store`). The cache is saved by
+/// [`save`](PersistentCache::save), and when it is dropped.
+#[derive(Debug)]
+pub struct PersistentCache {
+    path: PathBuf,
+    fingerprint: u64,
+    data: Mutex<PersistentData>,
+}
+
+#[derive(Debug, Default)]
+struct PersistentData {
+    results: FxHashMap<(u64, u64), PersistedResult>,
+
+    /// True if there are results that are not saved yet.
+    dirty: bool,
+}
+
+impl PersistentCache {
+    /// Opens the cache stored at `path`, if any, for results computed with
+    /// the given `fingerprint`. An unreadable file, or one with another
+    /// fingerprint, gives an empty cache.
+    pub fn open(path: impl Into<PathBuf>, fingerprint: u64) -> Self {
+        let path = path.into();
+        let results = match fs::read_to_string(&path) {
+            Ok(text) => parse(&text, fingerprint).unwrap_or_else(|| {
+                debug!(?path, "ignoring cache file for another fingerprint");
+                FxHashMap::default()
+            }),
+            Err(e) => {
+                if e.kind() != io::ErrorKind::NotFound {
+                    warn!(?path, "cannot read cache file: {}", e);
+                }
+                FxHashMap::default()
+            }
+        };
+        PersistentCache {
+            path,
+            fingerprint,
+            data: Mutex::new(PersistentData {
+                results,
+                dirty: false,
+            }),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// The number of results in the cache.
+    pub fn len(&self) -> usize {
+        self.data.lock().unwrap().results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The result recorded for `goal`, if any.
+    pub fn get<I: Interner>(
+        &self,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+    ) -> Option<PersistedResult> {
+        let key = goal_key(goal);
+        self.data.lock().unwrap().results.get(&key).copied()
+    }
+
+    /// Records the result of `goal`.
+    pub fn insert<I: Interner>(
+        &self,
+        goal: &UCanonical<InEnvironment<Goal<I>>>,
+        result: PersistedResult,
+    ) {
+        let key = goal_key(goal);
+        let mut data = self.data.lock().unwrap();
+        if data.results.insert(key, result) != Some(result) {
+            data.dirty = true;
+        }
+    }
+
+    /// Writes the results to the file, if any were added since it was
+    /// read or last saved.
+    pub fn save(&self) -> io::Result<()> {
+        let mut data = self.data.lock().unwrap();
+        if !data.dirty {
+            return Ok(());
+        }
+        let mut results: Vec<_> = data.results.iter().collect();
+        results.sort_by_key(|(key, _)| **key);
+        let mut text = format!("{} {:016x}\n", HEADER, self.fingerprint);
+        for ((high, low), result) in results {
+            text += &format!("{:016x}{:016x} {}\n", high, low, result.name());
+        }
+        // Write the whole file at once, so that a concurrent run never
+        // reads half of it. Each save writes a file of its own, so that
+        // concurrent saves do not write to the same one.
+        static SAVES: AtomicUsize = AtomicUsize::new(0);
+        let temporary = self.path.with_extension(format!(
+            "{}-{}.tmp",
+            std::process::id(),
+            SAVES.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temporary, text)?;
+        fs::rename(&temporary, &self.path)?;
+        data.dirty = false;
+        Ok(())
+    }
+}
+
+impl Drop for PersistentCache {
+    fn drop(&mut self) {
+        if let Err(e) = self.save() {
+            warn!(path = ?self.path, "cannot save cache file: {}", e);
+        }
+    }
+}
+
+/// The key of a goal: its [`stable_hash`], and a hash of the same data by
+/// another algorithm, so that two goals whose `FxHasher` hashes collide are
+/// still unlikely to share a key.
+fn goal_key<I: Interner>(goal: &UCanonical<InEnvironment<Goal<I>>>) -> (u64, u64) {
+    let mut hasher = FnvHasher::default();
+    goal.hash(&mut hasher);
+    (stable_hash(goal), hasher.finish())
+}
+
+/// The 64-bit FNV-1a hash, which (like `FxHasher`) is not randomly seeded,
+/// but shares nothing else with it.
+struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Reads the results from a cache file, or returns `None` if it was written
+/// for another fingerprint or is not a cache file.
+fn parse(text: &str, fingerprint: u64) -> Option<FxHashMap<(u64, u64), PersistedResult>> {
+    let mut lines = text.lines();
+    if lines.next()? != format!("{} {:016x}", HEADER, fingerprint) {
+        return None;
+    }
+    lines
+        .map(|line| {
+            let (key, result) = line.split_once(' ')?;
+            if key.len() != 32 {
+                return None;
+            }
+            let high = u64::from_str_radix(&key[..16], 16).ok()?;
+            let low = u64::from_str_radix(&key[16..], 16).ok()?;
+            Some(((high, low), PersistedResult::from_name(result)?))
+        })
+        .collect()
+}
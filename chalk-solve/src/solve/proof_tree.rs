@@ -0,0 +1,431 @@
+//! Proof trees: a structured record of how a solver arrived at (or failed
+//! to arrive at) the solution for a goal.
+//!
+//! A proof tree alternates between two kinds of nodes. A [`ProofTree`]
+//! node represents a goal; for domain goals, its children are the
+//! [`ClauseAttempt`]s the solver made with each program clause that could
+//! match, and for other goals (`forall`, `exists`, `if`, ...) its children
+//! are the subgoals the goal was simplified into. Each clause attempt, in
+//! turn, has the subgoals that the clause's conditions produced.
+
+use crate::solve::leak_check::PlaceholderLeak;
+use crate::solve::{Guidance, Solution};
+use chalk_ir::interner::Interner;
+use chalk_ir::{DomainGoal, Goal, GoalData, InEnvironment, ProgramClause, UCanonical};
+use std::fmt;
+
+/// The derivation of a single goal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProofTree<I: Interner> {
+    /// The goal that was being solved.
+    pub goal: UCanonical<InEnvironment<Goal<I>>>,
+
+    /// What the solver concluded about `goal`.
+    pub outcome: ProofOutcome<I>,
+
+    /// For domain goals, the program clauses that were tried, in order.
+    pub clauses: Vec<ClauseAttempt<I>>,
+
+    /// For goals that are not domain goals, the subgoals they were
+    /// simplified into.
+    pub subgoals: Vec<ProofTree<I>>,
+
+    /// True if the derivation of `goal` was not recorded here because it
+    /// already appears elsewhere in the tree (or was answered from a
+    /// cache); `outcome` is still accurate.
+    pub elided: bool,
+}
+
+/// An attempt to prove a goal using a particular program clause.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClauseAttempt<I: Interner> {
+    /// The clause that was tried.
+    pub clause: ProgramClause<I>,
+
+    /// The result of applying this clause.
+    pub outcome: ProofOutcome<I>,
+
+    /// The conditions of the clause, as goals.
+    pub subgoals: Vec<ProofTree<I>>,
+}
+
+/// The result recorded for a goal or clause in a [`ProofTree`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProofOutcome<I: Interner> {
+    /// The goal holds, though possibly only ambiguously.
+    Proven(Solution<I>),
+
+    /// The goal cannot be proven.
+    NoSolution,
+
+    /// The conclusion of the clause does not unify with the goal.
+    DoesNotUnify,
+
+    /// The goal does not have enough type information to be solved, e.g.
+    /// `?T: Sized` or a negative goal with unresolved variables.
+    Floundered,
+
+    /// Solving the goal required nesting deeper than the solver's
+    /// overflow depth.
+    Overflow,
+
+    /// The goal (or one of its answers) exceeded the solver's maximum size,
+    /// so it was treated as ambiguous.
+    Truncated,
+
+    /// The goal depends on itself. The provisional result that was used at
+    /// this point in the cycle is recorded, if there was one.
+    Cycle(Option<Solution<I>>),
+
+    /// The solver stopped pursuing this alternative before it was finished,
+    /// typically because another alternative already settled the goal.
+    Abandoned,
+
+    /// The goal (or clause) was proven, but the solution was rejected by the
+    /// [leak check](super::leak_check::leak_check), so the solver treats it
+    /// as having no solution.
+    PlaceholderLeak(PlaceholderLeak<I>),
+}
+
+impl<I: Interner> ProofTree<I> {
+    /// Creates a childless node.
+    pub fn leaf(goal: UCanonical<InEnvironment<Goal<I>>>, outcome: ProofOutcome<I>) -> Self {
+        ProofTree {
+            goal,
+            outcome,
+            clauses: vec![],
+            subgoals: vec![],
+            elided: false,
+        }
+    }
+
+    /// The solution the solver gives for the goal. Goals that floundered,
+    /// overflowed or were truncated are reported as ambiguous.
+    pub fn solution(&self) -> Option<Solution<I>> {
+        match &self.outcome {
+            ProofOutcome::Proven(solution) => Some(solution.clone()),
+            ProofOutcome::Floundered | ProofOutcome::Overflow | ProofOutcome::Truncated => {
+                Some(Solution::Ambig(Guidance::Unknown))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the goal was not proven with a unique solution.
+    fn failed(&self) -> bool {
+        !matches!(self.solution(), Some(Solution::Unique(_)))
+    }
+
+    /// If the goal failed, follows the failures down the tree and returns
+    /// the failing goals along the way, from this one to the innermost: the
+    /// first failing subgoal is followed, first among the goal's own
+    /// subgoals and then among those of the clauses that were tried.
+    /// Cycles and abandoned goals are not followed, and neither are
+    /// `FromEnv` goals, whose failure only means that an assumption is
+    /// absent. Returns an empty path if the goal did not fail.
+    pub fn failure_path(&self, interner: I) -> Vec<&ProofTree<I>> {
+        if !self.failed() {
+            return vec![];
+        }
+        let is_from_env = |tree: &ProofTree<I>| {
+            matches!(
+                tree.goal.canonical.value.goal.data(interner),
+                GoalData::DomainGoal(DomainGoal::FromEnv(_))
+            )
+        };
+        let mut path = vec![self];
+        let mut tree = self;
+        loop {
+            let clause_subgoals = tree
+                .clauses
+                .iter()
+                .filter(|attempt| attempt.outcome != ProofOutcome::DoesNotUnify)
+                .flat_map(|attempt| &attempt.subgoals);
+            let next = tree.subgoals.iter().chain(clause_subgoals).find(|subgoal| {
+                subgoal.failed()
+                    && !is_from_env(subgoal)
+                    && !matches!(
+                        subgoal.outcome,
+                        ProofOutcome::Cycle(_) | ProofOutcome::Abandoned
+                    )
+            });
+            match next {
+                Some(subgoal) => {
+                    path.push(subgoal);
+                    tree = subgoal;
+                }
+                None => return path,
+            }
+        }
+    }
+
+    /// The last goal of the [`failure_path`](ProofTree::failure_path): the
+    /// innermost failing goal, if the goal failed.
+    pub fn innermost_failure(&self, interner: I) -> Option<&ProofTree<I>> {
+        self.failure_path(interner).pop()
+    }
+
+    pub fn display(&self, interner: I) -> ProofTreeDisplay<'_, I> {
+        ProofTreeDisplay {
+            tree: self,
+            interner,
+        }
+    }
+}
+
+impl<I: Interner> ProofOutcome<I> {
+    pub fn display(&self, interner: I) -> ProofOutcomeDisplay<'_, I> {
+        ProofOutcomeDisplay {
+            outcome: self,
+            interner,
+        }
+    }
+}
+
+pub struct ProofOutcomeDisplay<'a, I: Interner> {
+    outcome: &'a ProofOutcome<I>,
+    interner: I,
+}
+
+impl<I: Interner> fmt::Display for ProofOutcomeDisplay<'_, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.outcome {
+            ProofOutcome::Proven(solution) => write!(f, "{}", solution.display(self.interner)),
+            ProofOutcome::NoSolution => write!(f, "No possible solution"),
+            ProofOutcome::DoesNotUnify => write!(f, "Does not unify"),
+            ProofOutcome::Floundered => write!(f, "Floundered"),
+            ProofOutcome::Overflow => write!(f, "Overflow"),
+            ProofOutcome::Truncated => write!(f, "Truncated"),
+            ProofOutcome::Cycle(None) => write!(f, "Cycle"),
+            ProofOutcome::Cycle(Some(solution)) => {
+                write!(
+                    f,
+                    "Cycle; provisionally {}",
+                    solution.display(self.interner)
+                )
+            }
+            ProofOutcome::Abandoned => write!(f, "Abandoned"),
+            ProofOutcome::PlaceholderLeak(leak) => write!(f, "No possible solution: {}", leak),
+        }
+    }
+}
+
+/// Renders a proof tree as an indented outline, one goal or clause per
+/// line.
+pub struct ProofTreeDisplay<'a, I: Interner> {
+    tree: &'a ProofTree<I>,
+    interner: I,
+}
+
+impl<I: Interner> ProofTreeDisplay<'_, I> {
+    fn fmt_tree(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        tree: &ProofTree<I>,
+        depth: usize,
+    ) -> fmt::Result {
+        let interner = self.interner;
+        let ProofTree {
+            goal,
+            outcome,
+            clauses,
+            subgoals,
+            elided,
+        } = tree;
+        write!(f, "{:indent$}", "", indent = depth * 2)?;
+        let binders = goal.canonical.binders.as_slice(interner);
+        if !binders.is_empty() {
+            write!(f, "for<")?;
+            for (i, pk) in binders.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, "?{}", pk.skip_kind())?;
+            }
+            write!(f, "> ")?;
+        }
+        write!(
+            f,
+            "{:?} -- {}",
+            goal.canonical.value.goal,
+            outcome.display(interner)
+        )?;
+        if *elided {
+            write!(f, " (see above)")?;
+        }
+        writeln!(f)?;
+        for attempt in clauses {
+            write!(f, "{:indent$}", "", indent = (depth + 1) * 2)?;
+            writeln!(
+                f,
+                "clause {:?} -- {}",
+                attempt.clause,
+                attempt.outcome.display(interner)
+            )?;
+            for subgoal in &attempt.subgoals {
+                self.fmt_tree(f, subgoal, depth + 2)?;
+            }
+        }
+        for subgoal in subgoals {
+            self.fmt_tree(f, subgoal, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl<I: Interner> fmt::Display for ProofTreeDisplay<'_, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_tree(f, self.tree, 0)
+    }
+}
+
+/// Incrementally assembles a [`ProofTree`] while a solver walks the goal
+/// tree depth-first. Each `enter_*` call must be paired with the matching
+/// `exit_*` call.
+#[derive(Debug)]
+pub struct ProofTreeBuilder<I: Interner> {
+    stack: Vec<Frame<I>>,
+    root: Option<ProofTree<I>>,
+}
+
+#[derive(Debug)]
+enum Frame<I: Interner> {
+    Goal {
+        tree: ProofTree<I>,
+        /// Set when the solver knows more about the outcome than the final
+        /// solution conveys (e.g., that an ambiguous result was due to
+        /// floundering).
+        outcome_override: Option<ProofOutcome<I>>,
+    },
+    Clause {
+        attempt: ClauseAttempt<I>,
+        /// Like the override of a goal.
+        outcome_override: Option<ProofOutcome<I>>,
+    },
+}
+
+impl<I: Interner> Default for ProofTreeBuilder<I> {
+    fn default() -> Self {
+        ProofTreeBuilder {
+            stack: vec![],
+            root: None,
+        }
+    }
+}
+
+impl<I: Interner> ProofTreeBuilder<I> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts recording the derivation of `goal`.
+    pub fn enter_goal(&mut self, goal: UCanonical<InEnvironment<Goal<I>>>) {
+        self.stack.push(Frame::Goal {
+            tree: ProofTree::leaf(goal, ProofOutcome::NoSolution),
+            outcome_override: None,
+        });
+    }
+
+    /// Finishes the goal started by the innermost `enter_goal`. If
+    /// `override_outcome` was called for this goal, that outcome is recorded
+    /// instead of `outcome`.
+    pub fn exit_goal(&mut self, outcome: ProofOutcome<I>) {
+        match self.stack.pop() {
+            Some(Frame::Goal {
+                mut tree,
+                outcome_override,
+            }) => {
+                tree.outcome = outcome_override.unwrap_or(outcome);
+                self.push_tree(tree);
+            }
+            frame => panic!("exit_goal without matching enter_goal: {:?}", frame),
+        }
+    }
+
+    /// Records a goal whose derivation is not explored further.
+    pub fn leaf(
+        &mut self,
+        goal: UCanonical<InEnvironment<Goal<I>>>,
+        outcome: ProofOutcome<I>,
+        elided: bool,
+    ) {
+        let mut tree = ProofTree::leaf(goal, outcome);
+        tree.elided = elided;
+        self.push_tree(tree);
+    }
+
+    /// Overrides the outcome that will be recorded for the innermost goal or
+    /// clause attempt.
+    pub fn override_outcome(&mut self, outcome: ProofOutcome<I>) {
+        match self.stack.last_mut() {
+            Some(Frame::Goal {
+                outcome_override, ..
+            })
+            | Some(Frame::Clause {
+                outcome_override, ..
+            }) => *outcome_override = Some(outcome),
+            None => panic!("override_outcome outside of a goal"),
+        }
+    }
+
+    /// Discards everything recorded so far for the innermost goal. Used when
+    /// a goal is re-solved, e.g. while iterating to a fixed point.
+    pub fn restart_goal(&mut self) {
+        match self.stack.last_mut() {
+            Some(Frame::Goal {
+                tree,
+                outcome_override,
+            }) => {
+                tree.clauses.clear();
+                tree.subgoals.clear();
+                *outcome_override = None;
+            }
+            frame => panic!("restart_goal outside of a goal: {:?}", frame),
+        }
+    }
+
+    /// Starts recording an attempt to prove the innermost goal with `clause`.
+    pub fn enter_clause(&mut self, clause: ProgramClause<I>) {
+        self.stack.push(Frame::Clause {
+            attempt: ClauseAttempt {
+                clause,
+                outcome: ProofOutcome::NoSolution,
+                subgoals: vec![],
+            },
+            outcome_override: None,
+        });
+    }
+
+    /// Finishes the clause attempt started by the innermost `enter_clause`.
+    /// Like `exit_goal`, records the outcome given to `override_outcome`
+    /// instead of `outcome`, if any.
+    pub fn exit_clause(&mut self, outcome: ProofOutcome<I>) {
+        match self.stack.pop() {
+            Some(Frame::Clause {
+                mut attempt,
+                outcome_override,
+            }) => {
+                attempt.outcome = outcome_override.unwrap_or(outcome);
+                match self.stack.last_mut() {
+                    Some(Frame::Goal { tree, .. }) => tree.clauses.push(attempt),
+                    frame => panic!("clause attempt outside of a goal: {:?}", frame),
+                }
+            }
+            frame => panic!("exit_clause without matching enter_clause: {:?}", frame),
+        }
+    }
+
+    fn push_tree(&mut self, tree: ProofTree<I>) {
+        match self.stack.last_mut() {
+            Some(Frame::Goal { tree: parent, .. }) => parent.subgoals.push(tree),
+            Some(Frame::Clause { attempt, .. }) => attempt.subgoals.push(tree),
+            None => self.root = Some(tree),
+        }
+    }
+
+    /// Returns the finished tree for the outermost goal.
+    pub fn finish(self) -> Option<ProofTree<I>> {
+        assert!(self.stack.is_empty(), "unfinished proof tree");
+        self.root
+    }
+}
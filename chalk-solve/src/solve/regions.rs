@@ -0,0 +1,321 @@
+//! A lexical solver for region constraints. See
+//! [`Solution::solve_regions`](super::Solution::solve_regions).
+//!
+//! The solvers prove outlives goals like `T: 'a` by returning them as
+//! constraints of the solution, which are left for the caller to check.
+//! Most of them can be decided without the rest of the caller's region
+//! information though: the constraints only mention placeholders (the
+//! universally quantified lifetimes and types of the goal), `'static` and
+//! the existential variables of the solution. Constraints between
+//! placeholders and `'static` either follow from the outlives facts of the
+//! environment, or can never hold. Only the constraints on existential
+//! variables are left for the caller.
+
+use chalk_ir::fold::shift::Shift;
+use chalk_ir::interner::Interner;
+use chalk_ir::visit::{TypeSuperVisitable, TypeVisitable, TypeVisitor};
+use chalk_ir::*;
+use std::fmt;
+use std::ops::ControlFlow;
+
+/// A region constraint that can never hold, whatever the values of the
+/// existential variables.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsatisfiableConstraint<I: Interner> {
+    /// The constraint, as found in the solution.
+    pub constraint: InEnvironment<Constraint<I>>,
+
+    /// The part of the constraint that does not hold: the constraint
+    /// itself, or for `T: 'a`, the requirement on one of the lifetimes or
+    /// type parameters in `T`.
+    pub cause: Constraint<I>,
+}
+
+impl<I: Interner> fmt::Display for UnsatisfiableConstraint<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "region constraint `{:?}` cannot hold",
+            self.constraint.goal
+        )?;
+        if self.cause != self.constraint.goal {
+            write!(f, ", because `{:?}` does not", self.cause)?;
+        }
+        Ok(())
+    }
+}
+
+impl<I: Interner> std::error::Error for UnsatisfiableConstraint<I> {}
+
+/// Checks `constraints` (those of a solution) against the outlives facts of
+/// their environments, inside `environment`, the environment of the goal
+/// they were found for (see [`in_goal_environment`]). Constraints that
+/// follow from the facts are dropped, and `T: 'a` is broken down into
+/// requirements on the lifetimes and type parameters in `T`. The
+/// constraints that remain only concern existential variables (or types,
+/// like projections, whose outlives relations are not known here), and
+/// none of them is implied by another.
+///
+/// Returns an error for the first constraint that can never hold.
+pub fn solve_region_constraints<I: Interner>(
+    interner: I,
+    environment: &Environment<I>,
+    constraints: &[InEnvironment<Constraint<I>>],
+) -> Result<Vec<InEnvironment<Constraint<I>>>, UnsatisfiableConstraint<I>> {
+    let mut remaining: Vec<InEnvironment<Constraint<I>>> = vec![];
+    for constraint in constraints {
+        let environment = in_goal_environment(interner, environment, &constraint.environment);
+        let facts = OutlivesFacts::new(interner, &environment);
+        for simplified in facts.simplify(&constraint.goal) {
+            let simplified = simplified.map_err(|cause| UnsatisfiableConstraint {
+                constraint: constraint.clone(),
+                cause,
+            })?;
+            let simplified = InEnvironment::new(&environment, simplified);
+            if remaining
+                .iter()
+                .any(|kept| facts.implies(kept, &simplified))
+            {
+                continue;
+            }
+            remaining.retain(|kept| !facts.implies(&simplified, kept));
+            remaining.push(simplified);
+        }
+    }
+    Ok(remaining)
+}
+
+/// The environment of a constraint, `environment`, inside the environment of
+/// the goal it was found for. The constraints of program clauses are in the
+/// (usually empty) environment of the clause, but they must hold wherever
+/// the goal does: the solvers use this when they apply a clause to a goal,
+/// so that the outlives facts of the goal's environment are kept with them.
+pub fn in_goal_environment<I: Interner>(
+    interner: I,
+    goal_environment: &Environment<I>,
+    environment: &Environment<I>,
+) -> Environment<I> {
+    let clauses = environment.clauses.as_slice(interner);
+    let missing: Vec<_> = goal_environment
+        .clauses
+        .iter(interner)
+        .filter(|clause| !clauses.contains(clause))
+        .cloned()
+        .collect();
+    environment.add_clauses(interner, missing)
+}
+
+/// The outlives relations that hold in an environment: its facts `'a: 'b`
+/// and `T: 'a`, and everything outlives `'static`.
+pub(super) struct OutlivesFacts<I: Interner> {
+    interner: I,
+    lifetimes: Vec<(Lifetime<I>, Lifetime<I>)>,
+    types: Vec<(Ty<I>, Lifetime<I>)>,
+}
+
+impl<I: Interner> OutlivesFacts<I> {
+    pub(super) fn new(interner: I, environment: &Environment<I>) -> Self {
+        let mut facts = OutlivesFacts {
+            interner,
+            lifetimes: vec![],
+            types: vec![],
+        };
+        for clause in environment.clauses.iter(interner) {
+            let implication = &clause.data(interner).0;
+            if !implication.binders.is_empty(interner) {
+                continue;
+            }
+            let implication = implication.skip_binders();
+            if !implication.conditions.is_empty(interner) {
+                continue;
+            }
+            match &implication.consequence {
+                DomainGoal::Holds(WhereClause::LifetimeOutlives(LifetimeOutlives { a, b })) => {
+                    facts.lifetimes.push((a.clone(), b.clone()))
+                }
+                DomainGoal::Holds(WhereClause::TypeOutlives(TypeOutlives { ty, lifetime })) => {
+                    facts.types.push((ty.clone(), lifetime.clone()))
+                }
+                _ => {}
+            }
+        }
+        facts
+    }
+
+    /// Whether `a: b` follows from the facts.
+    fn lifetime_outlives(&self, a: &Lifetime<I>, b: &Lifetime<I>) -> bool {
+        let interner = self.interner;
+        let mut reached = vec![a.clone()];
+        let mut i = 0;
+        while let Some(lifetime) = reached.get(i) {
+            if lifetime == b
+                || matches!(
+                    lifetime.data(interner),
+                    LifetimeData::Static | LifetimeData::Error
+                )
+            {
+                return true;
+            }
+            let next: Vec<_> = self
+                .lifetimes
+                .iter()
+                .filter(|(longer, shorter)| longer == lifetime && !reached.contains(shorter))
+                .map(|(_, shorter)| shorter.clone())
+                .collect();
+            reached.extend(next);
+            i += 1;
+        }
+        matches!(b.data(interner), LifetimeData::Error)
+    }
+
+    /// Whether `ty: b` follows from the facts, for a type that cannot be
+    /// broken down any further.
+    fn ty_outlives(&self, ty: &Ty<I>, b: &Lifetime<I>) -> bool {
+        self.types
+            .iter()
+            .any(|(fact_ty, lifetime)| fact_ty == ty && self.lifetime_outlives(lifetime, b))
+    }
+
+    /// Whether the constraint `a` implies `b`, given the facts.
+    fn implies(&self, a: &InEnvironment<Constraint<I>>, b: &InEnvironment<Constraint<I>>) -> bool {
+        if a.environment != b.environment {
+            return false;
+        }
+        match (&a.goal, &b.goal) {
+            (Constraint::LifetimeOutlives(a1, a2), Constraint::LifetimeOutlives(b1, b2)) => {
+                a1 == b1 && self.lifetime_outlives(a2, b2)
+            }
+            (Constraint::TypeOutlives(a1, a2), Constraint::TypeOutlives(b1, b2)) => {
+                a1 == b1 && self.lifetime_outlives(a2, b2)
+            }
+            _ => false,
+        }
+    }
+
+    /// Breaks `constraint` down into the constraints that do not follow
+    /// from the facts; `Err` marks those that cannot hold.
+    pub(super) fn simplify(
+        &self,
+        constraint: &Constraint<I>,
+    ) -> Vec<Result<Constraint<I>, Constraint<I>>> {
+        match constraint {
+            Constraint::LifetimeOutlives(a, b) => {
+                self.simplify_lifetime(a, b).into_iter().collect()
+            }
+            Constraint::TypeOutlives(ty, b) => {
+                let mut components = Components {
+                    interner: self.interner,
+                    lifetimes: vec![],
+                    tys: vec![],
+                };
+                let _ = ty.visit_with(&mut components, DebruijnIndex::INNERMOST);
+                let lifetimes = components
+                    .lifetimes
+                    .into_iter()
+                    .filter_map(|a| self.simplify_lifetime(&a, b));
+                let tys = components.tys.into_iter().filter_map(|ty| {
+                    if self.ty_outlives(&ty, b) {
+                        return None;
+                    }
+                    let decidable = matches!(ty.kind(self.interner), TyKind::Placeholder(_))
+                        && self.is_universal(b);
+                    let constraint = Constraint::TypeOutlives(ty, b.clone());
+                    Some(if decidable {
+                        Err(constraint)
+                    } else {
+                        Ok(constraint)
+                    })
+                });
+                lifetimes.chain(tys).collect()
+            }
+        }
+    }
+
+    fn simplify_lifetime(
+        &self,
+        a: &Lifetime<I>,
+        b: &Lifetime<I>,
+    ) -> Option<Result<Constraint<I>, Constraint<I>>> {
+        if self.lifetime_outlives(a, b) {
+            return None;
+        }
+        let constraint = Constraint::LifetimeOutlives(a.clone(), b.clone());
+        Some(if self.is_universal(a) && self.is_universal(b) {
+            Err(constraint)
+        } else {
+            Ok(constraint)
+        })
+    }
+
+    /// Whether the value of `lifetime` is fixed by the goal (it is not an
+    /// existential variable, nor erased).
+    fn is_universal(&self, lifetime: &Lifetime<I>) -> bool {
+        matches!(
+            lifetime.data(self.interner),
+            LifetimeData::Placeholder(_) | LifetimeData::Static
+        )
+    }
+}
+
+/// Collects the lifetimes that must outlive `'a` for a type to outlive
+/// `'a`, and the types in it that cannot be broken down (placeholders,
+/// variables, aliases and associated or opaque types). Lifetimes bound in
+/// the type itself, like `'b` in `for<'b> fn(&'b u32)`, are left out.
+struct Components<I: Interner> {
+    interner: I,
+    lifetimes: Vec<Lifetime<I>>,
+    tys: Vec<Ty<I>>,
+}
+
+impl<I: Interner> TypeVisitor<I> for Components<I> {
+    type BreakTy = ();
+
+    fn as_dyn(&mut self) -> &mut dyn TypeVisitor<I, BreakTy = Self::BreakTy> {
+        self
+    }
+
+    fn visit_ty(&mut self, ty: &Ty<I>, outer_binder: DebruijnIndex) -> ControlFlow<()> {
+        match ty.kind(self.interner) {
+            TyKind::Placeholder(_)
+            | TyKind::InferenceVar(..)
+            | TyKind::Alias(_)
+            | TyKind::AssociatedType(..)
+            | TyKind::OpaqueType(..) => self.push_ty(ty, outer_binder),
+            TyKind::BoundVar(bound_var) if bound_var.shifted_out_to(outer_binder).is_some() => {
+                self.push_ty(ty, outer_binder)
+            }
+            _ => ty.super_visit_with(self, outer_binder),
+        }
+    }
+
+    fn visit_lifetime(
+        &mut self,
+        lifetime: &Lifetime<I>,
+        outer_binder: DebruijnIndex,
+    ) -> ControlFlow<()> {
+        if let Ok(lifetime) = lifetime.clone().shifted_out_to(self.interner, outer_binder) {
+            if !self.lifetimes.contains(&lifetime) {
+                self.lifetimes.push(lifetime);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn interner(&self) -> I {
+        self.interner
+    }
+}
+
+impl<I: Interner> Components<I> {
+    fn push_ty(&mut self, ty: &Ty<I>, outer_binder: DebruijnIndex) -> ControlFlow<()> {
+        // An alias or variable under a binder of the type (e.g. in
+        // `for<'b> fn(<T as Trait<'b>>::Assoc)`) would have to outlive `'a`
+        // for every choice of the bound lifetimes; leave it to the caller.
+        if let Ok(ty) = ty.clone().shifted_out_to(self.interner, outer_binder) {
+            if !self.tys.contains(&ty) {
+                self.tys.push(ty);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
@@ -0,0 +1,112 @@
+//! Counters of the work done by the solvers. See [`SolverStats`].
+
+use chalk_ir::interner::Interner;
+use chalk_ir::{DomainGoal, Goal, GoalData, TraitId, WhereClause};
+use rustc_hash::FxHashMap;
+use std::cmp::Reverse;
+use std::fmt;
+
+/// How much work a solver has done since it was created, as returned by
+/// [`Solver::stats`](crate::Solver::stats). Each solver fills in the
+/// counters that apply to it, and leaves the others at zero.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SolverStats<I: Interner> {
+    /// SLG: the number of tables created, not counting those whose answers
+    /// were taken from an answer cache.
+    pub tables: usize,
+
+    /// SLG: the number of strands created.
+    pub strands: usize,
+
+    /// SLG: the number of answers found.
+    pub answers: usize,
+
+    /// SLG: the number of times a strand selected a subgoal whose table
+    /// was already on the stack, inductively or coinductively.
+    pub cycles: usize,
+
+    /// SLG: the number of tables that floundered.
+    pub flounders: usize,
+
+    /// Recursive: the number of goals whose result was computed earlier,
+    /// and found in the cache or in the search graph.
+    pub cache_hits: usize,
+
+    /// Recursive: the number of goals that had to be solved.
+    pub cache_misses: usize,
+
+    /// Recursive: the number of times a goal was solved, counting each
+    /// iteration towards the fixed point of a cyclic goal.
+    pub fixed_point_iterations: usize,
+
+    /// Recursive: the number of goals that were not solved because the
+    /// stack reached the overflow depth.
+    pub overflows: usize,
+
+    /// The number of goals `T: Trait` solved for each trait: the SLG tables
+    /// created for them, or the cache misses of the recursive solver.
+    pub goals_by_trait: FxHashMap<TraitId<I>, usize>,
+}
+
+impl<I: Interner> SolverStats<I> {
+    /// Counts `goal` towards the goals of its trait, if it is a trait goal.
+    pub fn record_goal(&mut self, interner: I, goal: &Goal<I>) {
+        if let GoalData::DomainGoal(DomainGoal::Holds(WhereClause::Implemented(trait_ref))) =
+            goal.data(interner)
+        {
+            *self.goals_by_trait.entry(trait_ref.trait_id).or_default() += 1;
+        }
+    }
+}
+
+impl<I: Interner> Default for SolverStats<I> {
+    fn default() -> Self {
+        SolverStats {
+            tables: 0,
+            strands: 0,
+            answers: 0,
+            cycles: 0,
+            flounders: 0,
+            cache_hits: 0,
+            cache_misses: 0,
+            fixed_point_iterations: 0,
+            overflows: 0,
+            goals_by_trait: FxHashMap::default(),
+        }
+    }
+}
+
+/// Shows the counters of the solver that filled them in, and the goals of
+/// each trait, most first.
+impl<I: Interner> fmt::Display for SolverStats<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.tables > 0 {
+            writeln!(
+                f,
+                "{} tables, {} strands, {} answers, {} cycles, {} flounders",
+                self.tables, self.strands, self.answers, self.cycles, self.flounders
+            )?;
+        }
+        if self.cache_hits + self.cache_misses + self.overflows > 0 {
+            writeln!(
+                f,
+                "{} cache hits, {} cache misses, {} fixed-point iterations, {} overflows",
+                self.cache_hits, self.cache_misses, self.fixed_point_iterations, self.overflows
+            )?;
+        }
+        if self.goals_by_trait.is_empty() {
+            return writeln!(f, "no trait goals solved");
+        }
+        let mut traits: Vec<_> = self
+            .goals_by_trait
+            .iter()
+            .map(|(trait_id, goals)| (format!("{:?}", trait_id), goals))
+            .collect();
+        traits.sort_by_key(|(name, &goals)| (Reverse(goals), name.clone()));
+        writeln!(f, "trait goals solved:")?;
+        for (name, goals) in traits {
+            writeln!(f, "  {}: {}", name, goals)?;
+        }
+        Ok(())
+    }
+}
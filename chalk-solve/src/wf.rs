@@ -13,31 +13,89 @@ use chalk_ir::{
 };
 use tracing::debug;
 
-#[derive(Debug)]
+/// A well-formedness error. Each error carries the obligation that could
+/// not be proven, when it is known; it is not known for impls that violate
+/// the built-in rules for a well-known trait (e.g. `Copy` or `Drop`).
+#[derive(Clone, Debug)]
 pub enum WfError<I: Interner> {
-    IllFormedTypeDecl(chalk_ir::AdtId<I>),
-    IllFormedOpaqueTypeDecl(chalk_ir::OpaqueTyId<I>),
-    IllFormedTraitImpl(chalk_ir::TraitId<I>),
+    IllFormedTypeDecl(chalk_ir::AdtId<I>, Option<WfCause<I>>),
+    IllFormedOpaqueTypeDecl(chalk_ir::OpaqueTyId<I>, Option<WfCause<I>>),
+    IllFormedTraitImpl(chalk_ir::TraitId<I>, Option<WfCause<I>>),
+}
+
+/// Why a declaration is not well-formed.
+#[derive(Clone, Debug)]
+pub struct WfCause<I: Interner> {
+    /// The obligation that does not hold, e.g. `WellFormed(Vec<!1_0>)` for
+    /// a field type, or `WellFormed(!1_0: Foo)` for the where clauses of
+    /// the trait of an impl. The variables bound by the declaration are
+    /// instantiated with placeholders, as in the failing subgoal.
+    pub obligation: Goal<I>,
+
+    /// The innermost subgoal that could not be proven in the derivation of
+    /// the obligation (see [`ProofTree::failure_path`]), among those with
+    /// no variables: the others are about types the solver tried to find,
+    /// rather than about the declaration.
+    ///
+    /// [`ProofTree::failure_path`]: crate::solve::proof_tree::ProofTree::failure_path
+    pub failing_subgoal: Option<UCanonical<InEnvironment<Goal<I>>>>,
+
+    /// True if the failing subgoal was ambiguous (e.g. because solving it
+    /// overflowed) rather than refuted, so that it may still hold.
+    pub ambiguous: bool,
+}
+
+impl<I: Interner> WfError<I> {
+    pub fn cause(&self) -> Option<&WfCause<I>> {
+        match self {
+            WfError::IllFormedTypeDecl(_, cause)
+            | WfError::IllFormedOpaqueTypeDecl(_, cause)
+            | WfError::IllFormedTraitImpl(_, cause) => cause.as_ref(),
+        }
+    }
 }
 
 impl<I: Interner> fmt::Display for WfError<I> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            WfError::IllFormedTypeDecl(id) => write!(
+            WfError::IllFormedTypeDecl(id, _) => write!(
                 f,
                 "type declaration `{:?}` does not meet well-formedness requirements",
                 id
-            ),
-            WfError::IllFormedOpaqueTypeDecl(id) => write!(
+            )?,
+            WfError::IllFormedOpaqueTypeDecl(id, _) => write!(
                 f,
                 "opaque type declaration `{:?}` does not meet well-formedness requirements",
                 id
-            ),
-            WfError::IllFormedTraitImpl(id) => write!(
+            )?,
+            WfError::IllFormedTraitImpl(id, _) => write!(
                 f,
                 "trait impl for `{:?}` does not meet well-formedness requirements",
                 id
+            )?,
+        }
+        match self.cause() {
+            Some(cause) => write!(f, ": {}", cause),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<I: Interner> fmt::Display for WfCause<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fails = if self.ambiguous {
+            "may not hold"
+        } else {
+            "cannot be proven"
+        };
+        match &self.failing_subgoal {
+            Some(subgoal) if subgoal.canonical.value.goal != self.obligation => write!(
+                f,
+                "`{:?}` does not hold, because `{:?}` {}",
+                self.obligation, subgoal.canonical.value.goal, fails
             ),
+            _ if self.ambiguous => write!(f, "`{:?}` may not hold", self.obligation),
+            _ => write!(f, "`{:?}` does not hold", self.obligation),
         }
     }
 }
@@ -299,12 +357,15 @@ where
             },
         );
 
-        let wg_goal = wg_goal.into_closed_goal(interner);
         let mut fresh_solver = (self.solver_builder)();
-        let is_legal = fresh_solver.has_unique_solution(self.db, &wg_goal);
+        let is_legal =
+            fresh_solver.has_unique_solution(self.db, &wg_goal.clone().into_closed_goal(interner));
 
         if !is_legal {
-            Err(WfError::IllFormedTypeDecl(adt_id))
+            Err(WfError::IllFormedTypeDecl(
+                adt_id,
+                self.unmet_obligation(&wg_goal),
+            ))
         } else {
             Ok(())
         }
@@ -333,13 +394,16 @@ where
         debug!("WF trait goal: {:?}", impl_goal);
 
         let mut fresh_solver = (self.solver_builder)();
-        let is_legal =
-            fresh_solver.has_unique_solution(self.db, &impl_goal.into_closed_goal(interner));
+        let is_legal = fresh_solver
+            .has_unique_solution(self.db, &impl_goal.clone().into_closed_goal(interner));
 
         if is_legal {
             Ok(())
         } else {
-            Err(WfError::IllFormedTraitImpl(trait_id))
+            Err(WfError::IllFormedTraitImpl(
+                trait_id,
+                self.unmet_obligation(&impl_goal),
+            ))
         }
     }
 
@@ -388,12 +452,16 @@ where
         debug!("WF opaque type goal: {:#?}", goal);
 
         let mut new_solver = (self.solver_builder)();
-        let is_legal = new_solver.has_unique_solution(self.db, &goal.into_closed_goal(interner));
+        let is_legal =
+            new_solver.has_unique_solution(self.db, &goal.clone().into_closed_goal(interner));
 
         if is_legal {
             Ok(())
         } else {
-            Err(WfError::IllFormedOpaqueTypeDecl(opaque_ty_id))
+            Err(WfError::IllFormedOpaqueTypeDecl(
+                opaque_ty_id,
+                self.unmet_obligation(&goal),
+            ))
         }
     }
 
@@ -447,9 +515,103 @@ where
         if is_legal {
             Ok(())
         } else {
-            Err(WfError::IllFormedTraitImpl(impl_datum.trait_id()))
+            Err(WfError::IllFormedTraitImpl(impl_datum.trait_id(), None))
         }
     }
+
+    /// Given the goal for a declaration that is not well-formed, finds the
+    /// first of its obligations that cannot be proven, along with the
+    /// innermost subgoal responsible. Returns `None` if each obligation can
+    /// be proven on its own.
+    fn unmet_obligation(&self, goal: &Goal<I>) -> Option<WfCause<I>> {
+        let interner = self.db.interner();
+        obligations(interner, goal, UniverseIndex::ROOT)
+            .into_iter()
+            .find_map(|(obligation, goal)| {
+                let mut solver = (self.solver_builder)();
+                let tree = solver.explain(self.db, &goal.into_closed_goal(interner));
+                // The first goal of the path is the closed goal for the
+                // obligation, which is not of interest itself.
+                let path = tree.failure_path(interner);
+                let closed_goal = path.first()?;
+                let failure = path
+                    .iter()
+                    .skip(1)
+                    .rev()
+                    .find(|failure| failure.goal.canonical.binders.is_empty(interner));
+                Some(WfCause {
+                    obligation,
+                    failing_subgoal: failure.map(|failure| failure.goal.clone()),
+                    ambiguous: failure.unwrap_or(closed_goal).solution().is_some(),
+                })
+            })
+    }
+}
+
+/// Splits a well-formedness goal of the form `forall<..> { if (..) { G1, .., Gn } }`
+/// into its obligations `Gi`, each paired with the goal `forall<..> { if (..) { Gi } }`
+/// that checks it. The original goal holds iff all of these goals hold.
+///
+/// The variables bound by the `forall`s are instantiated in the `Gi` with
+/// placeholders, in the universes the solver creates for them when proving
+/// the goal in `universe`.
+fn obligations<I: Interner>(
+    interner: I,
+    goal: &Goal<I>,
+    universe: UniverseIndex,
+) -> Vec<(Goal<I>, Goal<I>)> {
+    match goal.data(interner) {
+        GoalData::Quantified(QuantifierKind::ForAll, subgoal) => {
+            // Like `InferenceTable::instantiate_binders_universally`, only
+            // create a universe if there are variables to bind.
+            let universe = if subgoal.binders.is_empty(interner) {
+                universe
+            } else {
+                universe.next()
+            };
+            let placeholders: Vec<GenericArg<I>> = subgoal
+                .binders
+                .iter(interner)
+                .enumerate()
+                .map(|(idx, kind)| {
+                    let placeholder = PlaceholderIndex { ui: universe, idx };
+                    match kind {
+                        VariableKind::Lifetime => placeholder.to_lifetime(interner).cast(interner),
+                        VariableKind::Ty(_) => placeholder.to_ty(interner).cast(interner),
+                        VariableKind::Const(ty) => {
+                            placeholder.to_const(interner, ty.clone()).cast(interner)
+                        }
+                    }
+                })
+                .collect();
+            obligations(interner, subgoal.skip_binders(), universe)
+                .into_iter()
+                .map(|(obligation, goal)| {
+                    let obligation = Binders::new(subgoal.binders.clone(), obligation)
+                        .substitute(interner, &placeholders);
+                    let goal = Binders::new(subgoal.binders.clone(), goal);
+                    (
+                        obligation,
+                        GoalData::Quantified(QuantifierKind::ForAll, goal).intern(interner),
+                    )
+                })
+                .collect()
+        }
+        GoalData::Implies(clauses, subgoal) => obligations(interner, subgoal, universe)
+            .into_iter()
+            .map(|(obligation, goal)| {
+                (
+                    obligation,
+                    GoalData::Implies(clauses.clone(), goal).intern(interner),
+                )
+            })
+            .collect(),
+        GoalData::All(goals) => goals
+            .iter(interner)
+            .flat_map(|goal| obligations(interner, goal, universe))
+            .collect(),
+        _ => vec![(goal.clone(), goal.clone())],
+    }
 }
 
 fn impl_header_wf_goal<I: Interner>(
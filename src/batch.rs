@@ -0,0 +1,232 @@
+//! Batch mode: solves every goal in a file against the loaded program and
+//! reports each result as one line of JSON, e.g.:
+//!
+//! ```text
+//! {"goal":"Vec<u32>: Clone","solver":"SLG { .. }","kind":"Unique","substitution":[],"constraints":[],"elapsed_ms":0.42}
+//! ```
+//!
+//! Ambiguous results also carry the kind of guidance (`Definite`, `Suggested`
+//! or `Unknown`). With `--solve-regions`, the region constraints of each
+//! solution are checked like those of a single goal; a solution whose
+//! constraints cannot hold is reported with the kind `None` and the
+//! `reason`. Goals the solver gave up on are reported with the kind
+//! `BudgetExhausted`, the `limit` that was reached and the `steps` taken.
+//! Goals that fail to parse or lower are reported with the kind `Error` and
+//! an `error` message, and do not stop the batch. The elapsed time covers
+//! solving the goal only.
+
+use std::io::{BufRead, Write};
+use std::time::Instant;
+
+use chalk_integration::interner::ChalkIr;
+use chalk_integration::query::LoweringDatabase;
+use chalk_solve::ext::*;
+use chalk_solve::{Guidance, Solution};
+use serde_json::{json, Map, Value};
+
+use crate::{LoadedProgram, Result};
+
+/// Solves the goals read from `goals`, one per line, writing a JSON record
+/// for each to `out`. Blank lines and lines starting with `//` are skipped.
+/// Returns the number of goals that could not be parsed or lowered; goals
+/// without a solution, or on which the solver gave up, are not counted.
+pub(crate) fn run_batch(
+    prog: &LoadedProgram,
+    goals: impl BufRead,
+    out: &mut dyn Write,
+) -> Result<usize> {
+    let solver = format!("{:?}", prog.db.solver_choice());
+    let mut errors = 0;
+    for line in goals.lines() {
+        let line = line?;
+        let goal = line.trim();
+        if goal.is_empty() || goal.starts_with("//") {
+            continue;
+        }
+
+        let mut record = Map::new();
+        record.insert("goal".into(), goal.into());
+        record.insert("solver".into(), solver.as_str().into());
+        match prog.db.parse_and_lower_goal(goal) {
+            Ok(lowered) => {
+                let peeled_goal = lowered.into_peeled_goal(ChalkIr);
+                let start = Instant::now();
+                let solution = prog.db.solve_with_budget(&peeled_goal, &prog.budget);
+                let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+                record.insert("elapsed_ms".into(), json!(elapsed_ms));
+                match solution.map(|solution| prog.solve_regions(&peeled_goal, solution)) {
+                    Ok(Ok(solution)) => solution_fields(solution, &mut record),
+                    Ok(Err(unsatisfiable)) => {
+                        solution_fields(None, &mut record);
+                        record.insert("reason".into(), unsatisfiable.to_string().into());
+                    }
+                    Err(exhausted) => {
+                        record.insert("kind".into(), "BudgetExhausted".into());
+                        record.insert("limit".into(), format!("{:?}", exhausted.limit).into());
+                        record.insert("steps".into(), exhausted.steps.into());
+                    }
+                }
+            }
+            Err(e) => {
+                errors += 1;
+                record.insert("kind".into(), "Error".into());
+                record.insert("error".into(), e.to_string().into());
+            }
+        }
+        writeln!(out, "{}", Value::Object(record))?;
+    }
+    out.flush()?;
+    Ok(errors)
+}
+
+/// Records the kind of `solution`, and the substitution and constraints it
+/// carries, if any. Values are rendered with their `Debug` formatting, so
+/// canonical variables appear as `^0.N`.
+fn solution_fields(solution: Option<Solution<ChalkIr>>, record: &mut Map<String, Value>) {
+    let (kind, guidance, subst, constraints) = match solution {
+        None => ("None", None, None, None),
+        Some(Solution::Unique(constrained)) => (
+            "Unique",
+            None,
+            Some(constrained.value.subst),
+            Some(constrained.value.constraints),
+        ),
+        Some(Solution::Ambig(Guidance::Definite(subst))) => {
+            ("Ambig", Some("Definite"), Some(subst.value), None)
+        }
+        Some(Solution::Ambig(Guidance::Suggested(subst))) => {
+            ("Ambig", Some("Suggested"), Some(subst.value), None)
+        }
+        Some(Solution::Ambig(Guidance::Unknown)) => ("Ambig", Some("Unknown"), None, None),
+    };
+    let subst: Vec<_> = subst
+        .iter()
+        .flat_map(|subst| subst.iter(ChalkIr))
+        .map(|arg| format!("{:?}", arg))
+        .collect();
+    let constraints: Vec<_> = constraints
+        .iter()
+        .flat_map(|constraints| constraints.iter(ChalkIr))
+        .map(|constraint| format!("{:?}", constraint.goal))
+        .collect();
+
+    record.insert("kind".into(), kind.into());
+    if let Some(guidance) = guidance {
+        record.insert("guidance".into(), guidance.into());
+    }
+    record.insert("substitution".into(), subst.into());
+    record.insert("constraints".into(), constraints.into());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chalk_integration::SolverChoice;
+    use chalk_solve::solve::budget::Budget;
+
+    const PROGRAM: &str = "
+        trait Foo { }
+        struct A { }
+        struct Vec<T> { }
+
+        impl Foo for A { }
+        impl<T> Foo for Vec<T> where T: Foo { }
+    ";
+
+    /// Runs `goals` in batch mode, and returns the number of errors and the
+    /// records written, without their (varying) elapsed time.
+    fn batch(goals: &str, budget: Budget, solve_regions: bool) -> (usize, Vec<String>) {
+        let prog = LoadedProgram::new(
+            PROGRAM.into(),
+            SolverChoice::slg_default(),
+            budget,
+            solve_regions,
+            None,
+        )
+        .unwrap();
+        let mut out = vec![];
+        let errors = prog
+            .db
+            .with_program(|_| run_batch(&prog, goals.as_bytes(), &mut out))
+            .unwrap();
+        let records = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let mut record: Map<String, Value> = serde_json::from_str(line).unwrap();
+                record.remove("elapsed_ms");
+                record.remove("solver");
+                Value::Object(record).to_string()
+            })
+            .collect();
+        (errors, records)
+    }
+
+    #[test]
+    fn records() {
+        let goals = "
+            A: Foo
+
+            // Skipped.
+            exists<T> { Vec<T>: Foo }
+            Vec<u32>: Foo
+        ";
+        let (errors, records) = batch(goals, Budget::default(), false);
+        assert_eq!(errors, 0);
+        assert_eq!(
+            records,
+            [
+                r#"{"constraints":[],"goal":"A: Foo","kind":"Unique","substitution":[]}"#,
+                r#"{"constraints":[],"goal":"exists<T> { Vec<T>: Foo }","guidance":"Unknown","kind":"Ambig","substitution":[]}"#,
+                r#"{"constraints":[],"goal":"Vec<u32>: Foo","kind":"None","substitution":[]}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_do_not_stop_the_batch() {
+        let goals = "
+            A: Foo
+            B: Foo
+            Vec<A>: Foo
+        ";
+        let (errors, records) = batch(goals, Budget::default(), false);
+        assert_eq!(errors, 1);
+        assert_eq!(
+            records,
+            [
+                r#"{"constraints":[],"goal":"A: Foo","kind":"Unique","substitution":[]}"#,
+                r#"{"error":"invalid parameter name `B`","goal":"B: Foo","kind":"Error"}"#,
+                r#"{"constraints":[],"goal":"Vec<A>: Foo","kind":"Unique","substitution":[]}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn solve_regions() {
+        let goals = "
+            forall<'a> { 'a: 'a }
+            forall<'a, 'b> { 'a: 'b }
+        ";
+        let (errors, records) = batch(goals, Budget::default(), true);
+        assert_eq!(errors, 0);
+        assert_eq!(
+            records,
+            [
+                r#"{"constraints":[],"goal":"forall<'a> { 'a: 'a }","kind":"Unique","substitution":[]}"#,
+                r#"{"constraints":[],"goal":"forall<'a, 'b> { 'a: 'b }","kind":"None","reason":"region constraint `'!1_0: '!1_1` cannot hold","substitution":[]}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn exhausted_budget() {
+        let budget = Budget::default().with_max_tables(1);
+        let (errors, records) = batch("Vec<Vec<A>>: Foo", budget, false);
+        assert_eq!(errors, 0);
+        assert_eq!(
+            records,
+            [r#"{"goal":"Vec<Vec<A>>: Foo","kind":"BudgetExhausted","limit":"Tables","steps":0}"#]
+        );
+    }
+}
@@ -1,18 +1,27 @@
 #[macro_use]
 extern crate serde_derive;
 
-use std::fs::File;
-use std::io::Read;
+mod batch;
+
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::sync::Arc;
+use std::time::Duration;
 
 use chalk_integration::db::ChalkDatabase;
+use chalk_integration::edit::ItemEdit;
 use chalk_integration::interner::ChalkIr;
 use chalk_integration::lowering::*;
 use chalk_integration::query::LoweringDatabase;
 use chalk_integration::SolverChoice;
+use chalk_ir::{Goal, InEnvironment, UCanonical};
 use chalk_solve::ext::*;
 use chalk_solve::logging;
-use chalk_solve::RustIrDatabase;
+use chalk_solve::solve::budget::Budget;
+use chalk_solve::solve::regions::UnsatisfiableConstraint;
+use chalk_solve::{RustIrDatabase, Solution};
 use docopt::Docopt;
 use rustyline::error::ReadlineError;
 
@@ -23,15 +32,36 @@ chalk repl
 
 Usage:
   chalk [options]
+  chalk --batch=PATH [options]
   chalk (-h | --help)
 
 Options:
   --help              Show this screen.
   --program=PATH      Specifies the path to the `.chalk` file containing traits/impls.
   --goal=GOAL         Specifies a goal to evaluate (may be given more than once).
+  --batch=PATH        Evaluates the goals in the given file, one per line, printing
+                      the result of each as a line of JSON. Exits with an error if
+                      a goal could not be parsed or lowered. Cannot be used with
+                      `--compare` or `--multiple`.
   --overflow-depth=N  Specifies the overflow depth [default: 10].
   --multiple          Output multiple answers instead of ambiguous solution.
   --solver=S          Specifies the solver to use. `slg` or `recursive`. Default is SLG.
+  --compare           Solve each goal with every solver and report whether they agree.
+  --timeout=MS        Give up on a goal after MS milliseconds.
+  --max-steps=N       Give up on a goal after N solver steps.
+  --max-tables=N      Give up on a goal once the SLG solver has created N tables.
+  --max-strands=N     Give up on a goal once the SLG solver has N pending strands.
+  --max-cache-entries=N
+                      Give up on a goal once the recursive solver has cached N goals.
+  --parallel          Have the recursive solver solve independent subgoals on several
+                      threads. Requires chalk to be built with the `parallel` feature.
+  --solve-regions     Check the region constraints of each solution, keeping only
+                      those on its inference variables.
+  --cache-dir=DIR     Keep the results of the solver in DIR, so that later runs over
+                      the same program can reuse them. Only results that do not
+                      constrain the variables of their goal are kept.
+  --stats             Print how much work the solver did on stderr, once the goals
+                      given by `--goal` or `--batch` are solved.
 ";
 
 /// This struct represents the various command line options available.
@@ -40,24 +70,76 @@ struct Args {
     flag_program: Option<String>,
     flag_solver: Option<String>,
     flag_goal: Vec<String>,
+    flag_batch: Option<String>,
     flag_overflow_depth: usize,
     flag_multiple: bool,
+    flag_compare: bool,
+    flag_timeout: Option<u64>,
+    flag_max_steps: Option<usize>,
+    flag_max_tables: Option<usize>,
+    flag_max_strands: Option<usize>,
+    flag_max_cache_entries: Option<usize>,
+    flag_parallel: bool,
+    flag_solve_regions: bool,
+    flag_cache_dir: Option<String>,
+    flag_stats: bool,
 }
 
 /// A loaded and parsed program.
 struct LoadedProgram {
-    text: String,
     db: ChalkDatabase,
+
+    /// The budget for solving each goal. Not used when asking for multiple
+    /// answers.
+    budget: Budget,
+
+    /// Whether to check the region constraints of solutions; see
+    /// [`Solution::solve_regions`].
+    solve_regions: bool,
 }
 
 impl LoadedProgram {
-    /// Creates a new Program struct, given a `.chalk` file as a String and
-    /// a [`SolverChoice`].
+    /// Creates a new Program struct, given a `.chalk` file as a String, a
+    /// [`SolverChoice`], the [`Budget`] for each goal, whether to check
+    /// region constraints and the directory to keep solver results in.
     ///
     /// [`SolverChoice`]: struct.solve.SolverChoice.html
-    fn new(text: String, solver_choice: SolverChoice) -> Result<LoadedProgram> {
-        let db = ChalkDatabase::with(&text, solver_choice);
-        Ok(LoadedProgram { text, db })
+    fn new(
+        text: String,
+        solver_choice: SolverChoice,
+        budget: Budget,
+        solve_regions: bool,
+        cache_dir: Option<Arc<PathBuf>>,
+    ) -> Result<LoadedProgram> {
+        let mut db = ChalkDatabase::with(&text, solver_choice);
+        db.set_persistent_cache_dir(cache_dir);
+        Ok(LoadedProgram {
+            db,
+            budget,
+            solve_regions,
+        })
+    }
+
+    /// Checks that the program is valid. Errors are annotated with the
+    /// parts of the program text they refer to.
+    fn check(&self) -> Result<()> {
+        match self.db.checked_program() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.render(&self.db.source_files()).into()),
+        }
+    }
+
+    /// Applies `edit` to the program, if the program is still valid
+    /// afterwards. Otherwise the edit is undone, and the error returned.
+    /// The whole program is checked again, not only the edited item.
+    fn edit(&mut self, edit: ItemEdit<'_>) -> Result<()> {
+        let text = self.db.edit_program(edit)?;
+        if let Err(e) = self.check() {
+            self.db.set_program_text(text);
+            self.db.load_included_files();
+            return Err(format!("{}\nthe edit was undone", e).into());
+        }
+        Ok(())
     }
 
     /// Parse a goal and attempt to solve it, using the specified solver.
@@ -99,13 +181,56 @@ impl LoadedProgram {
                 println!("No more solutions");
             }
         } else {
-            match self.db.solve(&peeled_goal) {
-                Some(v) => println!("{}\n", v.display(ChalkIr)),
-                None => println!("No possible solution.\n"),
+            let solution = self.db.solve_with_budget(&peeled_goal, &self.budget)?;
+            match self.solve_regions(&peeled_goal, solution) {
+                Ok(Some(v)) => println!("{}\n", v.display(ChalkIr)),
+                Ok(None) => println!("No possible solution.\n"),
+                Err(e) => println!("No possible solution: {}.\n", e),
             }
         }
         Ok(())
     }
+
+    /// Checks the region constraints of `solution`, the solution of
+    /// `peeled_goal`, if asked to when the program was loaded. Fails if
+    /// they cannot hold.
+    fn solve_regions(
+        &self,
+        peeled_goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+        solution: Option<Solution<ChalkIr>>,
+    ) -> std::result::Result<Option<Solution<ChalkIr>>, UnsatisfiableConstraint<ChalkIr>> {
+        let environment = &peeled_goal.canonical.value.environment;
+        match solution {
+            Some(v) if self.solve_regions => v.solve_regions(ChalkIr, environment).map(Some),
+            v => Ok(v),
+        }
+    }
+
+    /// Parse a goal, solve it with each of `solver_choices` and print the
+    /// solutions. Returns whether the solvers disagree.
+    fn compare(&self, text: &str, solver_choices: &[SolverChoice]) -> Result<bool> {
+        let goal = self.db.parse_and_lower_goal(text)?;
+        let peeled_goal = goal.into_peeled_goal(self.db.interner());
+        let comparison = self.db.compare_solvers(text, &peeled_goal, solver_choices);
+        print!("{}", comparison);
+        let diverges = comparison.diverges();
+        if diverges {
+            println!("The solvers disagree.\n");
+        } else {
+            println!("The solvers agree.\n");
+        }
+        Ok(diverges)
+    }
+
+    /// Parse a goal and print the proof tree showing how the solver
+    /// arrived at its solution.
+    fn explain(&self, text: &str) -> Result<()> {
+        let goal = self.db.parse_and_lower_goal(text)?;
+        let peeled_goal = goal.into_peeled_goal(self.db.interner());
+        let tree = self.db.explain(&peeled_goal);
+        println!("{}", tree.display(ChalkIr));
+        Ok(())
+    }
 }
 
 fn run() -> Result<()> {
@@ -119,6 +244,14 @@ fn run() -> Result<()> {
         eprintln!("error: overflow depth must be at least 1");
         exit(1);
     }
+    if args.flag_parallel && !cfg!(feature = "parallel") {
+        eprintln!("error: `--parallel` requires chalk to be built with the `parallel` feature");
+        exit(1);
+    }
+    if args.flag_batch.is_some() && (args.flag_compare || args.flag_multiple) {
+        eprintln!("error: `--compare` and `--multiple` cannot be used with `--batch`");
+        exit(1);
+    }
 
     // Load the .chalk file, if given.
     let mut prog = None;
@@ -132,7 +265,31 @@ fn run() -> Result<()> {
         }
     }
 
-    if args.flag_goal.is_empty() {
+    if let Some(batch) = &args.flag_batch {
+        let prog =
+            prog.ok_or("error: cannot eval without a program; use `--program` to specify one.")?;
+        if let Err(e) = prog.check() {
+            eprintln!("error: {}", e);
+            exit(1);
+        }
+
+        // Evaluate every goal, reporting errors in the output rather than
+        // stopping at the first one.
+        let errors = prog.db.with_program(|_| -> Result<usize> {
+            let goals = BufReader::new(File::open(batch)?);
+            let errors = batch::run_batch(&prog, goals, &mut std::io::stdout().lock())?;
+            if args.flag_stats {
+                eprint!("{}", prog.db.solver_stats());
+            }
+            Ok(errors)
+        })?;
+        if errors > 0 {
+            // Save the results of the solver first.
+            drop(prog);
+            exit(1);
+        }
+        Ok(())
+    } else if args.flag_goal.is_empty() {
         // The user specified no goal. Enter interactive mode.
         readline_loop(&mut rustyline::Editor::new()?, "?- ", |rl, line| {
             if let Err(e) = process(args, line, rl, &mut prog) {
@@ -145,18 +302,36 @@ fn run() -> Result<()> {
         // happens.
         let prog =
             prog.ok_or("error: cannot eval without a program; use `--program` to specify one.")?;
+        if let Err(e) = prog.check() {
+            eprintln!("error: {}", e);
+            exit(1);
+        }
 
         // Evaluate the goal(s). If any goal returns an error, print the error
         // and exit.
-        prog.db.with_program(|_| -> Result<()> {
+        let result = prog.db.with_program(|_| -> Result<bool> {
+            let mut diverged = false;
             for g in &args.flag_goal {
-                if let Err(e) = prog.goal(None, g, args.flag_multiple) {
-                    eprintln!("error: {}", e);
-                    exit(1);
+                if args.flag_compare {
+                    diverged |= prog.compare(g, &args.solver_choices())?;
+                } else {
+                    prog.goal(None, g, args.flag_multiple)?;
                 }
             }
-            Ok(())
-        })?;
+            if args.flag_stats {
+                eprint!("{}", prog.db.solver_stats());
+            }
+            Ok(diverged)
+        });
+        let failed = result.unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            true
+        });
+        if failed {
+            // Save the results of the solver first.
+            drop(prog);
+            exit(1);
+        }
 
         Ok(())
     }
@@ -210,16 +385,32 @@ fn process(
         help()
     } else if command == "program" {
         // Load a .chalk file via stdin, until EOF is found.
-        let chalk_prog = LoadedProgram::new(read_program(rl)?, args.solver_choice())?;
+        let mut chalk_prog = LoadedProgram::new(
+            read_program(rl)?,
+            args.solver_choice(),
+            args.budget(),
+            args.flag_solve_regions,
+            args.cache_dir(),
+        )?;
+        // Files included by the program are found relative to the working
+        // directory.
+        let dir = std::env::current_dir()?;
+        chalk_prog.db.set_program_dir(Some(Arc::new(dir)));
+        chalk_prog.db.load_included_files();
         // Let's do a sanity check before going forward.
-        let _ = chalk_prog.db.checked_program()?;
+        chalk_prog.check()?;
         *prog = Some(chalk_prog);
     } else if let Some(filename) = command.strip_prefix("load ") {
         // Load a .chalk file.
         let chalk_prog = load_program(args, filename)?;
         // Let's do a sanity check before going forward.
-        let _ = chalk_prog.db.checked_program()?;
+        chalk_prog.check()?;
         *prog = Some(chalk_prog);
+    } else if let Some(edit) = item_edit(command) {
+        // Edit the items of the loaded program.
+        prog.as_mut()
+            .ok_or("no program currently loaded; type 'help' to see available commands")?
+            .edit(edit)?;
     } else if let Some(level) = command.strip_prefix("debug ") {
         if level.is_empty() {
             println!("debug <level> set debug level to <level>");
@@ -227,7 +418,7 @@ fn process(
             std::env::set_var("CHALK_DEBUG", level);
         }
     } else {
-        // The command is either "print", "lowered", or a goal.
+        // The command is either "print", "lowered", "stats", or a goal.
 
         // Check that a program has been loaded.
         let prog = prog
@@ -238,11 +429,22 @@ fn process(
         prog.db.with_program(|_| -> Result<()> {
             match command {
                 // Print out the loaded program.
-                "print" => println!("{}", prog.text),
+                "print" => println!("{}", prog.db.program_text()),
 
                 // TODO: Write a line of documentation here.
                 "lowered" => println!("{:#?}", prog.db.environment()),
 
+                // Print out how much work the solver has done.
+                "stats" => print!("{}", prog.db.solver_stats()),
+
+                // Explain how the solver arrived at the solution to a goal.
+                _ if command.starts_with("why ") => prog.explain(&command["why ".len()..])?,
+
+                // Solve a goal with every solver.
+                _ if command.starts_with("compare ") => {
+                    prog.compare(&command["compare ".len()..], &args.solver_choices())?;
+                }
+
                 // Assume this is a goal.
                 // TODO: Print out "type 'help' to see available commands" if it
                 // fails to parse?
@@ -255,13 +457,35 @@ fn process(
     Ok(())
 }
 
+/// Parses an `add`, `remove` or `replace` command.
+fn item_edit(command: &str) -> Option<ItemEdit<'_>> {
+    if let Some(item) = command.strip_prefix("add ") {
+        Some(ItemEdit::Add(item))
+    } else if let Some(name) = command.strip_prefix("remove ") {
+        Some(ItemEdit::Remove(name))
+    } else {
+        command.strip_prefix("replace ").map(ItemEdit::Replace)
+    }
+}
+
 /// Load the file into a string, and parse it.
 // TODO: Could we pass in an Options struct or something? The Args struct
 // still has Strings where it should have Enums... (e.g. solver_choice)
 fn load_program(args: &Args, filename: &str) -> Result<LoadedProgram> {
     let mut text = String::new();
     File::open(filename)?.read_to_string(&mut text)?;
-    LoadedProgram::new(text, args.solver_choice())
+    let mut prog = LoadedProgram::new(
+        text,
+        args.solver_choice(),
+        args.budget(),
+        args.flag_solve_regions,
+        args.cache_dir(),
+    )?;
+    // Files included by the program are found relative to it.
+    let dir = fs::canonicalize(filename)?.parent().map(Path::to_path_buf);
+    prog.db.set_program_dir(dir.map(Arc::new));
+    prog.db.load_included_files();
+    Ok(prog)
 }
 
 /// Print out help for commands in interpreter mode.
@@ -272,8 +496,16 @@ fn help() {
     println!("  program       provide a program via stdin");
     println!("  load <file>   load program from <file>");
     println!("  print         print the current program");
+    println!("  add <item>    add <item> to the current program");
+    println!("  remove <name> remove the item named <name> (for an impl, its header,");
+    println!("                e.g. `impl<T> Clone for Vec<T>`) from the current program");
+    println!("  replace <item> replace the item with the same name as <item> by <item>");
+    println!("                (after an edit, the whole program is checked again)");
     println!("  lowered       print the lowered program");
+    println!("  stats         print how much work the solver has done so far");
     println!("  <goal>        attempt to solve <goal>");
+    println!("  why <goal>    explain how the solver arrives at the solution to <goal>");
+    println!("  compare <goal> solve <goal> with every solver and compare the solutions");
     println!("  debug <level> set debug level to <level>");
 }
 
@@ -290,18 +522,46 @@ fn read_program(rl: &mut rustyline::DefaultEditor) -> Result<String> {
 }
 
 impl Args {
+    fn cache_dir(&self) -> Option<Arc<PathBuf>> {
+        let dir = self.flag_cache_dir.as_ref()?;
+        Some(Arc::new(PathBuf::from(dir)))
+    }
+
     fn solver_choice(&self) -> SolverChoice {
-        match self.flag_solver.as_deref() {
-            None | Some("slg") => SolverChoice::SLG {
+        self.named_solver_choice(self.flag_solver.as_deref().unwrap_or("slg"))
+    }
+
+    fn budget(&self) -> Budget {
+        Budget {
+            timeout: self.flag_timeout.map(Duration::from_millis),
+            max_steps: self.flag_max_steps,
+            max_tables: self.flag_max_tables,
+            max_strands: self.flag_max_strands,
+            max_cache_entries: self.flag_max_cache_entries,
+        }
+    }
+
+    /// Every solver, configured as it would be by `--solver`.
+    fn solver_choices(&self) -> Vec<SolverChoice> {
+        vec![
+            self.named_solver_choice("slg"),
+            self.named_solver_choice("recursive"),
+        ]
+    }
+
+    fn named_solver_choice(&self, name: &str) -> SolverChoice {
+        match name {
+            "slg" => SolverChoice::SLG {
                 max_size: self.flag_overflow_depth,
                 expected_answers: None,
             },
-            Some("recursive") => SolverChoice::Recursive {
+            "recursive" => SolverChoice::Recursive {
                 overflow_depth: 100,
                 caching_enabled: true,
                 max_size: 30,
+                parallel: self.flag_parallel,
             },
-            Some(s) => panic!("invalid solver {}", s),
+            s => panic!("invalid solver {}", s),
         }
     }
 }
@@ -15,6 +15,24 @@ fn test_trait_impl_assoc_type() {
     );
 }
 #[test]
+fn test_impl_default_assoc_type() {
+    reparse_test!(
+        program {
+            struct Foo<T> { }
+            struct Floo { }
+            trait Bar {
+                type Assoc;
+            }
+            impl<T> Bar for Foo<T> {
+                default type Assoc = Floo;
+            }
+            impl Bar for Foo<Floo> {
+                type Assoc = Foo<Floo>;
+            }
+        }
+    );
+}
+#[test]
 fn test_trait_with_multiple_assoc_types() {
     // Test multiple associated types per trait
     reparse_test!(
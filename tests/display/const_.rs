@@ -58,3 +58,34 @@ fn test_basic_const_values_in_assoc_ty_values() {
         }
     );
 }
+
+#[test]
+fn test_const_exprs() {
+    // Test we render unevaluated const expressions correctly.
+    reparse_test!(
+        program {
+            struct Foo<const N> { }
+            trait Bar { }
+            impl<const N> Bar for Foo<{ N + 1 }> { }
+            impl<const N> Bar for Foo<{ { N - 1 } * 2 }> { }
+        }
+    );
+}
+
+#[test]
+fn test_typed_const_generics() {
+    // Test we render typed const parameters and values correctly.
+    reparse_test!(
+        program {
+            struct Flag<const B: bool> { }
+            struct Letter<const C: char> { }
+            struct Offset<const N: i8> { }
+            struct Name<const S: &'static str> { }
+            trait Bar { }
+            impl Bar for Flag<true> { }
+            impl Bar for Letter<'x'> { }
+            impl Bar for Offset<-128> { }
+            impl Bar for Name<"chalk"> { }
+        }
+    );
+}
@@ -208,6 +208,9 @@ pub fn reparse_into_different_test<'a>(
             e, output_text
         )
     });
+    // The items of the written program are naturally in different places.
+    let (output_program, target_program) =
+        (without_spans(output_program), without_spans(target_program));
     if output_program != target_program {
         panic!(
             "WriteProgram produced different program.\n\
@@ -238,6 +241,14 @@ pub fn reparse_into_different_test<'a>(
     }
 }
 
+fn without_spans(program: Arc<Program>) -> Arc<Program> {
+    Arc::new(Program {
+        item_spans: Default::default(),
+        parameter_names: Default::default(),
+        ..(*program).clone()
+    })
+}
+
 /// Tests that a string matches a given regex pattern, erroring out if it
 /// doesn't.
 ///
@@ -37,6 +37,16 @@ impl UnificationDatabase<ChalkIr> for MockDatabase {
     fn adt_variance(&self, _adt_id: AdtId<ChalkIr>) -> Variances<ChalkIr> {
         Variances::empty(self.interner())
     }
+
+    fn evaluate_const_expr(
+        &self,
+        _ty: &Ty<ChalkIr>,
+        _op: ConstBinOp,
+        _lhs: &ConcreteConst<ChalkIr>,
+        _rhs: &ConcreteConst<ChalkIr>,
+    ) -> Option<ConcreteConst<ChalkIr>> {
+        None
+    }
 }
 
 /// This DB represents the following lowered program:
@@ -1,4 +1,7 @@
 use chalk_integration::db::ChalkDatabase;
+use chalk_integration::error::SourceFiles;
+use chalk_integration::interner::ChalkIr;
+use chalk_integration::lowering::Lower;
 use chalk_integration::query::LoweringDatabase;
 use chalk_integration::SolverChoice;
 
@@ -195,6 +198,7 @@ fn atc_accounting() {
     value: for<type, lifetime> AssociatedTyValueBound {
         ty: Iter<'^0.1, ^0.0>
     },
+    defaultness: Final,
 }"#
             .replace(",\n", "\n"),
         );
@@ -815,3 +819,480 @@ fn algebraic_data_types() {
         }
     }
 }
+
+#[test]
+fn error_spans() {
+    let render = |text: &str| {
+        ChalkDatabase::with(text, SolverChoice::default())
+            .checked_program()
+            .unwrap_err()
+            .render(&SourceFiles::new(text))
+    };
+
+    assert_eq!(
+        render("struct Foo { }\ntrait Bar { }\nimpl Foo for Bar { }"),
+        "expected a trait, found `Foo`, which is not a trait
+ --> 3:6
+  |
+3 | impl Foo for Bar { }
+  |      ^^^
+"
+    );
+
+    assert_eq!(
+        render("trait Foo { }\nstruct S<T> where T: Foo { }\nstruct X { f: S<u32> }"),
+        "type declaration `X` does not meet well-formedness requirements: \
+         `WellFormed(S<Uint(U32)>)` does not hold, because `Implemented(Uint(U32): Foo)` cannot be proven
+ --> 3:8
+  |
+3 | struct X { f: S<u32> }
+  |        ^
+"
+    );
+
+    assert_eq!(
+        render("trait Foo { }\nstruct S {\nimpl Foo for S { }"),
+        r##"parse error: Unrecognized token `impl` found at 25:29
+Expected one of ",", "}" or r#"([A-Za-z]|_)([A-Za-z0-9]|_)*"#
+invalid parameter name `S`
+ --> 3:1
+  |
+3 | impl Foo for S { }
+  | ^^^^
+  |
+ ::: 3:14
+  |
+3 | impl Foo for S { }
+  |              - error here
+"##
+    );
+}
+
+#[test]
+fn error_sources() {
+    let source = |text: &str| {
+        let error = ChalkDatabase::with(text, SolverChoice::default())
+            .checked_program()
+            .unwrap_err();
+        format!("{:?}", error.source_error().unwrap())
+    };
+
+    assert!(source("struct Foo { }\nimpl Foo for Foo { }").starts_with("Lowering(NotTrait("));
+    assert!(
+        source("trait Foo { }\nstruct S<T> where T: Foo { }\nstruct X { f: S<u32> }")
+            .starts_with("WellFormedness(IllFormedTypeDecl(")
+    );
+    assert!(
+        source("trait Foo { }\nstruct S { }\nimpl Foo for S { }\nimpl Foo for S { }")
+            .starts_with("Coherence(OverlappingImpls {")
+    );
+    assert!(source("trait Foo { }\nstruct S {").starts_with("Syntax(["));
+}
+
+#[test]
+fn syntax_error_recovery() {
+    let text = "trait Foo { }\nstruct S {\nstruct T { }\nimpl Foo for *i32 { }\nimpl Foo for T { }";
+
+    // Every item with a syntax error is reported...
+    let error = ChalkDatabase::with(text, SolverChoice::default())
+        .checked_program()
+        .unwrap_err();
+    assert_eq!(
+        error.render(&SourceFiles::new(text)),
+        r##"parse error: Unrecognized token `struct` found at 25:31
+Expected one of ",", "}" or r#"([A-Za-z]|_)([A-Za-z0-9]|_)*"#
+parse error: Unrecognized token `i32` found at 52:55
+Expected one of "const" or "mut"
+ --> 3:1
+  |
+3 | struct T { }
+  | ^^^^^^
+  |
+ ::: 4:15
+  |
+4 | impl Foo for *i32 { }
+  |               --- another syntax error
+"##
+    );
+
+    // ...and the rest of the program can still be lowered.
+    let (program, errors) = chalk_parse::parse_program_recovering(text);
+    assert_eq!(errors.len(), 2);
+    assert_eq!(program.items.len(), 3);
+    program.lower().unwrap();
+
+    // Errors in the rest of the program are reported after the syntax errors.
+    let render = |text: &str| {
+        ChalkDatabase::with(text, SolverChoice::default())
+            .checked_program()
+            .unwrap_err()
+            .render(&SourceFiles::new(text))
+    };
+    assert_eq!(
+        render("struct S { }\nimpl Foo for S { }\nstruct T {"),
+        r##"parse error: Unrecognized EOF found at 42
+Expected one of ",", "}" or r#"([A-Za-z]|_)([A-Za-z0-9]|_)*"#
+invalid trait name `Foo`
+ --> 3:11
+  |
+3 | struct T {
+  |           ^
+  |
+ ::: 2:6
+  |
+2 | impl Foo for S { }
+  |      --- error here
+"##
+    );
+    assert_eq!(
+        render("trait Foo { }\nstruct S<T> where T: Foo { }\nstruct X { f: S<u32> }\nstruct Y {"),
+        r##"parse error: Unrecognized EOF found at 76
+Expected one of ",", "}" or r#"([A-Za-z]|_)([A-Za-z0-9]|_)*"#
+type declaration `X` does not meet well-formedness requirements: `WellFormed(S<Uint(U32)>)` does not hold, because `Implemented(Uint(U32): Foo)` cannot be proven
+ --> 4:11
+  |
+4 | struct Y {
+  |           ^
+  |
+ ::: 3:8
+  |
+3 | struct X { f: S<u32> }
+  |        - error here
+"##
+    );
+
+    // Errors reported by the grammar point at what they are about.
+    assert_eq!(
+        render("#[repr(foo)]\nstruct S { }"),
+        r##"parse error: unknown adt repr flag
+ --> 1:8
+  |
+1 | #[repr(foo)]
+  |        ^^^
+"##
+    );
+    assert_eq!(
+        render("struct S<const N> { }\nstruct T { f: S<340282366920938463463374607431768211456> }"),
+        r##"parse error: integer literal is too large
+ --> 2:17
+  |
+2 | struct T { f: S<340282366920938463463374607431768211456> }
+  |                 ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+"##
+    );
+}
+
+#[test]
+fn modules() {
+    lowering_success! {
+        program {
+            trait Clone { }
+            mod collections {
+                struct Vec<T> { }
+                impl<T> Clone for Vec<T> where T: Clone { }
+                mod raw {
+                    struct RawVec<T> { v: super::Vec<T> }
+                    impl<T> crate::Clone for RawVec<T> { }
+                }
+            }
+            mod num {
+                use crate::collections::raw::RawVec as Raw;
+                struct Int { r: Raw<Int> }
+            }
+            use collections::*;
+            struct Foo { v: Vec<num::Int>, r: collections::raw::RawVec<Foo> }
+        }
+    }
+
+    // The items of a module are only in scope in it (and its submodules)
+    lowering_error! {
+        program {
+            mod m {
+                struct A { }
+            }
+            struct B { f: A }
+        }
+        error_msg {
+            "invalid parameter name `A`"
+        }
+    }
+
+    lowering_error! {
+        program {
+            mod m {
+                trait Foo { }
+            }
+            use m::Bar;
+            struct A { }
+            impl Bar for A { }
+        }
+        error_msg {
+            "invalid trait name `Bar`"
+        }
+    }
+
+    // Paths must name an item
+    lowering_error! {
+        program {
+            mod m {
+                trait Foo { }
+            }
+            struct A { }
+            impl m::Bar for A { }
+        }
+        error_msg {
+            "unresolved path `m::Bar`"
+        }
+    }
+
+    // Cyclic imports do not resolve
+    lowering_error! {
+        program {
+            use A as B;
+            use B as A;
+            struct C { f: A }
+        }
+        error_msg {
+            "invalid parameter name `A`"
+        }
+    }
+}
+
+#[test]
+fn include() {
+    use chalk_solve::ext::GoalExt;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    let text = "mod std { include \"libstd.chalk\"; }\nuse std::Vec;\nstruct Foo { }";
+    let mut db = ChalkDatabase::with(text, SolverChoice::default());
+    db.set_program_dir(Some(Arc::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")))));
+    db.load_included_files();
+    db.with_program(|_| {
+        let goal = db
+            .parse_and_lower_goal("Vec<std::Box<i32>>: std::Clone")
+            .unwrap();
+        let solution = db.solve(&goal.into_peeled_goal(ChalkIr));
+        assert_eq!(solution.unwrap().display(ChalkIr).to_string(), "Unique");
+        let goal = db.parse_and_lower_goal("Vec<Foo>: std::Clone").unwrap();
+        assert!(db.solve(&goal.into_peeled_goal(ChalkIr)).is_none());
+    });
+
+    let text = "struct Foo { }\ninclude \"no_such_file.chalk\";";
+    let mut db = ChalkDatabase::with(text, SolverChoice::default());
+    db.set_program_dir(Some(Arc::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")))));
+    db.load_included_files();
+    let error = db.checked_program().unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("cannot include `no_such_file.chalk`: "));
+    assert_eq!(error.span(), Some(chalk_parse::ast::Span::new(15, 44)));
+
+    // Without a directory, a program cannot include files
+    let error = ChalkDatabase::with(text, SolverChoice::default())
+        .checked_program()
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "cannot include `no_such_file.chalk`: the program has no directory to include files from"
+    );
+
+    // Included files are only read again when they are loaded
+    let dir = std::env::temp_dir().join(format!("chalk-include-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("foo.chalk"), "struct Foo { }").unwrap();
+    let text = "include \"foo.chalk\";\nstruct Bar { f: Foo }";
+    let mut db = ChalkDatabase::with(text, SolverChoice::default());
+    db.set_program_dir(Some(Arc::new(dir.clone())));
+    db.load_included_files();
+    assert!(db.checked_program().is_ok());
+    std::fs::write(dir.join("foo.chalk"), "struct Baz { }").unwrap();
+    assert!(db.checked_program().is_ok());
+    db.load_included_files();
+    assert_eq!(
+        db.checked_program().unwrap_err().to_string(),
+        "invalid parameter name `Foo`"
+    );
+
+    // Errors in included files point into them
+    std::fs::write(
+        dir.join("foo.chalk"),
+        "trait Foo { }\nstruct A { }\nimpl Foo for A { }\nstruct B { f: C }",
+    )
+    .unwrap();
+    db.set_program_text(Arc::new("include \"foo.chalk\";\nstruct D { }".to_string()));
+    db.load_included_files();
+    assert_eq!(
+        db.checked_program().unwrap_err().render(&db.source_files()),
+        "invalid parameter name `C`
+ --> foo.chalk:4:15
+  |
+4 | struct B { f: C }
+  |               ^
+"
+    );
+    db.set_program_text(Arc::new(
+        "include \"foo.chalk\";\nstruct C { }\nimpl Foo for A { }".to_string(),
+    ));
+    db.load_included_files();
+    assert_eq!(
+        db.checked_program().unwrap_err().render(&db.source_files()),
+        "overlapping impls of trait `Foo`: `impl Foo for A` and `impl Foo for A` both apply to `A: Foo`
+ --> 3:1
+  |
+3 | impl Foo for A { }
+  | ^^^^^^^^^^^^^^
+  |
+ ::: foo.chalk:3:1
+  |
+3 | impl Foo for A { }
+  | -------------- first impl here
+"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crates() {
+    lowering_success! {
+        program {
+            struct Root { }
+            crate std {
+                struct Vec<T> { }
+            }
+            crate app: std {
+                use std::Vec;
+                struct Foo { v: Vec<Root>, s: crate::Bar }
+                struct Bar { }
+            }
+        }
+    }
+
+    // A crate cannot name the crates it does not depend on
+    lowering_error! {
+        program {
+            crate a {
+                struct A { }
+            }
+            crate b {
+                struct B { a: a::A }
+            }
+        }
+        error_msg {
+            "unresolved path `a::A`"
+        }
+    }
+
+    lowering_error! {
+        program {
+            crate app: std { }
+        }
+        error_msg {
+            "invalid crate name `std`"
+        }
+    }
+
+    lowering_error! {
+        program {
+            crate app: std { }
+            crate std { }
+        }
+        error_msg {
+            "crate `std` must be declared before the crates that depend on it"
+        }
+    }
+}
+
+#[test]
+fn aliases() {
+    lowering_success! {
+        program {
+            trait Fn<T> { }
+            #[auto] trait Send { }
+            trait Callback<T> = Fn<T> + Send;
+            type Boxed<'a, T> = dyn Callback<T> + 'a;
+            struct Foo<'a> { f: Boxed<'a, ()> }
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Clone { }
+            trait Alias = Clone;
+            struct Foo { }
+            impl Alias for Foo { }
+        }
+        error_msg {
+            "expected a trait, found trait alias `Alias`"
+        }
+    }
+
+    lowering_error! {
+        program {
+            struct Vec<T> { }
+            type Foo<T> = Vec<T>;
+            struct Bar { f: Foo }
+        }
+        error_msg {
+            "`Foo` takes 1 type parameters, not 0"
+        }
+    }
+
+    lowering_error! {
+        program {
+            type A = B;
+            type B = A;
+        }
+        error_msg {
+            "invalid parameter name `B`"
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Fn<T> { }
+            trait Callback = forall<'a> Fn<&'a ()>;
+            struct Foo<T> where T: Callback { }
+        }
+        error_msg {
+            "trait alias `Callback` has higher-ranked bounds, which cannot be used here"
+        }
+    }
+}
+
+#[test]
+fn typed_const_params() {
+    lowering_success! {
+        program {
+            struct Flag<const B: bool> { }
+            struct Byte<const N: u8> { }
+            struct Name<const S: &str> { }
+            trait Trait { }
+            impl Trait for Flag<false> { }
+            impl Trait for Byte<{ 2 * 3 }> { }
+            impl Trait for Name<"x"> { }
+        }
+    }
+
+    lowering_error! {
+        program {
+            struct Byte<const N: u8> { }
+            trait Trait { }
+            impl Trait for Byte<256> { }
+        }
+        error_msg {
+            "literal `256` does not fit in `u8`"
+        }
+    }
+
+    lowering_error! {
+        program {
+            struct Index<const N: usize> { }
+            trait Trait { }
+            impl Trait for Index<-1> { }
+        }
+        error_msg {
+            "literal `-1` does not fit in `usize`"
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Tests related to type aliases and trait aliases.
+
+use super::*;
+
+#[test]
+fn type_alias() {
+    test! {
+        program {
+            struct Vec<T> { }
+            struct Foo { }
+            trait Clone { }
+
+            type Foos = Vec<Foo>;
+            type Pair<T> = (T, VecOf<T>);
+            type VecOf<T> = Vec<T>;
+
+            impl Clone for Foos { }
+            impl<T> Clone for Pair<T> where T: Clone { }
+        }
+
+        goal {
+            Vec<Foo>: Clone
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            Pair<Foos>: Clone
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            exists<T> { Pair<T> = (Foo, Vec<Foo>) }
+        } yields {
+            expect![["Unique; substitution [?0 := Foo]"]]
+        }
+    }
+}
+
+#[test]
+fn trait_alias_in_where_clause() {
+    test! {
+        program {
+            trait Clone { }
+            trait Debug { }
+            trait CloneDebug = Clone + Debug;
+            struct Foo { }
+            struct Bar { }
+            struct Wrapper<T> { }
+            impl Clone for Foo { }
+            impl Debug for Foo { }
+            impl Clone for Bar { }
+            impl<T> Clone for Wrapper<T> where T: CloneDebug { }
+        }
+
+        goal {
+            Wrapper<Foo>: Clone
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            Wrapper<Bar>: Clone
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            Foo: CloneDebug
+        } yields {
+            expect![["Unique"]]
+        }
+    }
+}
+
+#[test]
+fn trait_alias_with_parameters() {
+    test! {
+        program {
+            trait Iterator { type Item; }
+            trait Clone { }
+            trait IterOf<T> = Iterator<Item = T> + Clone;
+            struct Foo { }
+            struct Ints { }
+            impl Iterator for Ints { type Item = Foo; }
+            impl Clone for Ints { }
+        }
+
+        goal {
+            Ints: IterOf<Foo>
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            Ints: IterOf<Ints>
+        } yields {
+            expect![["No possible solution"]]
+        }
+    }
+}
+
+#[test]
+fn trait_alias_in_dyn() {
+    test! {
+        program {
+            trait Clone { }
+            #[auto] trait Send { }
+            trait CloneSend = Clone + Send;
+        }
+
+        goal {
+            forall<'s> {
+                dyn CloneSend + 's: Send
+            }
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            forall<'s> {
+                dyn CloneSend + 's = dyn Clone + Send + 's
+            }
+        } yields {
+            expect![["Unique"]]
+        }
+    }
+
+    test! {
+        program {
+            trait Fn<T> { }
+            trait Callback = forall<'a> Fn<&'a ()>;
+        }
+
+        goal {
+            forall<'s> {
+                dyn Callback + 's = dyn forall<'a> Fn<&'a ()> + 's
+            }
+        } yields {
+            expect![["Unique"]]
+        }
+    }
+}
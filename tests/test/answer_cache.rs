@@ -0,0 +1,98 @@
+//! Tests for sharing the answers of SLG solvers through an `AnswerCache`.
+
+use super::*;
+use chalk_engine::cache::AnswerCache;
+use chalk_engine::forest::ForestStats;
+use chalk_engine::solve::SLGSolver;
+use chalk_solve::Solver;
+use std::thread;
+
+const PROGRAM: &str = "
+    #[lang(sized)]
+    trait Sized { }
+    trait Foo { }
+    trait Bar { }
+    struct A { }
+    struct Vec<T> { }
+
+    impl<T> Foo for Vec<T> where T: Sized { }
+    impl<T> Bar for T where T: Foo { }
+";
+
+/// Solves `goal` with `solver`, returning the solution and the memory
+/// statistics of the solver afterwards.
+fn solve(solver: &mut SLGSolver<ChalkIr>, goal: &str) -> (Option<String>, ForestStats) {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::slg_default());
+    let solution = solver
+        .solve(&db, &peeled_goal(&db, goal))
+        .map(|solution| solution.display(ChalkIr).to_string());
+    (solution, solver.memory_stats())
+}
+
+#[test]
+fn answers_are_shared() {
+    let cache = AnswerCache::new();
+    let mut first = SLGSolver::new(10, None).with_answer_cache(cache.clone());
+    let (solution, stats) = solve(&mut first, "Vec<A>: Foo");
+    assert_eq!(solution.as_deref(), Some("Unique"));
+    assert_eq!(stats.cache_hits, 0);
+    assert_eq!(cache.len(), stats.tables);
+
+    // The second solver finds the root goal in the cache, and so has no
+    // subgoals to solve.
+    let mut second = SLGSolver::new(10, None).with_answer_cache(cache.clone());
+    let (solution, stats) = solve(&mut second, "Vec<A>: Foo");
+    assert_eq!(solution.as_deref(), Some("Unique"));
+    assert_eq!((stats.tables, stats.cache_hits), (1, 1));
+
+    // Subgoals are taken from the cache too.
+    let mut third = SLGSolver::new(10, None).with_answer_cache(cache);
+    let (solution, stats) = solve(&mut third, "Vec<A>: Bar");
+    assert_eq!(solution.as_deref(), Some("Unique"));
+    assert_eq!(stats.cache_hits, 1);
+}
+
+#[test]
+fn snapshot_is_read_only() {
+    let cache = AnswerCache::new();
+    let snapshot = cache.snapshot();
+    let mut solver = SLGSolver::new(10, None).with_answer_cache(cache.clone());
+    solve(&mut solver, "Vec<A>: Foo");
+    assert!(!cache.is_empty());
+    assert!(snapshot.is_empty());
+}
+
+#[test]
+fn answers_are_shared_between_threads() {
+    let cache = AnswerCache::new();
+    let goals = ["Vec<A>: Foo", "Vec<Vec<A>>: Foo", "A: Foo", "A: Sized"];
+    let handles: Vec<_> = goals
+        .iter()
+        .map(|&goal| {
+            // The solvers are sent to the threads.
+            let mut solver = SLGSolver::new(10, None).with_answer_cache(cache.clone());
+            thread::spawn(move || solve(&mut solver, goal).0)
+        })
+        .collect();
+    let solutions: Vec<_> = handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect();
+    assert_eq!(
+        solutions,
+        [
+            Some("Unique".to_string()),
+            Some("Unique".to_string()),
+            None,
+            Some("Unique".to_string()),
+        ]
+    );
+
+    // Whatever the threads found first, solving the goals again only takes
+    // answers from the cache.
+    let mut solver = SLGSolver::new(10, None).with_answer_cache(cache);
+    for goal in &goals {
+        let stats = solve(&mut solver, goal).1;
+        assert_eq!(stats.tables, stats.cache_hits);
+    }
+}
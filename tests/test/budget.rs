@@ -0,0 +1,223 @@
+//! Tests for `Solver::solve_with_budget`, which gives up on a goal once the
+//! solver has used up its budget.
+
+use super::proof_tree::SolveOnly;
+use super::*;
+use chalk_engine::solve::SLGSolver;
+use chalk_solve::solve::budget::{Budget, BudgetLimit};
+use chalk_solve::Solver;
+
+/// Will try to prove `S<G<Z>>: Q`, then `S<G<G<Z>>>: Q` etc. until it
+/// overflows.
+const OVERFLOW: &str = "
+    trait Q { }
+    struct Z { }
+    struct G<X> { }
+    struct S<X> { }
+
+    impl Q for Z { }
+    impl<X> Q for G<X> where X: Q { }
+    impl<X> Q for S<X> where X: Q, S<G<X>>: Q { }
+";
+
+#[test]
+fn unlimited_budget() {
+    test! {
+        budget(Budget::default());
+        program {
+            trait Q { }
+            struct Z { }
+            struct G<X> { }
+            struct S<X> { }
+
+            impl Q for Z { }
+            impl<X> Q for G<X> where X: Q { }
+            impl<X> Q for S<X> where X: Q, S<G<X>>: Q { }
+        }
+
+        goal {
+            S<Z>: Q
+        } yields {
+            expect![["Ambiguous; no inference guidance"]]
+        }
+    }
+}
+
+#[test]
+fn max_steps() {
+    test! {
+        budget(Budget::default().with_max_steps(3));
+        program {
+            trait Q { }
+            struct Z { }
+            struct G<X> { }
+            struct S<X> { }
+
+            impl Q for Z { }
+            impl<X> Q for G<X> where X: Q { }
+            impl<X> Q for S<X> where X: Q, S<G<X>>: Q { }
+        }
+
+        goal {
+            S<Z>: Q
+        } yields {
+            expect![["budget exhausted: step limit reached after 4 steps"]]
+        }
+    }
+}
+
+#[test]
+fn max_steps_not_reached() {
+    test! {
+        budget(Budget::default().with_max_steps(1000));
+        program {
+            trait Q { }
+            struct Z { }
+            struct G<X> { }
+            struct S<X> { }
+
+            impl Q for Z { }
+            impl<X> Q for G<X> where X: Q { }
+            impl<X> Q for S<X> where X: Q, S<G<X>>: Q { }
+        }
+
+        goal {
+            Z: Q
+        } yields {
+            expect![["Unique"]]
+        }
+    }
+}
+
+#[test]
+fn timeout() {
+    test! {
+        budget(Budget::default().with_timeout(std::time::Duration::ZERO));
+        program {
+            trait Q { }
+            struct Z { }
+            struct G<X> { }
+            struct S<X> { }
+
+            impl Q for Z { }
+            impl<X> Q for G<X> where X: Q { }
+            impl<X> Q for S<X> where X: Q, S<G<X>>: Q { }
+        }
+
+        goal {
+            S<Z>: Q
+        } yields {
+            expect![["budget exhausted: timed out after 1 steps"]]
+        }
+    }
+}
+
+#[test]
+fn max_tables() {
+    test! {
+        budget(Budget::default().with_max_tables(2));
+        program {
+            trait Q { }
+            struct Z { }
+            struct G<X> { }
+            struct S<X> { }
+
+            impl Q for Z { }
+            impl<X> Q for G<X> where X: Q { }
+            impl<X> Q for S<X> where X: Q, S<G<X>>: Q { }
+        }
+
+        goal {
+            S<Z>: Q
+        } yields[SolverChoice::default()] {
+            expect![["budget exhausted: table limit reached after 1 steps"]]
+        }
+    }
+}
+
+#[test]
+fn max_strands() {
+    test! {
+        budget(Budget::default().with_max_strands(1));
+        program {
+            trait Q { }
+            struct Z { }
+            struct G<X> { }
+            struct S<X> { }
+
+            impl Q for Z { }
+            impl<X> Q for G<X> where X: Q { }
+            impl<X> Q for S<X> where X: Q, S<G<X>>: Q { }
+        }
+
+        goal {
+            S<Z>: Q
+        } yields[SolverChoice::slg_default()] {
+            expect![["budget exhausted: strand limit reached after 1 steps"]]
+        }
+    }
+}
+
+#[test]
+fn max_cache_entries() {
+    test! {
+        budget(Budget::default().with_max_cache_entries(1));
+        program {
+            trait Q { }
+            struct Z { }
+            struct G<X> { }
+            struct S<X> { }
+
+            impl Q for Z { }
+            impl<X> Q for G<X> where X: Q { }
+            impl<X> Q for S<X> where X: Q, S<G<X>>: Q { }
+        }
+
+        goal {
+            S<Z>: Q
+        } yields[SolverChoice::recursive_default()] {
+            expect![["budget exhausted: cache entry limit reached after 5 steps"]]
+        }
+    }
+}
+
+/// A goal that was cut short must not be cached, so solving it again with
+/// a larger budget finds the complete solution.
+#[test]
+fn exhausted_results_are_not_cached() {
+    for solver_choice in SolverChoice::all_defaults() {
+        let db = ChalkDatabase::with(OVERFLOW, solver_choice);
+        let peeled_goal = peeled_goal(&db, "S<Z>: Q");
+        let budget = Budget::default().with_max_steps(3);
+        assert!(db.solve_with_budget(&peeled_goal, &budget).is_err());
+        let solution = db.solve_with_budget(&peeled_goal, &Budget::default());
+        assert_eq!(
+            solution.map(|s| s.map(|s| s.display(ChalkIr).to_string())),
+            Ok(Some("Ambiguous; no inference guidance".to_string())),
+            "{:?}",
+            solver_choice
+        );
+    }
+}
+
+/// Solvers that only implement `solve_limited` are held to the step limit.
+#[test]
+fn default_solve_with_budget() {
+    let db = ChalkDatabase::with(OVERFLOW, SolverChoice::slg_default());
+    let mut solver = SolveOnly(SLGSolver::new(10, None));
+    let peeled_goal = peeled_goal(&db, "S<Z>: Q");
+    let budget = Budget::default().with_max_steps(3);
+    let exhausted = solver
+        .solve_with_budget(&db, &peeled_goal, &budget)
+        .unwrap_err();
+    assert_eq!(exhausted.limit, BudgetLimit::Steps);
+    assert_eq!(exhausted.steps, 4);
+
+    let solution = solver
+        .solve_with_budget(&db, &peeled_goal, &Budget::default())
+        .unwrap();
+    assert_eq!(
+        solution.unwrap().display(ChalkIr).to_string(),
+        "Ambiguous; no inference guidance"
+    );
+}
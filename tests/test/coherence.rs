@@ -10,7 +10,7 @@ fn two_impls_for_same_type() {
             impl Foo for Bar { }
         }
         error_msg {
-            "overlapping impls of trait `Foo`"
+            "overlapping impls of trait `Foo`: `impl Foo for Bar` and `impl Foo for Bar` both apply to `Bar: Foo`"
         }
     }
 
@@ -27,6 +27,38 @@ fn two_impls_for_same_type() {
     }
 }
 
+#[test]
+fn overlap_witness() {
+    lowering_error! {
+        program {
+            trait Foo<X> { }
+            trait Bar { }
+            struct Vec<T> { }
+            struct U { }
+            impl Bar for U { }
+            impl<T> Foo<U> for Vec<T> where T: Bar { }
+            impl<T> Foo<T> for Vec<U> { }
+        }
+        error_msg {
+            "overlapping impls of trait `Foo`: `impl<T> Foo<U> for Vec<T>` and `impl<T> Foo<T> for Vec<U>` both apply to `Vec<U>: Foo<U>`"
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Foo { }
+            trait Bar { }
+            trait Baz { }
+            struct Vec<T> { }
+            impl<T> Foo for Vec<T> where T: Bar { }
+            impl<T> Foo for Vec<T> where T: Baz { }
+        }
+        error_msg {
+            "overlapping impls of trait `Foo`: `impl<T> Foo for Vec<T>` and `impl<T> Foo for Vec<T>` may both apply"
+        }
+    }
+}
+
 #[test]
 fn generic_vec_and_specific_vec() {
     lowering_success! {
@@ -355,7 +387,8 @@ fn orphan_check() {
 
             impl Foo for Bar { }
         } error_msg {
-            "impl for trait `Foo` violates the orphan rules"
+            "impl for trait `Foo` violates the orphan rules: none of the types in the trait reference is local; \
+             `Bar` is not local because it is `#[upstream]`"
         }
     }
 
@@ -376,7 +409,22 @@ fn orphan_check() {
 
             impl<T> Foo<Bar> for T { }
         } error_msg {
-            "impl for trait `Foo` violates the orphan rules"
+            "impl for trait `Foo` violates the orphan rules: `T` (the self type) is not local and \
+             contains an uncovered type parameter, which may not appear before the first local type"
+        }
+    }
+
+    lowering_error! {
+        program {
+            #[upstream] trait Foo<T> { }
+            #[upstream] struct Pair<T, U> { }
+            struct Bar { }
+
+            impl<'a, U, T> Foo<Bar> for Pair<&'a T, U> { }
+        } error_msg {
+            "impl for trait `Foo` violates the orphan rules: `Pair<(&'a T), U>` (the self type) is not \
+             local and contains an uncovered type parameter, which may not appear before the first \
+             local type; `Pair` is not local because it is `#[upstream]`"
         }
     }
 
@@ -403,7 +451,9 @@ fn orphan_check() {
 
             impl<T> Remote for Pair<Cover<T>, T> { }
         } error_msg {
-            "impl for trait `Remote` violates the orphan rules"
+            "impl for trait `Remote` violates the orphan rules: `Pair<Cover<T>, T>` (the self type) \
+             is not local and contains an uncovered type parameter, which may not appear before the \
+             first local type; `Pair` is not local because it is `#[upstream]`"
         }
     }
     lowering_error! {
@@ -583,7 +633,233 @@ fn fundamental_type_multiple_parameters() {
 
             impl<T> Remote for Box<T, Up> { }
         } error_msg {
-            "impl for trait `Remote` violates the orphan rules"
+            "impl for trait `Remote` violates the orphan rules: `Box<T, Up>` (the self type) is not \
+             local and contains an uncovered type parameter, which may not appear before the first \
+             local type; `Up` is not local because it is `#[upstream]`; `Box` is `#[fundamental]`, \
+             so only its parameters were considered"
+        }
+    }
+}
+
+#[test]
+fn crates() {
+    // The impls of sibling crates are not checked against each other
+    lowering_success! {
+        program {
+            crate std {
+                trait Clone { }
+            }
+            crate a: std {
+                struct A { }
+                impl std::Clone for A { }
+            }
+            crate b: std {
+                struct B { }
+                impl std::Clone for B { }
+            }
+        }
+    }
+
+    // ...but the impls of a crate are checked against those of its dependencies
+    lowering_error! {
+        program {
+            crate std {
+                trait From<T> { }
+                struct String { }
+                impl<T> From<String> for T { }
+            }
+            crate app: std {
+                struct Foo { }
+                impl<T> std::From<T> for Foo { }
+            }
+        }
+        error_msg {
+            "overlapping impls of trait `std::From`: `impl<T> From<String> for T` and `impl<T> std::From<T> for Foo` both apply to `app::Foo: std::From<std::String>`"
+        }
+    }
+
+    // A dependency may add impls to its own types later on
+    lowering_error! {
+        program {
+            crate std {
+                trait Clone { }
+                struct Vec { }
+            }
+            crate app: std {
+                trait Foo { }
+                impl<T> Foo for T where T: std::Clone { }
+                impl Foo for std::Vec { }
+            }
+        }
+        error_msg {
+            "overlapping impls of trait `app::Foo`"
+        }
+    }
+
+    // The orphan rules are checked from the crate of the impl
+    lowering_error! {
+        program {
+            crate std {
+                trait Clone { }
+                struct Vec<T> { }
+            }
+            crate app: std {
+                impl<T> std::Clone for std::Vec<T> { }
+            }
+        }
+        error_msg {
+            "impl for trait `std::Clone` violates the orphan rules"
+        }
+    }
+}
+
+/// The specialization priorities of the impls of a trait are merged from
+/// every crate that sees them.
+#[test]
+fn crate_specialization_priorities() {
+    let db = chalk_integration::db::ChalkDatabase::with(
+        "
+        crate std {
+            trait Foo<T> { }
+            struct Vec<T> { }
+            impl<T> Foo<T> for Vec<T> { }
+        }
+        crate a: std {
+            struct A { }
+            impl std::Foo<A> for std::Vec<A> { }
+        }
+        crate b: std { }
+        ",
+        chalk_integration::SolverChoice::default(),
+    );
+    let program = db.checked_program().unwrap();
+    let priorities = db.coherence().unwrap();
+    let (trait_id, _) = program.trait_data.first_key_value().unwrap();
+    let priority = |binders| {
+        let (&impl_id, _) = program
+            .impl_data
+            .iter()
+            .find(|(_, datum)| datum.binders.len(chalk_integration::interner::ChalkIr) == binders)
+            .unwrap();
+        priorities[trait_id].priority(impl_id)
+    };
+    assert!(priority(0) > priority(1));
+}
+
+/// A crate sees the items of its dependencies as upstream, and does not see
+/// those of the other crates at all.
+#[test]
+fn crate_views() {
+    let db = chalk_integration::db::ChalkDatabase::with(
+        "
+        crate std { struct Vec { } }
+        crate a: std { struct A { } }
+        crate b: std { struct B { } }
+        ",
+        chalk_integration::SolverChoice::default(),
+    );
+    let program = db.program_ir().unwrap();
+    let view = program.crate_view(Some(1));
+    let upstream: Vec<_> = view
+        .adt_data
+        .values()
+        .map(|datum| datum.flags.upstream)
+        .collect();
+    assert_eq!(upstream, [true, false]);
+}
+
+#[test]
+fn specializing_default_assoc_types() {
+    lowering_success! {
+        program {
+            trait Foo { type Assoc; }
+            trait Clone { }
+            struct S<T> { }
+
+            impl<T> Foo for S<T> { default type Assoc = u32; }
+            impl<T> Foo for S<T> where T: Clone { type Assoc = i32; }
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Foo { type Assoc; }
+            trait Clone { }
+            struct S<T> { }
+
+            impl<T> Foo for S<T> { type Assoc = u32; }
+            impl<T> Foo for S<T> where T: Clone { type Assoc = i32; }
+        }
+        error_msg {
+            "impl `impl<T> Foo for S<T>` of trait `Foo` overrides associated type `Assoc`, \
+             which the impl `impl<T> Foo for S<T>` it specializes does not declare `default`"
+        }
+    }
+}
+
+#[test]
+fn specializing_impls_always_applicable() {
+    lowering_error! {
+        program {
+            trait Foo { type Assoc; }
+
+            impl<A, B> Foo for (A, B) { default type Assoc = u32; }
+            impl<T> Foo for (T, T) { type Assoc = i32; }
+        }
+        error_msg {
+            "impl `impl<T> Foo for (T, T)` of trait `Foo` overrides items of the impl \
+             `impl<A, B> Foo for (A, B)` it specializes, but is not always applicable: \
+             it repeats parameter `^0.0`"
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Foo { type Assoc; }
+
+            impl<'a, A, B> Foo for (&'a A, B) { default type Assoc = u32; }
+            impl<T> Foo for (&'static T, u32) { type Assoc = i32; }
+        }
+        error_msg {
+            "impl `impl<T> Foo for (&'static T, u32)` of trait `Foo` overrides items of the impl \
+             `impl<'a, A, B> Foo for (&'a A, B)` it specializes, but is not always applicable: \
+             it specializes on lifetime `'static`"
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Foo { type Assoc; }
+
+            impl<'a, A, B> Foo for (&'a A, B) { default type Assoc = u32; }
+            impl<'a, T> Foo for (&'a T, u32) where T: 'a { type Assoc = i32; }
+        }
+        error_msg {
+            "impl `impl<'a, T> Foo for (&'a T, u32)` of trait `Foo` overrides items of the \
+             impl `impl<'a, A, B> Foo for (&'a A, B)` it specializes, but is not always applicable: \
+             its where clause `^0.1: '^0.0` is not implied by the specialized impl"
+        }
+    }
+
+    // Specializing on traits, or on types, is fine.
+    lowering_success! {
+        program {
+            trait Foo { type Assoc; }
+            trait Clone { }
+            struct S { }
+
+            impl<'a, A, B> Foo for (&'a A, B) { default type Assoc = u32; }
+            impl<'a, T> Foo for (&'a T, S) where T: Clone { type Assoc = i32; }
+        }
+    }
+
+    // Impls that don't override anything are not checked.
+    lowering_success! {
+        program {
+            trait Foo { type Assoc; }
+
+            impl<A, B> Foo for (A, B) { default type Assoc = u32; }
+            impl<T> Foo for (T, T) { }
         }
     }
 }
@@ -0,0 +1,105 @@
+//! Tests for `chalk_integration::compare`, which reports the goals that the
+//! solvers disagree on.
+
+use super::*;
+use chalk_integration::compare::divergent_goals;
+
+#[test]
+fn divergent_goals_issue_727() {
+    let program = "
+        #[non_enumerable] #[object_safe]
+        trait Database {}
+
+        #[non_enumerable]
+        trait QueryGroup
+        {
+            type DynDb: Database + HasQueryGroup<Self>;
+        }
+
+        #[non_enumerable] #[object_safe]
+        trait HasQueryGroup<G>
+        where
+            Self: Database,
+            G: QueryGroup,
+        { }
+
+        struct HelloWorldStorage {}
+
+        impl QueryGroup for HelloWorldStorage {
+            type DynDb = dyn HasQueryGroup<HelloWorldStorage> + 'static;
+        }
+    ";
+    let goals = [
+        "HelloWorldStorage: QueryGroup",
+        "forall<T> { if (FromEnv(T: HasQueryGroup<HelloWorldStorage>)) { T: Database } }",
+        "HelloWorldStorage: Database",
+    ];
+    let divergent = divergent_goals(program, goals, &SolverChoice::all_defaults()).unwrap();
+    assert_eq!(divergent.len(), 1);
+    assert_eq!(divergent[0].goal(), goals[1]);
+
+    let db = ChalkDatabase::with(program, SolverChoice::default());
+    db.with_program(|_| {
+        expect![[r#"
+            forall<T> { if (FromEnv(T: HasQueryGroup<HelloWorldStorage>)) { T: Database } }
+              SLG { max_size: 10, expected_answers: None }: Unique
+              Recursive { overflow_depth: 100, caching_enabled: true, max_size: 30, parallel: false }: Ambiguous; no inference guidance
+        "#]]
+        .assert_eq(&divergent[0].to_string());
+    });
+}
+
+#[test]
+fn divergent_goals_program_error() {
+    let error = divergent_goals(
+        "struct Foo { x: Bar }",
+        ["Foo: Sized"],
+        &SolverChoice::all_defaults(),
+    )
+    .unwrap_err();
+    assert_eq!(error.to_string(), "invalid parameter name `Bar`");
+}
+
+/// A goal that cannot be lowered is reported, and the goals after it are
+/// still compared.
+#[test]
+fn divergent_goals_lowering_error() {
+    let program = "
+        #[non_enumerable] #[object_safe]
+        trait Database {}
+
+        #[non_enumerable]
+        trait QueryGroup
+        {
+            type DynDb: Database + HasQueryGroup<Self>;
+        }
+
+        #[non_enumerable] #[object_safe]
+        trait HasQueryGroup<G>
+        where
+            Self: Database,
+            G: QueryGroup,
+        { }
+
+        struct HelloWorldStorage {}
+
+        impl QueryGroup for HelloWorldStorage {
+            type DynDb = dyn HasQueryGroup<HelloWorldStorage> + 'static;
+        }
+    ";
+    let goals = [
+        "HelloWorldStorage: Bar",
+        "forall<T> { if (FromEnv(T: HasQueryGroup<HelloWorldStorage>)) { T: Database } }",
+    ];
+    let divergent = divergent_goals(program, goals, &SolverChoice::all_defaults()).unwrap();
+    let reported: Vec<_> = divergent
+        .iter()
+        .map(|divergence| divergence.goal())
+        .collect();
+    assert_eq!(reported, goals);
+    expect![[r#"
+        HelloWorldStorage: Bar
+          error: invalid trait name `Bar`
+    "#]]
+    .assert_eq(&divergent[0].to_string());
+}
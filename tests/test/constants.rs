@@ -147,3 +147,221 @@ fn placeholders_eq() {
         }
     }
 }
+
+#[test]
+fn const_exprs_evaluate() {
+    test! {
+        program {
+            struct S<const N> {}
+
+            trait Trait {}
+
+            impl Trait for S<3> {}
+        }
+
+        goal {
+            [u32; 2 + 1] = [u32; 3]
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            S<{ 2 + 1 }>: Trait
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            S<{ (7 - 1) * 2 / 4 }>: Trait
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            S<{ 10 % 4 }>: Trait
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            [u32; 1 - 2] = [u32; 0]
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            [u32; 1 / 0] = [u32; 0]
+        } yields {
+            expect![["No possible solution"]]
+        }
+    }
+}
+
+#[test]
+fn const_exprs_structural() {
+    test! {
+        goal {
+            forall<const N> {
+                [u32; N + 1] = [u32; N + 1]
+            }
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            forall<const N> {
+                [u32; N + 1] = [u32; 1 + N]
+            }
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            forall<const N> {
+                [u32; N + 1] = [u32; 3]
+            }
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            forall<const N> {
+                exists<const M> {
+                    [u32; N * 2] = [u32; M * 2]
+                }
+            }
+        } yields {
+            expect![["Unique; substitution [?0 := !1_0]"]]
+        }
+
+        goal {
+            exists<const M> {
+                [u32; M + 1] = [u32; 3]
+            }
+        } yields {
+            expect![["Ambiguous; no inference guidance"]]
+        }
+
+        goal {
+            exists<const M> {
+                M = 2, [u32; M + 1] = [u32; 3]
+            }
+        } yields[SolverChoice::recursive_default()] {
+            expect![["Unique; substitution [?0 := 2]"]]
+        } yields[SolverChoice::slg_default()] {
+            // SLG relates the array types before `M` is known
+            expect![["Ambiguous; definite substitution [?0 := 2]"]]
+        }
+    }
+}
+
+#[test]
+fn typed_consts() {
+    test! {
+        program {
+            struct Flag<const B: bool> {}
+            struct Letter<const C: char> {}
+            struct Byte<const N: u8> {}
+            struct Offset<const N: i32> {}
+            struct Name<const S: &'static str> {}
+
+            trait Trait {}
+
+            impl Trait for Flag<true> {}
+            impl Trait for Letter<'x'> {}
+            impl Trait for Byte<255> {}
+            impl Trait for Offset<-1> {}
+            impl Trait for Name<"chalk"> {}
+        }
+
+        goal {
+            exists<const B: bool> {
+                Flag<B>: Trait
+            }
+        } yields {
+            expect![["Unique; substitution [?0 := true]"]]
+        }
+
+        goal {
+            Flag<false>: Trait
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            exists<const C: char> {
+                Letter<C>: Trait
+            }
+        } yields {
+            expect![["Unique; substitution [?0 := 'x']"]]
+        }
+
+        goal {
+            Byte<{ 254 + 1 }>: Trait
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            Byte<{ 255 + 1 }>: Trait
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            Offset<{ 1 - 2 }>: Trait
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            exists<const S: &'static str> {
+                Name<S>: Trait
+            }
+        } yields {
+            expect![[r#"Unique; substitution [?0 := "chalk"]"#]]
+        }
+    }
+}
+
+#[test]
+fn typed_consts_mismatch() {
+    test! {
+        program {
+            struct Flag<const B: bool> {}
+            struct Byte<const N: u8> {}
+
+            trait Trait {}
+
+            impl Trait for Flag<true> {}
+            impl Trait for Byte<1> {}
+        }
+
+        // Arguments of the wrong type are not equal to anything.
+        goal {
+            Flag<1>: Trait
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            Byte<1u16>: Trait
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            Byte<1u8>: Trait
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            exists<const N: u16> {
+                Byte<N>: Trait
+            }
+        } yields {
+            expect![["No possible solution"]]
+        }
+    }
+}
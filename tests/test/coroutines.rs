@@ -105,7 +105,7 @@ fn coroutine_test() {
                 }
             }
         } yields {
-            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([]), goal: '!2_0: '!2_1 }, InEnvironment { environment: Env([]), goal: '!2_1: '!2_0 }]"]]
+            expect![["No possible solution"]]
         }
 
         goal {
@@ -171,6 +171,36 @@ fn overflow() {
     }
 }
 
+/// Results that depend on a goal that overflowed are not cached: `X: B`
+/// overflows when solved as a subgoal of `X: A`, but not on its own.
+#[test]
+fn overflow_not_cached() {
+    test! {
+        program {
+            trait A { }
+            trait B { }
+            trait C { }
+            struct X { }
+
+            impl A for X where X: B { }
+            impl B for X where X: C { }
+            impl C for X { }
+        }
+
+        goal {
+            X: A
+        } yields[SolverChoice::recursive(30, 2)] {
+            expect![["Ambiguous; no inference guidance"]]
+        }
+
+        goal {
+            X: B
+        } yields[SolverChoice::recursive(30, 2)] {
+            expect![["Unique"]]
+        }
+    }
+}
+
 #[test]
 fn overflow_universe() {
     test! {
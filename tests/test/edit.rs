@@ -0,0 +1,76 @@
+//! Tests for editing the items of a loaded program.
+
+use super::*;
+use chalk_integration::edit::ItemEdit;
+
+const PROGRAM: &str = "
+// a program
+trait Clone {}
+struct Foo {}
+struct Vec<T> {}
+impl<T> Clone for Vec<T> where T: Clone {}
+";
+
+#[test]
+fn edit_items() {
+    let edit = |edit: ItemEdit<'_>| edit.apply(PROGRAM).unwrap();
+    expect![[r#"
+
+        // a program
+        trait Clone {}
+        struct Foo {}
+        struct Vec<T> {}
+        impl<T> Clone for Vec<T> where T: Clone {}
+        impl Clone for Foo {}
+    "#]]
+    .assert_eq(&edit(ItemEdit::Add("impl Clone for Foo {}")));
+    expect![[r#"
+
+        // a program
+        trait Clone {}
+        struct Vec<T> {}
+        impl<T> Clone for Vec<T> where T: Clone {}
+    "#]]
+    .assert_eq(&edit(ItemEdit::Remove("Foo")));
+    expect![[r#"
+
+        // a program
+        trait Clone {}
+        struct Foo {}
+        struct Vec<T> {}
+        impl<T>  Clone for Vec<T> {}
+    "#]]
+    .assert_eq(&edit(ItemEdit::Replace("impl<T>  Clone for Vec<T> {}")));
+
+    let error = |edit: ItemEdit<'_>| edit.apply(PROGRAM).unwrap_err().to_string();
+    assert_eq!(error(ItemEdit::Remove("Bar")), "no item named `Bar`");
+    assert_eq!(
+        error(ItemEdit::Replace("impl Clone for Foo {}")),
+        "no item named `impl Clone for Foo`"
+    );
+    assert_eq!(
+        error(ItemEdit::Replace("struct Foo {} struct Bar {}")),
+        "expected exactly one item"
+    );
+}
+
+#[test]
+fn edit_program() {
+    let solve = |db: &ChalkDatabase| {
+        let goal = peeled_goal(db, "Vec<Foo>: Clone");
+        format_solution(db.solve(&goal), ChalkIr)
+    };
+    let mut db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    assert_eq!(solve(&db), "No possible solution");
+
+    let text = db
+        .edit_program(ItemEdit::Add("impl Clone for Foo {}"))
+        .unwrap();
+    assert_eq!(&*text, PROGRAM);
+    db.checked_program().unwrap();
+    assert_eq!(solve(&db), "Unique");
+
+    db.edit_program(ItemEdit::Add("impl Clone for Foo {}"))
+        .unwrap();
+    assert!(db.checked_program().is_err());
+}
@@ -333,7 +333,7 @@ fn dyn_binders_reverse() {
                 >
             }
         } yields {
-            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([]), goal: '!5_0: '!5_1 }, InEnvironment { environment: Env([]), goal: '!5_1: '!5_0 }]"]]
+            expect![["No possible solution"]]
         }
 
         // Note: these constraints are ultimately unresolveable (we
@@ -345,7 +345,7 @@ fn dyn_binders_reverse() {
                 >
             }
         } yields {
-            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([]), goal: '!3_0: '!3_1 }, InEnvironment { environment: Env([]), goal: '!3_1: '!3_0 }]"]]
+            expect![["No possible solution"]]
         }
 
         // Note: ordering of parameters is reversed here, but that's no problem
@@ -397,11 +397,8 @@ fn dyn_associated_type_binding() {
                     <dyn FnOnce<(), Output = i32> + 's as FnOnce<()>>::Output = T
                 }
             }
-        } yields[SolverChoice::recursive_default()] {
+        } yields {
             expect![["Unique; substitution [?0 := Int(I32)]"]]
-        } yields[SolverChoice::slg_default()] {
-            // #234
-            expect![["Ambiguous; no inference guidance"]]
         }
     }
 }
@@ -0,0 +1,91 @@
+//! Tests for collecting the tables of the SLG solver.
+
+use super::*;
+use chalk_engine::forest::ForestStats;
+use chalk_engine::solve::SLGSolver;
+use chalk_solve::Solver;
+
+const PROGRAM: &str = "
+    trait Foo { }
+    trait Bar { }
+    struct A { }
+    struct B { }
+    struct C { }
+    struct S<T> { }
+
+    impl Bar for A { }
+    impl Bar for B { }
+    impl<T> Foo for S<T> where T: Bar { }
+";
+
+/// Solves each of `goals` in turn with `solver`, returning the solutions
+/// and the memory statistics after each of them.
+fn solve_all(
+    solver: &mut SLGSolver<ChalkIr>,
+    goals: &[&str],
+) -> Vec<(Option<String>, ForestStats)> {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::slg_default());
+    goals
+        .iter()
+        .map(|goal| {
+            let solution = solver
+                .solve(&db, &peeled_goal(&db, goal))
+                .map(|solution| solution.display(ChalkIr).to_string());
+            (solution, solver.memory_stats())
+        })
+        .collect()
+}
+
+#[test]
+fn tables_are_kept_by_default() {
+    let mut solver = SLGSolver::new(10, None);
+    let results = solve_all(&mut solver, &["S<A>: Foo", "S<B>: Foo", "S<C>: Foo"]);
+    let stats = results.last().unwrap().1;
+    assert_eq!(stats.tables, 15);
+    assert_eq!(stats.complete_tables, 15);
+    assert_eq!(stats.answers, 6);
+    assert_eq!(stats.strands, 0);
+    assert_eq!(stats.collected_tables, 0);
+}
+
+#[test]
+fn collect_garbage() {
+    let mut solver = SLGSolver::new(10, None);
+    let goals = ["S<A>: Foo", "S<B>: Foo", "S<C>: Foo"];
+    let before = solve_all(&mut solver, &goals);
+    let tables = solver.memory_stats().tables;
+    assert_eq!(solver.collect_garbage(), tables);
+    assert_eq!(
+        solver.memory_stats(),
+        ForestStats {
+            collected_tables: tables,
+            ..ForestStats::default()
+        }
+    );
+
+    // The goals are solved again, with the same solutions.
+    let after = solve_all(&mut solver, &goals);
+    let solutions = |results: &[(Option<String>, ForestStats)]| {
+        results.iter().map(|r| r.0.clone()).collect::<Vec<_>>()
+    };
+    assert_eq!(solutions(&before), solutions(&after));
+    assert_eq!(after.last().unwrap().1.tables, tables);
+}
+
+#[test]
+fn table_capacity() {
+    let mut solver = SLGSolver::new(10, None).with_table_capacity(2);
+    let goals = ["S<A>: Foo", "S<B>: Foo", "S<C>: Foo", "S<A>: Foo"];
+    let results = solve_all(&mut solver, &goals);
+    let solutions: Vec<_> = results.iter().map(|r| r.0.as_deref()).collect();
+    assert_eq!(
+        solutions,
+        [Some("Unique"), Some("Unique"), None, Some("Unique"),]
+    );
+
+    // Each query needs five tables. Before each query, all but two of the
+    // tables of the previous queries are collected.
+    let tables: Vec<_> = results.iter().map(|r| r.1.tables).collect();
+    assert_eq!(tables, [5, 7, 7, 7]);
+    assert_eq!(results.last().unwrap().1.collected_tables, 13);
+}
@@ -157,3 +157,186 @@ fn higher_ranked_implied_bounds() {
         }
     }
 }
+
+#[test]
+fn implied_bounds_from_adt_fields() {
+    test! {
+        program {
+            struct Ref<'a, T> { r: &'a T }
+            enum Either<'a, T, U> { Left(Ref<'a, T>), Right(U) }
+        }
+
+        // The outlives bound can be proven both from the environment and
+        // with a region constraint; SLG does not pick the former.
+        goal {
+            forall<'a, T> {
+                if (FromEnv(Ref<'a, T>)) {
+                    T: 'a
+                }
+            }
+        } yields[SolverChoice::recursive_default()] {
+            expect![["Unique"]]
+        } yields[SolverChoice::slg_default()] {
+            expect![["Ambiguous; no inference guidance"]]
+        }
+
+        goal {
+            forall<'a, T, U> {
+                if (FromEnv(Either<'a, T, U>)) {
+                    T: 'a
+                }
+            }
+        } yields[SolverChoice::recursive_default()] {
+            expect![["Unique"]]
+        } yields[SolverChoice::slg_default()] {
+            expect![["Ambiguous; no inference guidance"]]
+        }
+
+        goal {
+            forall<'a, T, U> {
+                if (FromEnv(Either<'a, T, U>)) {
+                    U: 'a
+                }
+            }
+        } yields {
+            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([for<> FromEnv(Either<'!1_0, !1_1, !1_2>)]), goal: !1_2: '!1_0 }]"]]
+        }
+    }
+}
+
+#[test]
+fn implied_bounds_from_fn_signature() {
+    test! {
+        program {
+            trait Clone { }
+            struct Set<T> where T: Clone { }
+
+            fn insert<T>(set: Set<T>, value: T);
+            fn make<T>() -> Set<T>;
+            fn len<T>(value: T) -> u32;
+        }
+
+        goal {
+            forall<T> {
+                if (FromEnv(insert<T>)) {
+                    T: Clone
+                }
+            }
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            forall<T> {
+                if (FromEnv(make<T>)) {
+                    T: Clone
+                }
+            }
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            forall<T> {
+                if (FromEnv(len<T>)) {
+                    T: Clone
+                }
+            }
+        } yields {
+            expect![["No possible solution"]]
+        }
+    }
+}
+
+#[test]
+fn implied_bounds_from_projection() {
+    test! {
+        program {
+            trait Clone { }
+            trait Iterator { type Item: Clone; }
+            struct Set<T> where T: Clone { }
+        }
+
+        goal {
+            forall<T> {
+                if (FromEnv(<T as Iterator>::Item)) {
+                    T: Iterator
+                }
+            }
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            forall<T> {
+                if (FromEnv(<T as Iterator>::Item)) {
+                    <T as Iterator>::Item: Clone
+                }
+            }
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            forall<T> {
+                if (FromEnv(Set<<T as Iterator>::Item>)) {
+                    <T as Iterator>::Item: Clone
+                }
+            }
+        } yields {
+            expect![["Unique"]]
+        }
+    }
+}
+
+#[test]
+fn implied_bounds_from_references() {
+    test! {
+        program {
+            trait Clone { }
+            struct Set<T> where T: Clone { }
+        }
+
+        goal {
+            forall<'a, T> {
+                if (FromEnv(&'a Set<T>)) {
+                    T: Clone
+                }
+            }
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            forall<T> {
+                if (FromEnv((u32, [Set<T>]))) {
+                    T: Clone
+                }
+            }
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            forall<'a, T> {
+                if (FromEnv(&'a T)) {
+                    T: 'a
+                }
+            }
+        } yields[SolverChoice::recursive_default()] {
+            expect![["Unique"]]
+        } yields[SolverChoice::slg_default()] {
+            expect![["Ambiguous; no inference guidance"]]
+        }
+
+        goal {
+            forall<'a, 'b, T> {
+                if (FromEnv(&'a [&'b Set<T>; 2])) {
+                    T: Clone
+                }
+            }
+        } yields {
+            expect![["Unique"]]
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Tests for the leak check, which rejects answers that relate a placeholder
+//! to another placeholder, or require it to outlive a region from an outer
+//! universe (see `chalk_solve::solve::leak_check`).
+//!
+//! The outermost quantifiers of a test goal are peeled, and their
+//! placeholders stand for the parameters of an item, which the leak check
+//! leaves alone. The goals below put the `forall`s they check after a
+//! `Foo<..>: Any` goal, so that they are solved like any other.
+
+use super::*;
+
+#[test]
+fn higher_ranked_fn_ptr_impl() {
+    test! {
+        program {
+            trait Trait { }
+            trait Static { }
+            trait HigherRanked { }
+
+            impl<'b> Trait for fn(&'b u8) { }
+            impl Static for fn(&'static u8) { }
+            impl HigherRanked for for<'a> fn(&'a u8) { }
+        }
+
+        // `'b` would have to be equal to every `'a`.
+        goal {
+            for<'a> fn(&'a u8): Trait
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            fn(&'static u8): Trait
+        } yields {
+            expect![["Unique; for<?U0> { lifetime constraints [InEnvironment { environment: Env([]), goal: '^0.0: 'static }, InEnvironment { environment: Env([]), goal: 'static: '^0.0 }] }"]]
+        }
+
+        // `'!1_0: 'static` is left to the region solver.
+        goal {
+            for<'a> fn(&'a u8): Static
+        } yields {
+            expect![["Unique; for<?U1> { lifetime constraints [InEnvironment { environment: Env([]), goal: '!1_0: 'static }, InEnvironment { environment: Env([]), goal: 'static: '^0.0 }] }"]]
+        }
+
+        goal {
+            for<'a> fn(&'a u8): HigherRanked
+        } yields {
+            expect![["Unique"]]
+        }
+
+        // The leak is found in the subgoal whose `forall` it comes from.
+        goal {
+            exists<T> {
+                T = u8,
+                for<'a> fn(&'a T): Trait
+            }
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            not { for<'a> fn(&'a u8): Trait }
+        } yields {
+            expect![["Unique"]]
+        }
+    }
+}
+
+#[test]
+fn placeholder_outlives_outer_region() {
+    test! {
+        program {
+            struct Foo<'a> { }
+            trait Any { }
+            impl<T> Any for T { }
+        }
+
+        goal {
+            exists<'x> { Foo<'x>: Any, forall<'a> { 'a: 'x } }
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            exists<'x> { Foo<'x>: Any, forall<'a> { Foo<'x> = Foo<'a> } }
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            exists<'x> { fn(&'x u8) = for<'a> fn(&'a u8) }
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        // An outer region may outlive the placeholder: `'x` can be `'static`.
+        goal {
+            exists<'x> { Foo<'x>: Any, forall<'a> { 'x: 'a } }
+        } yields {
+            expect![["Unique; for<?U0> { substitution [?0 := '^0.0], lifetime constraints [InEnvironment { environment: Env([]), goal: '^0.0: '!1_0 }] }"]]
+        }
+
+        // Region variables created inside the `forall` can name the placeholder.
+        goal {
+            Foo<'static>: Any, forall<'a> { exists<'x> { Foo<'x> = Foo<'a> } }
+        } yields {
+            expect![["Unique"]]
+        }
+
+        // Type placeholders are left to the region solver.
+        goal {
+            exists<'x> { Foo<'x>: Any, forall<T> { T: 'x } }
+        } yields {
+            expect![["Unique; for<?U0> { substitution [?0 := '^0.0], lifetime constraints [InEnvironment { environment: Env([]), goal: !1_0: '^0.0 }] }"]]
+        }
+    }
+}
+
+#[test]
+fn placeholder_outlives_placeholder() {
+    test! {
+        program {
+            struct Foo<'a> { }
+            trait Any { }
+            impl<T> Any for T { }
+        }
+
+        goal {
+            Foo<'static>: Any, forall<'a, 'b> { 'a: 'b }
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            forall<'a> { Foo<'a>: Any, forall<'b> { 'a: 'b } }
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            forall<'a> { Foo<'a>: Any, forall<'b> { 'b: 'a } }
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            Foo<'static>: Any, forall<'a> { 'a: 'a }
+        } yields {
+            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([]), goal: '!1_0: '!1_0 }]"]]
+        }
+
+        goal {
+            Foo<'static>: Any, forall<'a> { 'a: 'static }
+        } yields {
+            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([]), goal: '!1_0: 'static }]"]]
+        }
+
+        // The placeholders of the peeled `forall` are left to the region solver.
+        goal {
+            forall<'a, 'b> { 'a: 'b }
+        } yields {
+            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([]), goal: '!1_0: '!1_1 }]"]]
+        }
+    }
+}
+
+#[test]
+fn placeholder_outlives_from_environment() {
+    test! {
+        program {
+            struct Foo<'a> { }
+            trait Any { }
+            impl<T> Any for T { }
+        }
+
+        goal {
+            forall<'a> { Foo<'a>: Any, forall<'b> { if ('b: 'a) { &'b u8: 'a } } }
+        } yields {
+            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([for<> '!2_0: '!1_0]), goal: (&'!2_0 Uint(U8)): '!1_0 }]"]]
+        }
+
+        goal {
+            forall<'a> { Foo<'a>: Any, forall<'b> { &'b u8: 'a } }
+        } yields {
+            expect![["No possible solution"]]
+        }
+    }
+}
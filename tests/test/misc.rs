@@ -817,14 +817,18 @@ fn env_bound_vars() {
         } yields[SolverChoice::recursive_default()] {
             expect![[r#"Unique; for<?U0> { substitution [?0 := '^0.0] }"#]]
         }
+        // `FromEnv(&'a ())` implies `(): 'a`, which the recursive solver
+        // uses instead of the region constraint.
         goal {
             exists<'a> {
                 if (FromEnv(&'a ())) {
                     WellFormed(&'a ())
                 }
             }
-        } yields {
-            expect![["Unique; for<?U0> { substitution [?0 := '^0.0], lifetime constraints [InEnvironment { environment: Env([]), goal: 0: '^0.0 }] }"]]
+        } yields[SolverChoice::slg_default()] {
+            expect![["Unique; for<?U0> { substitution [?0 := '^0.0], lifetime constraints [InEnvironment { environment: Env([for<> FromEnv((&'^1.0 0))]), goal: 0: '^0.0 }] }"]]
+        } yields[SolverChoice::recursive_default()] {
+            expect![[r#"Unique; for<?U0> { substitution [?0 := '^0.0] }"#]]
         }
     }
 }
@@ -878,3 +882,41 @@ fn coinductive_wrapper() {
        }
     */
 }
+
+/// Both solvers enumerate answers one at a time, by pursuing each impl
+/// (and, recursively, each answer to an impl's where clauses) separately.
+#[test]
+fn enumerate_answers_through_where_clauses() {
+    test! {
+        program {
+            struct A { }
+            struct B { }
+            struct Vec<T> { }
+
+            trait Foo { }
+            impl Foo for A { }
+            impl Foo for B { }
+            impl<T> Foo for Vec<T> where T: Foo { }
+
+            trait Bar<T> { }
+            impl Bar<A> for B { }
+            impl Bar<B> for A { }
+        }
+
+        goal {
+            exists<T, U> { T: Bar<U> }
+        } yields_all {
+            expect![["substitution [?0 := B, ?1 := A]"]],
+            expect![["substitution [?0 := A, ?1 := B]"]]
+        }
+
+        goal {
+            exists<T> { Vec<T>: Foo }
+        } yields_first {
+            expect![["substitution [?0 := A]"]],
+            expect![["substitution [?0 := B]"]],
+            expect![["substitution [?0 := Vec<A>]"]],
+            expect![["substitution [?0 := Vec<B>]"]]
+        }
+    }
+}
@@ -11,9 +11,10 @@ use chalk_integration::interner::ChalkIr;
 use chalk_integration::lowering::lower_goal;
 use chalk_integration::query::LoweringDatabase;
 use chalk_integration::SolverChoice;
-use chalk_ir::Constraints;
+use chalk_ir::{Constraints, Goal, InEnvironment, UCanonical};
 use chalk_solve::ext::*;
 use chalk_solve::logging::with_tracing_logs;
+use chalk_solve::solve::budget::Budget;
 use chalk_solve::RustIrDatabase;
 use chalk_solve::Solution;
 
@@ -35,11 +36,6 @@ fn format_solution(mut result: Option<Solution<ChalkIr>>, interner: ChalkIr) ->
     }
 }
 
-pub fn assert_result(result: Option<Solution<ChalkIr>>, expected: &Expect, interner: ChalkIr) {
-    let result = format_solution(result, interner);
-    expected.assert_eq(&result);
-}
-
 pub fn assert_result_str(result: Option<Solution<ChalkIr>>, expected: &str, interner: ChalkIr) {
     let result = format_solution(result, interner);
     assert_same(&result, expected);
@@ -56,14 +52,51 @@ pub enum TestGoal<T = Expect> {
     First(Vec<T>),
 }
 
+// what is done with each goal, whose outcome is compared to the expected one
+#[derive(Clone)]
+pub enum TestMode {
+    // solve the goal, and display the solution
+    Solve,
+    // solve the goal within the budget, and display the solution, or how the
+    // budget was exhausted
+    Budget(Budget),
+    // solve the goal, check the region constraints of the solution (see
+    // `Solution::solve_regions`), and display the solution, or the constraint
+    // that cannot hold
+    SolveRegions,
+    // solve the goal, and display the statistics of the solver afterwards,
+    // which add up over the goals solved one after the other by the same
+    // solver
+    Stats,
+    // display the proof tree of the goal (see `Solver::explain`), and check
+    // that it reaches the solution
+    Explain,
+}
+
 macro_rules! test {
     (program $program:tt $($goals:tt)*) => {{
         let (program, goals) = parse_test_data!(program $program $($goals)*);
-        solve_goal(program, goals, true)
+        solve_goal(program, goals, true, TestMode::Solve)
     }};
     (disable_coherence; program $program:tt $($goals:tt)*) => {{
         let (program, goals) = parse_test_data!(program $program $($goals)*);
-        solve_goal(program, goals, false)
+        solve_goal(program, goals, false, TestMode::Solve)
+    }};
+    (budget($budget:expr); program $program:tt $($goals:tt)*) => {{
+        let (program, goals) = parse_test_data!(program $program $($goals)*);
+        solve_goal(program, goals, true, TestMode::Budget($budget))
+    }};
+    (solve_regions; program $program:tt $($goals:tt)*) => {{
+        let (program, goals) = parse_test_data!(program $program $($goals)*);
+        solve_goal(program, goals, true, TestMode::SolveRegions)
+    }};
+    (stats; program $program:tt $($goals:tt)*) => {{
+        let (program, goals) = parse_test_data!(program $program $($goals)*);
+        solve_goal(program, goals, true, TestMode::Stats)
+    }};
+    (explain; program $program:tt $($goals:tt)*) => {{
+        let (program, goals) = parse_test_data!(program $program $($goals)*);
+        solve_goal(program, goals, true, TestMode::Explain)
     }};
 
     // If `program` is omitted, default to an empty one.
@@ -96,9 +129,8 @@ macro_rules! parse_test_data {
               @unparsed_goals[$($unparsed_goals)*])
     };
 
-    // goal { G } yields_all { "Y1", "Y2", ... , "YN" } -- test that the SLG
-    // solver gets exactly N answers in this order (the recursive solver can't
-    // return multiple answers)
+    // goal { G } yields_all { "Y1", "Y2", ... , "YN" } -- test that both
+    // solvers get exactly N answers in this order
     (@program[$program:tt] @parsed_goals[$($parsed_goals:tt)*] @unparsed_goals[
         goal $goal:tt yields_all { $($expected:expr),* }
         $($unparsed_goals:tt)*
@@ -106,13 +138,13 @@ macro_rules! parse_test_data {
         parse_test_data!(@program[$program]
               @parsed_goals[
                   $($parsed_goals)*
-                      (stringify!($goal), vec![SolverChoice::slg_default()], TestGoal::All(vec![$($expected),*]))
+                      (stringify!($goal), vec![SolverChoice::slg_default(), SolverChoice::recursive_default()], TestGoal::All(vec![$($expected),*]))
               ]
               @unparsed_goals[$($unparsed_goals)*])
     };
 
-    // goal { G } yields_first { "Y1", "Y2", ... , "YN" } -- test that the SLG
-    // solver gets at least N same first answers
+    // goal { G } yields_first { "Y1", "Y2", ... , "YN" } -- test that both
+    // solvers get at least N same first answers
     (@program[$program:tt] @parsed_goals[$($parsed_goals:tt)*] @unparsed_goals[
         goal $goal:tt yields_first { $($expected:expr),* }
         $($unparsed_goals:tt)*
@@ -120,7 +152,7 @@ macro_rules! parse_test_data {
         parse_test_data!(@program[$program]
               @parsed_goals[
                   $($parsed_goals)*
-                      (stringify!($goal), vec![SolverChoice::default()], TestGoal::First(vec![$($expected),*]))
+                      (stringify!($goal), vec![SolverChoice::slg_default(), SolverChoice::recursive_default()], TestGoal::First(vec![$($expected),*]))
               ]
               @unparsed_goals[$($unparsed_goals)*])
     };
@@ -237,6 +269,7 @@ fn solve_goal(
     program_text: &str,
     goals: Vec<(&str, Vec<SolverChoice>, TestGoal)>,
     coherence: bool,
+    mode: TestMode,
 ) {
     with_tracing_logs(|| {
         println!("program {}", program_text);
@@ -256,105 +289,102 @@ fn solve_goal(
 
         for (goal_text, solver_choices, expected) in goals {
             let solver_choices = &*solver_choices;
-            let solver_choice = match solver_choices {
-                [] => panic!("No solvers?"),
-                [x] => *x,
-                _ => {
-                    let expected = match expected {
-                        TestGoal::Aggregated(x) => x,
-                        _ => todo!("solver comparison only supported for `Aggregated` goals"),
-                    };
-
-                    solve_aggregated(
-                        &mut db,
-                        program.clone(),
-                        goal_text,
-                        solver_choices,
-                        expected,
-                    );
-                    continue;
+            match (solver_choices, expected) {
+                ([], _) => panic!("No solvers?"),
+                ([_, _, ..], TestGoal::Aggregated(expected)) => solve_aggregated(
+                    &mut db,
+                    program.clone(),
+                    goal_text,
+                    solver_choices,
+                    &mode,
+                    expected,
+                ),
+                (_, expected) => {
+                    for &solver_choice in solver_choices {
+                        solve_with(
+                            &mut db,
+                            &program,
+                            goal_text,
+                            solver_choice,
+                            &mode,
+                            &expected,
+                        );
+                    }
                 }
-            };
+            }
+        }
+    })
+}
 
-            match (&solver_choice, &expected) {
-                (SolverChoice::Recursive { .. }, TestGoal::All(_))
-                | (SolverChoice::Recursive { .. }, TestGoal::First(_)) => {
-                    panic!("cannot test the recursive solver with yields_first or yields_all");
-                }
-                _ => {}
-            };
+fn solve_with(
+    db: &mut ChalkDatabase,
+    program: &Arc<Program>,
+    goal_text: &str,
+    solver_choice: SolverChoice,
+    mode: &TestMode,
+    expected: &TestGoal,
+) {
+    if db.solver_choice() != solver_choice {
+        db.set_solver_choice(solver_choice);
+    }
 
-            if db.solver_choice() != solver_choice {
-                db.set_solver_choice(solver_choice);
+    chalk_integration::tls::set_current_program(program, || {
+        println!("----------------------------------------------------------------------");
+        println!("goal {}", goal_text);
+        assert!(goal_text.starts_with('{'));
+        assert!(goal_text.ends_with('}'));
+        let peeled_goal = peeled_goal(db, &goal_text[1..goal_text.len() - 1]);
+
+        println!("using solver: {:?}", solver_choice);
+        match &expected {
+            TestGoal::Aggregated(expected) => {
+                expected.assert_eq(&run_goal(db, &peeled_goal, mode));
             }
-
-            chalk_integration::tls::set_current_program(&program, || {
-                println!("----------------------------------------------------------------------");
-                println!("goal {}", goal_text);
-                assert!(goal_text.starts_with('{'));
-                assert!(goal_text.ends_with('}'));
-                let goal = lower_goal(
-                    &*chalk_parse::parse_goal(&goal_text[1..goal_text.len() - 1]).unwrap(),
-                    &*program,
-                )
-                .unwrap();
-
-                println!("using solver: {:?}", solver_choice);
-                let peeled_goal = goal.into_peeled_goal(db.interner());
-                match &expected {
-                    TestGoal::Aggregated(expected) => {
-                        let result = db.solve(&peeled_goal);
-                        assert_result(result, expected, db.interner());
-                    }
-                    TestGoal::All(expected) => {
-                        let mut expected = expected.iter();
-                        assert!(
-                            db.solve_multiple(&peeled_goal, &mut |result, next_result| {
-                                match expected.next() {
-                                    Some(expected) => {
-                                        let actual = format!(
-                                            "{}",
-                                            result.as_ref().map(|v| v.display(ChalkIr))
-                                        );
-                                        expected.assert_eq(&actual)
-                                    }
-                                    None => {
-                                        assert!(!next_result, "Unexpected next solution");
-                                    }
-                                }
-                                true
-                            }),
-                            "Not all solutions processed"
-                        );
-                        if expected.next().is_some() {
-                            panic!("Not all solutions processed");
-                        }
-                    }
-                    TestGoal::First(expected) => {
-                        let mut expected = expected.iter();
-                        db.solve_multiple(&peeled_goal, &mut |result, next_result| match expected
-                            .next()
-                        {
-                            Some(solution) => {
+            TestGoal::All(_) | TestGoal::First(_) if !matches!(mode, TestMode::Solve) => {
+                panic!("`yields_all` and `yields_first` can only solve goals")
+            }
+            TestGoal::All(expected) => {
+                let mut expected = expected.iter();
+                assert!(
+                    db.solve_multiple(&peeled_goal, &mut |result, next_result| {
+                        match expected.next() {
+                            Some(expected) => {
                                 let actual =
                                     format!("{}", result.as_ref().map(|v| v.display(ChalkIr)));
-                                solution.assert_eq(&actual);
-                                if !next_result {
-                                    assert!(
-                                        expected.next().is_none(),
-                                        "Not enough solutions found"
-                                    );
-                                }
-                                true
+                                expected.assert_eq(&actual)
                             }
-                            None => false,
-                        });
-                        assert!(expected.next().is_none(), "Not enough solutions found");
-                    }
+                            None => {
+                                assert!(!next_result, "Unexpected next solution");
+                            }
+                        }
+                        true
+                    }),
+                    "Not all solutions processed"
+                );
+                if expected.next().is_some() {
+                    panic!("Not all solutions processed");
                 }
-            });
+            }
+            TestGoal::First(expected) => {
+                let mut expected = expected.iter();
+                db.solve_multiple(
+                    &peeled_goal,
+                    &mut |result, next_result| match expected.next() {
+                        Some(solution) => {
+                            let actual = format!("{}", result.as_ref().map(|v| v.display(ChalkIr)));
+                            solution.assert_eq(&actual);
+                            if !next_result {
+                                assert!(expected.next().is_none(), "Not enough solutions found");
+                            }
+                            true
+                        }
+                        None => false,
+                    },
+                );
+                assert!(expected.next().is_none(), "Not enough solutions found");
+            }
         }
-    })
+    });
 }
 
 fn solve_aggregated(
@@ -362,6 +392,7 @@ fn solve_aggregated(
     program: Arc<Program>,
     goal_text: &str,
     choices: &[SolverChoice],
+    mode: &TestMode,
     expected: Expect,
 ) {
     let mut solutions = vec![];
@@ -376,16 +407,10 @@ fn solve_aggregated(
             println!("goal {}", goal_text);
             assert!(goal_text.starts_with('{'));
             assert!(goal_text.ends_with('}'));
-            let goal = lower_goal(
-                &*chalk_parse::parse_goal(&goal_text[1..goal_text.len() - 1]).unwrap(),
-                &*program,
-            )
-            .unwrap();
+            let peeled_goal = peeled_goal(db, &goal_text[1..goal_text.len() - 1]);
 
             println!("using solver: {:?}", solver_choice);
-            let peeled_goal = goal.into_peeled_goal(db.interner());
-            let result = db.solve(&peeled_goal);
-            solutions.push(format_solution(result, db.interner()));
+            solutions.push(run_goal(db, &peeled_goal, mode));
         });
     }
 
@@ -404,22 +429,78 @@ fn solve_aggregated(
     expected.assert_eq(head);
 }
 
+/// Lowers `goal_text` in the program of `db`, and peels it, as `test!` does
+/// with its goals. For the tests that solve goals in other ways.
+fn peeled_goal(db: &ChalkDatabase, goal_text: &str) -> UCanonical<InEnvironment<Goal<ChalkIr>>> {
+    let program = db.program_ir().unwrap();
+    let goal = lower_goal(&chalk_parse::parse_goal(goal_text).unwrap(), &program).unwrap();
+    goal.into_peeled_goal(db.interner())
+}
+
+/// Does what `mode` says with `goal`, and displays the outcome.
+fn run_goal(
+    db: &ChalkDatabase,
+    goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    mode: &TestMode,
+) -> String {
+    match mode {
+        TestMode::Solve => format_solution(db.solve(goal), db.interner()),
+        TestMode::Budget(budget) => match db.solve_with_budget(goal, budget) {
+            Ok(solution) => format_solution(solution, db.interner()),
+            Err(exhausted) => exhausted.to_string(),
+        },
+        TestMode::SolveRegions => {
+            let solution = db.solve(goal).expect("goal should be provable");
+            let environment = &goal.canonical.value.environment;
+            match solution.solve_regions(db.interner(), environment) {
+                Ok(solution) => format_solution(Some(solution), db.interner()),
+                Err(e) => e.to_string(),
+            }
+        }
+        TestMode::Stats => {
+            db.solve(goal);
+            db.solver_stats().to_string()
+        }
+        TestMode::Explain => {
+            let tree = db.explain(goal);
+            assert_eq!(tree.solution(), db.solve(goal));
+            tree.display(ChalkIr).to_string()
+        }
+    }
+}
+
+/// Without the `parallel` feature, a solver choice that solves in parallel is
+/// rejected when the solver is built (see `mod parallel` otherwise).
+#[cfg(not(feature = "parallel"))]
+#[test]
+#[should_panic(expected = "requires chalk to be built with the `parallel` feature")]
+fn parallel_requires_feature() {
+    SolverChoice::recursive_parallel().into_solver();
+}
+
+mod aliases;
 mod ambiguity_issue_727;
+mod answer_cache;
 mod arrays;
 mod auto_traits;
+mod budget;
 mod closures;
 mod coherence_goals;
 mod coinduction;
+mod compare;
 mod constants;
 mod coroutines;
 mod cycle;
 mod discriminant_kind;
+mod edit;
 mod existential_types;
 mod fn_def;
 mod foreign_types;
+mod forest_gc;
 mod functions;
 mod implied_bounds;
 mod impls;
+mod leak_check;
 mod lifetimes;
 mod misc;
 mod negation;
@@ -427,11 +508,17 @@ mod never;
 mod numerics;
 mod object_safe;
 mod opaque_types;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod persistent_cache;
 mod pointee;
 mod projection;
+mod proof_tree;
 mod refs;
+mod regions;
 mod scalars;
 mod slices;
+mod solver_stats;
 mod string;
 mod subtype;
 mod tuples;
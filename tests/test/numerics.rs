@@ -237,7 +237,9 @@ fn ambiguous_add() {
             exists<int T, U, V> {
                 <T as Add<U>>::Output = V
             }
-        } yields {
+        } yields[SolverChoice::slg_default()] {
+            expect![["Ambiguous; definite substitution for<?U0> { [?0 := Uint(U32), ?1 := ^0.0, ?2 := Uint(U32)] }"]]
+        } yields[SolverChoice::recursive_default()] {
             expect![["Ambiguous; no inference guidance"]]
         }
     }
@@ -164,9 +164,7 @@ fn opaque_generics() {
             exists<T> {
                 <Foo<Bar> as Iterator>::Item = T
             }
-        } yields[SolverChoice::slg_default()] {
-            expect![["Ambiguous; no inference guidance"]] // #234
-        } yields[SolverChoice::recursive_default()] {
+        } yields {
             expect![["Unique; substitution [?0 := Bar]"]]
         }
     }
@@ -0,0 +1,207 @@
+//! Tests for `RecursiveSolver::with_parallel`, which solves independent
+//! subgoals on several threads. Each goal is solved by a solver that solves
+//! in parallel and by one that does not, which must agree.
+
+use super::*;
+use chalk_recursive::{Cache, RecursiveSolver};
+use chalk_solve::solve::budget::{Budget, BudgetLimit};
+use chalk_solve::Solver;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[test]
+fn independent_subgoals() {
+    test! {
+        program {
+            trait Foo { }
+            trait Bar { }
+            struct A { }
+            struct B { }
+            struct S<T, U> { }
+
+            impl Bar for A { }
+            impl Bar for B { }
+            impl<T> Foo for T where T: Bar { }
+            impl<T, U> Foo for S<T, U> where T: Foo, U: Foo { }
+        }
+
+        goal {
+            S<A, S<B, A>>: Foo
+        } yields[SolverChoice::recursive_default()] {
+            expect![["Unique"]]
+        } yields[SolverChoice::recursive_parallel()] {
+            expect![["Unique"]]
+        }
+
+        goal {
+            exists<T> { S<T, A>: Foo }
+        } yields[SolverChoice::recursive_default()] {
+            expect![["Ambiguous; no inference guidance"]]
+        } yields[SolverChoice::recursive_parallel()] {
+            expect![["Ambiguous; no inference guidance"]]
+        }
+    }
+}
+
+#[test]
+fn cycles() {
+    test! {
+        program {
+            #[marker]
+            trait A { }
+            #[marker]
+            trait B { }
+            #[auto]
+            trait Send { }
+
+            struct Foo { }
+            struct Vec<T> { }
+            struct List<T> { head: T, tail: Box<List<T>> }
+            struct Box<T> { value: T }
+            struct Pair<T, U> { }
+
+            impl<T> A for T where T: B { }
+            impl A for Foo { }
+            impl<T> B for T where T: A { }
+            impl<T> B for Vec<T> where T: B { }
+            impl<T, U> A for Pair<T, U> where T: A, U: B, Pair<U, T>: A { }
+        }
+
+        goal {
+            exists<T> { T: A }
+        } yields[SolverChoice::recursive_default()] {
+            expect![["Ambiguous; no inference guidance"]]
+        } yields[SolverChoice::recursive_parallel()] {
+            expect![["Ambiguous; no inference guidance"]]
+        }
+
+        goal {
+            Vec<Foo>: B
+        } yields[SolverChoice::recursive_default()] {
+            expect![["Unique"]]
+        } yields[SolverChoice::recursive_parallel()] {
+            expect![["Unique"]]
+        }
+
+        goal {
+            Pair<Foo, Vec<Foo>>: A
+        } yields[SolverChoice::recursive_default()] {
+            expect![["No possible solution"]]
+        } yields[SolverChoice::recursive_parallel()] {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            List<Foo>: Send
+        } yields[SolverChoice::recursive_default()] {
+            expect![["Unique"]]
+        } yields[SolverChoice::recursive_parallel()] {
+            expect![["Unique"]]
+        }
+
+        goal {
+            Pair<List<Foo>, Box<Foo>>: A
+        } yields[SolverChoice::recursive_default()] {
+            expect![["No possible solution"]]
+        } yields[SolverChoice::recursive_parallel()] {
+            expect![["No possible solution"]]
+        }
+    }
+}
+
+#[test]
+fn overflow() {
+    test! {
+        program {
+            trait Q { }
+            struct Z { }
+            struct G<X> { }
+            struct S<X> { }
+
+            impl Q for Z { }
+            impl<X> Q for G<X> where X: Q { }
+            impl<X> Q for S<X> where X: Q, S<G<X>>: Q { }
+        }
+
+        goal {
+            S<Z>: Q
+        } yields[SolverChoice::recursive_default()] {
+            expect![["Ambiguous; no inference guidance"]]
+        } yields[SolverChoice::recursive_parallel()] {
+            expect![["Ambiguous; no inference guidance"]]
+        }
+
+        goal {
+            G<G<Z>>: Q
+        } yields[SolverChoice::recursive_default()] {
+            expect![["Unique"]]
+        } yields[SolverChoice::recursive_parallel()] {
+            expect![["Unique"]]
+        }
+    }
+}
+
+const SUBGOALS_PROGRAM: &str = "
+    trait Foo { }
+    trait Bar { }
+    struct A { }
+    struct B { }
+    struct S<T, U> { }
+
+    impl Bar for A { }
+    impl Bar for B { }
+    impl<T> Foo for T where T: Bar { }
+    impl<T, U> Foo for S<T, U> where T: Foo, U: Foo { }
+";
+
+/// The results found by the searches on other threads are cached.
+#[test]
+fn results_are_cached() {
+    let db = ChalkDatabase::with(SUBGOALS_PROGRAM, SolverChoice::recursive_default());
+    let program = db.program_ir().unwrap();
+    let cache = Cache::new();
+    RecursiveSolver::new(100, 30, Some(cache.clone()))
+        .with_parallel()
+        .solve(&*program, &peeled_goal(&db, "exists<T> { S<T, A>: Foo }"));
+    assert!(!cache.is_empty());
+}
+
+/// The searches on other threads check the `should_continue` of the caller,
+/// and so count against its budget.
+#[test]
+fn should_continue() {
+    let db = ChalkDatabase::with(SUBGOALS_PROGRAM, SolverChoice::recursive_default());
+    let program = db.program_ir().unwrap();
+    let peeled_goal = peeled_goal(&db, "S<S<A, B>, S<B, A>>: Foo");
+
+    let calls = AtomicUsize::new(0);
+    let solution = RecursiveSolver::new(100, 30, Some(Cache::new())).solve_limited(
+        &*program,
+        &peeled_goal,
+        &|| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            true
+        },
+    );
+    let sequential_calls = calls.swap(0, Ordering::Relaxed);
+    let parallel_solution = RecursiveSolver::new(100, 30, Some(Cache::new()))
+        .with_parallel()
+        .solve_limited_parallel(&*program, &peeled_goal, &|| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            true
+        });
+    assert_eq!(parallel_solution, solution);
+    // Each goal is solved by one of the searches, at least.
+    assert!(calls.load(Ordering::Relaxed) >= sequential_calls);
+
+    let cache = Cache::new();
+    let exhausted = RecursiveSolver::new(100, 30, Some(cache.clone()))
+        .with_parallel()
+        .solve_with_budget(
+            &*program,
+            &peeled_goal,
+            &Budget::default().with_max_steps(1),
+        )
+        .unwrap_err();
+    assert_eq!(exhausted.limit, BudgetLimit::Steps);
+    assert_eq!(cache.len(), 0);
+}
@@ -0,0 +1,232 @@
+//! Tests for keeping the results of the solvers on disk between runs.
+
+use super::*;
+use chalk_engine::cache::AnswerCache;
+use chalk_engine::solve::SLGSolver;
+use chalk_ir::NoSolution;
+use chalk_solve::solve::persistent::{PersistedResult, PersistentCache};
+use chalk_solve::Solver;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PROGRAM: &str = "
+    #[lang(sized)]
+    trait Sized { }
+    trait Foo { }
+    struct A { }
+    struct Vec<T> { }
+
+    impl<T> Foo for Vec<T> where T: Sized { }
+";
+
+const GOALS: &[&str] = &["Vec<A>: Foo", "A: Foo", "exists<T> { T: Foo }"];
+
+/// An empty directory for the cache files of the test `name`.
+fn cache_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "chalk-persistent-cache-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// The cache files in `dir`, with the fingerprint in their name.
+fn cache_files(dir: &Path) -> Vec<(PathBuf, u64)> {
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let name = path.file_stem().unwrap().to_str().unwrap();
+            let fingerprint = u64::from_str_radix(name, 16).unwrap();
+            (path, fingerprint)
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Solves `GOALS` in a database that keeps its results in `dir`.
+fn solve_all(program: &str, dir: &Path, solver_choice: SolverChoice) -> Vec<Option<String>> {
+    let mut db = ChalkDatabase::with(program, solver_choice);
+    db.set_persistent_cache_dir(Some(Arc::new(dir.to_path_buf())));
+    GOALS
+        .iter()
+        .map(|goal| {
+            db.solve(&peeled_goal(&db, goal))
+                .map(|solution| solution.display(ChalkIr).to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn results_are_kept_between_runs() {
+    let dir = cache_dir("kept");
+    for solver_choice in &[
+        SolverChoice::slg_default(),
+        SolverChoice::recursive_default(),
+    ] {
+        let first = solve_all(PROGRAM, &dir, *solver_choice);
+        let second = solve_all(PROGRAM, &dir, *solver_choice);
+        assert_eq!(first, second);
+    }
+
+    // One file for each solver.
+    let files = cache_files(&dir);
+    assert_eq!(files.len(), 2);
+    for (path, fingerprint) in &files {
+        assert!(!PersistentCache::open(path, *fingerprint).is_empty());
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn solver_starts_from_stored_results() {
+    let dir = cache_dir("warm");
+    solve_all(PROGRAM, &dir, SolverChoice::slg_default());
+    let (path, fingerprint) = cache_files(&dir).remove(0);
+
+    // A solver using the stored results finds the root goal there, and so
+    // has no subgoals to solve.
+    let store = Arc::new(PersistentCache::open(&path, fingerprint));
+    let mut solver =
+        SLGSolver::new(10, None).with_answer_cache(AnswerCache::with_store(ChalkIr, store));
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::slg_default());
+    let solution = solver.solve(&db, &peeled_goal(&db, "Vec<A>: Foo")).unwrap();
+    assert_eq!(solution.display(ChalkIr).to_string(), "Unique");
+    let stats = solver.memory_stats();
+    assert_eq!((stats.tables, stats.cache_hits), (1, 1));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn changed_program_is_not_solved_from_stored_results() {
+    let dir = cache_dir("changed");
+    solve_all(PROGRAM, &dir, SolverChoice::slg_default());
+    let (path, _) = cache_files(&dir).remove(0);
+
+    // Removing the impl changes the results, which are stored in a file of
+    // their own.
+    let changed = PROGRAM.replace("impl<T> Foo for Vec<T> where T: Sized { }", "");
+    let solutions = solve_all(&changed, &dir, SolverChoice::slg_default());
+    assert_eq!(solutions, [None, None, None]);
+    assert_eq!(cache_files(&dir).len(), 2);
+
+    // A file is not used for another fingerprint.
+    assert!(PersistentCache::open(&path, 0).is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn solver_settings_change_fingerprint() {
+    let dir = cache_dir("settings");
+    solve_all(PROGRAM, &dir, SolverChoice::recursive(30, 100));
+    solve_all(PROGRAM, &dir, SolverChoice::recursive(30, 10));
+    solve_all(PROGRAM, &dir, SolverChoice::recursive(10, 100));
+    assert_eq!(cache_files(&dir).len(), 3);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Goals are keyed by their contents: the same goal, lowered and interned
+/// again by another database, finds the results stored for it.
+#[test]
+fn goal_keys_do_not_depend_on_interning() {
+    let lower = |goal: &str| {
+        let db = ChalkDatabase::with(PROGRAM, SolverChoice::slg_default());
+        peeled_goal(&db, goal)
+    };
+    let dir = cache_dir("interning");
+    let cache = PersistentCache::open(dir.join("cache"), 1);
+    cache.insert(&lower("Vec<A>: Foo"), PersistedResult::Proven);
+    assert_eq!(
+        cache.get(&lower("Vec<A>: Foo")),
+        Some(PersistedResult::Proven)
+    );
+    assert_eq!(cache.get(&lower("A: Foo")), None);
+    drop(cache);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Only the results that can be rebuilt from their goal are persisted: a
+/// solution with a substitution is recomputed by each run.
+#[test]
+fn solutions_with_substitutions_are_not_persisted() {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::recursive_default());
+    for (goal, expected) in [
+        ("Vec<A>: Foo", Some(PersistedResult::Proven)),
+        ("A: Foo", Some(PersistedResult::NoSolution)),
+        ("exists<T> { T = A }", None),
+    ] {
+        let peeled_goal = peeled_goal(&db, goal);
+        let result = db.solve(&peeled_goal).ok_or(NoSolution);
+        assert_eq!(
+            PersistedResult::from_solution(ChalkIr, &peeled_goal, &result),
+            expected
+        );
+    }
+}
+
+#[test]
+fn unreadable_file_is_replaced() {
+    let dir = cache_dir("unreadable");
+    let path = dir.join("cache");
+    fs::write(&path, "not a cache file").unwrap();
+    let cache = PersistentCache::open(&path, 1);
+    assert!(cache.is_empty());
+
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::slg_default());
+    let peeled_goal = peeled_goal(&db, "A: Foo");
+    cache.insert(&peeled_goal, PersistedResult::NoSolution);
+    cache.save().unwrap();
+    // The file records the version of chalk that wrote it.
+    let header = format!("chalk-cache v2 {} ", env!("CARGO_PKG_VERSION"));
+    assert!(fs::read_to_string(&path).unwrap().starts_with(&header));
+    let reopened = PersistentCache::open(&path, 1);
+    assert_eq!(
+        reopened.get(&peeled_goal),
+        Some(PersistedResult::NoSolution)
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn layout_does_not_change_fingerprint() {
+    let fingerprint = |text: &str| {
+        ChalkDatabase::with(text, SolverChoice::slg_default())
+            .program_fingerprint()
+            .unwrap()
+    };
+    let reformatted = PROGRAM.replace("    ", "\t").replace("{ }", "{\n}");
+    assert_eq!(fingerprint(PROGRAM), fingerprint(&reformatted));
+    assert_ne!(
+        fingerprint(PROGRAM),
+        fingerprint(&PROGRAM.replace("struct A", "struct B"))
+    );
+}
+
+#[test]
+fn caches_of_the_same_file_save_separately() {
+    let dir = cache_dir("shared");
+    let path = dir.join("cache");
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::slg_default());
+    let peeled_goal = peeled_goal(&db, "A: Foo");
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                let cache = PersistentCache::open(&path, 1);
+                cache.insert(&peeled_goal, PersistedResult::NoSolution);
+                cache.save().unwrap();
+            });
+        }
+    });
+    let reopened = PersistentCache::open(&path, 1);
+    assert_eq!(
+        reopened.get(&peeled_goal),
+        Some(PersistedResult::NoSolution)
+    );
+    // No temporary file is left behind.
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
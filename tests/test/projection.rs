@@ -139,10 +139,7 @@ fn projection_equality() {
             exists<U> {
                 S: Trait1<Type = U>
             }
-        } yields[SolverChoice::slg_default()] {
-            // this is wrong, chalk#234
-            expect![["Ambiguous; no inference guidance"]]
-        } yields[SolverChoice::recursive_default()] {
+        } yields {
             expect![["Unique; substitution [?0 := Uint(U32)]"]]
         }
 
@@ -150,10 +147,7 @@ fn projection_equality() {
             exists<U> {
                 S: Trait2<U>
             }
-        } yields[SolverChoice::slg_default()] {
-            // this is wrong, chalk#234
-            expect![["Ambiguous; no inference guidance"]]
-        } yields[SolverChoice::recursive_default()] {
+        } yields {
             expect![["Unique; substitution [?0 := Uint(U32)]"]]
         }
     }
@@ -266,14 +260,9 @@ fn projection_equality_priority2() {
                     }
                 }
             }
-        } yields[SolverChoice::slg_default()] {
-            // chalk#234: Constraining Out1 = S1 gives us only the choice to
-            // use the impl, but the SLG solver can't decide between
-            // the placeholder and the normalized form.
-            expect![["Ambiguous; definite substitution for<?U1> { [?0 := S1, ?1 := ^0.0] }"]]
-        } yields[SolverChoice::recursive_default()] {
+        } yields {
             // Constraining Out1 = S1 gives us only one choice, use the impl,
-            // and the recursive solver prefers the normalized form.
+            // and both solvers prefer the normalized form.
             expect![["Unique; substitution [?0 := S1, ?1 := Uint(U32)]"]]
         }
     }
@@ -295,10 +284,7 @@ fn projection_equality_from_env() {
                     }
                 }
             }
-        } yields[SolverChoice::slg_default()] {
-            // this is wrong, chalk#234
-            expect![["Ambiguous; no inference guidance"]]
-        } yields[SolverChoice::recursive_default()] {
+        } yields {
             expect![["Unique; substitution [?0 := Uint(U32)]"]]
         }
     }
@@ -323,10 +309,7 @@ fn projection_equality_nested() {
                     }
                 }
             }
-        } yields[SolverChoice::slg_default()] {
-            // this is wrong, chalk#234
-            expect![["Ambiguous; no inference guidance"]]
-        }  yields[SolverChoice::recursive_default()] {
+        } yields {
             expect![["Unique; substitution [?0 := Uint(U32)]"]]
         }
     }
@@ -365,10 +348,7 @@ fn iterator_flatten() {
                     }
                 }
             }
-        } yields[SolverChoice::slg_default()] {
-            // this is wrong, chalk#234
-            expect![["Ambiguous; no inference guidance"]]
-        } yields[SolverChoice::recursive_default()] {
+        } yields {
             expect![["Unique; substitution [?0 := Uint(U32)]"]]
         }
     }
@@ -795,10 +775,7 @@ fn normalize_under_binder() {
                     Ref<'a, I32>: Deref<'a, Item = U>
                 }
             }
-        } yields[SolverChoice::slg_default()] {
-            // chalk#234, I think
-            expect![["Ambiguous; no inference guidance"]]
-        } yields[SolverChoice::recursive_default()] {
+        } yields {
             expect![["Unique; substitution [?0 := I32]"]]
         }
 
@@ -818,10 +795,7 @@ fn normalize_under_binder() {
                     Ref<'a, I32>: Id<'a, Item = U>
                 }
             }
-        } yields[SolverChoice::slg_default()] {
-            // chalk#234, I think
-            expect![["Ambiguous; no inference guidance"]]
-        } yields[SolverChoice::recursive_default()] {
+        } yields {
             expect![["Unique; substitution [?0 := Ref<'!1_0, I32>]"]]
         }
 
@@ -852,6 +826,41 @@ fn normalize_under_binder() {
     }
 }
 
+/// The placeholder answer of the fallback clause is not an answer of its own
+/// when impls normalize the projection, just as `solve` discards it.
+#[test]
+fn normalize_enumerates_impl_answers_only() {
+    test! {
+        program {
+            struct A { }
+            struct B { }
+            struct I32 { }
+            struct Bool { }
+
+            trait Iterator {
+                type Item;
+            }
+
+            impl Iterator for A {
+                type Item = I32;
+            }
+
+            impl Iterator for B {
+                type Item = Bool;
+            }
+        }
+
+        goal {
+            exists<T, U> {
+                <T as Iterator>::Item = U
+            }
+        } yields_all {
+            expect!["substitution [?0 := A, ?1 := I32]"],
+            expect!["substitution [?0 := B, ?1 := Bool]"]
+        }
+    }
+}
+
 #[test]
 fn normalize_under_binder_multi() {
     test! {
@@ -875,12 +884,7 @@ fn normalize_under_binder_multi() {
                 }
             }
         } yields_all {
-            expect![["substitution [?0 := I32]"]],
-            expect![["for<?U0,?U0> { substitution [?0 := (Deref::Item)<Ref<'^0.0, I32>, '^0.1>], lifetime constraints [\
-            InEnvironment { environment: Env([]), goal: '!1_0: '^0.1 }, \
-            InEnvironment { environment: Env([]), goal: '^0.1: '!1_0 }, \
-            InEnvironment { environment: Env([]), goal: '!1_0: '^0.0 }, \
-            InEnvironment { environment: Env([]), goal: '^0.0: '!1_0 }] }"]]
+            expect![["substitution [?0 := I32]"]]
         }
 
         goal {
@@ -1245,11 +1249,8 @@ fn nested_proj_eq_nested_proj_should_flounder() {
             exists<T, U> {
                 <<T as Trait1>::Assoc as Trait2>::Assoc = <<U as Trait1>::Assoc as Trait2>::Assoc
             }
-        } yields[SolverChoice::slg_default()] {
-            // FIXME
-            expect![[r#"Ambiguous; definite substitution for<?U0> { [?0 := ^0.0, ?1 := ^0.0] }"#]]
-        } yields[SolverChoice::recursive_default()] {
-            expect![[r#"Ambiguous; no inference guidance"#]]
+        } yields {
+            expect![["Ambiguous; no inference guidance"]]
         }
     }
 }
@@ -1292,11 +1293,67 @@ fn clauses_for_placeholder_projection_types() {
                     <<Opaque<T> as IntoIterator>::IntoIter as Iterator>::Item = U
                 }
             }
-        } yields[SolverChoice::slg_default()] {
-            // FIXME: chalk#234?
-            expect![[r#"Ambiguous; no inference guidance"#]]
-        } yields[SolverChoice::recursive_default()] {
-            expect![[r#"Unique; substitution [?0 := !1_0]"#]]
+        } yields {
+            expect![["Unique; substitution [?0 := !1_0]"]]
+        }
+    }
+}
+
+#[test]
+fn projection_from_default_impl() {
+    test! {
+        program {
+            trait Foo { type Assoc; }
+            trait Copy { }
+            struct A { }
+            struct B { }
+            struct S<T> { }
+            impl Copy for u32 { }
+
+            impl<T> Foo for S<T> { default type Assoc = A; }
+            impl<T> Foo for S<T> where T: Copy { type Assoc = B; }
+        }
+
+        // Without revealing, the value of a default impl stays opaque.
+        goal {
+            <S<i32> as Foo>::Assoc = A
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            if (Reveal) {
+                <S<i32> as Foo>::Assoc = A
+            }
+        } yields {
+            expect![["Unique"]]
+        }
+
+        // The specializing impl applies, so its value is used instead.
+        goal {
+            if (Reveal) {
+                <S<u32> as Foo>::Assoc = A
+            }
+        } yields {
+            expect![["No possible solution"]]
+        }
+
+        goal {
+            <S<u32> as Foo>::Assoc = B
+        } yields {
+            expect![["Unique"]]
+        }
+
+        // We can't know whether the specializing impl applies to an unknown
+        // type, so its value stays opaque.
+        goal {
+            forall<T> {
+                if (Reveal) {
+                    <S<T> as Foo>::Assoc = A
+                }
+            }
+        } yields {
+            expect![["No possible solution"]]
         }
     }
 }
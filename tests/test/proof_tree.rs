@@ -0,0 +1,311 @@
+//! Tests for `Solver::explain`, which records how a solution was reached.
+
+use super::*;
+use chalk_engine::solve::SLGSolver;
+use chalk_ir::{Canonical, ConstrainedSubst};
+use chalk_solve::{Solver, SubstitutionResult};
+
+#[test]
+fn explain_unique() {
+    test! {
+        explain;
+        program {
+            struct Foo { }
+            struct Bar { }
+            struct Vec<T> { }
+            trait Clone { }
+            impl Clone for Foo { }
+            impl<T> Clone for Vec<T> where T: Clone { }
+        }
+
+        goal {
+            Vec<Foo>: Clone
+        } yields[SolverChoice::slg_default()] {
+            expect![[r#"
+                Implemented(Vec<Foo>: Clone) -- Unique
+                  clause for<type> Implemented(^0.0: Clone) :- FromEnv(^0.0: Clone) -- Abandoned
+                    FromEnv(Vec<Foo>: Clone) -- No possible solution
+                  clause for<type> Implemented(Vec<^0.0>: Clone) :- ForAll<> { Implemented(^1.0: Clone) } -- Unique
+                    ForAll<> { Implemented(Foo: Clone) } -- Unique
+                      Implemented(Foo: Clone) -- Unique
+                        clause for<type> Implemented(^0.0: Clone) :- FromEnv(^0.0: Clone) -- Abandoned
+                          FromEnv(Foo: Clone) -- No possible solution
+                        clause for<> Implemented(Foo: Clone) -- Unique
+            "#]]
+        } yields[SolverChoice::recursive_default()] {
+            expect![[r#"
+                Implemented(Vec<Foo>: Clone) -- Unique
+                  clause for<type> Implemented(^0.0: Clone) :- FromEnv(^0.0: Clone) -- No possible solution
+                    FromEnv(Vec<Foo>: Clone) -- No possible solution
+                  clause for<type> Implemented(Vec<^0.0>: Clone) :- ForAll<> { Implemented(^1.0: Clone) } -- Unique
+                    Implemented(Foo: Clone) -- Unique
+                      clause for<type> Implemented(^0.0: Clone) :- FromEnv(^0.0: Clone) -- No possible solution
+                        FromEnv(Foo: Clone) -- No possible solution
+                      clause for<> Implemented(Foo: Clone) -- Unique
+            "#]]
+        }
+    }
+}
+
+#[test]
+fn explain_no_solution() {
+    test! {
+        explain;
+        program {
+            struct Foo { }
+            struct Bar { }
+            struct Vec<T> { }
+            trait Clone { }
+            impl Clone for Foo { }
+            impl<T> Clone for Vec<T> where T: Clone { }
+        }
+
+        goal {
+            Vec<Bar>: Clone
+        } yields[SolverChoice::slg_default()] {
+            expect![[r#"
+                Implemented(Vec<Bar>: Clone) -- No possible solution
+                  clause for<type> Implemented(^0.0: Clone) :- FromEnv(^0.0: Clone) -- No possible solution
+                    FromEnv(Vec<Bar>: Clone) -- No possible solution
+                  clause for<type> Implemented(Vec<^0.0>: Clone) :- ForAll<> { Implemented(^1.0: Clone) } -- No possible solution
+                    ForAll<> { Implemented(Bar: Clone) } -- No possible solution
+                      Implemented(Bar: Clone) -- No possible solution
+                        clause for<type> Implemented(^0.0: Clone) :- FromEnv(^0.0: Clone) -- No possible solution
+                          FromEnv(Bar: Clone) -- No possible solution
+            "#]]
+        } yields[SolverChoice::recursive_default()] {
+            expect![[r#"
+                Implemented(Vec<Bar>: Clone) -- No possible solution
+                  clause for<type> Implemented(^0.0: Clone) :- FromEnv(^0.0: Clone) -- No possible solution
+                    FromEnv(Vec<Bar>: Clone) -- No possible solution
+                  clause for<type> Implemented(Vec<^0.0>: Clone) :- ForAll<> { Implemented(^1.0: Clone) } -- No possible solution
+                    Implemented(Bar: Clone) -- No possible solution
+                      clause for<type> Implemented(^0.0: Clone) :- FromEnv(^0.0: Clone) -- No possible solution
+                        FromEnv(Bar: Clone) -- No possible solution
+            "#]]
+        }
+    }
+}
+
+#[test]
+fn explain_cycle() {
+    test! {
+        explain;
+        program {
+            struct Foo { }
+            struct Bar { }
+            struct Vec<T> { }
+            trait Clone { }
+            impl Clone for Foo { }
+            impl<T> Clone for Vec<T> where T: Clone { }
+        }
+
+        goal {
+            exists<T> { Vec<T>: Clone }
+        } yields[SolverChoice::slg_default()] {
+            expect![[r#"
+                for<?U0> Implemented(Vec<^0.0>: Clone) -- Ambiguous; no inference guidance
+                  clause for<type> Implemented(^0.0: Clone) :- FromEnv(^0.0: Clone) -- No possible solution
+                    for<?U0> FromEnv(Vec<^0.0>: Clone) -- No possible solution
+                  clause for<type> Implemented(Vec<^0.0>: Clone) :- ForAll<> { Implemented(^1.0: Clone) } -- Ambiguous; no inference guidance
+                    for<?U0> ForAll<> { Implemented(^1.0: Clone) } -- Ambiguous; no inference guidance
+                      for<?U0> Implemented(^0.0: Clone) -- Ambiguous; no inference guidance
+                        clause for<type> Implemented(^0.0: Clone) :- FromEnv(^0.0: Clone) -- No possible solution
+                          for<?U0> FromEnv(^0.0: Clone) -- No possible solution
+                        clause for<> Implemented(Foo: Clone) -- Unique; substitution [?0 := Foo]
+                        clause for<type> Implemented(Vec<^0.0>: Clone) :- ForAll<> { Implemented(^1.0: Clone) } -- Unique; substitution [?0 := Vec<Foo>]
+                          for<?U0> ForAll<> { Implemented(^1.0: Clone) } -- Cycle; provisionally Ambiguous; no inference guidance
+            "#]]
+        } yields[SolverChoice::recursive_default()] {
+            expect![[r#"
+                for<?U0> Implemented(Vec<^0.0>: Clone) -- Ambiguous; no inference guidance
+                  clause for<type> Implemented(^0.0: Clone) :- FromEnv(^0.0: Clone) -- No possible solution
+                    for<?U0> FromEnv(Vec<^0.0>: Clone) -- No possible solution
+                  clause for<type> Implemented(Vec<^0.0>: Clone) :- ForAll<> { Implemented(^1.0: Clone) } -- Ambiguous; no inference guidance
+                    for<?U0> Implemented(^0.0: Clone) -- Ambiguous; no inference guidance
+                      clause for<type> Implemented(^0.0: Clone) :- FromEnv(^0.0: Clone) -- No possible solution
+                        for<?U0> FromEnv(^0.0: Clone) -- No possible solution (see above)
+                      clause for<> Implemented(Foo: Clone) -- Unique; substitution [?0 := Foo]
+                      clause for<type> Implemented(Vec<^0.0>: Clone) :- ForAll<> { Implemented(^1.0: Clone) } -- Unique; substitution [?0 := Vec<Foo>]
+                        for<?U0> Implemented(^0.0: Clone) -- Cycle; provisionally Unique; substitution [?0 := Foo]
+                    for<?U0> Implemented(^0.0: Clone) -- Ambiguous; no inference guidance (see above)
+            "#]]
+        }
+    }
+}
+
+#[test]
+fn explain_floundered() {
+    test! {
+        explain;
+        program {
+            #[auto] trait Send { }
+            struct Foo { }
+        }
+
+        goal {
+            exists<T> { T: Send }
+        } yields[SolverChoice::slg_default()] {
+            expect![[r#"
+                for<?U0> Implemented(^0.0: Send) -- Floundered
+            "#]]
+        } yields[SolverChoice::recursive_default()] {
+            expect![[r#"
+                for<?U0> Implemented(^0.0: Send) -- Floundered
+            "#]]
+        }
+    }
+}
+
+#[test]
+fn explain_overflow_and_truncation() {
+    test! {
+        explain;
+        program {
+            trait Foo { }
+            struct S<T> { }
+            impl<T> Foo for T where S<T>: Foo { }
+            struct Bar { }
+        }
+
+        goal {
+            Bar: Foo
+        } yields[SolverChoice::Recursive {
+            overflow_depth: 3,
+            caching_enabled: true,
+            max_size: 30,
+            parallel: false,
+        }] {
+            expect![[r#"
+                Implemented(Bar: Foo) -- Ambiguous; no inference guidance
+                  clause for<type> Implemented(^0.0: Foo) :- FromEnv(^0.0: Foo) -- No possible solution
+                    FromEnv(Bar: Foo) -- No possible solution
+                  clause for<type> Implemented(^0.0: Foo) :- ForAll<> { Implemented(S<^1.0>: Foo) } -- Ambiguous; no inference guidance
+                    Implemented(S<Bar>: Foo) -- Ambiguous; no inference guidance
+                      clause for<type> Implemented(^0.0: Foo) :- FromEnv(^0.0: Foo) -- No possible solution
+                        FromEnv(S<Bar>: Foo) -- No possible solution
+                      clause for<type> Implemented(^0.0: Foo) :- ForAll<> { Implemented(S<^1.0>: Foo) } -- Ambiguous; no inference guidance
+                        Implemented(S<S<Bar>>: Foo) -- Ambiguous; no inference guidance
+                          clause for<type> Implemented(^0.0: Foo) :- FromEnv(^0.0: Foo) -- Ambiguous; no inference guidance
+                            FromEnv(S<S<Bar>>: Foo) -- Overflow
+                            FromEnv(S<S<Bar>>: Foo) -- Overflow
+                          clause for<type> Implemented(^0.0: Foo) :- ForAll<> { Implemented(S<^1.0>: Foo) } -- Ambiguous; no inference guidance
+                            Implemented(S<S<S<Bar>>>: Foo) -- Overflow
+                            Implemented(S<S<S<Bar>>>: Foo) -- Overflow
+                        Implemented(S<S<Bar>>: Foo) -- Ambiguous; no inference guidance (see above)
+                    Implemented(S<Bar>: Foo) -- Ambiguous; no inference guidance (see above)
+            "#]]
+        } yields[SolverChoice::SLG {
+            max_size: 3,
+            expected_answers: None,
+        }] {
+            expect![[r#"
+                Implemented(Bar: Foo) -- Ambiguous; no inference guidance
+                  clause for<type> Implemented(^0.0: Foo) :- FromEnv(^0.0: Foo) -- No possible solution
+                    FromEnv(Bar: Foo) -- No possible solution
+                  clause for<type> Implemented(^0.0: Foo) :- ForAll<> { Implemented(S<^1.0>: Foo) } -- Ambiguous; no inference guidance
+                    ForAll<> { Implemented(S<Bar>: Foo) } -- Ambiguous; no inference guidance
+                      Implemented(S<Bar>: Foo) -- Ambiguous; no inference guidance
+                        clause for<type> Implemented(^0.0: Foo) :- FromEnv(^0.0: Foo) -- No possible solution
+                          FromEnv(S<Bar>: Foo) -- No possible solution
+                        clause for<type> Implemented(^0.0: Foo) :- ForAll<> { Implemented(S<^1.0>: Foo) } -- Ambiguous; no inference guidance
+                          ForAll<> { Implemented(S<S<Bar>>: Foo) } -- Ambiguous; no inference guidance
+                            Implemented(S<S<Bar>>: Foo) -- Ambiguous; no inference guidance
+                              clause for<type> Implemented(^0.0: Foo) :- FromEnv(^0.0: Foo) -- No possible solution
+                                FromEnv(S<S<Bar>>: Foo) -- No possible solution
+                              clause for<type> Implemented(^0.0: Foo) :- ForAll<> { Implemented(S<^1.0>: Foo) } -- Ambiguous; no inference guidance
+                                ForAll<> { Implemented(S<S<S<Bar>>>: Foo) } -- Truncated
+            "#]]
+        }
+    }
+}
+
+#[test]
+fn explain_placeholder_leak() {
+    test! {
+        explain;
+        program {
+            trait Trait { }
+            impl<'b> Trait for fn(&'b u8) { }
+        }
+
+        goal {
+            for<'a> fn(&'a u8): Trait
+        } yields[SolverChoice::slg_default()] {
+            expect![[r#"
+                Implemented("rust" for<1> [?0 := (&'^0.0 Uint(U8)), ?1 := 0]: Trait) -- No possible solution
+                  clause for<type> Implemented(^0.0: Trait) :- FromEnv(^0.0: Trait) -- No possible solution
+                    FromEnv("rust" for<1> [?0 := (&'^0.0 Uint(U8)), ?1 := 0]: Trait) -- No possible solution
+                  clause for<lifetime> Implemented("rust" for<0> [?0 := (&'^1.0 Uint(U8)), ?1 := 0]: Trait) -- No possible solution: placeholder `'!1_0` from universe U1 would have to outlive `'^0.0` from universe U0, in region constraint `'!1_0: '^0.0`
+                    for<?U0> '!1_0: '^0.0 -- Unique; for<?U0> { substitution [?0 := '^0.0], lifetime constraints [InEnvironment { environment: Env([]), goal: '!1_0: '^0.0 }] }
+                      clause for<lifetime, lifetime> '^0.0: '^0.1 -- Unique; for<?U0> { substitution [?0 := '^0.0], lifetime constraints [InEnvironment { environment: Env([]), goal: '!1_0: '^0.0 }] }
+            "#]]
+        } yields[SolverChoice::recursive_default()] {
+            expect![[r#"
+                Implemented("rust" for<1> [?0 := (&'^0.0 Uint(U8)), ?1 := 0]: Trait) -- No possible solution
+                  clause for<type> Implemented(^0.0: Trait) :- FromEnv(^0.0: Trait) -- No possible solution
+                    FromEnv("rust" for<1> [?0 := (&'^0.0 Uint(U8)), ?1 := 0]: Trait) -- No possible solution
+                  clause for<lifetime> Implemented("rust" for<0> [?0 := (&'^1.0 Uint(U8)), ?1 := 0]: Trait) -- No possible solution: placeholder `'!1_0` from universe U1 would have to outlive `'^0.0` from universe U0, in region constraint `'!1_0: '^0.0`
+                    for<?U0> '!1_0: '^0.0 -- Unique; for<?U0> { substitution [?0 := '^0.0], lifetime constraints [InEnvironment { environment: Env([]), goal: '!1_0: '^0.0 }] }
+                      clause for<lifetime, lifetime> '^0.0: '^0.1 -- Unique; for<?U0> { substitution [?0 := '^0.0], lifetime constraints [InEnvironment { environment: Env([]), goal: '!1_0: '^0.0 }] }
+            "#]]
+        }
+    }
+}
+
+/// A solver that only implements the required methods of `Solver`.
+#[derive(Debug)]
+pub(super) struct SolveOnly(pub(super) SLGSolver<ChalkIr>);
+
+impl Solver<ChalkIr> for SolveOnly {
+    fn solve(
+        &mut self,
+        program: &dyn RustIrDatabase<ChalkIr>,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> Option<Solution<ChalkIr>> {
+        self.0.solve(program, goal)
+    }
+
+    fn solve_limited(
+        &mut self,
+        program: &dyn RustIrDatabase<ChalkIr>,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+        should_continue: &dyn Fn() -> bool,
+    ) -> Option<Solution<ChalkIr>> {
+        self.0.solve_limited(program, goal, should_continue)
+    }
+
+    fn solve_multiple(
+        &mut self,
+        program: &dyn RustIrDatabase<ChalkIr>,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+        f: &mut dyn FnMut(SubstitutionResult<Canonical<ConstrainedSubst<ChalkIr>>>, bool) -> bool,
+    ) -> bool {
+        self.0.solve_multiple(program, goal, f)
+    }
+}
+
+#[test]
+fn explain_without_derivations() {
+    let program = "
+        struct Foo { }
+        struct Vec<T> { }
+        trait Clone { }
+        impl Clone for Foo { }
+        impl<T> Clone for Vec<T> where T: Clone { }
+    ";
+    let db = ChalkDatabase::with(program, SolverChoice::slg_default());
+    let mut solver = SolveOnly(SLGSolver::new(10, None));
+    db.with_program(|_| {
+        let peeled_goal = peeled_goal(&db, "Vec<Foo>: Clone");
+        expect![[r#"
+            Implemented(Vec<Foo>: Clone) -- Unique
+        "#]]
+        .assert_eq(
+            &solver
+                .explain(&db, &peeled_goal)
+                .display(ChalkIr)
+                .to_string(),
+        );
+    });
+}
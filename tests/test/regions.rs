@@ -0,0 +1,131 @@
+//! Tests for `Solution::solve_regions`, which checks the region constraints
+//! of a solution against the outlives facts of its environment.
+
+use super::*;
+
+#[test]
+fn unconstrained_placeholders() {
+    test! {
+        solve_regions;
+        program {
+            struct Foo<'a, T> where T: 'a { x: &'a T }
+            trait Bar { type Item; }
+        }
+
+        goal {
+            forall<'a, T> { T: 'a }
+        } yields {
+            expect![["region constraint `!1_1: '!1_0` cannot hold"]]
+        }
+
+        goal {
+            forall<'a, 'b> { &'a u32: 'b }
+        } yields {
+            expect![["region constraint `(&'!1_0 Uint(U32)): '!1_1` cannot hold, because `'!1_0: '!1_1` does not"]]
+        }
+    }
+}
+
+#[test]
+fn static_outlives_everything() {
+    test! {
+        solve_regions;
+        program {
+            struct Foo<'a, T> where T: 'a { x: &'a T }
+            trait Bar { type Item; }
+        }
+
+        goal {
+            forall<'a> { u32: 'a }
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            forall<'a> { &'static u32: 'a }
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            forall<'a> { 'a: 'static }
+        } yields {
+            expect![["region constraint `'!1_0: 'static` cannot hold"]]
+        }
+    }
+}
+
+#[test]
+fn outlives_facts_of_environment() {
+    test! {
+        solve_regions;
+        program {
+            struct Foo<'a, T> where T: 'a { x: &'a T }
+            trait Bar { type Item; }
+        }
+
+        goal {
+            forall<'a, 'b> { if ('a: 'b) { &'a u32: 'b } }
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            forall<'a, 'b, 'c> { if ('a: 'b) { if ('b: 'c) { &'a u32: 'c } } }
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            forall<'a, T> { if (T: 'a) { Foo<'a, T>: 'a } }
+        } yields {
+            expect![["Unique"]]
+        }
+
+        goal {
+            forall<'a, 'b, T> { if (T: 'a) { Foo<'b, T>: 'a } }
+        } yields {
+            expect![["region constraint `Foo<'!1_1, !1_2>: '!1_0` cannot hold, because `'!1_1: '!1_0` does not"]]
+        }
+    }
+}
+
+#[test]
+fn existential_constraints_are_kept() {
+    test! {
+        solve_regions;
+        program {
+            struct Foo<'a, T> where T: 'a { x: &'a T }
+            trait Bar { type Item; }
+        }
+
+        goal {
+            exists<'x> { forall<'a> { &'x u32: 'a } }
+        } yields {
+            expect![["Unique; for<?U0> { substitution [?0 := '^0.0], lifetime constraints [InEnvironment { environment: Env([]), goal: '^0.0: '!1_0 }] }"]]
+        }
+
+        goal {
+            forall<'a, 'b> { exists<'x> { if ('a: 'b) { &'x u32: 'a, &'x u32: 'b } } }
+        } yields {
+            expect![["Unique; for<?U1> { substitution [?0 := '^0.0], lifetime constraints [InEnvironment { environment: Env([for<> '!1_0: '!1_1]), goal: '^0.0: '!1_0 }] }"]]
+        }
+    }
+}
+
+#[test]
+fn projections_are_kept() {
+    test! {
+        solve_regions;
+        program {
+            struct Foo<'a, T> where T: 'a { x: &'a T }
+            trait Bar { type Item; }
+        }
+
+        goal {
+            forall<'a, T> { if (T: Bar) { <T as Bar>::Item: 'a } }
+        } yields {
+            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([for<> FromEnv(!1_1: Bar)]), goal: (Bar::Item)<!1_1>: '!1_0 }]"]]
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! Tests for the statistics of the work done by the solvers.
+
+use super::*;
+
+#[test]
+fn slg_stats() {
+    test! {
+        stats;
+        program {
+            #[lang(sized)]
+            trait Sized { }
+            trait Foo { }
+            trait Bar { }
+            struct A { }
+            struct Vec<T> { }
+
+            impl<T> Foo for Vec<T> where T: Sized { }
+            impl<T> Bar for T where T: Foo { }
+        }
+
+        goal {
+            Vec<Vec<A>>: Bar
+        } yields[SolverChoice::slg_default()] {
+            expect![[r#"
+                8 tables, 8 strands, 5 answers, 0 cycles, 0 flounders
+                trait goals solved:
+                  Bar: 1
+                  Foo: 1
+                  Sized: 1
+            "#]]
+        }
+
+        goal {
+            Vec<A>: Bar
+        } yields[SolverChoice::slg_default()] {
+            expect![[r#"
+                16 tables, 16 strands, 10 answers, 0 cycles, 0 flounders
+                trait goals solved:
+                  Bar: 2
+                  Foo: 2
+                  Sized: 2
+            "#]]
+        }
+    }
+}
+
+#[test]
+fn recursive_stats() {
+    test! {
+        stats;
+        program {
+            #[lang(sized)]
+            trait Sized { }
+            trait Foo { }
+            trait Bar { }
+            struct A { }
+            struct Vec<T> { }
+
+            impl<T> Foo for Vec<T> where T: Sized { }
+            impl<T> Bar for T where T: Foo { }
+        }
+
+        goal {
+            Vec<Vec<A>>: Bar
+        } yields[SolverChoice::recursive_default()] {
+            expect![[r#"
+                0 cache hits, 6 cache misses, 6 fixed-point iterations, 0 overflows
+                trait goals solved:
+                  Bar: 1
+                  Foo: 1
+                  Sized: 1
+            "#]]
+        }
+
+        goal {
+            Vec<A>: Bar
+        } yields[SolverChoice::recursive_default()] {
+            expect![[r#"
+                0 cache hits, 12 cache misses, 12 fixed-point iterations, 0 overflows
+                trait goals solved:
+                  Bar: 2
+                  Foo: 2
+                  Sized: 2
+            "#]]
+        }
+    }
+}
+
+#[test]
+fn slg_cycles() {
+    test! {
+        stats;
+        program {
+            trait Foo { }
+            struct A { }
+            struct Vec<T> { }
+
+            impl Foo for A { }
+            impl<T> Foo for Vec<T> where Vec<T>: Foo { }
+        }
+
+        goal {
+            Vec<A>: Foo
+        } yields[SolverChoice::slg_default()] {
+            expect![[r#"
+                3 tables, 3 strands, 0 answers, 3 cycles, 0 flounders
+                trait goals solved:
+                  Foo: 1
+            "#]]
+        }
+    }
+}
+
+#[test]
+fn fixed_point_iterations() {
+    test! {
+        stats;
+        program {
+            trait Foo { }
+            struct A { }
+            struct Vec<T> { }
+
+            impl Foo for A { }
+            impl<T> Foo for Vec<T> where T: Foo { }
+        }
+
+        goal {
+            exists<T> { T: Foo }
+        } yields[SolverChoice::recursive_default()] {
+            expect![[r#"
+                1 cache hits, 2 cache misses, 3 fixed-point iterations, 0 overflows
+                trait goals solved:
+                  Foo: 1
+            "#]]
+        }
+    }
+}
+
+#[test]
+fn flounders() {
+    test! {
+        stats;
+        program {
+            #[auto] trait Send { }
+            struct A { }
+        }
+
+        goal {
+            exists<T> { T: Send }
+        } yields[SolverChoice::slg_default()] {
+            expect![[r#"
+                1 tables, 0 strands, 0 answers, 0 cycles, 1 flounders
+                trait goals solved:
+                  Send: 1
+            "#]]
+        }
+    }
+}
+
+#[test]
+fn overflows() {
+    test! {
+        stats;
+        program {
+            trait Foo { }
+            struct A { }
+            struct Vec<T> { }
+
+            impl Foo for A { }
+            impl<T> Foo for Vec<T> where T: Foo { }
+        }
+
+        goal {
+            Vec<Vec<Vec<Vec<Vec<Vec<Vec<Vec<Vec<Vec<Vec<Vec<A>>>>>>>>>>>>: Foo
+        } yields[SolverChoice::Recursive {
+            overflow_depth: 10,
+            caching_enabled: true,
+            max_size: 30,
+            parallel: false,
+        }] {
+            expect![[r#"
+                9 cache hits, 19 cache misses, 19 fixed-point iterations, 4 overflows
+                trait goals solved:
+                  Foo: 10
+            "#]]
+        }
+    }
+}
+
+#[test]
+fn no_goals_solved() {
+    let db = ChalkDatabase::with("struct A { }", SolverChoice::slg_default());
+    expect![[r#"
+        no trait goals solved
+    "#]]
+    .assert_eq(&db.solver_stats().to_string());
+}
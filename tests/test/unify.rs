@@ -81,7 +81,7 @@ fn forall_equality_unsolveable_simple() {
             for<'a, 'b> fn(Ref<'a, Ref<'b, Ref<'a, Unit>>>): Eq<
                 for<'c, 'd> fn(Ref<'c, Ref<'d, Ref<'d, Unit>>>)>
         } yields {
-            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([]), goal: '!1_0: '!1_1 }, InEnvironment { environment: Env([]), goal: '!1_1: '!1_0 }, InEnvironment { environment: Env([]), goal: '!2_0: '!2_1 }, InEnvironment { environment: Env([]), goal: '!2_1: '!2_0 }]"]]
+            expect![["No possible solution"]]
         }
     }
 }
@@ -118,7 +118,7 @@ fn forall_equality() {
             for<'a, 'b> fn(Ref<'a, Ref<'b, Ref<'a, Unit>>>): Eq<
                 for<'c, 'd> fn(Ref<'c, Ref<'d, Ref<'d, Unit>>>)>
         } yields {
-            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([]), goal: '!1_0: '!1_1 }, InEnvironment { environment: Env([]), goal: '!1_1: '!1_0 }, InEnvironment { environment: Env([]), goal: '!2_0: '!2_1 }, InEnvironment { environment: Env([]), goal: '!2_1: '!2_0 }]"]]
+            expect![["No possible solution"]]
         }
 
         goal {
@@ -242,13 +242,7 @@ fn equality_binder() {
                 }
             }
         } yields {
-            expect![["Unique; for<?U1> { \
-                 substitution [?0 := '^0.0], \
-                 lifetime constraints [\
-                 InEnvironment { environment: Env([]), goal: '!2_0: '^0.0 }, \
-                 InEnvironment { environment: Env([]), goal: '^0.0: '!2_0 }\
-                 ] \
-             }"]]
+            expect![["No possible solution"]]
         }
     }
 }
@@ -263,13 +257,13 @@ fn equality_binder2() {
         goal {
             for<'b, 'c> fn(Ref<'b, 'c>) = for<'a> fn(Ref<'a, 'a>)
         } yields {
-            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([]), goal: '!1_0: '!1_1 }, InEnvironment { environment: Env([]), goal: '!1_1: '!1_0 }]"]]
+            expect![["No possible solution"]]
         }
 
         goal {
             for<'a> fn(Ref<'a, 'a>) = for<'b, 'c> fn(Ref<'b, 'c>)
         } yields {
-            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([]), goal: '!2_0: '!2_1 }, InEnvironment { environment: Env([]), goal: '!2_1: '!2_0 }]"]]
+            expect![["No possible solution"]]
         }
     }
 }
@@ -398,7 +392,7 @@ fn quantified_types() {
             forall<'a> { fn(fn1<'a>): Foo }
         } yields {
             // Lifetime constraints are unsatisfiable
-            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([]), goal: '!1_0: '!2_0 }, InEnvironment { environment: Env([]), goal: '!2_0: '!1_0 }]"]]
+            expect![["No possible solution"]]
         }
     }
 }
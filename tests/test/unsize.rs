@@ -283,7 +283,7 @@ fn dyn_upcasting() {
                 }
             }
         } yields {
-            expect!["Unique; lifetime constraints [InEnvironment { environment: Env([]), goal: '!1_0: '!3_0 }, InEnvironment { environment: Env([]), goal: '!2_0: '!5_0 }, InEnvironment { environment: Env([]), goal: '!5_0: '!2_0 }]"]
+            expect![["No possible solution"]]
         }
     }
 }
@@ -455,7 +455,7 @@ fn ty_to_dyn_unsizing() {
                 }
             }
         } yields {
-            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([]), goal: FooNotSized<!2_0>: '!1_0 }]"]]
+            expect![["Unique; lifetime constraints [InEnvironment { environment: Env([for<> FromEnv(!2_0: Sized)]), goal: FooNotSized<!2_0>: '!1_0 }]"]]
         }
     }
 }
@@ -26,7 +26,8 @@ fn ill_formed_trait_decl() {
 
             impl Copy for Foo { }
         } error_msg {
-            "trait impl for `Copy` does not meet well-formedness requirements"
+            "trait impl for `Copy` does not meet well-formedness requirements: \
+             `WellFormed(Foo: Copy)` does not hold, because `Implemented(Foo: Clone)` cannot be proven"
         }
     }
 }
@@ -105,7 +106,8 @@ fn ill_formed_assoc_ty() {
                 type Value = OnlyFoo<MyType>;
             }
         } error_msg {
-            "trait impl for `Bar` does not meet well-formedness requirements"
+            "trait impl for `Bar` does not meet well-formedness requirements: \
+             `WellFormed(OnlyFoo<MyType>)` does not hold, because `Implemented(MyType: Foo)` cannot be proven"
         }
     }
 }
@@ -144,7 +146,31 @@ fn ill_formed_ty_decl() {
                 value: Set<K>
             }
         } error_msg {
-            "type declaration `MyType` does not meet well-formedness requirements"
+            "type declaration `MyType` does not meet well-formedness requirements: \
+             `WellFormed(Set<K>)` does not hold, because `Implemented(K: Hash)` cannot be proven"
+        }
+    }
+}
+
+/// A goal that overflows only may not hold, rather than not hold.
+#[test]
+fn ambiguous_ty_decl() {
+    lowering_error! {
+        program {
+            trait Q { }
+            struct Z { }
+            struct G<X> { }
+            impl<X> Q for G<X> where G<G<X>>: Q { }
+
+            struct OnlyQ<T> where T: Q { }
+
+            struct MyType<K> {
+                value: OnlyQ<G<K>>
+            }
+        } error_msg {
+            "type declaration `MyType` does not meet well-formedness requirements: \
+             `WellFormed(OnlyQ<G<K>>)` does not hold, because \
+             `ForAll<> { Implemented(G<G<G<G<G<G<G<G<G<G<K>>>>>>>>>>: Q) }` may not hold"
         }
     }
 }
@@ -217,7 +243,10 @@ fn ill_formed_type_in_header() {
             // an explicit where clause would be needed (see below).
             impl<T> Bar for T where <T as Foo>::Value: Bar { }
         } error_msg {
-            "trait impl for `Bar` does not meet well-formedness requirements"
+            // The subgoals of `WellFormed(<T as Foo>::Value)` are about some
+            // type `?X` that `T` normalizes to, so they are not reported.
+            "trait impl for `Bar` does not meet well-formedness requirements: \
+             `WellFormed(<T as Foo>::Value)` does not hold"
         }
     }
 